@@ -1,34 +1,72 @@
 use actix_cors::Cors;
 use actix_files::Files;
-use actix_web::{web, App, HttpResponse, HttpServer, Result as ActixResult, middleware::Logger};
+use actix_web::{web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Result as ActixResult, middleware::Logger};
 use actix_identity::{Identity, IdentityMiddleware};
 use actix_web::cookie::Key;
-use actix_session::{SessionMiddleware, storage::CookieSessionStore};
-use crate::db::Db;
+use actix_web::http::header::{ETag, EntityTag, IfNoneMatch};
+use actix_session::{SessionMiddleware, config::PersistentSession, storage::CookieSessionStore};
+use crate::db::{Db, LoanFilter, SortOrder, LOAN_SORT_COLUMNS, USER_SORT_COLUMNS};
 use crate::user::UserManager;
-use crate::loan::LoanTracker;
-use crate::recovery::RecoveryEngine;
-use crate::models::{Loan, LoanStatus, UserRole};
+use crate::loan::{validate_principal, validate_metadata_key, validate_metadata_value, LoanTracker, NewLoan, MAX_AMOUNT, MAX_METADATA_KEYS};
+use crate::recovery::{action_effectiveness, RecoveryAction, RecoveryActionRecord, RecoveryEngine, RecoveryOutcome};
+use crate::notify::{notify_default, render_reminder, LogChannel, NotificationChannel, REMINDER_LEAD_DAYS};
+use crate::models::{ExtensionStatus, DEFAULT_CURRENCY, InterestKind, LateFeePolicy, Loan, LoanStatus, PaymentFrequency, PrincipalLimits, RiskModel, User, UserRole};
 use crate::config::Config;
+use crate::dto::{LoanResponse, Paged, UserProfile, UserResponse};
 use crate::error::{AppError, AppResult};
+use crate::extract::JsonBody;
+use crate::middleware::{AssignRequestId, ConcurrencyLimit};
+use crate::money::{Money, MoneyOverflow};
+use crate::webhook::WebhookDispatcher;
 use crate::auth::{config_auth_routes, init_auth_services, AuthState, middleware::auth::JwtAuth, services::TokenBlacklist};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 fn is_valid_4char_id(id: &str) -> bool {
     id.len() == 4 && id.chars().all(|c| c.is_alphanumeric())
 }
 
+/// The logged-in user; `AuthRequired` when there is no session or the user no longer exists.
+fn current_user(identity: &Identity, db: &Db) -> AppResult<User> {
+    let user_id = identity.id()
+        .map_err(|_| AppError::AuthRequired)?;
+    UserManager::new(db).get_user(&user_id)
+        .map_err(AppError::Database)?
+        .ok_or(AppError::AuthRequired)
+}
+
+/// The logged-in user, who must be a lender; otherwise `InsufficientPermissions`.
+fn require_lender(identity: &Identity, db: &Db) -> AppResult<User> {
+    let user = current_user(identity, db)?;
+    if !matches!(user.role, UserRole::Lender) {
+        return Err(AppError::InsufficientPermissions);
+    }
+    Ok(user)
+}
+
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RegisterUserReq {
     name: String,
     role: String, // "borrower" or "lender"
     #[serde(default)]
     email: Option<String>,
     #[serde(default)]
+    phone: Option<String>,
+    #[serde(default)]
     lender_name: Option<String>, // for borrowers
     #[serde(default)]
     organization: Option<String>, // for lenders
+    /// What the user logs in with at `POST /login`; 6+ characters.
+    password: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoginReq {
+    name: String,
+    password: String,
 }
 
 #[derive(Deserialize)]
@@ -40,80 +78,343 @@ pub struct UsersQuery {
     role: Option<String>,
     #[serde(default)]
     lender_id: Option<String>,
+    /// `column` or `-column`; defaults to creation order.
+    #[serde(default)]
+    sort: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct LoansQuery {
+    /// Loan status name, e.g. `overdue` or `partially_repaid` (case-insensitive).
+    #[serde(default)]
+    status: Option<String>,
+    /// `all` (or empty) means no borrower filter.
     #[serde(default)]
     borrower_id: Option<String>,
     #[serde(default)]
     lender_id: Option<String>,
+    /// RFC 3339 timestamps bounding `disbursement_date`, both inclusive.
+    #[serde(default)]
+    disbursed_from: Option<String>,
+    #[serde(default)]
+    disbursed_to: Option<String>,
+    /// Only active/overdue loans without a payment in this many days, oldest first.
+    #[serde(default)]
+    no_payment_since: Option<i64>,
+    /// Also list archived loans.
+    #[serde(default)]
+    include_archived: bool,
+    /// `column` or `-column`; defaults to creation order. Ignored with `no_payment_since`,
+    /// which is always ordered by how long the loan has been silent.
+    #[serde(default)]
+    sort: Option<String>,
+}
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// `?limit=&offset=` for list endpoints. `limit` defaults to 50 and is capped at 500.
+#[derive(Debug, Deserialize)]
+pub struct Pagination {
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: Option<usize>,
+}
+
+impl LoansQuery {
+    /// Validated filter from `status` / `borrower_id` / `lender_id` / `disbursed_from` /
+    /// `disbursed_to` / `include_archived`; blank values are ignored.
+    fn filter(&self) -> AppResult<LoanFilter> {
+        let present = |v: &Option<String>| {
+            v.as_deref().map(str::trim).filter(|s| !s.is_empty() && *s != "all").map(str::to_string)
+        };
+        let status = match present(&self.status) {
+            None => None,
+            Some(s) => Some(LoanStatus::parse(&s).ok_or_else(|| {
+                let names: Vec<String> = LoanStatus::ALL.iter().map(|s| format!("{:?}", s)).collect();
+                AppError::InvalidInput(format!("Unknown status '{}'; expected one of: {}", s, names.join(", ")))
+            })?),
+        };
+        let borrower_id = present(&self.borrower_id);
+        let lender_id = present(&self.lender_id);
+        for id in borrower_id.iter().chain(lender_id.iter()) {
+            if !is_valid_4char_id(id) {
+                return Err(AppError::InvalidInput(format!("Invalid user ID '{}'", id)));
+            }
+        }
+        let date = |name: &str, v: &Option<String>| -> AppResult<Option<chrono::DateTime<chrono::Utc>>> {
+            present(v)
+                .map(|s| {
+                    chrono::DateTime::parse_from_rfc3339(&s)
+                        .map(|d| d.with_timezone(&chrono::Utc))
+                        .map_err(|_| AppError::InvalidInput(format!("{} must be an RFC 3339 timestamp, got '{}'", name, s)))
+                })
+                .transpose()
+        };
+        let disbursed_from = date("disbursed_from", &self.disbursed_from)?;
+        let disbursed_to = date("disbursed_to", &self.disbursed_to)?;
+        if let (Some(from), Some(to)) = (disbursed_from, disbursed_to) {
+            if from > to {
+                return Err(AppError::InvalidInput("disbursed_from must not be after disbursed_to".to_string()));
+            }
+        }
+        Ok(LoanFilter { status, borrower_id, lender_id, disbursed_from, disbursed_to, include_archived: self.include_archived })
+    }
+}
+
+impl Pagination {
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE)
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset.unwrap_or(0)
+    }
+
+    /// Cuts the current page out of an already filtered, ordered list.
+    fn page_of<T>(&self, items: Vec<T>) -> Vec<T> {
+        items.into_iter().skip(self.offset()).take(self.limit()).collect()
+    }
+
+    /// Response body for `items`, this page of `total` matches.
+    fn wrap<T>(&self, items: Vec<T>, total: usize) -> Paged<T> {
+        Paged::new(items, total, self.limit(), self.offset())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoanStatusQuery {
+    /// Recompute the status from schedule and payments instead of trusting the stored value.
+    #[serde(default)]
+    live: bool,
+}
+
+/// Resolves a `?sort=` value against the columns allowed for that listing.
+fn parse_sort(sort: &Option<String>, allowed: &[&'static str]) -> AppResult<SortOrder> {
+    match sort.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        None => Ok(SortOrder::DEFAULT),
+        Some(spec) => SortOrder::parse(spec, allowed).ok_or_else(|| {
+            AppError::InvalidInput(format!("sort must be one of: {} (prefix with '-' for descending)", allowed.join(", ")))
+        }),
+    }
 }
 
 #[derive(Serialize)]
 struct LoanApiJson {
     id: uuid::Uuid,
-    borrower_id: uuid::Uuid,
-    lender_id: uuid::Uuid,
-    principal: f64,
-    amount: f64,
+    borrower_id: String,
+    lender_id: String,
+    guarantor_id: Option<String>,
+    principal: Money,
+    amount: Money,
+    currency: String,
     interest_rate: f64,
+    interest_kind: InterestKind,
     status: String,
     recovery_status: f64,
-    outstanding_amount: f64,
+    outstanding_amount: Money,
     risk_score: f64,
     ai_recommendation: String,
+    metadata: BTreeMap<String, String>,
+    archived: bool,
+    created_at: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
 }
 
-fn loan_api_json(loan: &Loan) -> LoanApiJson {
-    let recovery_status = match loan.status {
-        LoanStatus::Repaid => 100.0,
-        LoanStatus::Active => 42.0,
-        LoanStatus::Overdue => 28.0,
-        LoanStatus::Defaulted => 12.0,
-    };
+/// Engine using the configured recovery thresholds, or the defaults when no `Config` is registered.
+fn recovery_engine(config: Option<&web::Data<Config>>) -> RecoveryEngine {
+    config.map(|c| RecoveryEngine::new(c.recovery_thresholds)).unwrap_or_default()
+}
+
+/// Risk model with the configured miss half-life, or the default one when no `Config` is registered.
+fn risk_model(config: Option<&web::Data<Config>>) -> RiskModel {
+    config.map(|c| c.risk_model()).unwrap_or_default()
+}
+
+/// `risk_score` is the loan's score from [`LoanTracker::risk_score`].
+fn loan_api_json(loan: &Loan, metadata: BTreeMap<String, String>, risk_score: f64, recovery: &RecoveryEngine) -> LoanApiJson {
+    let recovery_status = loan.recovery_progress();
     let amount = loan.principal;
-    let outstanding_amount = amount * (1.0 - recovery_status / 100.0);
-    let recovery = RecoveryEngine;
-    let risk_score = recovery.predict_default(loan);
-    let action = recovery.recommend_action(risk_score, 0);
+    let outstanding_amount = loan.outstanding_amount();
+    let now = chrono::Utc::now();
+    let action = recovery.recommend_action(risk_score, loan.missed_installments(now), &loan.status);
     let ai_recommendation = match action {
         crate::recovery::RecoveryAction::SendReminder => "send_reminder",
         crate::recovery::RecoveryAction::RenegotiateTerms => "renegotiate_terms",
         crate::recovery::RecoveryAction::EscalateToCollection => "escalate_to_collection",
+        crate::recovery::RecoveryAction::WriteOff => "write_off",
     }
     .to_string();
     LoanApiJson {
         id: loan.id,
-        borrower_id: loan.borrower_id,
-        lender_id: loan.lender_id,
+        borrower_id: loan.borrower_id.clone(),
+        lender_id: loan.lender_id.clone(),
+        guarantor_id: loan.guarantor_id.clone(),
         principal: loan.principal,
         amount,
+        currency: loan.currency.clone(),
         interest_rate: loan.interest_rate,
+        interest_kind: loan.interest_kind,
         status: format!("{:?}", loan.status).to_lowercase(),
         recovery_status,
         outstanding_amount,
         risk_score,
         ai_recommendation,
+        metadata,
+        archived: loan.archived,
+        created_at: loan.created_at,
+        updated_at: loan.updated_at,
     }
 }
 
 #[derive(Deserialize)]
-struct CreateLoanReq {
+#[serde(deny_unknown_fields)]
+pub struct CreateLoanReq {
     borrower_id: String,
     lender_id: String,
     principal: f64,
     interest_rate: f64,
     months: i64,
+    /// `amortized` (default), `simple` or `compound_monthly`
+    #[serde(default)]
+    interest_kind: InterestKind,
+    /// ISO 4217 code; defaults to USD
+    #[serde(default)]
+    currency: Option<String>,
+    /// Leading interest-free installments, at most `months`
+    #[serde(default)]
+    promo_months: i64,
+    /// `weekly`, `biweekly` or `monthly` (default)
+    #[serde(default)]
+    payment_frequency: PaymentFrequency,
+    /// User id of a guarantor, who must exist and not be the borrower
+    #[serde(default)]
+    guarantor_id: Option<String>,
+    /// `{"kind": "flat", "amount": "25.00"}` or `{"kind": "percentage", "pct": 5.0}`;
+    /// unset falls back to `LATE_FEE_POLICY`
+    #[serde(default)]
+    late_fee_policy: Option<LateFeePolicy>,
+}
+
+impl CreateLoanReq {
+    /// Checks the ids for a loan the given lender is creating; the terms themselves are
+    /// validated by `LoanTracker` when the loan is built.
+    fn to_new_loan(&self, lender: &User) -> Result<NewLoan, String> {
+        let borrower_id = self.borrower_id.trim();
+        let lender_id = self.lender_id.trim();
+        if !is_valid_4char_id(borrower_id) || !is_valid_4char_id(lender_id) || lender_id != lender.id {
+            return Err("Invalid borrower/lender ID format".to_string());
+        }
+        let guarantor_id = self.guarantor_id.as_deref().map(str::trim).filter(|id| !id.is_empty());
+        if guarantor_id.is_some_and(|id| !is_valid_4char_id(id)) {
+            return Err("Invalid guarantor ID format".to_string());
+        }
+        Ok(NewLoan {
+            borrower_id: borrower_id.to_string(),
+            lender_id: lender_id.to_string(),
+            principal: self.principal,
+            interest_rate: self.interest_rate,
+            duration_months: self.months,
+            interest_kind: self.interest_kind,
+            currency: self.currency.clone().unwrap_or_else(|| DEFAULT_CURRENCY.to_string()),
+            promo_months: self.promo_months,
+            payment_frequency: self.payment_frequency,
+            guarantor_id: guarantor_id.map(str::to_string),
+            late_fee_policy: self.late_fee_policy,
+        })
+    }
+}
+
+/// Most loans accepted by one `POST /loans/batch`.
+pub const MAX_BATCH_LOANS: usize = 1000;
+
+/// Partial update for `PATCH /loans/{id}`; omitted fields keep their current value.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateTermsReq {
+    #[serde(default)]
+    interest_rate: Option<f64>,
+    #[serde(default)]
+    months: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecordPaymentReq {
+    amount: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogRecoveryActionReq {
+    loan_id: uuid::Uuid,
+    action: RecoveryAction,
+    #[serde(default)]
+    outcome: Option<RecoveryOutcome>,
+}
+
+/// A risk model under a caller-chosen label; weights not given fall back to the defaults.
+#[derive(Deserialize)]
+pub struct NamedRiskModel {
+    name: String,
+    #[serde(flatten)]
+    model: RiskModel,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompareModelsReq {
+    baseline: NamedRiskModel,
+    candidate: NamedRiskModel,
+}
+
+#[derive(Deserialize)]
+pub struct GenerateRemindersQuery {
+    /// Render the batch without logging any reminder actions.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+struct ModelVerdict {
+    risk_score: f64,
+    recommended_action: RecoveryAction,
+}
+
+#[derive(Serialize)]
+struct ModelComparisonRow {
+    loan_id: uuid::Uuid,
+    missed_payments: usize,
+    baseline: ModelVerdict,
+    candidate: ModelVerdict,
+    score_delta: f64,
+    action_changed: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecoveryOutcomeReq {
+    outcome: RecoveryOutcome,
 }
 
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtensionReq {
+    /// How many days to push the next installment back
+    days: i64,
+}
+
+/// Longest single extension a borrower may ask for.
+const MAX_EXTENSION_DAYS: i64 = 90;
+
 #[derive(Serialize)]
 struct CreateLoanRes {
     id: uuid::Uuid,
 }
 
 pub async fn register_user(
-    data: web::Json<RegisterUserReq>,
+    data: JsonBody<RegisterUserReq>,
     db: web::Data<Db>,
 ) -> AppResult<ActixResult<HttpResponse>> {
     let mgr = UserManager::new(&db);
@@ -140,24 +441,66 @@ pub async fn register_user(
         return Err(AppError::InvalidInput("Lenders must specify an organization".to_string()));
     }
 
-    let user_id = mgr
-        .register_user(data.name.clone(), email, role, lender_id, organization)
-        .map_err(AppError::Database)?;
+    let user_id = mgr.register_user_with_password(
+        data.name.clone(),
+        email,
+        data.phone.clone(),
+        role,
+        lender_id,
+        organization,
+        &data.password,
+    )?;
 
     let user = mgr
         .get_user(&user_id)
         .map_err(AppError::Database)?
         .ok_or_else(|| AppError::NotFound("User not found after insert".to_string()))?;
 
-    Ok(Ok(HttpResponse::Ok().json(user)))
+    Ok(Ok(HttpResponse::Ok().json(UserResponse::from(user))))
+}
+
+/// Starts a session for the user whose name and password match.
+pub async fn login(
+    req: HttpRequest,
+    data: JsonBody<LoginReq>,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = UserManager::new(&db).authenticate(data.name.trim(), &data.password)?;
+    Identity::login(&req.extensions(), user.id.clone())?;
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Login successful",
+        "user_id": user.id,
+        "name": user.name,
+        "role": user.role,
+    }))))
+}
+
+pub async fn logout(identity: Identity) -> AppResult<ActixResult<HttpResponse>> {
+    identity.logout();
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Logged out"
+    }))))
 }
 
 pub async fn get_users(
     query: web::Query<UsersQuery>,
+    page: web::Query<Pagination>,
     db: web::Data<Db>,
 ) -> AppResult<ActixResult<HttpResponse>> {
+    let order = parse_sort(&query.sort, USER_SORT_COLUMNS)?;
     let mgr = UserManager::new(&db);
-    let mut users = mgr.get_all_users().map_err(AppError::Database)?;
+    let filtered = [&query.email, &query.role, &query.lender_id]
+        .iter()
+        .any(|f| f.as_deref().is_some_and(|v| !v.trim().is_empty()));
+    if !filtered {
+        let (users, total_count) = mgr.get_users_paged(order, page.limit(), page.offset())
+            .map_err(AppError::Database)?;
+        let users: Vec<UserResponse> = users.into_iter().map(UserResponse::from).collect();
+        return Ok(Ok(HttpResponse::Ok().json(page.wrap(users, total_count))));
+    }
+
+    let mut users = mgr.get_all_users_sorted(order).map_err(AppError::Database)?;
 
     if let Some(ref em) = query.email {
         let needle = em.trim();
@@ -189,167 +532,1393 @@ pub async fn get_users(
         }
     }
 
-    Ok(Ok(HttpResponse::Ok().json(users)))
+    let total_count = users.len();
+    let users: Vec<UserResponse> = page.page_of(users).into_iter().map(UserResponse::from).collect();
+    Ok(Ok(HttpResponse::Ok().json(page.wrap(users, total_count))))
+}
+
+/// A named guarantor has to be a registered user.
+/// Header a client sets on `POST /loans` so a retried request returns the first loan
+/// instead of creating another.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+fn idempotency_key(req: &HttpRequest) -> AppResult<Option<String>> {
+    let Some(value) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    let key = value.to_str().map(str::trim).unwrap_or("");
+    if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "{} must be 1 to {} visible ASCII characters",
+            IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LEN
+        )));
+    }
+    Ok(Some(key.to_string()))
+}
+
+/// Response to a request whose Idempotency-Key already created `loan_id`.
+fn replayed_loan(loan_id: uuid::Uuid) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("Idempotent-Replayed", "true"))
+        .json(CreateLoanRes { id: loan_id })
 }
 
-async fn create_loan(
-    data: web::Json<CreateLoanReq>,
+/// Creates a loan for the calling lender. With an `Idempotency-Key` header, a repeat of the
+/// key within `IDEMPOTENCY_WINDOW_SECS` returns the loan the first request created.
+pub async fn create_loan(
+    req: HttpRequest,
+    data: JsonBody<CreateLoanReq>,
     identity: Identity,
     db: web::Data<Db>,
+    config: web::Data<Config>,
 ) -> AppResult<ActixResult<HttpResponse>> {
-    let user_id = identity.id()
-        .map_err(|_| AppError::AuthRequired)?;
+    let user = require_lender(&identity, &db)?;
+    let key = idempotency_key(&req)?;
+    if let Some(key) = &key {
+        let since = chrono::Utc::now() - chrono::Duration::seconds(config.idempotency_window_secs as i64);
+        db.purge_idempotency_keys(since).map_err(AppError::Database)?;
+        if let Some(loan_id) = db.load_idempotent_loan(&user.id, key, since).map_err(AppError::Database)? {
+            return Ok(Ok(replayed_loan(loan_id)));
+        }
+    }
+    let new_loan = data.to_new_loan(&user).map_err(AppError::InvalidInput)?;
+    check_principal_policy(&db, &config, &new_loan)?;
+    check_active_loan_cap(&db, &config, &user.id, 1)?;
 
-    let mgr = UserManager::new(&db);
-    let user = mgr.get_user(&user_id)
-        .map_err(|e| AppError::Database(e))?
-        .ok_or_else(|| AppError::AuthRequired)?;
+    let tracker = LoanTracker::new(&db).with_interest_rounding(config.interest_rounding).with_actor(&user.id);
+    let loan_id = match &key {
+        Some(key) => match tracker.create_new_loan_once(new_loan, &user.id, key)? {
+            Ok(loan_id) => loan_id,
+            // A concurrent request with the same key got there first
+            Err(existing) => return Ok(Ok(replayed_loan(existing))),
+        },
+        None => tracker.create_new_loan(new_loan)?,
+    };
 
-    if !matches!(user.role, UserRole::Lender) {
-        return Err(AppError::InsufficientPermissions);
-    }
+    Ok(Ok(HttpResponse::Ok().json(CreateLoanRes { id: loan_id })))
+}
 
-    let borrower_id = data.borrower_id.trim();
-    let lender_id = data.lender_id.trim();
-    if !is_valid_4char_id(borrower_id) || !is_valid_4char_id(lender_id) || lender_id != user_id {
-        return Err(AppError::InvalidInput("Invalid borrower/lender ID format".to_string()));
+/// Creates many loans at once, all or nothing: an invalid entry rejects the whole batch
+/// with its index in the message, and nothing is stored.
+pub async fn create_loans_batch(
+    data: JsonBody<Vec<CreateLoanReq>>,
+    identity: Identity,
+    db: web::Data<Db>,
+    config: web::Data<Config>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = require_lender(&identity, &db)?;
+    if data.is_empty() || data.len() > MAX_BATCH_LOANS {
+        return Err(AppError::InvalidInput(format!("A batch must hold between 1 and {} loans", MAX_BATCH_LOANS)));
     }
+    let new_loans = data
+        .iter()
+        .enumerate()
+        .map(|(i, req)| req.to_new_loan(&user).map_err(|msg| AppError::InvalidInput(format!("loans[{}]: {}", i, msg))))
+        .collect::<AppResult<Vec<_>>>()?;
+    for (i, new_loan) in new_loans.iter().enumerate() {
+        check_principal_policy(&db, &config, new_loan)
+            .map_err(|e| match e {
+                AppError::InvalidInput(msg) => AppError::InvalidInput(format!("loans[{}]: {}", i, msg)),
+                other => other,
+            })?;
+    }
+    check_active_loan_cap(&db, &config, &user.id, new_loans.len())?;
 
-    let tracker = LoanTracker::new(&db);
-    let loan_id = tracker.create_loan(borrower_id.to_string(), lender_id.to_string(), data.principal, data.interest_rate, data.months)
-        .map_err(|e| AppError::Database(e))?;
+    let tracker = LoanTracker::new(&db).with_interest_rounding(config.interest_rounding).with_actor(&user.id);
+    let ids = tracker.create_loans_bulk(new_loans)?;
 
-    Ok(Ok(HttpResponse::Ok().json(CreateLoanRes { id: loan_id })))
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "created": ids.len(),
+        "ids": ids,
+    }))))
+}
+
+/// The lender's own principal limits, with unset bounds taken from the global config.
+fn effective_principal_limits(db: &Db, config: &Config, lender_id: &str) -> AppResult<PrincipalLimits> {
+    let policy = db.load_lender_policy(lender_id).map_err(AppError::Database)?.unwrap_or_default();
+    Ok(policy.or(config.principal_limits()))
+}
+
+fn check_principal_policy(db: &Db, config: &Config, new_loan: &NewLoan) -> AppResult<()> {
+    effective_principal_limits(db, config, &new_loan.lender_id)?
+        .check(new_loan.principal)
+        .map_err(AppError::InvalidInput)
+}
+
+/// Rejects new loans that would take the lender past `max_active_loans_per_lender`.
+fn check_active_loan_cap(db: &Db, config: &Config, lender_id: &str, adding: usize) -> AppResult<()> {
+    if let Some(max_active) = config.max_active_loans_per_lender {
+        let active = db.count_active_loans_by_lender(lender_id).map_err(AppError::Database)?;
+        if active + adding > max_active {
+            return Err(AppError::InvalidInput(format!(
+                "Lender already holds {} active loans (limit {})",
+                active, max_active
+            )));
+        }
+    }
+    Ok(())
 }
 
-async fn get_loans(
+pub async fn get_loans(
     query: web::Query<LoansQuery>,
+    page: web::Query<Pagination>,
     db: web::Data<Db>,
+    config: Option<web::Data<Config>>,
 ) -> AppResult<ActixResult<HttpResponse>> {
-    let tracker = LoanTracker::new(&db);
-    let mut loans = tracker.get_all_loans().map_err(AppError::Database)?;
+    let recovery = recovery_engine(config.as_ref());
+    let order = parse_sort(&query.sort, LOAN_SORT_COLUMNS)?;
+    let tracker = LoanTracker::new(&db).with_risk_model(risk_model(config.as_ref()));
+    let filter = query.filter()?;
 
-    if let Some(ref bid) = query.borrower_id {
-        let b = bid.trim();
-        if !b.is_empty() && b != "all" && is_valid_4char_id(b) {
-            loans.retain(|_| false); // No filter impl for now
+    let (loans, total_count) = match query.no_payment_since {
+        Some(days) if days < 0 => {
+            return Err(AppError::InvalidInput("no_payment_since must be a non-negative number of days".to_string()));
         }
-    }
-
-    if let Some(ref lid) = query.lender_id {
-        let l = lid.trim();
-        if !l.is_empty() && is_valid_4char_id(l) {
-            loans.retain(|_loan| false); // No filter
+        Some(days) => {
+            let mut loans = tracker.loans_without_payment_since(days).map_err(AppError::Database)?;
+            loans.retain(|loan| filter.matches(loan));
+            let total_count = loans.len();
+            (page.page_of(loans), total_count)
         }
-    }
+        None if !filter.is_empty() => {
+            let loans = tracker.query_loans(&filter, order).map_err(AppError::Database)?;
+            let total_count = loans.len();
+            (page.page_of(loans), total_count)
+        }
+        None => tracker.get_loans_paged(order, page.limit(), page.offset())
+            .map_err(AppError::Database)?,
+    };
+
+    let now = chrono::Utc::now();
+    let payload = loans
+        .iter()
+        .map(|loan| Ok(loan_api_json(loan, tracker.get_metadata(loan.id)?, tracker.risk_score(loan, now)?, &recovery)))
+        .collect::<rusqlite::Result<Vec<LoanApiJson>>>()
+        .map_err(AppError::Database)?;
+    Ok(Ok(HttpResponse::Ok().json(page.wrap(payload, total_count))))
+}
 
-    let payload: Vec<LoanApiJson> = loans.iter().map(loan_api_json).collect();
-    Ok(Ok(HttpResponse::Ok().json(payload)))
+#[derive(Deserialize)]
+pub struct SweepQuery {
+    /// Report the loans that would change without writing anything.
+    #[serde(default)]
+    dry_run: bool,
 }
 
-async fn flag_overdues(
+pub async fn flag_overdues(
+    query: web::Query<SweepQuery>,
     identity: Identity,
     db: web::Data<Db>,
+    config: web::Data<Config>,
+    webhook: Option<web::Data<WebhookDispatcher>>,
 ) -> AppResult<ActixResult<HttpResponse>> {
-    let user_id = identity.id()
-        .map_err(|_| AppError::AuthRequired)?;
+    let user = require_lender(&identity, &db)?;
 
-    let mgr = UserManager::new(&db);
-    let user = mgr.get_user(&user_id)
-        .map_err(|e| AppError::Database(e))?
-        .ok_or_else(|| AppError::AuthRequired)?;
+    let tracker = LoanTracker::new(&db).with_actor(&user.id).with_webhook(webhook.as_ref().map(|w| w.get_ref()));
+    let flagged = tracker.flag_overdues(config.grace_period_days, query.dry_run)
+        .map_err(AppError::Database)?;
 
-    if !matches!(user.role, UserRole::Lender) {
-        return Err(AppError::InsufficientPermissions);
-    }
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "dry_run": query.dry_run,
+        "flagged_count": flagged.len(),
+        "loan_ids": flagged,
+    }))))
+}
 
-    let tracker = LoanTracker::new(&db);
-    let flagged_count = tracker.flag_overdues()
-        .map_err(|e| AppError::Database(e))?;
+/// Moves overdue loans past `DEFAULT_AFTER_DAYS` to Defaulted and sends each borrower (and
+/// guarantor, if any) a default notice through the notification channel.
+pub async fn flag_defaults(
+    query: web::Query<SweepQuery>,
+    identity: Identity,
+    db: web::Data<Db>,
+    config: web::Data<Config>,
+    channel: Option<web::Data<dyn NotificationChannel>>,
+    webhook: Option<web::Data<WebhookDispatcher>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = require_lender(&identity, &db)?;
+
+    let tracker = LoanTracker::new(&db).with_actor(&user.id).with_webhook(webhook.as_ref().map(|w| w.get_ref()));
+    let defaulted = tracker.flag_defaults(config.default_after_days, query.dry_run)
+        .map_err(AppError::Database)?;
+
+    let mut notified = 0;
+    if !query.dry_run {
+        let channel: &dyn NotificationChannel = match channel.as_ref() {
+            Some(data) => data.as_ref(),
+            None => &LogChannel,
+        };
+        let users = UserManager::new(&db);
+        for loan_id in &defaulted {
+            let Some(loan) = tracker.get_loan(*loan_id).map_err(AppError::Database)? else {
+                continue;
+            };
+            let Some(borrower) = users.get_user(&loan.borrower_id).map_err(AppError::Database)? else {
+                log::warn!("Loan {} has no borrower record {}; skipping default notice", loan.id, loan.borrower_id);
+                continue;
+            };
+            let guarantor = match &loan.guarantor_id {
+                Some(id) => users.get_user(id).map_err(AppError::Database)?,
+                None => None,
+            };
+            notified += notify_default(&loan, &borrower, guarantor.as_ref(), channel).await?.len();
+        }
+    }
 
     Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
-        "flagged_count": flagged_count
+        "dry_run": query.dry_run,
+        "defaulted_count": defaulted.len(),
+        "loan_ids": defaulted,
+        "notified_count": notified,
     }))))
 }
 
-async fn recommend_action(
+/// Recomputes every loan's status from its schedule and payments in one transaction, using the
+/// configured grace and default thresholds, and reports which loans changed.
+pub async fn reconcile_loans(
+    identity: Identity,
+    db: web::Data<Db>,
+    config: web::Data<Config>,
+    webhook: Option<web::Data<WebhookDispatcher>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = require_lender(&identity, &db)?;
+
+    let tracker = LoanTracker::new(&db).with_actor(&user.id).with_webhook(webhook.as_ref().map(|w| w.get_ref()));
+    let summary = tracker.reconcile(config.grace_period_days, config.default_after_days)
+        .map_err(AppError::Database)?;
+
+    Ok(Ok(HttpResponse::Ok().json(summary)))
+}
+
+pub async fn recommend_action(
     path: web::Path<uuid::Uuid>,
     identity: Identity,
     db: web::Data<Db>,
+    config: Option<web::Data<Config>>,
 ) -> AppResult<ActixResult<HttpResponse>> {
     let _user_id = identity.id()
         .map_err(|_| AppError::AuthRequired)?;
 
-    let tracker = LoanTracker::new(&db);
-    let recovery = RecoveryEngine;
+    let tracker = LoanTracker::new(&db).with_risk_model(risk_model(config.as_ref()));
+    let recovery = recovery_engine(config.as_ref());
 
     let loan = tracker.get_loan(path.into_inner())
-        .map_err(|e| AppError::Database(e))?
+        .map_err(AppError::Database)?
         .ok_or_else(|| AppError::NotFound("Loan not found".to_string()))?;
 
-    let risk = recovery.predict_default(&loan);
-    let action = recovery.recommend_action(risk, 0);
+    let missed_payments = tracker.count_missed_payments(loan.id)?;
+    let assessment = tracker.assess_risk(&loan, chrono::Utc::now())
+        .map_err(AppError::Database)?;
+    let action = recovery.recommend_action(assessment.risk_score, missed_payments, &loan.status);
 
     Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
         "loan_id": loan.id,
-        "risk_score": risk,
+        "risk_score": assessment.risk_score,
+        "borrower_reliability": assessment.borrower_history.reliability(),
+        "missed_payments": missed_payments,
         "recommended_action": action
     }))))
 }
 
-pub async fn run_server(config: Config) -> std::io::Result<()> {
-    log::info!("🚀 Smart Loan Recovery Server starting at http://{}", config.server_addr());
-    log::info!(
-        "Web UI (same-origin, avoids CORS): http://{}/app/",
-        config.server_addr()
-    );
+/// Scores the caller's book (every loan for admins) under two models and reports where they disagree.
+pub async fn compare_risk_models(
+    data: JsonBody<CompareModelsReq>,
+    identity: Identity,
+    db: web::Data<Db>,
+    config: Option<web::Data<Config>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = current_user(&identity, &db)?;
 
-    // Initialize Firebase authentication services
-    log::info!("🔐 Initializing Firebase authentication...");
-    let auth_state: web::Data<AuthState> = match init_auth_services().await {
-        Ok(state) => {
-            log::info!("✅ Firebase authentication initialized successfully");
-            web::Data::new(state)
-        }
-        Err(e) => {
-            log::error!("❌ Failed to initialize Firebase authentication: {}", e);
-            log::warn!("⚠️  Starting server WITHOUT Firebase authentication - only demo mode will work");
-            // Create a minimal auth state for demo mode
-            panic!("Firebase auth initialization failed. Please check .env.firebase configuration.");
-        }
-    };
+    let tracker = LoanTracker::new(&db);
+    let loans = match user.role {
+        UserRole::Lender => tracker.get_loans_by_lender(&user.id),
+        UserRole::Admin => tracker.get_all_loans(),
+        _ => return Err(AppError::InsufficientPermissions),
+    }
+    .map_err(AppError::Database)?;
 
-    // Initialize token blacklist for logout functionality
-    let token_blacklist = web::Data::new(Arc::new(TokenBlacklist::new()));
+    let req = data.into_inner();
+    if req.baseline.name.trim().is_empty() || req.candidate.name.trim().is_empty() {
+        return Err(AppError::InvalidInput("Both models need a name".to_string()));
+    }
 
-    let fe = std::path::Path::new(&config.frontend_dir);
-    if !fe.is_dir() {
-        log::warn!(
-            "FRONTEND_DIR {:?} is not a directory — GET /app/ will fail. Run `cargo run` from the repo root or set FRONTEND_DIR.",
-            fe
-        );
+    let recovery = recovery_engine(config.as_ref());
+    let now = chrono::Utc::now();
+    let verdict = |loan: &Loan, model: &RiskModel, missed: usize| {
+        let risk_score = recovery.predict_default(loan, model, now);
+        ModelVerdict { risk_score, recommended_action: recovery.recommend_action(risk_score, missed, &loan.status) }
+    };
+
+    let mut rows = Vec::with_capacity(loans.len());
+    for loan in &loans {
+        let missed_payments = tracker.count_missed_payments(loan.id)?;
+        let baseline = verdict(loan, &req.baseline.model, missed_payments);
+        let candidate = verdict(loan, &req.candidate.model, missed_payments);
+        rows.push(ModelComparisonRow {
+            loan_id: loan.id,
+            missed_payments,
+            score_delta: candidate.risk_score - baseline.risk_score,
+            action_changed: baseline.recommended_action != candidate.recommended_action,
+            baseline,
+            candidate,
+        });
     }
+    let action_changes = rows.iter().filter(|r| r.action_changed).count();
 
-    log::info!("Server configured successfully");
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "baseline": req.baseline.name,
+        "candidate": req.candidate.name,
+        "loans_compared": rows.len(),
+        "action_changes": action_changes,
+        "loans": rows
+    }))))
+}
 
-    let _config_clone = config.clone();
-    let frontend_dir = _config_clone.frontend_dir.clone();
-    
-    HttpServer::new(move || {
-        let db = match Db::new_with_path(&_config_clone.database_url) {
-            Ok(db) => db,
-            Err(e) => {
-                log::error!("Failed to create database connection: {}", e);
-                panic!("Database connection failed");
-            }
-        };
+/// Renders a reminder for every loan in the caller's book that is due for one (all loans for
+/// admins). Unless `dry_run` is set, each message is sent through the app's notification
+/// channel (the log when none is registered) and a `SendReminder` action is logged.
+pub async fn generate_reminders(
+    query: web::Query<GenerateRemindersQuery>,
+    identity: Identity,
+    db: web::Data<Db>,
+    channel: Option<web::Data<dyn NotificationChannel>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = current_user(&identity, &db)?;
 
-        let key = Key::derive_from(&_config_clone.session_secret.as_bytes());
+    let lender_scope = match user.role {
+        UserRole::Lender => Some(user.id.as_str()),
+        UserRole::Admin => None,
+        _ => return Err(AppError::InsufficientPermissions),
+    };
+
+    let now = chrono::Utc::now();
+    let tracker = LoanTracker::new(&db);
+    let loans = tracker.loans_due_for_reminder(lender_scope, now, REMINDER_LEAD_DAYS)
+        .map_err(AppError::Database)?;
+
+    let mut reminders = Vec::with_capacity(loans.len());
+    for loan in &loans {
+        let Some(borrower) = UserManager::new(&db).get_user(&loan.borrower_id).map_err(AppError::Database)? else {
+            log::warn!("Loan {} has no borrower record {}; skipping reminder", loan.id, loan.borrower_id);
+            continue;
+        };
+        let reminder = if query.dry_run {
+            render_reminder(loan, &borrower, now)
+        } else {
+            let channel: &dyn NotificationChannel = match channel.as_ref() {
+                Some(data) => data.as_ref(),
+                None => &LogChannel,
+            };
+            RecoveryEngine::default().dispatch(RecoveryAction::SendReminder, loan, &borrower, channel, now).await?
+        };
+        let Some(reminder) = reminder else {
+            continue;
+        };
+        if !query.dry_run {
+            let record = RecoveryActionRecord {
+                id: uuid::Uuid::new_v4(),
+                loan_id: loan.id,
+                action: RecoveryAction::SendReminder,
+                outcome: None,
+                taken_at: now,
+            };
+            db.save_recovery_action(&record).map_err(AppError::Database)?;
+        }
+        reminders.push(reminder);
+    }
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "dry_run": query.dry_run,
+        "generated": reminders.len(),
+        "reminders": reminders
+    }))))
+}
+
+/// Renders the dunning message for one of the caller's loans without sending or logging it.
+pub async fn remind_loan(
+    path: web::Path<uuid::Uuid>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let loan = load_party_loan(&db, &identity, path.into_inner(), true)?;
+    let borrower = UserManager::new(&db).get_user(&loan.borrower_id)
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("Borrower not found".to_string()))?;
+    let reminder = render_reminder(&loan, &borrower, chrono::Utc::now())
+        .ok_or_else(|| AppError::InvalidInput("Loan has nothing left to pay".to_string()))?;
+
+    Ok(Ok(HttpResponse::Ok().json(reminder)))
+}
+
+pub async fn log_recovery_action(
+    data: JsonBody<LogRecoveryActionReq>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    require_lender(&identity, &db)?;
+
+    let tracker = LoanTracker::new(&db);
+    tracker.get_loan(data.loan_id)
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("Loan not found".to_string()))?;
+
+    let record = RecoveryActionRecord {
+        id: uuid::Uuid::new_v4(),
+        loan_id: data.loan_id,
+        action: data.action,
+        outcome: data.outcome,
+        taken_at: chrono::Utc::now(),
+    };
+    db.save_recovery_action(&record).map_err(AppError::Database)?;
+
+    Ok(Ok(HttpResponse::Ok().json(record)))
+}
+
+pub async fn set_recovery_outcome(
+    path: web::Path<uuid::Uuid>,
+    data: JsonBody<RecoveryOutcomeReq>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    require_lender(&identity, &db)?;
+
+    let action_id = path.into_inner();
+    if !db.set_recovery_outcome(action_id, data.outcome).map_err(AppError::Database)? {
+        return Err(AppError::NotFound("Recovery action not found".to_string()));
+    }
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "id": action_id,
+        "outcome": data.outcome
+    }))))
+}
+
+pub async fn recovery_effectiveness(
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    require_lender(&identity, &db)?;
+
+    let records = db.load_recovery_actions().map_err(AppError::Database)?;
+    Ok(Ok(HttpResponse::Ok().json(action_effectiveness(&records))))
+}
+
+pub async fn request_extension(
+    path: web::Path<uuid::Uuid>,
+    data: JsonBody<ExtensionReq>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = current_user(&identity, &db)?;
+
+    let tracker = LoanTracker::new(&db);
+    let loan = tracker.get_loan(path.into_inner())
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("Loan not found".to_string()))?;
+
+    if loan.borrower_id != user.id {
+        return Err(AppError::InsufficientPermissions);
+    }
+
+    if data.days < 1 || data.days > MAX_EXTENSION_DAYS {
+        return Err(AppError::InvalidInput(format!("days must be between 1 and {}", MAX_EXTENSION_DAYS)));
+    }
+
+    let index = loan.next_due_index(chrono::Utc::now())
+        .ok_or_else(|| AppError::InvalidInput("Loan has no upcoming installment to extend".to_string()))?;
+    let requested_due = loan.repayment_schedule[index].due_date + chrono::Duration::days(data.days);
+    if let Some(following) = loan.repayment_schedule.get(index + 1) {
+        if requested_due >= following.due_date {
+            return Err(AppError::InvalidInput("Extension would run into the following installment".to_string()));
+        }
+    }
+
+    let request = tracker.request_extension(&loan, index, requested_due)?;
+
+    Ok(Ok(HttpResponse::Ok().json(request)))
+}
+
+async fn decide_extension(
+    path: web::Path<(uuid::Uuid, uuid::Uuid)>,
+    identity: Identity,
+    db: web::Data<Db>,
+    approve: bool,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = current_user(&identity, &db)?;
+
+    let (loan_id, request_id) = path.into_inner();
+    let tracker = LoanTracker::new(&db);
+    let loan = tracker.get_loan(loan_id)
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("Loan not found".to_string()))?;
+
+    if !matches!(user.role, UserRole::Lender) || loan.lender_id != user.id {
+        return Err(AppError::InsufficientPermissions);
+    }
+
+    let request = db.load_extension_request(request_id)
+        .map_err(AppError::Database)?
+        .filter(|r| r.loan_id == loan.id)
+        .ok_or_else(|| AppError::NotFound("Extension request not found".to_string()))?;
+
+    if request.status != ExtensionStatus::Pending {
+        return Err(AppError::InvalidInput("Extension request has already been decided".to_string()));
+    }
+
+    let request = tracker.decide_extension(request, approve)?;
+
+    Ok(Ok(HttpResponse::Ok().json(request)))
+}
+
+pub async fn approve_extension(
+    path: web::Path<(uuid::Uuid, uuid::Uuid)>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    decide_extension(path, identity, db, true).await
+}
+
+pub async fn reject_extension(
+    path: web::Path<(uuid::Uuid, uuid::Uuid)>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    decide_extension(path, identity, db, false).await
+}
+
+pub async fn settlement_quote(
+    path: web::Path<uuid::Uuid>,
+    identity: Identity,
+    db: web::Data<Db>,
+    config: web::Data<Config>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let loan = load_party_loan(&db, &identity, path.into_inner(), false)?;
+    let quote = LoanTracker::new(&db).settlement_quote(loan.id, &config.settlement_policy(), chrono::Utc::now())
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("Loan not found".to_string()))?;
+
+    Ok(Ok(HttpResponse::Ok().json(quote)))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetadataValueReq {
+    value: String,
+}
+
+/// Loads a loan the caller is party to; `lender_only` further restricts it to the loan's lender.
+fn load_party_loan(db: &Db, identity: &Identity, loan_id: uuid::Uuid, lender_only: bool) -> AppResult<Loan> {
+    let user_id = identity.id()
+        .map_err(|_| AppError::AuthRequired)?;
+
+    let loan = LoanTracker::new(db).get_loan(loan_id)
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("Loan not found".to_string()))?;
+
+    let allowed = loan.lender_id == user_id || (!lender_only && loan.borrower_id == user_id);
+    if !allowed {
+        return Err(AppError::InsufficientPermissions);
+    }
+    Ok(loan)
+}
+
+/// Full record of a loan the caller is party to, with its current risk score and the balance
+/// still owed including late fees.
+pub async fn get_loan(
+    req: HttpRequest,
+    path: web::Path<uuid::Uuid>,
+    identity: Identity,
+    db: web::Data<Db>,
+    config: Option<web::Data<Config>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let loan = load_party_loan(&db, &identity, path.into_inner(), false)?;
+    let now = chrono::Utc::now();
+    let risk_score = LoanTracker::new(&db).with_risk_model(risk_model(config.as_ref())).risk_score(&loan, now).map_err(AppError::Database)?;
+    let etag = loan_etag(&loan, risk_score, now);
+    let unchanged = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+    if unchanged {
+        return Ok(Ok(HttpResponse::NotModified().insert_header(ETag(etag)).finish()));
+    }
+    let with_fees = loan.clone().with_default_late_fee_policy(config.and_then(|c| c.late_fee_policy));
+    let mut body = serde_json::to_value(LoanResponse::from(loan))?;
+    body["risk_score"] = serde_json::json!(risk_score);
+    body["late_fee"] = serde_json::json!(with_fees.late_fee(now));
+    body["outstanding_balance"] = serde_json::json!(with_fees.outstanding_balance(now).ok_or(MoneyOverflow)?);
+    Ok(Ok(HttpResponse::Ok().insert_header(ETag(etag)).json(body)))
+}
+
+/// Entity tag for `GET /loans/{id}`: the loan's last write time, plus the day, since late fees
+/// in the response move with the calendar even when the row doesn't, and the risk score, which
+/// also moves with the borrower's other loans.
+fn loan_etag(loan: &Loan, risk_score: f64, as_of: chrono::DateTime<chrono::Utc>) -> EntityTag {
+    EntityTag::new_strong(format!(
+        "{}-{}-{}-{:x}",
+        loan.id.simple(),
+        loan.updated_at.timestamp_micros(),
+        as_of.format("%Y%m%d"),
+        risk_score.to_bits()
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ScheduleQuery {
+    /// UTC offset to show due dates in, e.g. `+03:00`; UTC when absent.
+    #[serde(default)]
+    tz: Option<String>,
+}
+
+/// Repayment plan for a loan the caller is party to, with each installment marked paid, upcoming
+/// or overdue and the late fees accrued on overdue ones. With `?tz=` the due dates are given in
+/// that offset or zone (each at the offset in force on its date); they are the same instants
+/// either way.
+pub async fn get_loan_schedule(
+    path: web::Path<uuid::Uuid>,
+    query: web::Query<ScheduleQuery>,
+    identity: Identity,
+    db: web::Data<Db>,
+    config: Option<web::Data<Config>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let zone = match query.tz.as_deref() {
+        Some(tz) => Some(crate::timezone::Zone::parse(tz).ok_or_else(|| {
+            AppError::InvalidInput(format!("tz must be UTC, an offset like +03:00 or a zone name like Africa/Nairobi, got '{}'", tz))
+        })?),
+        None => None,
+    };
+    let loan = load_party_loan(&db, &identity, path.into_inner(), false)?
+        .with_default_late_fee_policy(config.and_then(|c| c.late_fee_policy));
+    let now = chrono::Utc::now();
+    let entries = loan.schedule_as_of(now).ok_or(MoneyOverflow)?;
+    let mut schedule = serde_json::to_value(&entries)?;
+    if let (Some(zone), Some(rendered)) = (&zone, schedule.as_array_mut()) {
+        for (json, entry) in rendered.iter_mut().zip(&entries) {
+            json["due_date"] = serde_json::json!(zone.localize(entry.due_date).to_rfc3339());
+        }
+    }
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "loan_id": loan.id,
+        "timezone": zone.unwrap_or_else(|| crate::timezone::utc().into()).to_string(),
+        "schedule": schedule,
+        "breakdown": loan.payment_breakdown(),
+        "late_fee_policy": loan.late_fee_policy,
+        "late_fee": loan.late_fee(now),
+        "outstanding_balance": loan.outstanding_balance(now).ok_or(MoneyOverflow)?,
+    }))))
+}
+
+/// Status transitions of a loan the caller is party to, oldest first.
+pub async fn get_loan_history(
+    path: web::Path<uuid::Uuid>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let loan = load_party_loan(&db, &identity, path.into_inner(), false)?;
+    let history = LoanTracker::new(&db).get_audit_log(loan.id)
+        .map_err(AppError::Database)?;
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "loan_id": loan.id,
+        "history": history,
+    }))))
+}
+
+/// Pays the loan off today for the amount `GET /loans/{id}/settlement` quotes (borrower or lender).
+pub async fn settle_loan(
+    path: web::Path<uuid::Uuid>,
+    identity: Identity,
+    db: web::Data<Db>,
+    webhook: Option<web::Data<WebhookDispatcher>>,
+    config: Option<web::Data<Config>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = current_user(&identity, &db)?;
+    let loan = load_party_loan(&db, &identity, path.into_inner(), false)?;
+
+    let policy = config.map(|c| c.settlement_policy()).unwrap_or_default();
+    let tracker = LoanTracker::new(&db).with_actor(&user.id).with_webhook(webhook.as_ref().map(|w| w.get_ref()));
+    let settled_amount = tracker.settle_early(loan.id, &policy, chrono::Utc::now())?;
+    let loan = tracker.get_loan(loan.id)
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("Loan not found".to_string()))?;
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "loan_id": loan.id,
+        "settled_amount": settled_amount,
+        "amount_paid": loan.amount_paid,
+        "status": format!("{:?}", loan.status).to_lowercase(),
+    }))))
+}
+
+/// Records a payment from the borrower or the lender on their behalf, returning the new
+/// balance, status and risk score.
+pub async fn record_loan_payment(
+    path: web::Path<uuid::Uuid>,
+    data: JsonBody<RecordPaymentReq>,
+    identity: Identity,
+    db: web::Data<Db>,
+    webhook: Option<web::Data<WebhookDispatcher>>,
+    config: Option<web::Data<Config>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = current_user(&identity, &db)?;
+    let loan = load_party_loan(&db, &identity, path.into_inner(), false)?;
+    if !data.amount.is_finite() || data.amount <= 0.0 {
+        return Err(AppError::InvalidInput("amount must be a positive number".to_string()));
+    }
+
+    let tracker = LoanTracker::new(&db)
+        .with_actor(&user.id)
+        .with_webhook(webhook.as_ref().map(|w| w.get_ref()))
+        .with_risk_model(risk_model(config.as_ref()));
+    let receipt = tracker.record_payment(loan.id, data.amount)?;
+    let loan = tracker.get_loan(loan.id)
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("Loan not found".to_string()))?;
+    let risk_score = tracker.risk_score(&loan, chrono::Utc::now())
+        .map_err(AppError::Database)?;
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "loan_id": loan.id,
+        "applied": receipt.applied,
+        "excess": receipt.excess,
+        "remaining_balance": receipt.remaining_balance,
+        "amount_paid": loan.amount_paid,
+        "status": format!("{:?}", loan.status).to_lowercase(),
+        "risk_score": risk_score,
+    }))))
+}
+
+pub async fn get_loan_metadata(
+    path: web::Path<uuid::Uuid>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let loan = load_party_loan(&db, &identity, path.into_inner(), false)?;
+    let metadata = LoanTracker::new(&db).get_metadata(loan.id)
+        .map_err(AppError::Database)?;
+    Ok(Ok(HttpResponse::Ok().json(metadata)))
+}
+
+pub async fn get_loan_metadata_key(
+    path: web::Path<(uuid::Uuid, String)>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let (loan_id, key) = path.into_inner();
+    let loan = load_party_loan(&db, &identity, loan_id, false)?;
+    let metadata = LoanTracker::new(&db).get_metadata(loan.id)
+        .map_err(AppError::Database)?;
+    let value = metadata.get(&key)
+        .ok_or_else(|| AppError::NotFound(format!("Metadata key '{}' not set", key)))?;
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({ "key": key, "value": value }))))
+}
+
+/// Sets one metadata entry on a loan (loan's lender only).
+pub async fn set_loan_metadata(
+    path: web::Path<(uuid::Uuid, String)>,
+    data: JsonBody<MetadataValueReq>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let (loan_id, key) = path.into_inner();
+    let loan = load_party_loan(&db, &identity, loan_id, true)?;
+    validate_metadata_key(&key).map_err(AppError::InvalidInput)?;
+    validate_metadata_value(&data.value).map_err(AppError::InvalidInput)?;
+
+    let tracker = LoanTracker::new(&db);
+    let existing = tracker.get_metadata(loan.id)
+        .map_err(AppError::Database)?;
+    if !existing.contains_key(&key) && existing.len() >= MAX_METADATA_KEYS {
+        return Err(AppError::InvalidInput(format!("a loan can carry at most {} metadata keys", MAX_METADATA_KEYS)));
+    }
+
+    tracker.set_metadata(loan.id, &key, &data.value)
+        .map_err(AppError::Database)?;
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({ "key": key, "value": data.value }))))
+}
+
+pub async fn delete_loan_metadata(
+    path: web::Path<(uuid::Uuid, String)>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let (loan_id, key) = path.into_inner();
+    let loan = load_party_loan(&db, &identity, loan_id, true)?;
+    let removed = LoanTracker::new(&db).delete_metadata(loan.id, &key)
+        .map_err(AppError::Database)?;
+    if !removed {
+        return Err(AppError::NotFound(format!("Metadata key '{}' not set", key)));
+    }
+    Ok(Ok(HttpResponse::NoContent().finish()))
+}
+
+/// One user by id. The user themselves and the lender they borrow from get the full
+/// `UserResponse`; anyone else logged in only the `UserProfile`.
+pub async fn get_user(
+    path: web::Path<String>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let viewer = current_user(&identity, &db)?;
+    let user = UserManager::new(&db).get_user(path.as_str())
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    let own_borrower = matches!(viewer.role, UserRole::Lender) && user.lender_id.as_deref() == Some(viewer.id.as_str());
+    if user.id == viewer.id || own_borrower {
+        return Ok(Ok(HttpResponse::Ok().json(UserResponse::from(user))));
+    }
+    Ok(Ok(HttpResponse::Ok().json(UserProfile::from(user))))
+}
+
+/// Deletes a user. Lenders may remove themselves or borrowers who signed up with them.
+pub async fn delete_user(
+    path: web::Path<String>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = require_lender(&identity, &db)?;
+
+    let mgr = UserManager::new(&db);
+    let target = mgr.get_user(path.as_str())
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    if target.id != user.id && target.lender_id.as_deref() != Some(user.id.as_str()) {
+        return Err(AppError::InsufficientPermissions);
+    }
+
+    mgr.delete_user(&target.id)?;
+    Ok(Ok(HttpResponse::NoContent().finish()))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateRoleReq {
+    role: String, // "borrower", "lender" or "admin"
+}
+
+/// Fixes a user registered under the wrong role. Admins may change anyone; lenders only
+/// themselves and their own borrowers, and never to or from admin.
+pub async fn update_user_role(
+    path: web::Path<String>,
+    data: JsonBody<UpdateRoleReq>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = current_user(&identity, &db)?;
+    let role = match data.role.as_str() {
+        "borrower" => UserRole::Borrower,
+        "lender" => UserRole::Lender,
+        "admin" => UserRole::Admin,
+        _ => return Err(AppError::InvalidInput("Role must be 'borrower', 'lender' or 'admin'".to_string())),
+    };
+
+    let mgr = UserManager::new(&db).with_actor(&user.id);
+    let target = mgr.get_user(path.as_str())
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    match user.role {
+        UserRole::Admin => {}
+        UserRole::Lender => {
+            let own = target.id == user.id || target.lender_id.as_deref() == Some(user.id.as_str());
+            if !own || role == UserRole::Admin || target.role == UserRole::Admin {
+                return Err(AppError::InsufficientPermissions);
+            }
+        }
+        UserRole::Borrower => return Err(AppError::InsufficientPermissions),
+    }
+
+    let updated = mgr.update_role(&target.id, role)?;
+    Ok(Ok(HttpResponse::Ok().json(UserResponse::from(updated))))
+}
+
+/// Corrects a loan's rate or term before any money has been paid against it.
+pub async fn update_loan_terms(
+    path: web::Path<uuid::Uuid>,
+    data: JsonBody<UpdateTermsReq>,
+    identity: Identity,
+    db: web::Data<Db>,
+    config: web::Data<Config>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let loan = load_party_loan(&db, &identity, path.into_inner(), true)?;
+
+    if data.interest_rate.is_none() && data.months.is_none() {
+        return Err(AppError::InvalidInput("Provide interest_rate and/or months".to_string()));
+    }
+
+    let updated = LoanTracker::new(&db)
+        .with_interest_rounding(config.interest_rounding)
+        .update_terms(loan.id, data.interest_rate, data.months)?;
+
+    Ok(Ok(HttpResponse::Ok().json(LoanResponse::from(updated))))
+}
+
+/// Loan records are retained, so deleting one of the caller's loans archives it; payments and
+/// history are kept and `POST /loans/{id}/restore` brings it back.
+pub async fn delete_loan(
+    path: web::Path<uuid::Uuid>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let loan = load_party_loan(&db, &identity, path.into_inner(), true)?;
+    LoanTracker::new(&db).archive_loan(loan.id)?;
+    Ok(Ok(HttpResponse::NoContent().finish()))
+}
+
+/// Archives one of the caller's loans: it stays on record but drops out of listings and sweeps.
+pub async fn archive_loan(
+    path: web::Path<uuid::Uuid>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let loan = load_party_loan(&db, &identity, path.into_inner(), true)?;
+    let archived = LoanTracker::new(&db).archive_loan(loan.id)?;
+    Ok(Ok(HttpResponse::Ok().json(LoanResponse::from(archived))))
+}
+
+/// Brings an archived loan back into listings and sweeps.
+pub async fn restore_loan(
+    path: web::Path<uuid::Uuid>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let loan = load_party_loan(&db, &identity, path.into_inner(), true)?;
+    let restored = LoanTracker::new(&db).restore_loan(loan.id)?;
+    Ok(Ok(HttpResponse::Ok().json(LoanResponse::from(restored))))
+}
+
+/// Stored status of a loan, plus the live recomputation with `?live=true` so clients can spot drift.
+pub async fn loan_status(
+    path: web::Path<uuid::Uuid>,
+    query: web::Query<LoanStatusQuery>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let loan = load_party_loan(&db, &identity, path.into_inner(), false)?;
+    let computed = query.live.then(|| loan.computed_status(chrono::Utc::now()));
+    let drift = computed.as_ref().map(|c| *c != loan.status);
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "loan_id": loan.id,
+        "stored_status": loan.status,
+        "computed_status": computed,
+        "drift": drift
+    }))))
+}
+
+#[derive(Serialize)]
+struct PartyLoanJson {
+    /// The caller's side of this loan: `borrower` or `lender`
+    role: &'static str,
+    #[serde(flatten)]
+    loan: LoanApiJson,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WalletTopUpReq {
+    amount: f64,
+}
+
+pub async fn get_wallet(
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user_id = identity.id()
+        .map_err(|_| AppError::AuthRequired)?;
+
+    let user = UserManager::new(&db).get_user(&user_id)
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::AuthRequired)?;
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "user_id": user.id,
+        "wallet_balance": user.wallet_balance
+    }))))
+}
+
+/// Borrower adds funds that due installments are auto-debited from.
+pub async fn top_up_wallet(
+    data: JsonBody<WalletTopUpReq>,
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = current_user(&identity, &db)?;
+
+    if !matches!(user.role, UserRole::Borrower) {
+        return Err(AppError::InsufficientPermissions);
+    }
+    if !data.amount.is_finite() || !Money::from_f64(data.amount).is_positive() {
+        return Err(AppError::InvalidInput("amount must be a positive number".to_string()));
+    }
+    if data.amount > MAX_AMOUNT {
+        return Err(AppError::InvalidInput(format!("amount must not exceed {}", MAX_AMOUNT)));
+    }
+
+    let balance = UserManager::new(&db).top_up_wallet(&user.id, Money::from_f64(data.amount))
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::AuthRequired)?;
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "user_id": user.id,
+        "wallet_balance": balance
+    }))))
+}
+
+/// Periodically auto-debits due installments from borrower wallets.
+fn spawn_auto_debit_job(db: Db, webhook: WebhookDispatcher, interval_secs: u64) {
+    if interval_secs == 0 {
+        log::info!("Wallet auto-debit job disabled");
+        return;
+    }
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            match LoanTracker::new(&db).with_webhook(Some(&webhook)).run_auto_debits(chrono::Utc::now()) {
+                Ok(summary) if summary.payments_made > 0 || summary.flagged_overdue > 0 => {
+                    log::info!(
+                        "Auto-debit: {} payments ({} total), {} loans flagged overdue",
+                        summary.payments_made, summary.amount_debited, summary.flagged_overdue
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => log::error!("Auto-debit run failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Periodically writes a timestamped JSON backup into `dir`, keeping the newest `retention`.
+fn spawn_backup_job(db: Db, dir: String, retention: usize, interval_secs: u64) {
+    if interval_secs == 0 {
+        log::info!("Scheduled backups disabled");
+        return;
+    }
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            match db.write_backup(&dir, retention, chrono::Utc::now()) {
+                Ok(files) => log::info!(
+                    "Backup written to {} and {} ({} old files removed)",
+                    files.users.display(), files.loans.display(), files.removed.len()
+                ),
+                Err(e) => log::error!("Scheduled backup to {} failed: {}", dir, e),
+            }
+        }
+    });
+}
+
+/// The caller's own loans: the ones they lent as a lender, otherwise the ones they borrowed.
+pub async fn my_loans(
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = current_user(&identity, &db)?;
+    let loans: Vec<LoanResponse> = LoanTracker::new(&db).loans_for_user(&user.id, &user.role)
+        .map_err(AppError::Database)?
+        .into_iter()
+        .map(LoanResponse::from)
+        .collect();
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "user_id": user.id,
+        "loans": loans
+    }))))
+}
+
+/// Every loan the caller is party to, as borrower or lender, tagged with their role in each.
+pub async fn my_all_loans(
+    identity: Identity,
+    db: web::Data<Db>,
+    config: Option<web::Data<Config>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let recovery = recovery_engine(config.as_ref());
+    let user_id = identity.id()
+        .map_err(|_| AppError::AuthRequired)?;
+
+    let tracker = LoanTracker::new(&db).with_risk_model(risk_model(config.as_ref()));
+    let borrowed = tracker.get_loans_by_borrower(&user_id)
+        .map_err(AppError::Database)?;
+    let lent = tracker.get_loans_by_lender(&user_id)
+        .map_err(AppError::Database)?;
+
+    let tagged = borrowed.iter().map(|loan| ("borrower", loan))
+        .chain(lent.iter().map(|loan| ("lender", loan)));
+    let mut loans = Vec::with_capacity(borrowed.len() + lent.len());
+    for (role, loan) in tagged {
+        let metadata = tracker.get_metadata(loan.id)
+            .map_err(AppError::Database)?;
+        let risk_score = tracker.risk_score(loan, chrono::Utc::now())
+            .map_err(AppError::Database)?;
+        loans.push(PartyLoanJson { role, loan: loan_api_json(loan, metadata, risk_score, &recovery) });
+    }
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "user_id": user_id,
+        "loans": loans
+    }))))
+}
+
+pub async fn lender_risk_distribution(
+    identity: Identity,
+    db: web::Data<Db>,
+    config: Option<web::Data<Config>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = require_lender(&identity, &db)?;
+
+    let tracker = LoanTracker::new(&db).with_risk_model(risk_model(config.as_ref()));
+    let bands = tracker.risk_distribution(&user.id)
+        .map_err(AppError::Database)?;
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "lender_id": user.id,
+        "bands": bands
+    }))))
+}
+
+#[derive(Deserialize)]
+pub struct AtRiskQuery {
+    /// Only loans whose risk score is above this, between 0 and 1.
+    #[serde(default = "default_risk_threshold")]
+    threshold: f64,
+}
+
+fn default_risk_threshold() -> f64 {
+    0.5
+}
+
+/// Collections worklist: open loans above `?threshold=` (default 0.5), riskiest first, each with
+/// the borrower's name, what is still owed and the recommended next step. Lenders see their own
+/// loans, admins everyone's. Paginated like other lists.
+pub async fn at_risk_report(
+    query: web::Query<AtRiskQuery>,
+    page: web::Query<Pagination>,
+    identity: Identity,
+    db: web::Data<Db>,
+    config: Option<web::Data<Config>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = current_user(&identity, &db)?;
+    if !matches!(user.role, UserRole::Lender | UserRole::Admin) {
+        return Err(AppError::InsufficientPermissions);
+    }
+    if !query.threshold.is_finite() || !(0.0..=1.0).contains(&query.threshold) {
+        return Err(AppError::InvalidInput("threshold must be between 0 and 1".to_string()));
+    }
+
+    let mut ranked = LoanTracker::new(&db).with_risk_model(risk_model(config.as_ref())).loans_above_risk(query.threshold)
+        .map_err(AppError::Database)?;
+    if user.role == UserRole::Lender {
+        ranked.retain(|r| r.loan.lender_id == user.id);
+    }
+    let total = ranked.len();
+    let recovery = recovery_engine(config.as_ref());
+    let users = UserManager::new(&db);
+    let now = chrono::Utc::now();
+    let mut items = Vec::new();
+    for r in page.page_of(ranked) {
+        let loan = &r.loan;
+        let borrower_name = users.get_user(&loan.borrower_id)
+            .map_err(AppError::Database)?
+            .map(|b| b.name);
+        let missed = loan.missed_installments(now);
+        items.push(serde_json::json!({
+            "loan_id": loan.id,
+            "borrower_id": loan.borrower_id,
+            "borrower_name": borrower_name,
+            "lender_id": loan.lender_id,
+            "status": format!("{:?}", loan.status).to_lowercase(),
+            "risk_score": r.risk_score,
+            "outstanding": loan.remaining_balance().ok_or(MoneyOverflow)?,
+            "currency": loan.currency,
+            "days_overdue": loan.days_overdue(now),
+            "missed_installments": missed,
+            "recommended_action": recovery.recommend_action(r.risk_score, missed, &loan.status),
+        }));
+    }
+    Ok(Ok(HttpResponse::Ok().json(page.wrap(items, total))))
+}
+
+pub async fn lender_portfolio_summary(
+    path: web::Path<String>,
+    identity: Identity,
+    db: web::Data<Db>,
+    config: Option<web::Data<Config>>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = require_lender(&identity, &db)?;
+    let lender_id = path.into_inner();
+    if lender_id != user.id {
+        return Err(AppError::InsufficientPermissions);
+    }
+
+    let summary = LoanTracker::new(&db).with_risk_model(risk_model(config.as_ref())).portfolio_summary(&lender_id)
+        .map_err(AppError::Database)?;
+
+    Ok(Ok(HttpResponse::Ok().json(summary)))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LenderPolicyReq {
+    min_principal: Option<f64>,
+    max_principal: Option<f64>,
+}
+
+/// Your own principal limits and the limits actually enforced once the global config fills
+/// in whatever you left unset.
+pub async fn get_lender_policy(
+    path: web::Path<String>,
+    identity: Identity,
+    db: web::Data<Db>,
+    config: web::Data<Config>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = require_lender(&identity, &db)?;
+    let lender_id = path.into_inner();
+    if lender_id != user.id {
+        return Err(AppError::InsufficientPermissions);
+    }
+
+    let policy = db.load_lender_policy(&lender_id).map_err(AppError::Database)?.unwrap_or_default();
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "lender_id": lender_id,
+        "policy": policy,
+        "effective": policy.or(config.principal_limits()),
+    }))))
+}
+
+/// Replaces your principal limits; a `null` bound falls back to the global config.
+pub async fn set_lender_policy(
+    path: web::Path<String>,
+    data: JsonBody<LenderPolicyReq>,
+    identity: Identity,
+    db: web::Data<Db>,
+    config: web::Data<Config>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = require_lender(&identity, &db)?;
+    let lender_id = path.into_inner();
+    if lender_id != user.id {
+        return Err(AppError::InsufficientPermissions);
+    }
+
+    let policy = PrincipalLimits { min_principal: data.min_principal, max_principal: data.max_principal };
+    for bound in [policy.min_principal, policy.max_principal].into_iter().flatten() {
+        validate_principal(bound).map_err(AppError::InvalidInput)?;
+    }
+    if let (Some(min), Some(max)) = (policy.min_principal, policy.max_principal) {
+        if min > max {
+            return Err(AppError::InvalidInput("min_principal must not exceed max_principal".to_string()));
+        }
+    }
+    db.save_lender_policy(&lender_id, &policy).map_err(AppError::Database)?;
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "lender_id": lender_id,
+        "policy": policy,
+        "effective": policy.or(config.principal_limits()),
+    }))))
+}
+
+/// System-wide totals. Restricted to lenders and admins: borrowers have no business seeing the
+/// size of the whole book, while lenders already see aggregate risk through their own summaries.
+pub async fn get_stats(
+    identity: Identity,
+    db: web::Data<Db>,
+) -> AppResult<ActixResult<HttpResponse>> {
+    let user = current_user(&identity, &db)?;
+    if !matches!(user.role, UserRole::Lender | UserRole::Admin) {
+        return Err(AppError::InsufficientPermissions);
+    }
+
+    let counts = db.loan_status_counts()
+        .map_err(AppError::Database)?;
+    let loans_by_status: BTreeMap<String, i64> = LoanStatus::ALL
+        .iter()
+        .map(|status| {
+            let n = counts.get(&format!("{:?}", status)).copied().unwrap_or(0);
+            (format!("{:?}", status).to_lowercase(), n)
+        })
+        .collect();
+    let principal_outstanding = LoanTracker::new(&db).get_all_loans()
+        .map_err(AppError::Database)?
+        .iter()
+        .try_fold(Money::ZERO, |total, loan| total.checked_add(loan.principal_outstanding()?))
+        .ok_or(MoneyOverflow)?;
+
+    Ok(Ok(HttpResponse::Ok().json(serde_json::json!({
+        "total_users": db.count_users().map_err(AppError::Database)?,
+        "total_loans": counts.values().sum::<i64>(),
+        "principal_outstanding": principal_outstanding,
+        "loans_by_status": loans_by_status,
+    }))))
+}
+
+pub async fn run_server(config: Config) -> std::io::Result<()> {
+    log::info!("🚀 Smart Loan Recovery Server starting at http://{}", config.server_addr());
+    log::info!(
+        "Web UI (same-origin, avoids CORS): http://{}/app/",
+        config.server_addr()
+    );
+
+    // Initialize Firebase authentication services
+    log::info!("🔐 Initializing Firebase authentication...");
+    let auth_state: web::Data<AuthState> = match init_auth_services().await {
+        Ok(state) => {
+            log::info!("✅ Firebase authentication initialized successfully");
+            web::Data::new(state)
+        }
+        Err(e) => {
+            log::error!("❌ Failed to initialize Firebase authentication: {}", e);
+            log::warn!("⚠️  Starting server WITHOUT Firebase authentication - only demo mode will work");
+            // Create a minimal auth state for demo mode
+            panic!("Firebase auth initialization failed. Please check .env.firebase configuration.");
+        }
+    };
+
+    // Initialize token blacklist for logout functionality
+    let token_blacklist = web::Data::new(Arc::new(TokenBlacklist::new()));
+
+    let fe = std::path::Path::new(&config.frontend_dir);
+    if !fe.is_dir() {
+        log::warn!(
+            "FRONTEND_DIR {:?} is not a directory — GET /app/ will fail. Run `cargo run` from the repo root or set FRONTEND_DIR.",
+            fe
+        );
+    }
+
+    log::info!("Server configured successfully");
+
+    let _config_clone = config.clone();
+    let frontend_dir = _config_clone.frontend_dir.clone();
+    let concurrency_limit = ConcurrencyLimit::new(config.max_in_flight_requests);
+    // One pool shared by every worker; each request checks out its own connection.
+    let db = match Db::new_with_settings(&config.database_url, config.db_pool_size, config.db_busy_timeout_ms) {
+        Ok(db) => db.with_write_retries(config.db_write_retries),
+        Err(e) => {
+            log::error!("Failed to create database connection pool: {}", e);
+            panic!("Database connection failed");
+        }
+    };
+    let webhook = WebhookDispatcher::spawn(config.webhook_url.clone());
+    spawn_auto_debit_job(db.clone(), webhook.clone(), config.auto_debit_interval_secs);
+    spawn_backup_job(db.clone(), config.backup_dir.clone(), config.backup_retention, config.backup_interval_secs);
+    let db = web::Data::new(db);
+    let webhook = web::Data::new(webhook);
+    let shutdown_db = db.clone();
+    // Swap in an email/SMS backend here; reminders are only logged for now
+    let notifier: web::Data<dyn NotificationChannel> = web::Data::from(Arc::new(LogChannel) as Arc<dyn NotificationChannel>);
+    
+    let server = HttpServer::new(move || {
+
+        let key = Key::derive_from(_config_clone.session_secret.as_bytes());
         let session_middleware = SessionMiddleware::builder(
             CookieSessionStore::default(),
             key,
         )
-        .cookie_secure(false)
+        .cookie_secure(_config_clone.cookie_secure)
+        .session_lifecycle(
+            PersistentSession::default()
+                .session_ttl(actix_web::cookie::time::Duration::seconds(_config_clone.session_ttl_secs as i64)),
+        )
         .build();
 
         // Initialize JWT auth middleware
@@ -357,12 +1926,17 @@ pub async fn run_server(config: Config) -> std::io::Result<()> {
         let jwt_auth = JwtAuth::new(auth_state.jwt.clone(), token_blacklist_arc);
 
         App::new()
-            .app_data(web::Data::new(db))
+            .app_data(db.clone())
+            .app_data(web::Data::new(_config_clone.clone()))
             .app_data(auth_state.clone())
             .app_data(token_blacklist.clone())
+            .app_data(notifier.clone())
+            .app_data(webhook.clone())
             .wrap(IdentityMiddleware::default())
             .wrap(session_middleware)
-            .wrap(Logger::default())
+            .wrap(concurrency_limit.clone())
+            .wrap(AssignRequestId)
+            .wrap(Logger::new(r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T request_id=%{X-Request-Id}o"#))
             .wrap(
                 Cors::default()
                     .allow_any_origin()
@@ -391,9 +1965,45 @@ pub async fn run_server(config: Config) -> std::io::Result<()> {
                             "/auth/me",
                             "/auth/google"
                         ],
-                        "users": ["/users"],
-                        "loans": ["/loans"],
-                        "recovery": ["/overdues", "/recommend/{loan_id}"]
+                        "session": ["/login", "/logout"],
+                        "users": ["/users", "/users/{id}", "/users/{id}/role"],
+                        "loans": [
+                            "/loans",
+                            "/loans/batch",
+                            "/loans/mine",
+                            "/loans/{id}",
+                            "/loans/{id}/archive",
+                            "/loans/{id}/restore",
+                            "/loans/{id}/settlement",
+                            "/loans/{id}/status",
+                            "/loans/{id}/schedule",
+                            "/loans/{id}/history",
+                            "/loans/{id}/remind",
+                            "/loans/{id}/payments",
+                            "/loans/{id}/settle",
+                            "/loans/{id}/metadata",
+                            "/loans/{id}/metadata/{key}",
+                            "/me/all-loans",
+                            "/me/wallet",
+                            "/me/wallet/top-up",
+                            "/me/loans/{id}/extension",
+                            "/loans/{id}/extensions/{req}/approve",
+                            "/loans/{id}/extensions/{req}/reject"
+                        ],
+                        "lenders": ["/lenders/me/risk-distribution", "/lenders/{id}/summary", "/lenders/{id}/policy"],
+                        "stats": ["/stats"],
+                        "reports": ["/reports/at-risk"],
+                        "recovery": [
+                            "/overdues",
+                            "/defaults",
+                            "/reconcile",
+                            "/recommend/{loan_id}",
+                            "/recovery/actions",
+                            "/recovery/actions/{id}/outcome",
+                            "/recovery/effectiveness",
+                            "/recovery/compare-models",
+                            "/recovery/reminders/generate"
+                        ]
                     }
                 })))
             }))
@@ -411,14 +2021,103 @@ pub async fn run_server(config: Config) -> std::io::Result<()> {
                     .wrap(jwt_auth.clone())
                     .route("/users", web::get().to(get_users))
                     .route("/users", web::post().to(register_user))
+                    .route("/login", web::post().to(login))
+                    .route("/logout", web::post().to(logout))
+                    .route("/users/{id}", web::get().to(get_user))
+                    .route("/users/{id}", web::delete().to(delete_user))
+                    .route("/users/{id}/role", web::patch().to(update_user_role))
                     .route("/loans", web::get().to(get_loans))
                     .route("/loans", web::post().to(create_loan))
+                    .route("/loans/batch", web::post().to(create_loans_batch))
+                    .route("/loans/mine", web::get().to(my_loans))
+                    .route("/loans/{id}", web::get().to(get_loan))
+                    .route("/loans/{id}", web::patch().to(update_loan_terms))
+                    .route("/loans/{id}", web::delete().to(delete_loan))
+                    .route("/loans/{id}/archive", web::post().to(archive_loan))
+                    .route("/loans/{id}/restore", web::post().to(restore_loan))
+                    .route("/loans/{id}/settlement", web::get().to(settlement_quote))
+                    .route("/loans/{id}/status", web::get().to(loan_status))
+                    .route("/loans/{id}/schedule", web::get().to(get_loan_schedule))
+                    .route("/loans/{id}/history", web::get().to(get_loan_history))
+                    .route("/loans/{id}/remind", web::post().to(remind_loan))
+                    .route("/loans/{id}/payments", web::post().to(record_loan_payment))
+                    .route("/loans/{id}/settle", web::post().to(settle_loan))
+                    .route("/loans/{id}/metadata", web::get().to(get_loan_metadata))
+                    .route("/loans/{id}/metadata/{key}", web::get().to(get_loan_metadata_key))
+                    .route("/loans/{id}/metadata/{key}", web::put().to(set_loan_metadata))
+                    .route("/loans/{id}/metadata/{key}", web::delete().to(delete_loan_metadata))
+                    .route("/me/all-loans", web::get().to(my_all_loans))
+                    .route("/me/wallet", web::get().to(get_wallet))
+                    .route("/me/wallet/top-up", web::post().to(top_up_wallet))
+                    .route("/me/loans/{id}/extension", web::post().to(request_extension))
+                    .route("/loans/{id}/extensions/{req}/approve", web::post().to(approve_extension))
+                    .route("/loans/{id}/extensions/{req}/reject", web::post().to(reject_extension))
                     .route("/overdues", web::post().to(flag_overdues))
+                    .route("/defaults", web::post().to(flag_defaults))
+                    .route("/reconcile", web::post().to(reconcile_loans))
                     .route("/recommend/{loan_id}", web::post().to(recommend_action))
+                    .route("/recovery/actions", web::post().to(log_recovery_action))
+                    .route("/recovery/actions/{id}/outcome", web::put().to(set_recovery_outcome))
+                    .route("/recovery/effectiveness", web::get().to(recovery_effectiveness))
+                    .route("/recovery/compare-models", web::post().to(compare_risk_models))
+                    .route("/recovery/reminders/generate", web::post().to(generate_reminders))
+                    .route("/lenders/me/risk-distribution", web::get().to(lender_risk_distribution))
+                    .route("/lenders/{id}/summary", web::get().to(lender_portfolio_summary))
+                    .route("/lenders/{id}/policy", web::get().to(get_lender_policy))
+                    .route("/lenders/{id}/policy", web::put().to(set_lender_policy))
+                    .route("/stats", web::get().to(get_stats))
+                    .route("/reports/at-risk", web::get().to(at_risk_report))
             )
     })
+    .workers(config.workers)
+    .keep_alive(std::time::Duration::from_secs(config.keep_alive_secs))
     .bind(config.server_addr())?
-    .run()
-    .await
+    .shutdown_timeout(config.shutdown_timeout_secs)
+    .disable_signals()
+    .run();
+
+    let handle = server.handle();
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        log::info!("Received shutdown signal, shutting down gracefully");
+        handle.stop(true).await;
+    });
+
+    server.await?;
+    if let Err(e) = shutdown_db.checkpoint() {
+        log::error!("Failed to checkpoint database on shutdown: {}", e);
+    }
+    log::info!("Server stopped");
+    Ok(())
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM where the platform has it (e.g. `docker stop`).
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            log::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                log::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
 
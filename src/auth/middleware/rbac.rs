@@ -54,6 +54,7 @@ impl RequireRole {
     }
 
     /// Check if user has required role
+    #[allow(dead_code)]
     fn check_role(&self, user_role: &UserRole) -> bool {
         if self.require_all {
             // All roles must match (not typically used for simple RBAC)
@@ -8,6 +8,7 @@
 use crate::auth::models::{DecodedFirebaseToken, UserLink};
 use crate::db::Db;
 use crate::models::UserRole;
+use base64::Engine;
 use chrono::Utc;
 use reqwest::Client;
 use serde_json::{json, Value};
@@ -15,8 +16,10 @@ use std::time::Duration;
 
 pub struct FirebaseAuthService {
     client: Client,
+    #[allow(dead_code)]
     project_id: String,
     api_key: String,
+    #[allow(dead_code)]
     service_account_key: Option<Value>,
 }
 
@@ -39,7 +42,7 @@ impl FirebaseAuthService {
             }
         } else if let Ok(key_base64) = std::env::var("FIREBASE_SERVICE_ACCOUNT_JSON_BASE64") {
             if !key_base64.is_empty() {
-                let decoded = base64::decode(key_base64)?;
+                let decoded = base64::engine::general_purpose::STANDARD.decode(key_base64)?;
                 let content = String::from_utf8(decoded)?;
                 Some(serde_json::from_str(&content)?)
             } else {
@@ -16,6 +16,7 @@ use serde::{Deserialize, Serialize};
 pub struct JwtService {
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
+    #[allow(dead_code)]
     jwt_secret: String,
     access_token_expiry_hours: i64,
     refresh_token_expiry_days: i64,
@@ -159,11 +160,7 @@ impl JwtService {
 
     /// Extract token from Authorization header
     pub fn extract_token_from_header(auth_header: &str) -> Option<&str> {
-        if auth_header.starts_with("Bearer ") {
-            Some(&auth_header[7..])
-        } else {
-            None
-        }
+        auth_header.strip_prefix("Bearer ")
     }
 
     /// Get token expiration time
@@ -15,6 +15,12 @@ pub struct TokenBlacklist {
     revoked_tokens: Mutex<HashSet<String>>,
 }
 
+impl Default for TokenBlacklist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TokenBlacklist {
     pub fn new() -> Self {
         Self {
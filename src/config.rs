@@ -1,13 +1,72 @@
+use crate::models::{InterestRounding, LateFeePolicy, PrincipalLimits, RiskModel};
+use crate::recovery::RecoveryThresholds;
+use crate::timezone::Zone;
 use std::env;
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub database_url: String,
+    /// Maximum number of pooled SQLite connections shared by all workers.
+    pub db_pool_size: u32,
+    /// Milliseconds a connection waits on a locked database before failing with `SQLITE_BUSY`.
+    pub db_busy_timeout_ms: u64,
+    /// Retries, with exponential backoff, for a loan or user write that still hits `SQLITE_BUSY`.
+    pub db_write_retries: u32,
+    /// Directory `backup` and the scheduled backup job write timestamped JSON backups into.
+    pub backup_dir: String,
+    /// Timestamped backups kept in `backup_dir`; older ones are deleted after each new backup.
+    pub backup_retention: usize,
+    /// Seconds between scheduled backups while the server runs; 0 disables them.
+    pub backup_interval_secs: u64,
     pub server_host: String,
     pub server_port: u16,
+    /// HTTP worker threads; defaults to the number of CPUs.
+    pub workers: usize,
+    /// Seconds an idle connection is kept open for further requests; 0 closes it after each response.
+    pub keep_alive_secs: u64,
     pub session_secret: String,
+    /// Send the session cookie only over HTTPS. Defaults to on when `PRODUCTION=1`.
+    pub cookie_secure: bool,
+    /// Seconds a session cookie stays valid after it is issued.
+    pub session_ttl_secs: u64,
+    /// Zone (an offset or IANA name) dates are shown in by CLI commands and the demo; storage stays UTC.
+    pub display_timezone: Zone,
     /// Directory containing static HTML/CSS assets (served at `/app`).
     pub frontend_dir: String,
+    /// Cap on open (active or overdue) loans per lender; `None` means unlimited.
+    pub max_active_loans_per_lender: Option<usize>,
+    /// Smallest principal a new loan may have, unless the lender's own policy sets one.
+    pub min_principal: Option<f64>,
+    /// Largest principal a new loan may have, unless the lender's own policy sets one.
+    pub max_principal: Option<f64>,
+    /// Annual rate, in percent, for `create-loan` when `--interest-rate` is left out.
+    pub default_interest_rate: Option<f64>,
+    /// Term for `create-loan` when `--months` is left out.
+    pub default_duration_months: Option<i64>,
+    /// Late-fee policy for loans that don't set their own; `None` charges no late fees.
+    pub late_fee_policy: Option<LateFeePolicy>,
+    /// Percentage of accrued interest waived when a loan is settled before its final due date.
+    pub early_settlement_discount_pct: f64,
+    /// Requests handled at once before new ones are turned away with 503; at least 1.
+    pub max_in_flight_requests: usize,
+    /// Whether schedule interest is rounded on each installment or only on the payoff total.
+    pub interest_rounding: InterestRounding,
+    /// Seconds between wallet auto-debit runs; 0 disables the background job.
+    pub auto_debit_interval_secs: u64,
+    /// Days an overdue loan's earliest missed installment may age before the loan is marked Defaulted.
+    pub default_after_days: i64,
+    /// Days the earliest unpaid installment may be past due before the loan is marked Overdue.
+    pub grace_period_days: i64,
+    /// Risk and missed-payment cutoffs for recovery recommendations.
+    pub recovery_thresholds: RecoveryThresholds,
+    /// Seconds in-flight requests get to finish after SIGTERM/SIGINT before workers are stopped.
+    pub shutdown_timeout_secs: u64,
+    /// URL POSTed a JSON event whenever a loan changes status; `None` disables the webhook.
+    pub webhook_url: Option<String>,
+    /// Seconds an `Idempotency-Key` on `POST /loans` keeps replaying the loan it created.
+    pub idempotency_window_secs: u64,
+    /// Age in days at which a missed installment weighs half as much in risk scores; above 0.
+    pub miss_half_life_days: f64,
 }
 
 impl Config {
@@ -19,20 +78,203 @@ impl Config {
             Ok(s) if !s.trim().is_empty() => s,
             _ => "super-secret-key-change-in-production-at-least-47-characters-long".to_string(),
         };
+        let principal_limits = principal_limits_from_env()?;
 
         Ok(Config {
             database_url: env::var("DATABASE_URL").unwrap_or_else(|_| "loans.db".to_string()),
+            db_pool_size: match env::var("DB_POOL_SIZE") {
+                Ok(v) if !v.trim().is_empty() => v.trim().parse().map_err(|_| "Invalid DB_POOL_SIZE")?,
+                _ => crate::db::DEFAULT_POOL_SIZE,
+            },
+            db_busy_timeout_ms: match env::var("DB_BUSY_TIMEOUT_MS") {
+                Ok(v) if !v.trim().is_empty() => v.trim().parse().map_err(|_| "Invalid DB_BUSY_TIMEOUT_MS")?,
+                _ => crate::db::DEFAULT_BUSY_TIMEOUT_MS,
+            },
+            db_write_retries: match env::var("DB_WRITE_RETRIES") {
+                Ok(v) if !v.trim().is_empty() => v.trim().parse().map_err(|_| "Invalid DB_WRITE_RETRIES")?,
+                _ => crate::db::DEFAULT_WRITE_RETRIES,
+            },
+            backup_dir: env::var("BACKUP_DIR").unwrap_or_else(|_| "backups".to_string()),
+            backup_retention: match env::var("BACKUP_RETENTION") {
+                Ok(v) if !v.trim().is_empty() => match v.trim().parse() {
+                    Ok(n) if n >= 1 => n,
+                    _ => return Err("Invalid BACKUP_RETENTION (must be at least 1)".into()),
+                },
+                _ => crate::db::DEFAULT_BACKUP_RETENTION,
+            },
+            backup_interval_secs: env::var("BACKUP_INTERVAL_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .map_err(|_| "Invalid BACKUP_INTERVAL_SECS")?,
             server_host: env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
             server_port: env::var("SERVER_PORT")
                 .unwrap_or_else(|_| "3000".to_string())
                 .parse()
                 .map_err(|_| "Invalid SERVER_PORT")?,
+            workers: match env::var("WORKERS") {
+                Ok(v) if !v.trim().is_empty() => match v.trim().parse() {
+                    Ok(n) if n >= 1 => n,
+                    _ => return Err("Invalid WORKERS (must be at least 1)".into()),
+                },
+                _ => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            },
+            keep_alive_secs: env::var("KEEP_ALIVE_SECS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .map_err(|_| "Invalid KEEP_ALIVE_SECS")?,
             session_secret,
+            cookie_secure: match env::var("COOKIE_SECURE") {
+                Ok(v) if !v.trim().is_empty() => v.trim().parse().map_err(|_| "Invalid COOKIE_SECURE (true or false)")?,
+                _ => env::var("PRODUCTION").map(|v| v.trim() == "1").unwrap_or(false),
+            },
+            session_ttl_secs: env::var("SESSION_TTL_SECS")
+                .unwrap_or_else(|_| "86400".to_string())
+                .parse()
+                .map_err(|_| "Invalid SESSION_TTL_SECS")?,
+            display_timezone: match env::var("DISPLAY_TIMEZONE") {
+                Ok(v) if !v.trim().is_empty() => Zone::parse(&v).ok_or("Invalid DISPLAY_TIMEZONE (UTC, an offset like +03:00, or a zone name like Africa/Nairobi)")?,
+                _ => crate::timezone::utc().into(),
+            },
             frontend_dir: env::var("FRONTEND_DIR").unwrap_or_else(|_| "frontend".to_string()),
+            max_active_loans_per_lender: match env::var("MAX_ACTIVE_LOANS_PER_LENDER") {
+                Ok(v) if !v.trim().is_empty() => Some(v.trim().parse().map_err(|_| "Invalid MAX_ACTIVE_LOANS_PER_LENDER")?),
+                _ => None,
+            },
+            min_principal: principal_limits.min_principal,
+            max_principal: principal_limits.max_principal,
+            default_interest_rate: match env::var("DEFAULT_INTEREST_RATE") {
+                Ok(v) if !v.trim().is_empty() => {
+                    let rate = v.trim().parse().map_err(|_| "Invalid DEFAULT_INTEREST_RATE")?;
+                    crate::loan::validate_interest_rate(rate).map_err(|e| format!("Invalid DEFAULT_INTEREST_RATE: {}", e))?;
+                    Some(rate)
+                }
+                _ => None,
+            },
+            default_duration_months: match env::var("DEFAULT_DURATION_MONTHS") {
+                Ok(v) if !v.trim().is_empty() => {
+                    let months = v.trim().parse().map_err(|_| "Invalid DEFAULT_DURATION_MONTHS")?;
+                    crate::loan::validate_duration(months).map_err(|e| format!("Invalid DEFAULT_DURATION_MONTHS: {}", e))?;
+                    Some(months)
+                }
+                _ => None,
+            },
+            late_fee_policy: match env::var("LATE_FEE_POLICY") {
+                Ok(v) if !v.trim().is_empty() => Some(LateFeePolicy::parse(&v).ok_or("Invalid LATE_FEE_POLICY (flat:<amount> or percentage:<pct>)")?),
+                // Deprecated: the old settlement-only flat fee, now the same as LATE_FEE_POLICY=flat:<amount>
+                _ => match env::var("LATE_FEE_PER_MISSED_INSTALLMENT") {
+                    Ok(v) if !v.trim().is_empty() && v.trim().parse::<f64>() != Ok(0.0) => Some(
+                        LateFeePolicy::parse(&format!("flat:{}", v.trim())).ok_or("Invalid LATE_FEE_PER_MISSED_INSTALLMENT")?,
+                    ),
+                    _ => None,
+                },
+            },
+            early_settlement_discount_pct: env::var("EARLY_SETTLEMENT_DISCOUNT_PCT")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .map_err(|_| "Invalid EARLY_SETTLEMENT_DISCOUNT_PCT")?,
+            max_in_flight_requests: match env::var("MAX_IN_FLIGHT_REQUESTS") {
+                Ok(v) if !v.trim().is_empty() => match v.trim().parse() {
+                    Ok(n) if n >= 1 => n,
+                    _ => return Err("Invalid MAX_IN_FLIGHT_REQUESTS (must be at least 1)".into()),
+                },
+                _ => 256,
+            },
+            interest_rounding: match env::var("INTEREST_ROUNDING") {
+                Ok(v) if !v.trim().is_empty() => InterestRounding::parse(&v).ok_or("Invalid INTEREST_ROUNDING (per_installment or at_payoff)")?,
+                _ => InterestRounding::default(),
+            },
+            auto_debit_interval_secs: env::var("AUTO_DEBIT_INTERVAL_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .map_err(|_| "Invalid AUTO_DEBIT_INTERVAL_SECS")?,
+            default_after_days: env::var("DEFAULT_AFTER_DAYS")
+                .unwrap_or_else(|_| crate::loan::DEFAULT_GRACE_DAYS.to_string())
+                .parse()
+                .map_err(|_| "Invalid DEFAULT_AFTER_DAYS")?,
+            grace_period_days: env::var("GRACE_PERIOD_DAYS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .map_err(|_| "Invalid GRACE_PERIOD_DAYS")?,
+            recovery_thresholds: recovery_thresholds_from_env()?,
+            shutdown_timeout_secs: env::var("SHUTDOWN_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .map_err(|_| "Invalid SHUTDOWN_TIMEOUT_SECS")?,
+            webhook_url: env::var("WEBHOOK_URL").ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()),
+            idempotency_window_secs: env::var("IDEMPOTENCY_WINDOW_SECS")
+                .unwrap_or_else(|_| "86400".to_string())
+                .parse()
+                .map_err(|_| "Invalid IDEMPOTENCY_WINDOW_SECS")?,
+            miss_half_life_days: match env::var("MISS_HALF_LIFE_DAYS") {
+                Ok(v) if !v.trim().is_empty() => match v.trim().parse::<f64>() {
+                    Ok(days) if days.is_finite() && days > 0.0 => days,
+                    _ => return Err("Invalid MISS_HALF_LIFE_DAYS (must be greater than 0)".into()),
+                },
+                _ => RiskModel::default().miss_half_life_days,
+            },
         })
     }
 
+    pub fn settlement_policy(&self) -> crate::loan::SettlementPolicy {
+        crate::loan::SettlementPolicy {
+            late_fee_policy: self.late_fee_policy,
+            early_settlement_discount_pct: self.early_settlement_discount_pct,
+        }
+    }
+
+    /// The default [`RiskModel`] with the configured miss half-life.
+    pub fn risk_model(&self) -> RiskModel {
+        RiskModel { miss_half_life_days: self.miss_half_life_days, ..RiskModel::default() }
+    }
+
+    pub fn principal_limits(&self) -> PrincipalLimits {
+        PrincipalLimits {
+            min_principal: self.min_principal,
+            max_principal: self.max_principal,
+        }
+    }
+
     pub fn server_addr(&self) -> String {
         format!("{}:{}", self.server_host, self.server_port)
     }
-}
\ No newline at end of file
+}
+
+/// Reads `MIN_PRINCIPAL` and `MAX_PRINCIPAL`. Each bound that is set must itself be a valid
+/// principal, and the minimum may not exceed the maximum.
+fn principal_limits_from_env() -> Result<PrincipalLimits, Box<dyn std::error::Error>> {
+    fn read(name: &str) -> Result<Option<f64>, String> {
+        match env::var(name) {
+            Ok(v) if !v.trim().is_empty() => {
+                let bound = v.trim().parse().map_err(|_| format!("Invalid {}", name))?;
+                crate::loan::validate_principal(bound).map_err(|e| format!("Invalid {}: {}", name, e))?;
+                Ok(Some(bound))
+            }
+            _ => Ok(None),
+        }
+    }
+    let limits = PrincipalLimits { min_principal: read("MIN_PRINCIPAL")?, max_principal: read("MAX_PRINCIPAL")? };
+    if let (Some(min), Some(max)) = (limits.min_principal, limits.max_principal) {
+        if min > max {
+            return Err("Invalid MIN_PRINCIPAL (must not exceed MAX_PRINCIPAL)".into());
+        }
+    }
+    Ok(limits)
+}
+
+/// Reads `ESCALATE_RISK`, `RENEGOTIATE_RISK`, `ESCALATE_MISSED` and `RENEGOTIATE_MISSED`;
+/// unset ones keep their default.
+fn recovery_thresholds_from_env() -> Result<RecoveryThresholds, Box<dyn std::error::Error>> {
+    fn read<T: std::str::FromStr>(name: &str, default: T) -> Result<T, String> {
+        match env::var(name) {
+            Ok(v) if !v.trim().is_empty() => v.trim().parse().map_err(|_| format!("Invalid {}", name)),
+            _ => Ok(default),
+        }
+    }
+    let defaults = RecoveryThresholds::default();
+    Ok(RecoveryThresholds {
+        escalate_risk: read("ESCALATE_RISK", defaults.escalate_risk)?,
+        renegotiate_risk: read("RENEGOTIATE_RISK", defaults.renegotiate_risk)?,
+        escalate_missed: read("ESCALATE_MISSED", defaults.escalate_missed)?,
+        renegotiate_missed: read("RENEGOTIATE_MISSED", defaults.renegotiate_missed)?,
+    })
+}
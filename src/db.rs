@@ -1,36 +1,404 @@
-use rusqlite::{Connection, Result, params};
-use crate::models::{User, UserRole, Loan, LoanStatus};
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Connection, Result, Transaction, params};
+use crate::models::{AuditEntry, ExtensionRequest, ExtensionStatus, InterestKind, User, UserRole, Loan, LoanStatus, Payment, PaymentFrequency, PrincipalLimits, RoleChange, ScheduledPayment};
+use crate::money::{Money, MoneyOverflow};
+use crate::recovery::{RecoveryAction, RecoveryActionRecord, RecoveryOutcome};
 use chrono::{DateTime, Duration, Utc};
 use uuid::Uuid;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
+/// Timestamped backups `write_backup` keeps unless told otherwise.
+pub const DEFAULT_BACKUP_RETENTION: usize = 7;
+const USERS_BACKUP_PREFIX: &str = "users_backup_";
+const LOANS_BACKUP_PREFIX: &str = "loans_backup_";
+/// UTC, down to the millisecond so backups taken in the same second don't overwrite each other.
+const BACKUP_STAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// Files written, and old ones deleted, by one `write_backup`.
+#[derive(Debug, Clone)]
+pub struct BackupFiles {
+    pub users: PathBuf,
+    pub loans: PathBuf,
+    pub removed: Vec<PathBuf>,
+}
+
+/// Column list matching `row_to_user`.
+const USER_COLUMNS: &str = "id, name, role, email, lender_id, organization, wallet_balance_cents, phone, created_at, updated_at";
+
+/// Column list matching `row_to_loan`.
+const LOAN_COLUMNS: &str = "id, borrower_id, lender_id, principal_cents, interest_rate, disbursement_date, start_date, last_repayment_date, status, repayment_schedule, amount_paid_cents, interest_kind, currency, created_at, updated_at, promo_months, archived, payment_frequency, guarantor_id, late_fee_policy";
+
+/// Columns `?sort=` may name on loan listings.
+pub const LOAN_SORT_COLUMNS: &[&str] = &["created_at", "updated_at", "disbursement_date", "principal", "interest_rate", "status", "id"];
+/// Columns `?sort=` may name on user listings.
+pub const USER_SORT_COLUMNS: &[&str] = &["created_at", "updated_at", "name", "role", "id"];
+
+/// Validated ORDER BY for list queries. `id` is always appended as a tie-breaker
+/// so repeated queries (and pages of them) come back in the same order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortOrder {
+    column: &'static str,
+    descending: bool,
+}
+
+impl SortOrder {
+    pub const DEFAULT: SortOrder = SortOrder { column: "created_at", descending: false };
+
+    /// Parses `column` or `-column` (descending); `None` if the column is not in `allowed`.
+    pub fn parse(spec: &str, allowed: &[&'static str]) -> Option<SortOrder> {
+        let spec = spec.trim();
+        let (name, descending) = match spec.strip_prefix('-') {
+            Some(rest) => (rest, true),
+            None => (spec, false),
+        };
+        allowed
+            .iter()
+            .find(|c| c.eq_ignore_ascii_case(name))
+            .map(|&column| SortOrder { column, descending })
+    }
+
+    fn to_sql(self) -> String {
+        let dir = if self.descending { "DESC" } else { "ASC" };
+        if self.column == "id" {
+            format!("ORDER BY id {}", dir)
+        } else {
+            format!("ORDER BY {} {}, id ASC", self.column, dir)
+        }
+    }
+}
+
+/// Schema changes after the original `users` / `loans` tables, applied in order by
+/// `run_migrations`. The database's `PRAGMA user_version` is the number already applied.
+/// Only ever append: editing or reordering an entry would skip it on existing databases.
+const MIGRATIONS: &[&str] = &[
+    // 1-3: contact and ownership fields on users
+    "ALTER TABLE users ADD COLUMN email TEXT",
+    "ALTER TABLE users ADD COLUMN lender_id TEXT",
+    "ALTER TABLE users ADD COLUMN organization TEXT",
+    // 4
+    "CREATE TABLE IF NOT EXISTS firebase_user_links (
+        firebase_uid TEXT PRIMARY KEY,
+        local_user_id TEXT NOT NULL UNIQUE,
+        email TEXT NOT NULL,
+        role TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    )",
+    // 5-6: creation order for stable listings
+    "ALTER TABLE users ADD COLUMN created_at TEXT",
+    "ALTER TABLE loans ADD COLUMN created_at TEXT",
+    // 7-9
+    "CREATE TABLE IF NOT EXISTS payments (
+        id TEXT PRIMARY KEY,
+        loan_id TEXT NOT NULL,
+        amount REAL NOT NULL,
+        paid_at TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS recovery_actions (
+        id TEXT PRIMARY KEY,
+        loan_id TEXT NOT NULL,
+        action TEXT NOT NULL,
+        outcome TEXT,
+        taken_at TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS extension_requests (
+        id TEXT PRIMARY KEY,
+        loan_id TEXT NOT NULL,
+        installment_index INTEGER NOT NULL,
+        current_due TEXT NOT NULL,
+        requested_due TEXT NOT NULL,
+        status TEXT NOT NULL,
+        requested_at TEXT NOT NULL,
+        decided_at TEXT
+    )",
+    // 10
+    "ALTER TABLE users ADD COLUMN wallet_balance REAL NOT NULL DEFAULT 0",
+    // 11-12: running paid total, starting from the payments already on file
+    "ALTER TABLE loans ADD COLUMN amount_paid REAL NOT NULL DEFAULT 0",
+    "UPDATE loans SET amount_paid = (SELECT SUM(amount) FROM payments WHERE payments.loan_id = loans.id)
+     WHERE EXISTS (SELECT 1 FROM payments WHERE payments.loan_id = loans.id)",
+    // 13
+    "CREATE TABLE IF NOT EXISTS loan_metadata (
+        loan_id TEXT NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        PRIMARY KEY (loan_id, key)
+    )",
+    // 14: loans created before interest kinds were all amortized
+    "ALTER TABLE loans ADD COLUMN interest_kind TEXT NOT NULL DEFAULT 'Amortized'",
+    // 15
+    "ALTER TABLE users ADD COLUMN phone TEXT",
+    // 16: who moved a loan between statuses, and when
+    "CREATE TABLE IF NOT EXISTS audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        loan_id TEXT NOT NULL,
+        from_status TEXT,
+        to_status TEXT NOT NULL,
+        changed_at TEXT NOT NULL,
+        actor TEXT NOT NULL
+    )",
+    // 17: argon2 PHC string; NULL for users who never set a password
+    "ALTER TABLE users ADD COLUMN password_hash TEXT",
+    // 18: lookups behind overdue/default sweeps, party filters and login
+    "CREATE INDEX IF NOT EXISTS idx_loans_status ON loans(status);
+     CREATE INDEX IF NOT EXISTS idx_loans_borrower_id ON loans(borrower_id);
+     CREATE INDEX IF NOT EXISTS idx_loans_lender_id ON loans(lender_id);
+     CREATE INDEX IF NOT EXISTS idx_users_name ON users(name);",
+    // 19: ISO 4217 code; loans created before this were all dollars
+    "ALTER TABLE loans ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD'",
+    // 20: last write time; backfilled from created_at at startup
+    "ALTER TABLE users ADD COLUMN updated_at TEXT;
+     ALTER TABLE loans ADD COLUMN updated_at TEXT;",
+    // 21: exact amounts in cents; the REAL columns are still written for sorting and older readers
+    "ALTER TABLE loans ADD COLUMN principal_cents INTEGER;
+     ALTER TABLE loans ADD COLUMN amount_paid_cents INTEGER;
+     ALTER TABLE payments ADD COLUMN amount_cents INTEGER;
+     UPDATE loans SET principal_cents = CAST(ROUND(principal * 100) AS INTEGER),
+                      amount_paid_cents = CAST(ROUND(amount_paid * 100) AS INTEGER);
+     UPDATE payments SET amount_cents = CAST(ROUND(amount * 100) AS INTEGER);",
+    // 22: leading interest-free installments
+    "ALTER TABLE loans ADD COLUMN promo_months INTEGER NOT NULL DEFAULT 0",
+    // 23: soft delete; archived loans are kept but left out of listings and sweeps
+    "ALTER TABLE loans ADD COLUMN archived INTEGER NOT NULL DEFAULT 0",
+    // 24: installment spacing; every earlier loan was monthly
+    "ALTER TABLE loans ADD COLUMN payment_frequency TEXT NOT NULL DEFAULT 'Monthly'",
+    // 25: optional guarantor, told alongside the borrower when the loan defaults
+    "ALTER TABLE loans ADD COLUMN guarantor_id TEXT",
+    // 26: per-lender principal limits; NULL bounds fall back to the global config
+    "CREATE TABLE IF NOT EXISTS lender_policies (
+        lender_id TEXT PRIMARY KEY,
+        min_principal REAL,
+        max_principal REAL
+    )",
+    // 27: Idempotency-Key replay for POST /loans, scoped to the user who sent the key
+    "CREATE TABLE IF NOT EXISTS idempotency (
+        user_id TEXT NOT NULL,
+        key TEXT NOT NULL,
+        loan_id TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY (user_id, key)
+    )",
+    // 28: JSON late-fee policy; NULL defers to LATE_FEE_POLICY
+    "ALTER TABLE loans ADD COLUMN late_fee_policy TEXT",
+    // 29: who changed a user's role, and when
+    "CREATE TABLE IF NOT EXISTS role_audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id TEXT NOT NULL,
+        from_role TEXT NOT NULL,
+        to_role TEXT NOT NULL,
+        changed_at TEXT NOT NULL,
+        actor TEXT NOT NULL
+    )",
+    // 30: wallet balances in cents; the REAL column is still written alongside
+    "ALTER TABLE users ADD COLUMN wallet_balance_cents INTEGER NOT NULL DEFAULT 0;
+     UPDATE users SET wallet_balance_cents = CAST(ROUND(wallet_balance * 100) AS INTEGER);",
+];
+
+/// Schema version of a fully migrated database.
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Optional criteria for `query_loans`; unset fields don't narrow the result.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoanFilter {
+    pub status: Option<LoanStatus>,
+    pub borrower_id: Option<String>,
+    pub lender_id: Option<String>,
+    /// Inclusive lower bound on `disbursement_date`.
+    pub disbursed_from: Option<DateTime<Utc>>,
+    /// Inclusive upper bound on `disbursement_date`.
+    pub disbursed_to: Option<DateTime<Utc>>,
+    /// Also return archived loans, which are left out by default.
+    pub include_archived: bool,
+}
+
+impl LoanFilter {
+    pub fn is_empty(&self) -> bool {
+        self.status.is_none()
+            && self.borrower_id.is_none()
+            && self.lender_id.is_none()
+            && self.disbursed_from.is_none()
+            && self.disbursed_to.is_none()
+            && !self.include_archived
+    }
+
+    /// Same test as the SQL `query_loans` runs, for lists that were loaded some other way.
+    pub fn matches(&self, loan: &Loan) -> bool {
+        self.status.as_ref().is_none_or(|s| *s == loan.status)
+            && self.borrower_id.as_ref().is_none_or(|b| *b == loan.borrower_id)
+            && self.lender_id.as_ref().is_none_or(|l| *l == loan.lender_id)
+            && self.disbursed_from.is_none_or(|from| loan.disbursement_date >= from)
+            && self.disbursed_to.is_none_or(|to| loan.disbursement_date <= to)
+            && (self.include_archived || !loan.archived)
+    }
+
+    /// `WHERE` clause (empty when nothing is set) with its positional parameters.
+    fn to_sql(&self) -> (String, Vec<String>) {
+        let mut clauses = Vec::new();
+        let mut values = Vec::new();
+        let criteria = [
+            ("status", self.status.as_ref().map(|s| format!("{:?}", s))),
+            ("borrower_id", self.borrower_id.clone()),
+            ("lender_id", self.lender_id.clone()),
+        ];
+        for (column, value) in criteria {
+            if let Some(value) = value {
+                values.push(value);
+                clauses.push(format!("{} = ?{}", column, values.len()));
+            }
+        }
+        // Dates are stored as UTC RFC 3339 text, which sorts chronologically
+        match (self.disbursed_from, self.disbursed_to) {
+            (Some(from), Some(to)) => {
+                values.push(from.to_rfc3339());
+                values.push(to.to_rfc3339());
+                clauses.push(format!("disbursement_date BETWEEN ?{} AND ?{}", values.len() - 1, values.len()));
+            }
+            (Some(from), None) => {
+                values.push(from.to_rfc3339());
+                clauses.push(format!("disbursement_date >= ?{}", values.len()));
+            }
+            (None, Some(to)) => {
+                values.push(to.to_rfc3339());
+                clauses.push(format!("disbursement_date <= ?{}", values.len()));
+            }
+            (None, None) => {}
+        }
+        if !self.include_archived {
+            clauses.push("archived = 0".to_string());
+        }
+        if clauses.is_empty() {
+            (String::new(), values)
+        } else {
+            (format!("WHERE {}", clauses.join(" AND ")), values)
+        }
+    }
+}
+
+/// Id of the sample loan created by `seed_demo_if_no_loans`.
+const DEMO_LOAN_ID: &str = "00000000-0000-4000-8000-000000000001";
+
+const POOL_ERROR_PREFIX: &str = "connection pool: ";
+
+/// Reports pool checkout failures (e.g. every connection busy past the timeout) as `SQLITE_BUSY`.
+fn pool_error(e: r2d2::Error) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+        Some(format!("{}{}", POOL_ERROR_PREFIX, e)),
+    )
+}
+
+/// Pool size used by `new_with_path`.
+pub const DEFAULT_POOL_SIZE: u32 = 8;
+/// How long a connection waits on a locked database before giving up with `SQLITE_BUSY`.
+pub const DEFAULT_BUSY_TIMEOUT_MS: u64 = 5000;
+
+/// Extra attempts `with_retry` makes after a busy or locked error.
+pub const DEFAULT_WRITE_RETRIES: u32 = 3;
+/// Wait before the first retry; doubled after each further busy error.
+const RETRY_INITIAL_BACKOFF: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Whether `e` is a transient lock conflict (`SQLITE_BUSY` / `SQLITE_LOCKED`) worth retrying.
+/// Pool checkout failures are left out: the pool has already waited out its own timeout.
+fn is_busy(e: &rusqlite::Error) -> bool {
+    match e {
+        rusqlite::Error::SqliteFailure(err, msg) => {
+            matches!(err.code, rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+                && !msg.as_deref().is_some_and(|m| m.starts_with(POOL_ERROR_PREFIX))
+        }
+        _ => false,
+    }
+}
+
+/// Handle to the SQLite database. Cloning is cheap and shares the same connection pool.
+#[derive(Clone)]
 pub struct Db {
-    conn: Connection,
+    pool: Pool<SqliteConnectionManager>,
+    write_retries: u32,
 }
 
 impl Db {
     pub fn new_with_path(database_path: &str) -> Result<Self> {
-        let conn = Connection::open(database_path)?;
-        Self::init_tables(&conn)?;
-        Ok(Db { conn })
+        Self::new_with_pool_size(database_path, DEFAULT_POOL_SIZE)
+    }
+
+    pub fn new_with_pool_size(database_path: &str, pool_size: u32) -> Result<Self> {
+        Self::new_with_settings(database_path, pool_size, DEFAULT_BUSY_TIMEOUT_MS)
+    }
+
+    /// Opens the pool with every connection in WAL mode, so readers don't block the writer,
+    /// and waiting up to `busy_timeout_ms` for a lock instead of failing at once.
+    pub fn new_with_settings(database_path: &str, pool_size: u32, busy_timeout_ms: u64) -> Result<Self> {
+        Self::open(SqliteConnectionManager::file(database_path), pool_size, busy_timeout_ms)
+    }
+
+    /// Private, fully migrated database that lives only as long as this handle and its clones.
+    /// Pooled connections share one named in-memory database rather than each getting its own.
+    pub fn new_in_memory() -> Result<Self> {
+        let uri = format!("file:lendwise-{}?mode=memory&cache=shared", Uuid::new_v4());
+        Self::open(SqliteConnectionManager::file(uri), DEFAULT_POOL_SIZE, DEFAULT_BUSY_TIMEOUT_MS)
+    }
+
+    fn open(manager: SqliteConnectionManager, pool_size: u32, busy_timeout_ms: u64) -> Result<Self> {
+        let manager = manager.with_init(move |conn| {
+            conn.busy_timeout(std::time::Duration::from_millis(busy_timeout_ms))?;
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+            Ok(())
+        });
+        let pool = Pool::builder()
+            .max_size(pool_size.max(1))
+            .build(manager)
+            .map_err(pool_error)?;
+        let db = Db { pool, write_retries: DEFAULT_WRITE_RETRIES };
+        Self::init_tables(&*db.conn()?)?;
+        Ok(db)
+    }
+
+    /// How many times `with_retry` retries a busy or locked write before giving up.
+    pub fn with_write_retries(mut self, retries: u32) -> Self {
+        self.write_retries = retries;
+        self
+    }
+
+    /// Runs `f`, running it again with exponential backoff while it fails with `SQLITE_BUSY` or
+    /// `SQLITE_LOCKED`, up to the configured number of retries. Any other error is returned at once.
+    pub fn with_retry<F, T>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let mut backoff = RETRY_INITIAL_BACKOFF;
+        let mut retries_left = self.write_retries;
+        loop {
+            match f() {
+                Err(e) if is_busy(&e) && retries_left > 0 => {
+                    log::warn!("Database busy ({}); retrying in {:?}", e, backoff);
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                    retries_left -= 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Checks a connection out of the pool for the duration of one operation.
+    fn conn(&self) -> Result<PooledConnection<SqliteConnectionManager>> {
+        self.pool.get().map_err(pool_error)
     }
 
     fn init_tables(conn: &Connection) -> Result<()> {
+        // The original schema; everything added since is a numbered migration.
         conn.execute(
             "CREATE TABLE IF NOT EXISTS users (
                 id TEXT PRIMARY KEY,
                 name TEXT NOT NULL,
-                role TEXT NOT NULL,
-                email TEXT,
-                lender_id TEXT,
-                organization TEXT
+                role TEXT NOT NULL
             )",
             [],
         )?;
-
-        Self::migrate_users_email_column(conn)?;
-
         conn.execute(
             "CREATE TABLE IF NOT EXISTS loans (
                 id TEXT PRIMARY KEY,
@@ -47,27 +415,69 @@ impl Db {
             [],
         )?;
 
+        Self::run_migrations(conn)?;
+
+        Self::migrate_legacy_demo_loan_id(conn)?;
         Self::seed_demo_if_no_loans(conn)?;
+        Self::backfill_created_at(conn)?;
+        Ok(())
+    }
 
-        // Create table for Firebase user links
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS firebase_user_links (
-                firebase_uid TEXT PRIMARY KEY,
-                local_user_id TEXT NOT NULL UNIQUE,
-                email TEXT NOT NULL,
-                role TEXT NOT NULL,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )",
-            [],
-        )?;
+    /// Applies every migration past the database's `user_version`, in order, in one transaction.
+    fn run_migrations(conn: &Connection) -> Result<()> {
+        let current: u32 = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+        if current as usize > MIGRATIONS.len() {
+            log::warn!(
+                "Database schema version {} is newer than this build knows ({}); skipping migrations",
+                current,
+                SCHEMA_VERSION
+            );
+            return Ok(());
+        }
 
-        Ok(())
+        let tx = conn.unchecked_transaction()?;
+        for (i, statement) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+            match tx.execute_batch(statement) {
+                Ok(()) => {}
+                // Databases from before versioning may already have columns added ad hoc
+                Err(rusqlite::Error::SqliteFailure(_, Some(ref msg))) if msg.contains("duplicate column name") => {}
+                Err(e) => {
+                    log::error!("Schema migration {} failed: {}", i + 1, e);
+                    return Err(e);
+                }
+            }
+        }
+        tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        tx.commit()
+    }
+
+    /// Folds the write-ahead log back into the main database file so nothing is left pending
+    /// in `-wal` when the process exits.
+    pub fn checkpoint(&self) -> Result<()> {
+        self.conn()?.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+    }
+
+    /// Runs `f` inside one transaction on a single pooled connection, committing if it returns
+    /// `Ok`. An `Err` (including an early `?` return) or a panic inside `f` drops the
+    /// transaction uncommitted, which rolls back everything it wrote.
+    pub fn transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> Result<T>,
+    {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let value = f(&tx)?;
+        tx.commit()?;
+        Ok(value)
+    }
+
+    /// Version recorded in the database file (`PRAGMA user_version`).
+    pub fn schema_version(&self) -> Result<u32> {
+        self.conn()?.query_row("PRAGMA user_version", [], |r| r.get(0))
     }
 
-    /// One sample borrower + lender + loan so `/app/` login works without a separate lender API flow.
     fn seed_demo_if_no_loans(conn: &Connection) -> Result<()> {
-        let n: i64 = conn.query_row("SELECT COUNT(*) FROM loans", [], |r| r.get(0))?;
+        let n: i64 = conn.query_row("SELECT COUNT(*) FROM loans WHERE archived = 0", [], |r| r.get(0))?;
         if n > 0 {
             return Ok(());
         }
@@ -122,20 +532,23 @@ impl Db {
         )?;
 
         let now = Utc::now();
-        let schedule = serde_json::to_string(&vec![now + Duration::days(30), now + Duration::days(60)])
+        let (principal, interest_rate) = (Money::from_cents(2_485_000), 8.4_f64);
+        let due_dates = [now + Duration::days(30), now + Duration::days(60)];
+        let schedule = serde_json::to_string(&ScheduledPayment::amortize(principal.to_f64(), interest_rate, &due_dates).ok_or(MoneyOverflow)?)
             .map_err(|e| {
                 rusqlite::Error::ToSqlConversionFailure(Box::new(e))
             })?;
 
         conn.execute(
-            "INSERT OR IGNORE INTO loans (id, borrower_id, lender_id, principal, interest_rate, disbursement_date, start_date, last_repayment_date, status, repayment_schedule)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, NULL, ?8, ?9)",
+            "INSERT OR IGNORE INTO loans (id, borrower_id, lender_id, principal, principal_cents, amount_paid_cents, interest_rate, disbursement_date, start_date, last_repayment_date, status, repayment_schedule)
+             VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6, ?7, ?8, NULL, ?9, ?10)",
             params![
-                "LOAN1",
+                DEMO_LOAN_ID,
                 "DEMO",
                 "BANK",
-                24_850.0_f64,
-                8.4_f64,
+                principal.to_f64(),
+                principal,
+                interest_rate,
                 now.to_rfc3339(),
                 now.to_rfc3339(),
                 "Active",
@@ -146,10 +559,21 @@ impl Db {
         Ok(())
     }
 
-    fn migrate_users_email_column(conn: &Connection) -> Result<()> {
-        let _ = conn.execute("ALTER TABLE users ADD COLUMN email TEXT", []);
-        let _ = conn.execute("ALTER TABLE users ADD COLUMN lender_id TEXT", []);
-        let _ = conn.execute("ALTER TABLE users ADD COLUMN organization TEXT", []);
+    /// Older databases seeded the sample loan as `LOAN1`, which is not a valid loan UUID.
+    fn migrate_legacy_demo_loan_id(conn: &Connection) -> Result<()> {
+        conn.execute("UPDATE loans SET id = ?1 WHERE id = 'LOAN1'", params![DEMO_LOAN_ID])?;
+        Ok(())
+    }
+
+    /// Fills `created_at` / `updated_at` for rows that predate the columns, including the seed rows.
+    fn backfill_created_at(conn: &Connection) -> Result<()> {
+        conn.execute("UPDATE loans SET created_at = disbursement_date WHERE created_at IS NULL", [])?;
+        conn.execute(
+            "UPDATE users SET created_at = ?1 WHERE created_at IS NULL",
+            params![Utc::now().to_rfc3339()],
+        )?;
+        conn.execute("UPDATE loans SET updated_at = created_at WHERE updated_at IS NULL", [])?;
+        conn.execute("UPDATE users SET updated_at = created_at WHERE updated_at IS NULL", [])?;
         Ok(())
     }
 
@@ -160,10 +584,15 @@ impl Db {
         let email: Option<String> = row.get(3)?;
         let lender_id: Option<String> = row.get(4)?;
         let organization: Option<String> = row.get(5)?;
+        let wallet_balance: Money = row.get(6)?;
+        let phone: Option<String> = row.get(7)?;
+        let created_at = timestamp_column(row, 8)?;
+        let updated_at = timestamp_column(row, 9)?;
 
         let role = match role_str.as_str() {
             "Borrower" => UserRole::Borrower,
             "Lender" => UserRole::Lender,
+            "Admin" => UserRole::Admin,
             _ => return Err(rusqlite::Error::InvalidColumnType(2, "UserRole".to_string(), rusqlite::types::Type::Text)),
         };
 
@@ -172,29 +601,128 @@ impl Db {
             name,
             role,
             email,
+            phone,
             lender_id,
             organization,
+            wallet_balance,
+            created_at,
+            updated_at,
         })
     }
 
     // User operations
+    /// Saves profile fields. An existing wallet balance is kept as stored; it only moves through
+    /// `credit_wallet` / `debit_wallet` so a stale `User` cannot overwrite it.
     pub fn save_user(&self, user: &User) -> Result<()> {
-        self.conn.execute(
-            "INSERT OR REPLACE INTO users (id, name, role, email, lender_id, organization) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![
-                &user.id,
-                &user.name,
-                format!("{:?}", user.role),
-                &user.email,
-                &user.lender_id,
-                &user.organization
-            ],
+        self.with_retry(|| write_user(&*self.conn()?, user))
+    }
+
+    /// Stores the password hash used for session login; kept off `User` so it is never serialized.
+    /// Returns false when no such user exists.
+    pub fn set_password_hash(&self, user_id: &str, hash: &str) -> Result<bool> {
+        let conn = self.conn()?;
+        let n = conn.execute("UPDATE users SET password_hash = ?1 WHERE id = ?2", params![hash, user_id])?;
+        Ok(n > 0)
+    }
+
+    /// Stores a new user together with the password hash they log in with, in one transaction,
+    /// so no account is ever left behind without its password.
+    pub fn save_user_with_password_hash(&self, user: &User, hash: &str) -> Result<()> {
+        self.transaction(|tx| {
+            write_user(tx, user)?;
+            tx.execute("UPDATE users SET password_hash = ?1 WHERE id = ?2", params![hash, &user.id])?;
+            Ok(())
+        })
+    }
+
+    /// `None` when the user does not exist or registered without a password.
+    pub fn load_password_hash(&self, user_id: &str) -> Result<Option<String>> {
+        let conn = self.conn()?;
+        match conn.query_row("SELECT password_hash FROM users WHERE id = ?1", params![user_id], |r| r.get(0)) {
+            Ok(hash) => Ok(hash),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Removes the user and any Firebase link to it. Returns false when no such user exists.
+    pub fn delete_user(&self, id: &str) -> Result<bool> {
+        self.transaction(|tx| {
+            tx.execute("DELETE FROM firebase_user_links WHERE local_user_id = ?1", params![id])?;
+            let n = tx.execute("DELETE FROM users WHERE id = ?1", params![id])?;
+            Ok(n > 0)
+        })
+    }
+
+    /// Sets the user's role and records the change under `actor`, in one transaction. Returns
+    /// false when no such user exists.
+    pub fn update_user_role(&self, id: &str, from: &UserRole, to: &UserRole, actor: &str) -> Result<bool> {
+        self.transaction(|tx| {
+            let now = Utc::now().to_rfc3339();
+            let n = tx.execute(
+                "UPDATE users SET role = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id, format!("{:?}", to), &now],
+            )?;
+            if n == 0 {
+                return Ok(false);
+            }
+            tx.execute(
+                "INSERT INTO role_audit_log (user_id, from_role, to_role, changed_at, actor) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![id, format!("{:?}", from), format!("{:?}", to), &now, actor],
+            )?;
+            Ok(true)
+        })
+    }
+
+    /// Role changes of a user, oldest first.
+    pub fn load_role_changes(&self, user_id: &str) -> Result<Vec<RoleChange>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT from_role, to_role, changed_at, actor FROM role_audit_log WHERE user_id = ?1 ORDER BY id"
         )?;
-        Ok(())
+        let entries = stmt.query_map(params![user_id], |row| {
+            let parse_role = |idx: usize, s: String| match s.as_str() {
+                "Borrower" => Ok(UserRole::Borrower),
+                "Lender" => Ok(UserRole::Lender),
+                "Admin" => Ok(UserRole::Admin),
+                _ => Err(rusqlite::Error::InvalidColumnType(idx, "UserRole".to_string(), rusqlite::types::Type::Text)),
+            };
+            Ok(RoleChange {
+                user_id: user_id.to_string(),
+                from_role: parse_role(0, row.get(0)?)?,
+                to_role: parse_role(1, row.get(1)?)?,
+                changed_at: timestamp_column(row, 2)?,
+                actor: row.get(3)?,
+            })
+        })?;
+        entries.collect()
+    }
+
+    /// Adds funds to a user's wallet; returns the new balance, or `None` for an unknown user.
+    pub fn credit_wallet(&self, user_id: &str, amount: Money) -> Result<Option<Money>> {
+        self.transaction(|tx| {
+            let n = tx.execute(
+                "UPDATE users SET wallet_balance_cents = wallet_balance_cents + ?2,
+                                  wallet_balance = (wallet_balance_cents + ?2) / 100.0
+                 WHERE id = ?1",
+                params![user_id, amount],
+            )?;
+            if n == 0 {
+                return Ok(None);
+            }
+            tx.query_row("SELECT wallet_balance_cents FROM users WHERE id = ?1", params![user_id], |r| r.get(0))
+                .map(Some)
+        })
+    }
+
+    /// Takes `amount` from a wallet only if the balance covers it; returns whether it did.
+    pub fn debit_wallet(&self, user_id: &str, amount: Money) -> Result<bool> {
+        debit_wallet(&*self.conn()?, user_id, amount)
     }
 
     pub fn load_user(&self, id: &str) -> Result<Option<User>> {
-        let mut stmt = self.conn.prepare("SELECT id, name, role, email, lender_id, organization FROM users WHERE id = ?1")?;
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM users WHERE id = ?1", USER_COLUMNS))?;
         let mut rows = stmt.query_map(params![id], Self::row_to_user)?;
 
         match rows.next() {
@@ -203,12 +731,51 @@ impl Db {
         }
     }
 
+    /// Every user named exactly `name`, earliest registered first.
+    pub fn load_users_by_name(&self, name: &str) -> Result<Vec<User>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM users WHERE name = ?1 {}",
+            USER_COLUMNS,
+            SortOrder::DEFAULT.to_sql()
+        ))?;
+        let users = stmt.query_map(params![name], Self::row_to_user)?;
+        users.collect()
+    }
+
     pub fn load_all_users(&self) -> Result<Vec<User>> {
-        let mut stmt = self.conn.prepare("SELECT id, name, role, email, lender_id, organization FROM users")?;
+        self.load_all_users_sorted(SortOrder::DEFAULT)
+    }
+
+    pub fn load_all_users_sorted(&self, order: SortOrder) -> Result<Vec<User>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM users {}",
+            USER_COLUMNS,
+            order.to_sql()
+        ))?;
         let users = stmt.query_map([], Self::row_to_user)?;
         users.collect()
     }
 
+    /// One page of users in `order`, via `LIMIT`/`OFFSET`.
+    pub fn load_users_paged(&self, order: SortOrder, limit: usize, offset: usize) -> Result<Vec<User>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM users {} LIMIT ?1 OFFSET ?2",
+            USER_COLUMNS,
+            order.to_sql()
+        ))?;
+        let users = stmt.query_map(params![limit as i64, offset as i64], Self::row_to_user)?;
+        users.collect()
+    }
+
+    pub fn count_users(&self) -> Result<usize> {
+        let conn = self.conn()?;
+        let n: i64 = conn.query_row("SELECT COUNT(*) FROM users", [], |r| r.get(0))?;
+        Ok(n as usize)
+    }
+
     #[allow(dead_code)]
     pub fn find_user_by_email_ci(&self, email: &str) -> Result<Option<User>> {
         let needle = email.trim().to_lowercase();
@@ -226,153 +793,604 @@ impl Db {
 
     // Loan operations (keep Uuid for loans)
     pub fn save_loan(&self, loan: &Loan) -> Result<()> {
-        let repayment_schedule_json = serde_json::to_string(&loan.repayment_schedule)
-            .map_err(|_| rusqlite::Error::InvalidColumnType(0, "JSON".to_string(), rusqlite::types::Type::Text))?;
+        self.with_retry(|| write_loan(&*self.conn()?, loan))
+    }
+
+    /// Inserts new loans, each with its creation audit entry, in one transaction: either all
+    /// of them are stored or none are.
+    pub fn insert_loans(&self, loans: &[Loan], actor: &str) -> Result<()> {
+        self.transaction(|tx| {
+            for loan in loans {
+                write_loan(tx, loan)?;
+                write_status_change(tx, loan.id, None, &loan.status, actor)?;
+            }
+            Ok(())
+        })
+    }
+
+
+    /// Recomputes every unarchived loan's status in one transaction: each loan whose status
+    /// `recompute` changes is saved and its transition audited under `actor`. Returns how many
+    /// loans were checked and the changed ones with their previous status.
+    pub fn reconcile_loan_statuses<F>(&self, actor: &str, recompute: F) -> Result<(usize, Vec<(Loan, LoanStatus)>)>
+    where
+        F: Fn(&Loan) -> LoanStatus,
+    {
+        self.transaction(|tx| {
+            let loans = {
+                let mut stmt = tx.prepare(&format!("SELECT {} FROM loans WHERE archived = 0 ORDER BY id", LOAN_COLUMNS))?;
+                let rows = stmt.query_map([], Self::row_to_loan)?;
+                rows.collect::<Result<Vec<_>>>()?
+            };
+            let checked = loans.len();
+            let mut changed = Vec::new();
+            for mut loan in loans {
+                let status = recompute(&loan);
+                if status == loan.status {
+                    continue;
+                }
+                let previous = std::mem::replace(&mut loan.status, status);
+                write_loan(tx, &loan)?;
+                write_status_change(tx, loan.id, Some(&previous), &loan.status, actor)?;
+                changed.push((loan, previous));
+            }
+            Ok((checked, changed))
+        })
+    }
+
+    fn row_to_loan(row: &rusqlite::Row<'_>) -> Result<Loan> {
+        let id_str: String = row.get(0)?;
+        let borrower_id: String = row.get(1)?;
+        let lender_id: String = row.get(2)?;
+        let principal: Money = row.get(3)?;
+        let interest_rate: f64 = row.get(4)?;
+        let disbursement_date_str: String = row.get(5)?;
+        let start_date_str: String = row.get(6)?;
+        let last_repayment_date_str: Option<String> = row.get(7)?;
+        let status_str: Option<String> = row.get(8)?;
+        let repayment_schedule_json: String = row.get(9)?;
+        let amount_paid: Money = row.get(10)?;
+        let interest_kind_str: String = row.get(11)?;
+        let currency: String = row.get(12)?;
+        let created_at = timestamp_column(row, 13)?;
+        let updated_at = timestamp_column(row, 14)?;
+        let promo_months: i64 = row.get(15)?;
+        let archived: bool = row.get(16)?;
+        let payment_frequency_str: String = row.get(17)?;
+        let guarantor_id: Option<String> = row.get(18)?;
+        let late_fee_policy_json: Option<String> = row.get(19)?;
+
+        let id = Uuid::parse_str(&id_str).map_err(|_| rusqlite::Error::InvalidColumnType(0, "UUID".to_string(), rusqlite::types::Type::Text))?;
+
+        let disbursement_date = DateTime::parse_from_rfc3339(&disbursement_date_str)
+            .map_err(|_| rusqlite::Error::InvalidColumnType(5, "DateTime".to_string(), rusqlite::types::Type::Text))?
+            .with_timezone(&Utc);
+        let start_date = DateTime::parse_from_rfc3339(&start_date_str)
+            .map_err(|_| rusqlite::Error::InvalidColumnType(6, "DateTime".to_string(), rusqlite::types::Type::Text))?
+            .with_timezone(&Utc);
+        let last_repayment_date = match last_repayment_date_str {
+            Some(date_str) => Some(DateTime::parse_from_rfc3339(&date_str)
+                .map_err(|_| rusqlite::Error::InvalidColumnType(7, "DateTime".to_string(), rusqlite::types::Type::Text))?
+                .with_timezone(&Utc)),
+            None => None,
+        };
+
+        // Unknown or missing values (e.g. written by a newer build) load as Active rather than failing the row
+        let status = match status_str.as_deref().and_then(LoanStatus::parse) {
+            Some(status) => status,
+            None => {
+                log::warn!("Loan {} has unrecognised status {:?}; treating it as Active", id, status_str);
+                LoanStatus::Active
+            }
+        };
+
+        let interest_kind = InterestKind::parse(&interest_kind_str).unwrap_or_else(|| {
+            log::warn!("Loan {} has unrecognised interest kind {:?}; treating it as amortized", id, interest_kind_str);
+            InterestKind::Amortized
+        });
 
-        self.conn.execute(
-            "INSERT OR REPLACE INTO loans (id, borrower_id, lender_id, principal, interest_rate, disbursement_date, start_date, last_repayment_date, status, repayment_schedule)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        let payment_frequency = PaymentFrequency::parse(&payment_frequency_str).unwrap_or_else(|| {
+            log::warn!("Loan {} has unrecognised payment frequency {:?}; treating it as monthly", id, payment_frequency_str);
+            PaymentFrequency::Monthly
+        });
+
+        let late_fee_policy = late_fee_policy_json.and_then(|json| match serde_json::from_str(&json) {
+            Ok(policy) => Some(policy),
+            Err(_) => {
+                log::warn!("Loan {} has unrecognised late fee policy {:?}; ignoring it", id, json);
+                None
+            }
+        });
+
+        // Older rows stored bare due dates; re-derive the principal/interest split for those.
+        let repayment_schedule: Vec<ScheduledPayment> = match serde_json::from_str(&repayment_schedule_json) {
+            Ok(schedule) => schedule,
+            Err(_) => {
+                let due_dates: Vec<DateTime<Utc>> = serde_json::from_str(&repayment_schedule_json)
+                    .map_err(|_| rusqlite::Error::InvalidColumnType(9, "JSON".to_string(), rusqlite::types::Type::Text))?;
+                ScheduledPayment::amortize(principal.to_f64(), interest_rate, &due_dates).ok_or(MoneyOverflow)?
+            }
+        };
+
+        Ok(Loan {
+            id,
+            borrower_id,
+            lender_id,
+            guarantor_id,
+            principal,
+            interest_rate,
+            disbursement_date,
+            start_date,
+            last_repayment_date,
+            status,
+            repayment_schedule,
+            amount_paid,
+            interest_kind,
+            currency,
+            promo_months,
+            payment_frequency,
+            archived,
+            late_fee_policy,
+            created_at,
+            updated_at,
+        })
+    }
+
+    pub fn load_loan(&self, id: Uuid) -> Result<Option<Loan>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM loans WHERE id = ?1", LOAN_COLUMNS))?;
+        let mut rows = stmt.query_map(params![id.to_string()], Self::row_to_loan)?;
+
+        match rows.next() {
+            Some(loan) => Ok(Some(loan?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Every loan that is not archived.
+    pub fn load_all_loans(&self) -> Result<Vec<Loan>> {
+        self.load_all_loans_sorted(SortOrder::DEFAULT)
+    }
+
+    pub fn load_all_loans_sorted(&self, order: SortOrder) -> Result<Vec<Loan>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM loans WHERE archived = 0 {}", LOAN_COLUMNS, order.to_sql()))?;
+        let loans = stmt.query_map([], Self::row_to_loan)?;
+        loans.collect()
+    }
+
+    /// One page of loans in `order`, via `LIMIT`/`OFFSET`.
+    pub fn load_loans_paged(&self, order: SortOrder, limit: usize, offset: usize) -> Result<Vec<Loan>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM loans WHERE archived = 0 {} LIMIT ?1 OFFSET ?2",
+            LOAN_COLUMNS,
+            order.to_sql()
+        ))?;
+        let loans = stmt.query_map(params![limit as i64, offset as i64], Self::row_to_loan)?;
+        loans.collect()
+    }
+
+    /// Number of loans that are not archived, matching what `load_loans_paged` pages through.
+    pub fn count_loans(&self) -> Result<usize> {
+        let conn = self.conn()?;
+        let n: i64 = conn.query_row("SELECT COUNT(*) FROM loans WHERE archived = 0", [], |r| r.get(0))?;
+        Ok(n as usize)
+    }
+
+    /// Archived loans included, for backups and exports that must keep every record.
+    pub fn load_all_loans_including_archived(&self) -> Result<Vec<Loan>> {
+        self.query_loans(&LoanFilter { include_archived: true, ..LoanFilter::default() }, SortOrder::DEFAULT)
+    }
+
+    /// Number of loans per stored status name (e.g. `"Overdue"`); statuses with no loans are absent.
+    pub fn loan_status_counts(&self) -> Result<HashMap<String, i64>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT status, COUNT(*) FROM loans WHERE archived = 0 GROUP BY status")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, Option<String>>(0)?.unwrap_or_default(), row.get(1)?)))?;
+        rows.collect()
+    }
+
+    /// Loans matching every criterion set in `filter`, in `order`.
+    pub fn query_loans(&self, filter: &LoanFilter, order: SortOrder) -> Result<Vec<Loan>> {
+        let (where_clause, values) = filter.to_sql();
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM loans {} {}",
+            LOAN_COLUMNS,
+            where_clause,
+            order.to_sql()
+        ))?;
+        let loans = stmt.query_map(rusqlite::params_from_iter(values.iter()), Self::row_to_loan)?;
+        loans.collect()
+    }
+
+    pub fn load_loans_by_borrower(&self, borrower_id: &str) -> Result<Vec<Loan>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM loans WHERE borrower_id = ?1 AND archived = 0 {}",
+            LOAN_COLUMNS,
+            SortOrder::DEFAULT.to_sql()
+        ))?;
+        let loans = stmt.query_map(params![borrower_id], Self::row_to_loan)?;
+        loans.collect()
+    }
+
+    pub fn load_loans_by_lender(&self, lender_id: &str) -> Result<Vec<Loan>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM loans WHERE lender_id = ?1 AND archived = 0 {}",
+            LOAN_COLUMNS,
+            SortOrder::DEFAULT.to_sql()
+        ))?;
+        let loans = stmt.query_map(params![lender_id], Self::row_to_loan)?;
+        loans.collect()
+    }
+
+    /// Number of open (active, partially repaid or overdue) loans held by a lender; archived loans are not counted.
+    pub fn count_active_loans_by_lender(&self, lender_id: &str) -> Result<usize> {
+        let conn = self.conn()?;
+        let n: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM loans WHERE lender_id = ?1 AND archived = 0 AND status IN ('Active', 'PartiallyRepaid', 'Overdue')",
+            params![lender_id],
+            |r| r.get(0),
+        )?;
+        Ok(n as usize)
+    }
+
+    /// Open, unarchived loans the user is party to, as borrower or lender.
+    pub fn count_open_loans_for_user(&self, user_id: &str) -> Result<usize> {
+        let conn = self.conn()?;
+        let n: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM loans WHERE (borrower_id = ?1 OR lender_id = ?1)
+               AND archived = 0 AND status IN ('Active', 'PartiallyRepaid', 'Overdue')",
+            params![user_id],
+            |r| r.get(0),
+        )?;
+        Ok(n as usize)
+    }
+
+    /// Active/overdue loans whose latest payment (or disbursement, if never paid) is before `cutoff`, oldest first.
+    pub fn load_loans_without_payment_since(&self, cutoff: DateTime<Utc>) -> Result<Vec<Loan>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM loans
+             LEFT JOIN (SELECT loan_id, MAX(paid_at) AS last_paid_at FROM payments GROUP BY loan_id) p
+               ON p.loan_id = loans.id
+             WHERE status IN ('Active', 'PartiallyRepaid', 'Overdue') AND archived = 0
+               AND COALESCE(p.last_paid_at, disbursement_date) < ?1
+             ORDER BY COALESCE(p.last_paid_at, disbursement_date) ASC",
+            LOAN_COLUMNS
+        ))?;
+        let loans = stmt.query_map(params![cutoff.to_rfc3339()], Self::row_to_loan)?;
+        loans.collect()
+    }
+
+    // Payment operations
+    pub fn save_payment(&self, payment: &Payment) -> Result<()> {
+        write_payment(&*self.conn()?, payment)
+    }
+
+    /// Stores a payment together with the loan it was applied to, auditing a status change from
+    /// `previous` under `actor`, in one transaction. With `wallet_of` set the payment is first
+    /// taken from that user's wallet; if the balance does not cover it nothing is written and
+    /// this returns `false`.
+    pub fn save_applied_payment(
+        &self,
+        loan: &Loan,
+        previous: &LoanStatus,
+        payment: Option<&Payment>,
+        wallet_of: Option<&str>,
+        actor: &str,
+    ) -> Result<bool> {
+        self.with_retry(|| {
+            self.transaction(|tx| {
+                if let (Some(user_id), Some(payment)) = (wallet_of, payment) {
+                    if !debit_wallet(tx, user_id, payment.amount)? {
+                        return Ok(false);
+                    }
+                }
+                if let Some(payment) = payment {
+                    write_payment(tx, payment)?;
+                }
+                write_loan(tx, loan)?;
+                if *previous != loan.status {
+                    write_status_change(tx, loan.id, Some(previous), &loan.status, actor)?;
+                }
+                Ok(true)
+            })
+        })
+    }
+
+    pub fn load_payments(&self, loan_id: Uuid) -> Result<Vec<Payment>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, loan_id, amount_cents, paid_at FROM payments WHERE loan_id = ?1 ORDER BY paid_at"
+        )?;
+        let payments = stmt.query_map(params![loan_id.to_string()], |row| {
+            let id_str: String = row.get(0)?;
+            let loan_id_str: String = row.get(1)?;
+            let paid_at_str: String = row.get(3)?;
+            Ok(Payment {
+                id: Uuid::parse_str(&id_str).map_err(|_| rusqlite::Error::InvalidColumnType(0, "UUID".to_string(), rusqlite::types::Type::Text))?,
+                loan_id: Uuid::parse_str(&loan_id_str).map_err(|_| rusqlite::Error::InvalidColumnType(1, "UUID".to_string(), rusqlite::types::Type::Text))?,
+                amount: row.get(2)?,
+                paid_at: DateTime::parse_from_rfc3339(&paid_at_str)
+                    .map_err(|_| rusqlite::Error::InvalidColumnType(3, "DateTime".to_string(), rusqlite::types::Type::Text))?
+                    .with_timezone(&Utc),
+            })
+        })?;
+        payments.collect()
+    }
+
+    // Loan metadata
+    pub fn save_loan_metadata(&self, loan_id: Uuid, key: &str, value: &str) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO loan_metadata (loan_id, key, value) VALUES (?1, ?2, ?3)",
+            params![loan_id.to_string(), key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn load_loan_metadata(&self, loan_id: Uuid) -> Result<BTreeMap<String, String>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT key, value FROM loan_metadata WHERE loan_id = ?1")?;
+        let entries = stmt.query_map(params![loan_id.to_string()], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        entries.collect()
+    }
+
+    /// Returns whether the key existed.
+    pub fn delete_loan_metadata(&self, loan_id: Uuid, key: &str) -> Result<bool> {
+        let conn = self.conn()?;
+        let n = conn.execute(
+            "DELETE FROM loan_metadata WHERE loan_id = ?1 AND key = ?2",
+            params![loan_id.to_string(), key],
+        )?;
+        Ok(n > 0)
+    }
+
+    // Lender policies
+    pub fn save_lender_policy(&self, lender_id: &str, limits: &PrincipalLimits) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO lender_policies (lender_id, min_principal, max_principal) VALUES (?1, ?2, ?3)",
+            params![lender_id, limits.min_principal, limits.max_principal],
+        )?;
+        Ok(())
+    }
+
+    pub fn load_lender_policy(&self, lender_id: &str) -> Result<Option<PrincipalLimits>> {
+        let conn = self.conn()?;
+        let result = conn.query_row(
+            "SELECT min_principal, max_principal FROM lender_policies WHERE lender_id = ?1",
+            params![lender_id],
+            |row| Ok(PrincipalLimits { min_principal: row.get(0)?, max_principal: row.get(1)? }),
+        );
+
+        match result {
+            Ok(limits) => Ok(Some(limits)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Idempotency keys
+    /// Loan created under `key` by `user_id` no earlier than `since`.
+    pub fn load_idempotent_loan(&self, user_id: &str, key: &str, since: DateTime<Utc>) -> Result<Option<Uuid>> {
+        load_idempotent_loan(&*self.conn()?, user_id, key, Some(since))
+    }
+
+    /// Forgets keys recorded before `before`, so a later request under one of them starts over.
+    /// Returns how many were removed.
+    pub fn purge_idempotency_keys(&self, before: DateTime<Utc>) -> Result<usize> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM idempotency WHERE created_at < ?1", params![before.to_rfc3339()])
+    }
+
+    /// Inserts a new loan with its creation audit entry and reserves `key` for `user_id` in the
+    /// same transaction. The reservation is a plain `INSERT` on the `(user_id, key)` primary key,
+    /// so of two requests racing on one key only the first stores a loan; the other stores
+    /// nothing and gets back the id recorded under the key.
+    pub fn insert_loan_with_idempotency_key(&self, loan: &Loan, actor: &str, user_id: &str, key: &str) -> Result<Option<Uuid>> {
+        self.transaction(|tx| {
+            let reserved = tx.execute(
+                "INSERT INTO idempotency (user_id, key, loan_id, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![user_id, key, loan.id.to_string(), Utc::now().to_rfc3339()],
+            );
+            match reserved {
+                Ok(_) => {}
+                Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::ConstraintViolation => {
+                    return load_idempotent_loan(tx, user_id, key, None);
+                }
+                Err(e) => return Err(e),
+            }
+            write_loan(tx, loan)?;
+            write_status_change(tx, loan.id, None, &loan.status, actor)?;
+            Ok(None)
+        })
+    }
+
+    // Extension requests
+    pub fn save_extension_request(&self, request: &ExtensionRequest) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO extension_requests (id, loan_id, installment_index, current_due, requested_due, status, requested_at, decided_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
-                loan.id.to_string(),
-                loan.borrower_id.to_string(),
-                loan.lender_id.to_string(),
-                loan.principal,
-                loan.interest_rate,
-                loan.disbursement_date.to_rfc3339(),
-                loan.start_date.to_rfc3339(),
-                loan.last_repayment_date.map(|dt| dt.to_rfc3339()),
-                format!("{:?}", loan.status),
-                repayment_schedule_json
+                request.id.to_string(),
+                request.loan_id.to_string(),
+                request.installment_index as i64,
+                request.current_due.to_rfc3339(),
+                request.requested_due.to_rfc3339(),
+                format!("{:?}", request.status),
+                request.requested_at.to_rfc3339(),
+                request.decided_at.map(|dt| dt.to_rfc3339())
             ],
         )?;
         Ok(())
     }
 
-    pub fn load_loan(&self, id: Uuid) -> Result<Option<Loan>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, borrower_id, lender_id, principal, interest_rate, disbursement_date, start_date, last_repayment_date, status, repayment_schedule
-             FROM loans WHERE id = ?1"
+    pub fn load_extension_request(&self, id: Uuid) -> Result<Option<ExtensionRequest>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, loan_id, installment_index, current_due, requested_due, status, requested_at, decided_at
+             FROM extension_requests WHERE id = ?1"
         )?;
         let mut rows = stmt.query_map(params![id.to_string()], |row| {
-            let id_str: String = row.get(0)?;
-            let borrower_id_str: String = row.get(1)?;
-            let lender_id_str: String = row.get(2)?;
-            let principal: f64 = row.get(3)?;
-            let interest_rate: f64 = row.get(4)?;
-            let disbursement_date_str: String = row.get(5)?;
-            let start_date_str: String = row.get(6)?;
-            let last_repayment_date_str: Option<String> = row.get(7)?;
-            let status_str: String = row.get(8)?;
-            let repayment_schedule_json: String = row.get(9)?;
-
-            let id = Uuid::parse_str(&id_str).map_err(|_| rusqlite::Error::InvalidColumnType(0, "UUID".to_string(), rusqlite::types::Type::Text))?;
-            let borrower_id = Uuid::parse_str(&borrower_id_str).map_err(|_| rusqlite::Error::InvalidColumnType(1, "UUID".to_string(), rusqlite::types::Type::Text))?;
-            let lender_id = Uuid::parse_str(&lender_id_str).map_err(|_| rusqlite::Error::InvalidColumnType(2, "UUID".to_string(), rusqlite::types::Type::Text))?;
-
-            let disbursement_date = DateTime::parse_from_rfc3339(&disbursement_date_str)
-                .map_err(|_| rusqlite::Error::InvalidColumnType(5, "DateTime".to_string(), rusqlite::types::Type::Text))?
-                .with_timezone(&Utc);
-            let start_date = DateTime::parse_from_rfc3339(&start_date_str)
-                .map_err(|_| rusqlite::Error::InvalidColumnType(6, "DateTime".to_string(), rusqlite::types::Type::Text))?
-                .with_timezone(&Utc);
-            let last_repayment_date = match last_repayment_date_str {
-                Some(date_str) => Some(DateTime::parse_from_rfc3339(&date_str)
-                    .map_err(|_| rusqlite::Error::InvalidColumnType(7, "DateTime".to_string(), rusqlite::types::Type::Text))?
-                    .with_timezone(&Utc)),
-                None => None,
+            let parse_date = |idx: usize, value: String| {
+                DateTime::parse_from_rfc3339(&value)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .map_err(|_| rusqlite::Error::InvalidColumnType(idx, "DateTime".to_string(), rusqlite::types::Type::Text))
             };
+            let id_str: String = row.get(0)?;
+            let loan_id_str: String = row.get(1)?;
+            let installment_index: i64 = row.get(2)?;
+            let status_str: String = row.get(5)?;
+            let decided_at: Option<String> = row.get(7)?;
 
             let status = match status_str.as_str() {
-                "Active" => LoanStatus::Active,
-                "Overdue" => LoanStatus::Overdue,
-                "Defaulted" => LoanStatus::Defaulted,
-                "Repaid" => LoanStatus::Repaid,
-                _ => return Err(rusqlite::Error::InvalidColumnType(8, "LoanStatus".to_string(), rusqlite::types::Type::Text)),
+                "Pending" => ExtensionStatus::Pending,
+                "Approved" => ExtensionStatus::Approved,
+                "Rejected" => ExtensionStatus::Rejected,
+                _ => return Err(rusqlite::Error::InvalidColumnType(5, "ExtensionStatus".to_string(), rusqlite::types::Type::Text)),
             };
 
-            let repayment_schedule: Vec<DateTime<Utc>> = serde_json::from_str(&repayment_schedule_json)
-                .map_err(|_| rusqlite::Error::InvalidColumnType(9, "JSON".to_string(), rusqlite::types::Type::Text))?;
-
-            Ok(Loan {
-                id,
-                borrower_id,
-                lender_id,
-                principal,
-                interest_rate,
-                disbursement_date,
-                start_date,
-                last_repayment_date,
+            Ok(ExtensionRequest {
+                id: Uuid::parse_str(&id_str).map_err(|_| rusqlite::Error::InvalidColumnType(0, "UUID".to_string(), rusqlite::types::Type::Text))?,
+                loan_id: Uuid::parse_str(&loan_id_str).map_err(|_| rusqlite::Error::InvalidColumnType(1, "UUID".to_string(), rusqlite::types::Type::Text))?,
+                installment_index: installment_index as usize,
+                current_due: parse_date(3, row.get(3)?)?,
+                requested_due: parse_date(4, row.get(4)?)?,
                 status,
-                repayment_schedule,
+                requested_at: parse_date(6, row.get(6)?)?,
+                decided_at: match decided_at {
+                    Some(value) => Some(parse_date(7, value)?),
+                    None => None,
+                },
             })
         })?;
 
         match rows.next() {
-            Some(loan) => Ok(Some(loan?)),
+            Some(request) => Ok(Some(request?)),
             None => Ok(None),
         }
     }
 
-    pub fn load_all_loans(&self) -> Result<Vec<Loan>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, borrower_id, lender_id, principal, interest_rate, disbursement_date, start_date, last_repayment_date, status, repayment_schedule
-             FROM loans"
+    // Status audit log
+    /// Appends a status transition; `from` is `None` when the loan is first created.
+    pub fn record_status_change(&self, loan_id: Uuid, from: Option<&LoanStatus>, to: &LoanStatus, actor: &str) -> Result<()> {
+        write_status_change(&*self.conn()?, loan_id, from, to, actor)
+    }
+
+    /// Status transitions of a loan, oldest first.
+    pub fn load_audit_log(&self, loan_id: Uuid) -> Result<Vec<AuditEntry>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT from_status, to_status, changed_at, actor FROM audit_log WHERE loan_id = ?1 ORDER BY id"
         )?;
-        let loans = stmt.query_map([], |row| {
-            let id_str: String = row.get(0)?;
-            let borrower_id_str: String = row.get(1)?;
-            let lender_id_str: String = row.get(2)?;
-            let principal: f64 = row.get(3)?;
-            let interest_rate: f64 = row.get(4)?;
-            let disbursement_date_str: String = row.get(5)?;
-            let start_date_str: String = row.get(6)?;
-            let last_repayment_date_str: Option<String> = row.get(7)?;
-            let status_str: String = row.get(8)?;
-            let repayment_schedule_json: String = row.get(9)?;
-
-            let id = Uuid::parse_str(&id_str).map_err(|_| rusqlite::Error::InvalidColumnType(0, "UUID".to_string(), rusqlite::types::Type::Text))?;
-            let borrower_id = Uuid::parse_str(&borrower_id_str).map_err(|_| rusqlite::Error::InvalidColumnType(1, "UUID".to_string(), rusqlite::types::Type::Text))?;
-            let lender_id = Uuid::parse_str(&lender_id_str).map_err(|_| rusqlite::Error::InvalidColumnType(2, "UUID".to_string(), rusqlite::types::Type::Text))?;
-
-            let disbursement_date = DateTime::parse_from_rfc3339(&disbursement_date_str)
-                .map_err(|_| rusqlite::Error::InvalidColumnType(5, "DateTime".to_string(), rusqlite::types::Type::Text))?
-                .with_timezone(&Utc);
-            let start_date = DateTime::parse_from_rfc3339(&start_date_str)
-                .map_err(|_| rusqlite::Error::InvalidColumnType(6, "DateTime".to_string(), rusqlite::types::Type::Text))?
-                .with_timezone(&Utc);
-            let last_repayment_date = match last_repayment_date_str {
-                Some(date_str) => Some(DateTime::parse_from_rfc3339(&date_str)
-                    .map_err(|_| rusqlite::Error::InvalidColumnType(7, "DateTime".to_string(), rusqlite::types::Type::Text))?
-                    .with_timezone(&Utc)),
-                None => None,
+        let entries = stmt.query_map(params![loan_id.to_string()], |row| {
+            let parse_status = |idx: usize, s: String| {
+                LoanStatus::parse(&s)
+                    .ok_or_else(|| rusqlite::Error::InvalidColumnType(idx, "LoanStatus".to_string(), rusqlite::types::Type::Text))
             };
+            let from_status: Option<String> = row.get(0)?;
+            let changed_at: String = row.get(2)?;
+            Ok(AuditEntry {
+                loan_id,
+                from_status: from_status.map(|s| parse_status(0, s)).transpose()?,
+                to_status: parse_status(1, row.get(1)?)?,
+                changed_at: DateTime::parse_from_rfc3339(&changed_at)
+                    .map_err(|_| rusqlite::Error::InvalidColumnType(2, "DateTime".to_string(), rusqlite::types::Type::Text))?
+                    .with_timezone(&Utc),
+                actor: row.get(3)?,
+            })
+        })?;
+        entries.collect()
+    }
 
-            let status = match status_str.as_str() {
-                "Active" => LoanStatus::Active,
-                "Overdue" => LoanStatus::Overdue,
-                "Defaulted" => LoanStatus::Defaulted,
-                "Repaid" => LoanStatus::Repaid,
-                _ => return Err(rusqlite::Error::InvalidColumnType(8, "LoanStatus".to_string(), rusqlite::types::Type::Text)),
-            };
+    // Recovery action log
+    pub fn save_recovery_action(&self, record: &RecoveryActionRecord) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO recovery_actions (id, loan_id, action, outcome, taken_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                record.id.to_string(),
+                record.loan_id.to_string(),
+                format!("{:?}", record.action),
+                record.outcome.map(|o| format!("{:?}", o)),
+                record.taken_at.to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
 
-            let repayment_schedule: Vec<DateTime<Utc>> = serde_json::from_str(&repayment_schedule_json)
-                .map_err(|_| rusqlite::Error::InvalidColumnType(9, "JSON".to_string(), rusqlite::types::Type::Text))?;
+    /// Returns false when no action with this id exists.
+    pub fn set_recovery_outcome(&self, id: Uuid, outcome: RecoveryOutcome) -> Result<bool> {
+        let conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE recovery_actions SET outcome = ?1 WHERE id = ?2",
+            params![format!("{:?}", outcome), id.to_string()],
+        )?;
+        Ok(updated > 0)
+    }
 
-            Ok(Loan {
-                id,
-                borrower_id,
-                lender_id,
-                principal,
-                interest_rate,
-                disbursement_date,
-                start_date,
-                last_repayment_date,
-                status,
-                repayment_schedule,
+    /// When `action` was last logged against the loan, if ever.
+    pub fn last_recovery_action_at(&self, loan_id: Uuid, action: RecoveryAction) -> Result<Option<DateTime<Utc>>> {
+        let conn = self.conn()?;
+        let taken_at: Option<String> = conn.query_row(
+            "SELECT MAX(taken_at) FROM recovery_actions WHERE loan_id = ?1 AND action = ?2",
+            params![loan_id.to_string(), format!("{:?}", action)],
+            |r| r.get(0),
+        )?;
+        taken_at
+            .map(|s| {
+                DateTime::parse_from_rfc3339(&s)
+                    .map(|d| d.with_timezone(&Utc))
+                    .map_err(|_| rusqlite::Error::InvalidColumnType(0, "DateTime".to_string(), rusqlite::types::Type::Text))
             })
-        })?;
+            .transpose()
+    }
 
-        loans.collect()
+    pub fn load_recovery_actions(&self) -> Result<Vec<RecoveryActionRecord>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, loan_id, action, outcome, taken_at FROM recovery_actions ORDER BY taken_at"
+        )?;
+        let records = stmt.query_map([], |row| {
+            let id_str: String = row.get(0)?;
+            let loan_id_str: String = row.get(1)?;
+            let action_str: String = row.get(2)?;
+            let outcome_str: Option<String> = row.get(3)?;
+            let taken_at_str: String = row.get(4)?;
+
+            // Stored with `{:?}`, so match against every variant's debug name
+            let action = RecoveryAction::ALL
+                .into_iter()
+                .find(|a| format!("{:?}", a) == action_str)
+                .ok_or_else(|| rusqlite::Error::InvalidColumnType(2, "RecoveryAction".to_string(), rusqlite::types::Type::Text))?;
+            let outcome = match outcome_str.as_deref() {
+                None => None,
+                Some("PaidAfterReminder") => Some(RecoveryOutcome::PaidAfterReminder),
+                Some("RenegotiatedSuccessfully") => Some(RecoveryOutcome::RenegotiatedSuccessfully),
+                Some("RecoveredByCollection") => Some(RecoveryOutcome::RecoveredByCollection),
+                Some("PromiseToPay") => Some(RecoveryOutcome::PromiseToPay),
+                Some("NoResponse") => Some(RecoveryOutcome::NoResponse),
+                Some("Refused") => Some(RecoveryOutcome::Refused),
+                Some(_) => return Err(rusqlite::Error::InvalidColumnType(3, "RecoveryOutcome".to_string(), rusqlite::types::Type::Text)),
+            };
+
+            Ok(RecoveryActionRecord {
+                id: Uuid::parse_str(&id_str).map_err(|_| rusqlite::Error::InvalidColumnType(0, "UUID".to_string(), rusqlite::types::Type::Text))?,
+                loan_id: Uuid::parse_str(&loan_id_str).map_err(|_| rusqlite::Error::InvalidColumnType(1, "UUID".to_string(), rusqlite::types::Type::Text))?,
+                action,
+                outcome,
+                taken_at: DateTime::parse_from_rfc3339(&taken_at_str)
+                    .map_err(|_| rusqlite::Error::InvalidColumnType(4, "DateTime".to_string(), rusqlite::types::Type::Text))?
+                    .with_timezone(&Utc),
+            })
+        })?;
+        records.collect()
     }
 
     // JSON fallback methods
@@ -381,7 +1399,7 @@ impl Db {
         let users_json = serde_json::to_string_pretty(&users)
             .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?;
 
-        let loans = self.load_all_loans()?;
+        let loans = self.load_all_loans_including_archived()?;
         let loans_json = serde_json::to_string_pretty(&loans)
             .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?;
 
@@ -392,6 +1410,129 @@ impl Db {
         Ok(())
     }
 
+    /// Writes a `save_to_json` backup named after `at` into `dir` (created if missing), then
+    /// deletes all but the newest `retention` backups there. Returns the new files and the
+    /// removed ones.
+    pub fn write_backup<P: AsRef<Path>>(&self, dir: P, retention: usize, at: DateTime<Utc>) -> Result<BackupFiles> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).map_err(io_to_sql)?;
+        let stamp = at.format(BACKUP_STAMP_FORMAT).to_string();
+        let users = dir.join(format!("{}{}.json", USERS_BACKUP_PREFIX, stamp));
+        let loans = dir.join(format!("{}{}.json", LOANS_BACKUP_PREFIX, stamp));
+        self.save_to_json(&users, &loans)?;
+        let removed = Self::rotate_backups(dir, retention)?;
+        Ok(BackupFiles { users, loans, removed })
+    }
+
+    /// Deletes the oldest timestamped backups in `dir` beyond the newest `retention` (at least
+    /// one is always kept), returning the deleted paths. Other files are left alone.
+    pub fn rotate_backups<P: AsRef<Path>>(dir: P, retention: usize) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        let mut stamps = Vec::new();
+        for entry in fs::read_dir(dir).map_err(io_to_sql)? {
+            let name = entry.map_err(io_to_sql)?.file_name();
+            let Some(stamp) = name.to_str().and_then(backup_stamp) else { continue };
+            if !stamps.contains(&stamp.to_string()) {
+                stamps.push(stamp.to_string());
+            }
+        }
+        // The stamp format sorts chronologically as text
+        stamps.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut removed = Vec::new();
+        for stamp in stamps.iter().skip(retention.max(1)) {
+            for prefix in [USERS_BACKUP_PREFIX, LOANS_BACKUP_PREFIX] {
+                let path = dir.join(format!("{}{}.json", prefix, stamp));
+                match fs::remove_file(&path) {
+                    Ok(()) => removed.push(path),
+                    // Half of a pair may already be gone
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(io_to_sql(e)),
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Reads users and loans previously written by `save_to_json`.
+    pub fn load_from_json<P: AsRef<Path>>(users_path: P, loans_path: P) -> Result<(Vec<User>, Vec<Loan>)> {
+        fn read<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+            let json = fs::read_to_string(path).map_err(io_to_sql)?;
+            serde_json::from_str(&json)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+        }
+        Ok((read(users_path.as_ref())?, read(loans_path.as_ref())?))
+    }
+
+    /// Restores a `save_to_json` backup into the database in one transaction, returning how many
+    /// users and loans were written. Records are upserted, so importing the same files twice is harmless.
+    pub fn import_from_json<P: AsRef<Path>>(&self, users_path: P, loans_path: P) -> Result<(usize, usize)> {
+        let (users, loans) = Self::load_from_json(users_path, loans_path)?;
+        self.transaction(|tx| {
+            for user in &users {
+                write_user(tx, user)?;
+            }
+            for loan in &loans {
+                write_loan(tx, loan)?;
+            }
+            Ok((users.len(), loans.len()))
+        })
+    }
+
+    /// Writes every loan as RFC 4180 CSV with a header row. The repayment schedule is flattened
+    /// into one field of `due_date amount` pairs joined by `;`.
+    pub fn export_loans_csv<W: Write>(&self, mut w: W) -> Result<()> {
+        let loans = self.load_all_loans_including_archived()?;
+        write_csv_record(&mut w, &[
+            "id", "borrower_id", "lender_id", "principal", "currency", "interest_rate", "interest_kind", "status",
+            "disbursement_date", "start_date", "last_repayment_date", "amount_paid", "repayment_schedule",
+        ]).map_err(io_to_sql)?;
+        for loan in loans {
+            let schedule = loan.repayment_schedule.iter()
+                .map(|p| Some(format!("{} {}", p.due_date.to_rfc3339(), p.amount()?)))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(MoneyOverflow)?
+                .join(";");
+            write_csv_record(&mut w, &[
+                &loan.id.to_string(),
+                &loan.borrower_id,
+                &loan.lender_id,
+                &loan.principal.to_string(),
+                &loan.currency,
+                &loan.interest_rate.to_string(),
+                &format!("{:?}", loan.interest_kind),
+                &format!("{:?}", loan.status),
+                &loan.disbursement_date.to_rfc3339(),
+                &loan.start_date.to_rfc3339(),
+                &loan.last_repayment_date.map(|d| d.to_rfc3339()).unwrap_or_default(),
+                &loan.amount_paid.to_string(),
+                &schedule,
+            ]).map_err(io_to_sql)?;
+        }
+        w.flush().map_err(io_to_sql)
+    }
+
+    /// Writes every user as RFC 4180 CSV with a header row; missing optional fields are empty.
+    pub fn export_users_csv<W: Write>(&self, mut w: W) -> Result<()> {
+        let users = self.load_all_users()?;
+        write_csv_record(&mut w, &[
+            "id", "name", "role", "email", "phone", "lender_id", "organization", "wallet_balance",
+        ]).map_err(io_to_sql)?;
+        for user in users {
+            write_csv_record(&mut w, &[
+                &user.id,
+                &user.name,
+                &format!("{:?}", user.role),
+                user.email.as_deref().unwrap_or_default(),
+                user.phone.as_deref().unwrap_or_default(),
+                user.lender_id.as_deref().unwrap_or_default(),
+                user.organization.as_deref().unwrap_or_default(),
+                &user.wallet_balance.to_string(),
+            ]).map_err(io_to_sql)?;
+        }
+        w.flush().map_err(io_to_sql)
+    }
+
     // Firebase user link methods
     pub fn create_linked_user(
         &self,
@@ -403,7 +1544,7 @@ impl Db {
         _firebase_uid: String,
     ) -> Result<String> {
         let id = Uuid::new_v4().to_string();
-        self.conn.execute(
+        self.conn()?.execute(
             "INSERT INTO users (id, name, role, email, lender_id, organization) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![id, name, format!("{:?}", role), email, lender_id, organization],
         )?;
@@ -411,7 +1552,8 @@ impl Db {
     }
 
     pub fn save_user_link(&self, link: &crate::auth::models::UserLink) -> Result<()> {
-        self.conn.execute(
+        let conn = self.conn()?;
+        conn.execute(
             "INSERT OR REPLACE INTO firebase_user_links (firebase_uid, local_user_id, email, role, created_at, updated_at)
             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![
@@ -427,7 +1569,8 @@ impl Db {
     }
 
     pub fn get_user_link(&self, firebase_uid: &str) -> Result<Option<crate::auth::models::UserLink>> {
-        let mut stmt = self.conn.prepare(
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
             "SELECT firebase_uid, local_user_id, email, role, created_at, updated_at 
              FROM firebase_user_links WHERE firebase_uid = ?1"
         )?;
@@ -466,7 +1609,8 @@ impl Db {
     }
 
     pub fn get_user_link_by_local_id(&self, local_user_id: &str) -> Result<Option<crate::auth::models::UserLink>> {
-        let mut stmt = self.conn.prepare(
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
             "SELECT firebase_uid, local_user_id, email, role, created_at, updated_at 
              FROM firebase_user_links WHERE local_user_id = ?1"
         )?;
@@ -505,3 +1649,163 @@ impl Db {
     }
 }
 
+/// Reads an RFC 3339 timestamp column; legacy values that don't parse load as the Unix epoch.
+fn timestamp_column(row: &rusqlite::Row<'_>, idx: usize) -> Result<DateTime<Utc>> {
+    let value: Option<String> = row.get(idx)?;
+    Ok(value
+        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_default())
+}
+
+/// Upserts one user row on `conn`, which may be a transaction. An existing row keeps its
+/// `created_at`, wallet balance and password hash; `updated_at` is set to now.
+fn write_user(conn: &Connection, user: &User) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO users (id, name, role, email, lender_id, organization, created_at, wallet_balance, phone, password_hash, updated_at,
+                                      wallet_balance_cents)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, COALESCE((SELECT created_at FROM users WHERE id = ?1), ?7),
+                 COALESCE((SELECT wallet_balance FROM users WHERE id = ?1), ?8), ?9,
+                 (SELECT password_hash FROM users WHERE id = ?1), ?10,
+                 COALESCE((SELECT wallet_balance_cents FROM users WHERE id = ?1), ?11))",
+        params![
+            &user.id,
+            &user.name,
+            format!("{:?}", user.role),
+            &user.email,
+            &user.lender_id,
+            &user.organization,
+            user.created_at.to_rfc3339(),
+            user.wallet_balance.to_f64(),
+            &user.phone,
+            Utc::now().to_rfc3339(),
+            user.wallet_balance
+        ],
+    )?;
+    Ok(())
+}
+
+/// Takes `amount` from a wallet on `conn` only if the balance covers it; returns whether it did.
+fn debit_wallet(conn: &Connection, user_id: &str, amount: Money) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE users SET wallet_balance_cents = wallet_balance_cents - ?2,
+                          wallet_balance = (wallet_balance_cents - ?2) / 100.0
+         WHERE id = ?1 AND wallet_balance_cents >= ?2",
+        params![user_id, amount],
+    )?;
+    Ok(n > 0)
+}
+
+/// Loan recorded under `user_id`'s `key` on `conn`, ignoring entries older than `since` if set.
+fn load_idempotent_loan(conn: &Connection, user_id: &str, key: &str, since: Option<DateTime<Utc>>) -> Result<Option<Uuid>> {
+    let result = conn.query_row(
+        "SELECT loan_id FROM idempotency WHERE user_id = ?1 AND key = ?2 AND (?3 IS NULL OR created_at >= ?3)",
+        params![user_id, key, since.map(|t| t.to_rfc3339())],
+        |row| {
+            let loan_id: String = row.get(0)?;
+            Uuid::parse_str(&loan_id).map_err(|_| rusqlite::Error::InvalidColumnType(0, "UUID".to_string(), rusqlite::types::Type::Text))
+        },
+    );
+
+    match result {
+        Ok(loan_id) => Ok(Some(loan_id)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Inserts (or replaces) one payment row on `conn`, which may be a transaction.
+fn write_payment(conn: &Connection, payment: &Payment) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO payments (id, loan_id, amount, amount_cents, paid_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            payment.id.to_string(),
+            payment.loan_id.to_string(),
+            payment.amount.to_f64(),
+            payment.amount,
+            payment.paid_at.to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
+
+/// Upserts one loan row on `conn`, which may be a transaction.
+fn write_loan(conn: &Connection, loan: &Loan) -> Result<()> {
+    let repayment_schedule_json = serde_json::to_string(&loan.repayment_schedule)
+        .map_err(|_| rusqlite::Error::InvalidColumnType(0, "JSON".to_string(), rusqlite::types::Type::Text))?;
+    let late_fee_policy_json = loan.late_fee_policy
+        .map(|policy| serde_json::to_string(&policy))
+        .transpose()
+        .map_err(|_| rusqlite::Error::InvalidColumnType(0, "JSON".to_string(), rusqlite::types::Type::Text))?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO loans (id, borrower_id, lender_id, principal, interest_rate, disbursement_date, start_date, last_repayment_date, status, repayment_schedule, amount_paid, interest_kind, currency, created_at, updated_at, principal_cents, amount_paid_cents, promo_months, archived, payment_frequency, guarantor_id, late_fee_policy)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, COALESCE((SELECT created_at FROM loans WHERE id = ?1), ?14), ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)",
+        params![
+            loan.id.to_string(),
+            &loan.borrower_id,
+            &loan.lender_id,
+            loan.principal.to_f64(),
+            loan.interest_rate,
+            loan.disbursement_date.to_rfc3339(),
+            loan.start_date.to_rfc3339(),
+            loan.last_repayment_date.map(|dt| dt.to_rfc3339()),
+            format!("{:?}", loan.status),
+            repayment_schedule_json,
+            loan.amount_paid.to_f64(),
+            format!("{:?}", loan.interest_kind),
+            &loan.currency,
+            loan.created_at.to_rfc3339(),
+            Utc::now().to_rfc3339(),
+            loan.principal,
+            loan.amount_paid,
+            loan.promo_months,
+            loan.archived,
+            format!("{:?}", loan.payment_frequency),
+            &loan.guarantor_id,
+            late_fee_policy_json
+        ],
+    )?;
+    Ok(())
+}
+
+fn write_status_change(conn: &Connection, loan_id: Uuid, from: Option<&LoanStatus>, to: &LoanStatus, actor: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO audit_log (loan_id, from_status, to_status, changed_at, actor) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            loan_id.to_string(),
+            from.map(|s| format!("{:?}", s)),
+            format!("{:?}", to),
+            Utc::now().to_rfc3339(),
+            actor
+        ],
+    )?;
+    Ok(())
+}
+
+/// Writes one CSV record terminated by CRLF, quoting fields that contain a comma, quote or line break.
+fn write_csv_record<W: Write>(w: &mut W, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            write!(w, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            w.write_all(field.as_bytes())?;
+        }
+    }
+    w.write_all(b"\r\n")
+}
+
+/// The timestamp in a `write_backup` file name, when `name` is one.
+fn backup_stamp(name: &str) -> Option<&str> {
+    let rest = name.strip_prefix(USERS_BACKUP_PREFIX).or_else(|| name.strip_prefix(LOANS_BACKUP_PREFIX))?;
+    let stamp = rest.strip_suffix(".json")?;
+    chrono::NaiveDateTime::parse_from_str(stamp, BACKUP_STAMP_FORMAT).ok()?;
+    Some(stamp)
+}
+
+fn io_to_sql(e: io::Error) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+}
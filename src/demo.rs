@@ -0,0 +1,171 @@
+//! Self-contained CLI demo.
+//!
+//! Every run works in a fresh temporary directory (database plus JSON backups) that is
+//! deleted afterwards, so repeated demos never write to the configured database.
+
+use crate::db::Db;
+use crate::loan::LoanTracker;
+use crate::models::{RiskScorable, UserRole};
+use crate::user::UserManager;
+use crate::timezone::{format_local, Zone};
+use chrono::Utc;
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Temporary directory holding the demo's files, removed on drop.
+struct DemoSandbox {
+    dir: PathBuf,
+}
+
+impl DemoSandbox {
+    fn create() -> Self {
+        let dir = std::env::temp_dir().join(format!("lendwise-demo-{}", Uuid::new_v4()));
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("❌ Failed to create demo sandbox {}: {}", dir.display(), e);
+        }
+        DemoSandbox { dir }
+    }
+
+    fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+}
+
+impl Drop for DemoSandbox {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            eprintln!("⚠️  Failed to remove demo sandbox {}: {}", self.dir.display(), e);
+        }
+    }
+}
+
+/// Runs the walkthrough against a throwaway sandbox and returns the sandbox directory,
+/// which has already been removed by the time this returns. Dates are printed in `display_tz`.
+pub fn run_demo(display_tz: Zone) -> PathBuf {
+    println!("🚀 Smart Loan Recovery System Starting...");
+
+    let sandbox = DemoSandbox::create();
+    println!("🧪 Demo sandbox: {}", sandbox.dir.display());
+    let dir = sandbox.dir.clone();
+    let db_path = sandbox.path("demo.db");
+    let db = match Db::new_with_path(&db_path.to_string_lossy()) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize sandbox database: {}", e);
+            return dir;
+        }
+    };
+
+    walk_through(&db, &sandbox, &display_tz);
+    dir
+}
+
+fn walk_through(db: &Db, sandbox: &DemoSandbox, display_tz: &Zone) {
+    // Initialize system components with database
+    let user_manager = UserManager::new(db);
+    let loan_tracker = LoanTracker::new(db);
+
+    // Demo: Register users
+    println!(" 📝 Registering users...");
+
+    let borrower_id = match user_manager.register_user(
+        "Alice Johnson".to_string(),
+        None,
+        None,
+        UserRole::Borrower,
+        None,
+        None
+    ) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("❌ Failed to register borrower: {}", e);
+            return;
+        }
+    };
+
+    let lender_id = match user_manager.register_user(
+        "Bob Smith".to_string(),
+        None,
+        None,
+        UserRole::Lender,
+        None,
+        Some("Demo Bank".to_string())
+    ) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("❌ Failed to register lender: {}", e);
+            return;
+        }
+    };
+
+    println!("✅ Registered borrower: {}", borrower_id);
+    println!("✅ Registered lender: {}", lender_id);
+
+    // Demo: Create a loan
+    println!(" 💰 Creating a loan...");
+
+    let loan_id = match loan_tracker.create_loan(
+        borrower_id.clone(),
+        lender_id,
+        10000.0,  // $10,000 principal
+        5.5,      // 5.5% interest rate
+        12        // 12 months duration
+    ) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("❌ Failed to create loan: {}", e);
+            return;
+        }
+    };
+
+    println!("✅ Created loan: {} for borrower {}", loan_id, borrower_id);
+
+    // Demo: Get loan details
+    match loan_tracker.get_loan(loan_id) {
+        Ok(Some(loan)) => {
+            println!(" 📊 Loan Details:");
+            println!("   ID: {}", loan.id);
+            println!("   Principal: {} {}", loan.principal, loan.currency);
+            println!("   Interest Rate: {:.1}%", loan.interest_rate);
+            println!("   Status: {}", loan.status);
+            println!("   Disbursed: {}", format_local(loan.disbursement_date, display_tz));
+            if let Some(first) = loan.repayment_schedule.first() {
+                println!("   First Payment Due: {}", format_local(first.due_date, display_tz));
+            }
+            println!("   Risk Score: {:.2}", loan.calculate_risk_score(Utc::now()));
+        }
+        Ok(None) => println!("❌ Loan not found"),
+        Err(e) => eprintln!("❌ Failed to load loan: {}", e),
+    }
+
+    // Demo: Update repayment
+    println!(" 💳 Processing repayment...");
+    if let Err(e) = loan_tracker.update_repayment(loan_id) {
+        eprintln!("❌ Failed to update repayment: {}", e);
+    } else {
+        println!("✅ Repayment updated successfully");
+    }
+
+    // Demo: Check updated loan status
+    match loan_tracker.get_loan(loan_id) {
+        Ok(Some(loan)) => {
+            println!(" 📈 Updated Loan Status: {}", loan.status);
+            println!("   Risk Score: {:.2}", loan.calculate_risk_score(Utc::now()));
+        }
+        Ok(None) => println!("❌ Loan not found"),
+        Err(e) => eprintln!("❌ Failed to load loan: {}", e),
+    }
+
+    // Demo: Save to JSON backup
+    println!(" 💾 Creating JSON backup...");
+    if let Err(e) = db.save_to_json(sandbox.path("users_backup.json"), sandbox.path("loans_backup.json")) {
+        eprintln!("❌ Failed to create JSON backup: {}", e);
+    } else {
+        println!("✅ JSON backup created successfully");
+    }
+
+    println!(" 🎉 Smart Loan Recovery System Demo Complete!");
+    println!("💡 The sandbox is removed on exit; the configured database was not touched");
+}
+
@@ -0,0 +1,145 @@
+//! Wire format of the HTTP API. Handlers convert storage models into these before serializing,
+//! so fields added to `User` or `Loan` for internal use stay off the wire until listed here.
+
+use crate::money::Money;
+use crate::models::{InterestKind, LateFeePolicy, Loan, LoanStatus, PaymentFrequency, ScheduledPayment, User, UserRole};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// One page of a list endpoint. `total` counts every matching item, not just this page.
+#[derive(Debug, Clone, Serialize)]
+pub struct Paged<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub limit: u32,
+    pub offset: u32,
+    /// Whether items remain after this page
+    pub has_next: bool,
+}
+
+impl<T> Paged<T> {
+    pub fn new(items: Vec<T>, total: usize, limit: usize, offset: usize) -> Self {
+        let clamp = |v: usize| u32::try_from(v).unwrap_or(u32::MAX);
+        Paged {
+            has_next: offset.saturating_add(items.len()) < total,
+            items,
+            total: total as u64,
+            limit: clamp(limit),
+            offset: clamp(offset),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserResponse {
+    pub id: String,
+    pub name: String,
+    pub role: UserRole,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub lender_id: Option<String>,
+    pub organization: Option<String>,
+    pub wallet_balance: Money,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<User> for UserResponse {
+    fn from(user: User) -> Self {
+        UserResponse {
+            id: user.id,
+            name: user.name,
+            role: user.role,
+            email: user.email,
+            phone: user.phone,
+            lender_id: user.lender_id,
+            organization: user.organization,
+            wallet_balance: user.wallet_balance,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+/// The parts of a `UserResponse` anyone logged in may see: no contact details or wallet.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserProfile {
+    pub id: String,
+    pub name: String,
+    pub role: UserRole,
+    pub lender_id: Option<String>,
+    pub organization: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<User> for UserProfile {
+    fn from(user: User) -> Self {
+        UserProfile {
+            id: user.id,
+            name: user.name,
+            role: user.role,
+            lender_id: user.lender_id,
+            organization: user.organization,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoanResponse {
+    pub id: Uuid,
+    pub borrower_id: String,
+    pub lender_id: String,
+    pub guarantor_id: Option<String>,
+    pub principal: Money,
+    pub currency: String,
+    pub interest_rate: f64,
+    pub interest_kind: InterestKind,
+    pub promo_months: i64,
+    pub payment_frequency: PaymentFrequency,
+    pub disbursement_date: DateTime<Utc>,
+    pub start_date: DateTime<Utc>,
+    pub repayment_schedule: Vec<ScheduledPayment>,
+    pub last_repayment_date: Option<DateTime<Utc>>,
+    pub status: LoanStatus,
+    pub amount_paid: Money,
+    pub archived: bool,
+    /// The loan's own late-fee policy; `None` means the configured default applies
+    pub late_fee_policy: Option<LateFeePolicy>,
+    /// Days the earliest unpaid installment is past due, as of the response
+    pub days_overdue: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<Loan> for LoanResponse {
+    fn from(loan: Loan) -> Self {
+        let days_overdue = loan.days_overdue(Utc::now());
+        LoanResponse {
+            id: loan.id,
+            borrower_id: loan.borrower_id,
+            lender_id: loan.lender_id,
+            guarantor_id: loan.guarantor_id,
+            principal: loan.principal,
+            currency: loan.currency,
+            interest_rate: loan.interest_rate,
+            interest_kind: loan.interest_kind,
+            promo_months: loan.promo_months,
+            payment_frequency: loan.payment_frequency,
+            disbursement_date: loan.disbursement_date,
+            start_date: loan.start_date,
+            repayment_schedule: loan.repayment_schedule,
+            last_repayment_date: loan.last_repayment_date,
+            status: loan.status,
+            amount_paid: loan.amount_paid,
+            archived: loan.archived,
+            late_fee_policy: loan.late_fee_policy,
+            days_overdue,
+            created_at: loan.created_at,
+            updated_at: loan.updated_at,
+        }
+    }
+}
@@ -1,5 +1,7 @@
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
 use actix_identity::error::LoginError;
+use crate::middleware::current_request_id;
+use crate::money::MoneyOverflow;
 use serde::Serialize;
 use thiserror::Error;
 
@@ -35,29 +37,56 @@ struct ErrorResponse {
     error: String,
     message: String,
     details: Option<String>,
+    /// Set when the request went through `AssignRequestId`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<uuid::Uuid>,
 }
 
 impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Database(_) | AppError::Login(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::UuidParse(_) | AppError::Serde(_) | AppError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            AppError::AuthRequired => StatusCode::UNAUTHORIZED,
+            AppError::InsufficientPermissions => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+        }
+    }
+
     fn error_response(&self) -> HttpResponse {
-        let (status, message) = match self {
-            AppError::Database(_) => (actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()),
-            AppError::UuidParse(_) => (actix_web::http::StatusCode::BAD_REQUEST, "Invalid UUID format".to_string()),
-            AppError::Serde(_) => (actix_web::http::StatusCode::BAD_REQUEST, "Invalid JSON".to_string()),
-            AppError::Login(_) => (actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, "Login error".to_string()),
-            AppError::AuthRequired => (actix_web::http::StatusCode::UNAUTHORIZED, "Authentication required".to_string()),
-            AppError::InsufficientPermissions => (actix_web::http::StatusCode::FORBIDDEN, "Insufficient permissions".to_string()),
-            AppError::InvalidInput(msg) => (actix_web::http::StatusCode::BAD_REQUEST, msg.clone()),
-            AppError::NotFound(msg) => (actix_web::http::StatusCode::NOT_FOUND, msg.clone()),
+        let status = self.status_code();
+        let message = match self {
+            AppError::Database(_) => "Database error".to_string(),
+            AppError::UuidParse(_) => "Invalid UUID format".to_string(),
+            AppError::Serde(_) => "Invalid JSON".to_string(),
+            AppError::Login(_) => "Login error".to_string(),
+            AppError::AuthRequired => "Authentication required".to_string(),
+            AppError::InsufficientPermissions => "Insufficient permissions".to_string(),
+            AppError::InvalidInput(msg) | AppError::NotFound(msg) => msg.clone(),
         };
 
+        let request_id = current_request_id();
+        if status.is_server_error() {
+            // The body hides the cause, so keep it in the log under the id the client sees
+            log::error!("[request {}] {}", request_id.map(|id| id.to_string()).unwrap_or_else(|| "-".to_string()), self);
+        }
+
         let error_response = ErrorResponse {
             error: status.to_string(),
-            message: message,
+            message,
             details: None,
+            request_id,
         };
 
         HttpResponse::build(status).json(error_response)
     }
 }
 
+/// Amounts out of range come from the request's figures, or from loans built on them.
+impl From<MoneyOverflow> for AppError {
+    fn from(e: MoneyOverflow) -> Self {
+        AppError::InvalidInput(e.to_string())
+    }
+}
+
 pub type AppResult<T> = Result<T, AppError>;
\ No newline at end of file
@@ -0,0 +1,100 @@
+//! JSON request bodies that fail as `AppError::InvalidInput` with a message naming the field at
+//! fault (`field 'principal' is required`), rather than actix's generic deserialization error.
+
+use crate::error::AppError;
+use actix_web::dev::Payload;
+use actix_web::{mime, FromRequest, HttpMessage, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+use std::ops::Deref;
+
+/// Largest body accepted, the same as actix's own `web::Json` default.
+pub const MAX_JSON_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Drop-in for `web::Json<T>` in handlers.
+#[derive(Debug)]
+pub struct JsonBody<T>(pub T);
+
+impl<T> JsonBody<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for JsonBody<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for JsonBody<T> {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, AppError>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let is_json = req.mime_type().ok().flatten().is_some_and(|mime| {
+            mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON)
+        });
+        let mut payload = payload.take();
+        Box::pin(async move {
+            if !is_json {
+                return Err(AppError::InvalidInput("request body must be JSON (Content-Type: application/json)".to_string()));
+            }
+            let mut body = Vec::new();
+            while let Some(chunk) = payload.next().await {
+                let chunk = chunk.map_err(|e| AppError::InvalidInput(format!("could not read request body: {}", e)))?;
+                if body.len() + chunk.len() > MAX_JSON_BODY_BYTES {
+                    return Err(AppError::InvalidInput(format!("request body is larger than {} bytes", MAX_JSON_BODY_BYTES)));
+                }
+                body.extend_from_slice(&chunk);
+            }
+            parse_body(&body).map(JsonBody)
+        })
+    }
+}
+
+/// Deserializes `body`, describing any failure in terms of the request's fields.
+pub fn parse_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, AppError> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Err(AppError::InvalidInput("request body is empty".to_string()));
+    }
+    let mut de = serde_json::Deserializer::from_slice(body);
+    let value = serde_path_to_error::deserialize(&mut de).map_err(|e| AppError::InvalidInput(describe(&e)))?;
+    de.end().map_err(|e| AppError::InvalidInput(describe_syntax(&e)))?;
+    Ok(value)
+}
+
+fn describe(err: &serde_path_to_error::Error<serde_json::Error>) -> String {
+    let inner = err.inner();
+    if !inner.is_data() {
+        return describe_syntax(inner);
+    }
+    let position = format!(" at line {} column {}", inner.line(), inner.column());
+    let message = inner.to_string();
+    let message = message.strip_suffix(&position).unwrap_or(&message);
+    let path = err.path().to_string();
+
+    if let Some(name) = backticked(message, "missing field `") {
+        // The path stops at the object missing the field
+        let field = if path == "." { name.to_string() } else { format!("{}.{}", path, name) };
+        format!("field '{}' is required", field)
+    } else if message.starts_with("unknown field `") {
+        format!("unknown field '{}'", path)
+    } else if path == "." {
+        format!("request body is invalid: {}", message)
+    } else {
+        format!("field '{}' is invalid: {}", path, message)
+    }
+}
+
+fn describe_syntax(err: &serde_json::Error) -> String {
+    format!("request body is not valid JSON (line {}, column {})", err.line(), err.column())
+}
+
+/// The name between `prefix` and the next backtick, when `message` starts with `prefix`.
+fn backticked<'a>(message: &'a str, prefix: &str) -> Option<&'a str> {
+    message.strip_prefix(prefix)?.split('`').next()
+}
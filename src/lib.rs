@@ -2,8 +2,17 @@ pub mod api;
 pub mod auth;
 pub mod config;
 pub mod db;
+pub mod demo;
+pub mod dto;
 pub mod error;
+pub mod extract;
 pub mod loan;
+pub mod middleware;
 pub mod models;
+pub mod money;
+pub mod notify;
 pub mod recovery;
-pub mod user;
\ No newline at end of file
+pub mod seed;
+pub mod timezone;
+pub mod user;
+pub mod webhook;
\ No newline at end of file
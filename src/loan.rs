@@ -1,91 +1,1123 @@
-use crate::models::{Loan, LoanStatus};
-use crate::db::Db;
-use chrono::{Duration, Utc};
+use crate::models::{AuditEntry, BorrowerHistory, UserRole, DEFAULT_CURRENCY, ExtensionRequest, ExtensionStatus, InterestKind, InterestRounding, LateFeePolicy, Loan, LoanStatus, Payment, PaymentFrequency, RiskBand, RiskModel, ScheduleEntry, ScheduledPayment};
+use crate::db::{Db, LoanFilter, SortOrder};
+use crate::error::{AppError, AppResult};
+use crate::money::{Money, MoneyOverflow};
+use crate::notify::REMINDER_COOLDOWN_HOURS;
+use crate::recovery::{RecoveryAction, RecoveryEngine};
+use crate::webhook::{StatusChangeEvent, WebhookDispatcher};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
 use uuid::Uuid;
 use rusqlite::Result;
+use std::collections::BTreeMap;
+
+/// Longest accepted loan term in months (50 years).
+pub const MAX_DURATION_MONTHS: i64 = 600;
+
+/// Rejects terms that would produce an empty or absurdly large repayment schedule.
+pub fn validate_duration(duration_months: i64) -> std::result::Result<(), String> {
+    if !(1..=MAX_DURATION_MONTHS).contains(&duration_months) {
+        return Err(format!("months must be between 1 and {}", MAX_DURATION_MONTHS));
+    }
+    Ok(())
+}
+
+/// Highest accepted annual interest rate, in percent.
+pub const MAX_INTEREST_RATE: f64 = 100.0;
+
+/// Largest accepted principal or single payment. `Money` holds cents in an i64 (about 9.2e16 in
+/// currency units), so this leaves room for a full term of interest and late fees on top.
+pub const MAX_AMOUNT: f64 = 1_000_000_000.0;
+
+pub fn validate_principal(principal: f64) -> std::result::Result<(), String> {
+    if !principal.is_finite() || !Money::from_f64(principal).is_positive() {
+        return Err("principal must be a positive number".to_string());
+    }
+    if principal > MAX_AMOUNT {
+        return Err(format!("principal must not exceed {}", MAX_AMOUNT));
+    }
+    Ok(())
+}
+
+/// Largest principal plus interest a schedule may add up to. Compounding at the top rate over
+/// the longest term multiplies a principal many times over, so the schedule is checked as well.
+pub const MAX_REPAYABLE: f64 = 1_000_000_000_000.0;
+
+/// Rejects a schedule whose installments add up to more than `MAX_REPAYABLE`, including one
+/// too large to build in cents at all.
+pub fn validate_schedule_total(schedule: Option<Vec<ScheduledPayment>>) -> std::result::Result<Vec<ScheduledPayment>, String> {
+    match schedule {
+        Some(schedule) if ScheduledPayment::total(&schedule).is_some_and(|total| total.to_f64() <= MAX_REPAYABLE) => Ok(schedule),
+        _ => Err(format!("principal plus interest must not exceed {}", MAX_REPAYABLE)),
+    }
+}
+
+/// A payment must be a positive amount no larger than `MAX_AMOUNT`.
+pub fn validate_payment_amount(amount: f64) -> std::result::Result<(), String> {
+    if !amount.is_finite() || !Money::from_f64(amount).is_positive() {
+        return Err("payment amount must be positive".to_string());
+    }
+    if amount > MAX_AMOUNT {
+        return Err(format!("payment amount must not exceed {}", MAX_AMOUNT));
+    }
+    Ok(())
+}
+
+/// A promotional window may cover the whole term but not exceed it.
+pub fn validate_promo_months(promo_months: i64, duration_months: i64) -> std::result::Result<(), String> {
+    if !(0..=duration_months).contains(&promo_months) {
+        return Err(format!("promo_months must be between 0 and the loan term ({} months)", duration_months));
+    }
+    Ok(())
+}
+
+pub fn validate_interest_rate(interest_rate: f64) -> std::result::Result<(), String> {
+    if !interest_rate.is_finite() || !(0.0..=MAX_INTEREST_RATE).contains(&interest_rate) {
+        return Err(format!("interest_rate must be between 0 and {}", MAX_INTEREST_RATE));
+    }
+    Ok(())
+}
+
+/// ISO 4217 codes accepted for new loans.
+pub const KNOWN_CURRENCIES: &[&str] = &[
+    "USD", "EUR", "GBP", "CHF", "JPY", "CNY", "INR", "CAD", "AUD", "KES", "UGX", "TZS", "RWF",
+    "ETB", "NGN", "GHS", "XOF", "XAF", "ZAR", "ZMW", "MWK", "BWP", "EGP", "MAD",
+];
+
+/// Normalises a currency code to upper case, rejecting anything not in `KNOWN_CURRENCIES`.
+pub fn validate_currency(code: &str) -> std::result::Result<String, String> {
+    let code = code.trim().to_ascii_uppercase();
+    if !KNOWN_CURRENCIES.contains(&code.as_str()) {
+        return Err(format!("unknown currency code {:?}", code));
+    }
+    Ok(code)
+}
+
+/// Audit-log actor for changes made by background jobs and CLI commands.
+pub const SYSTEM_ACTOR: &str = "system";
+
+/// Days an overdue installment may stay unpaid before the loan is treated as defaulted.
+pub const DEFAULT_GRACE_DAYS: i64 = 90;
+
+/// Longest accepted metadata key.
+pub const MAX_METADATA_KEY_LEN: usize = 64;
+/// Largest accepted metadata value, in bytes.
+pub const MAX_METADATA_VALUE_LEN: usize = 1024;
+/// Most metadata keys a single loan may carry.
+pub const MAX_METADATA_KEYS: usize = 32;
+
+/// Metadata keys are lowercase identifiers such as `crm_id` or `branch.code`.
+pub fn validate_metadata_key(key: &str) -> std::result::Result<(), String> {
+    let valid_chars = key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-' | '.'));
+    let starts_ok = key.chars().next().is_some_and(|c| c.is_ascii_lowercase());
+    if key.len() > MAX_METADATA_KEY_LEN || !valid_chars || !starts_ok {
+        return Err(format!(
+            "metadata key must start with a lowercase letter, use only a-z, 0-9, '_', '-' or '.', and be at most {} characters",
+            MAX_METADATA_KEY_LEN
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_metadata_value(value: &str) -> std::result::Result<(), String> {
+    if value.len() > MAX_METADATA_VALUE_LEN {
+        return Err(format!("metadata value must be at most {} bytes", MAX_METADATA_VALUE_LEN));
+    }
+    Ok(())
+}
+
+/// Monthly EMI schedule starting one calendar month from now, interest rounded per installment.
+/// Like the other schedule builders it returns `None` if an amount leaves the range of `Money`.
+pub fn generate_amortization_schedule(principal: f64, interest_rate: f64, months: i64) -> Option<Vec<ScheduledPayment>> {
+    generate_amortization_schedule_from(Utc::now(), principal, interest_rate, months, InterestRounding::PerInstallment)
+}
+
+/// Monthly EMI schedule with installment `m` due `m` calendar months after `start`
+/// (clamped to the month's last day, so Jan 31 is followed by Feb 28/29).
+pub fn generate_amortization_schedule_from(
+    start: DateTime<Utc>,
+    principal: f64,
+    interest_rate: f64,
+    months: i64,
+    rounding: InterestRounding,
+) -> Option<Vec<ScheduledPayment>> {
+    generate_schedule_from(start, principal, interest_rate, months, 0, InterestKind::Amortized, PaymentFrequency::Monthly, rounding)
+}
+
+/// Schedule over a term of `months` for any interest kind and payment frequency. Monthly
+/// schedules use the same calendar as [`generate_amortization_schedule_from`]; weekly and
+/// biweekly ones fall every 7 or 14 days. Installments due in the first `promo_months` are interest-free.
+#[allow(clippy::too_many_arguments)]
+pub fn generate_schedule_from(
+    start: DateTime<Utc>,
+    principal: f64,
+    interest_rate: f64,
+    months: i64,
+    promo_months: i64,
+    kind: InterestKind,
+    frequency: PaymentFrequency,
+    rounding: InterestRounding,
+) -> Option<Vec<ScheduledPayment>> {
+    let due_dates = frequency.due_dates(start, months);
+    let promo = frequency.installments(promo_months);
+    ScheduledPayment::build_with_promo(kind, frequency, principal, interest_rate, &due_dates, rounding, promo)
+}
+
+/// Fees and discounts applied when quoting an early settlement.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SettlementPolicy {
+    /// Late-fee policy for loans without their own, as on the loan's schedule.
+    pub late_fee_policy: Option<LateFeePolicy>,
+    pub early_settlement_discount_pct: f64,
+}
+
+/// Amount needed to close a loan today, broken into its parts.
+/// `net_settlement` is exactly the sum of the rounded components.
+#[derive(Debug, Clone, Serialize)]
+pub struct SettlementQuote {
+    pub loan_id: Uuid,
+    pub as_of: DateTime<Utc>,
+    pub outstanding_principal: Money,
+    pub accrued_interest: Money,
+    pub missed_installments: usize,
+    pub late_fees: Money,
+    pub early_settlement_discount: Money,
+    pub net_settlement: Money,
+}
+
+/// `loan` as it would stand once settled at `as_of`, and the quote for settling it. The settled
+/// loan keeps the installments already due and replaces the rest with one due at `as_of` for
+/// the remaining principal, the schedule interest accrued since the last due date, the late
+/// fees and less the discount, so its remaining balance is the net settlement.
+fn settlement_terms(loan: Loan, policy: &SettlementPolicy, as_of: DateTime<Utc>) -> std::result::Result<(Loan, SettlementQuote), MoneyOverflow> {
+    let mut loan = loan.with_default_late_fee_policy(policy.late_fee_policy);
+    // Payments cover installments in order, each one's interest before its principal
+    let (mut interest_paid, mut principal_paid, mut left) = (Money::ZERO, Money::ZERO, loan.amount_paid);
+    for p in &loan.repayment_schedule {
+        let interest = left.min(p.interest);
+        left = left.checked_sub(interest).ok_or(MoneyOverflow)?;
+        let principal = left.min(p.principal);
+        left = left.checked_sub(principal).ok_or(MoneyOverflow)?;
+        interest_paid = interest_paid.checked_add(interest).ok_or(MoneyOverflow)?;
+        principal_paid = principal_paid.checked_add(principal).ok_or(MoneyOverflow)?;
+    }
+    let accrued = Money::from_f64(loan.accrued_interest(as_of));
+    let outstanding_principal = loan.principal.checked_sub(principal_paid).ok_or(MoneyOverflow)?.non_negative();
+    let unpaid_interest = accrued.checked_sub(interest_paid).ok_or(MoneyOverflow)?.non_negative();
+
+    let missed_installments = loan.missed_installments(as_of);
+    let late_fees = loan.late_fee(as_of).ok_or(MoneyOverflow)?;
+
+    let before_maturity = loan.repayment_schedule.last().map(|last| as_of < last.due_date).unwrap_or(false);
+    let early_settlement_discount = if before_maturity && outstanding_principal.is_positive() {
+        unpaid_interest.scale(policy.early_settlement_discount_pct / 100.0)
+    } else {
+        Money::ZERO
+    };
+
+    let mut schedule: Vec<ScheduledPayment> = loan.repayment_schedule.iter().filter(|p| p.due_date <= as_of).copied().collect();
+    let principal_due = Money::checked_sum(schedule.iter().map(|p| p.principal)).ok_or(MoneyOverflow)?;
+    let interest_due = Money::checked_sum(schedule.iter().map(|p| p.interest)).ok_or(MoneyOverflow)?;
+    let settling = ScheduledPayment {
+        due_date: as_of,
+        principal: loan.principal.checked_sub(principal_due).ok_or(MoneyOverflow)?.non_negative(),
+        interest: accrued
+            .checked_sub(interest_due)
+            .and_then(|i| i.checked_add(late_fees))
+            .and_then(|i| i.checked_sub(early_settlement_discount))
+            .ok_or(MoneyOverflow)?,
+    };
+    if settling.amount() != Some(Money::ZERO) {
+        schedule.push(settling);
+    }
+    loan.repayment_schedule = schedule;
+
+    let net_settlement = loan.remaining_balance().ok_or(MoneyOverflow)?;
+    // Whatever rounding leaves over is interest, so the parts always add up to the net figure
+    let accrued_interest = net_settlement
+        .checked_sub(outstanding_principal)
+        .and_then(|i| i.checked_sub(late_fees))
+        .and_then(|i| i.checked_add(early_settlement_discount))
+        .ok_or(MoneyOverflow)?
+        .non_negative();
+    let quote = SettlementQuote {
+        loan_id: loan.id,
+        as_of,
+        outstanding_principal,
+        accrued_interest,
+        missed_installments,
+        late_fees,
+        early_settlement_discount,
+        net_settlement,
+    };
+    Ok((loan, quote))
+}
+
+/// Result of applying a payment to a loan's balance.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PaymentReceipt {
+    /// Portion of the payment credited to the loan
+    pub applied: Money,
+    pub remaining_balance: Money,
+    /// Overpayment beyond what was owed, to be refunded
+    pub excess: Money,
+}
+
+/// What one auto-debit run did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct AutoDebitSummary {
+    pub payments_made: usize,
+    pub amount_debited: Money,
+    /// Loans newly marked overdue because the wallet could not cover a due installment
+    pub flagged_overdue: usize,
+}
+
+/// One loan whose status a reconcile pass corrected.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReconciledLoan {
+    pub loan_id: Uuid,
+    pub from: LoanStatus,
+    pub to: LoanStatus,
+}
+
+/// What a reconcile pass did.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReconcileSummary {
+    /// Unarchived loans re-evaluated
+    pub checked: usize,
+    pub changed: usize,
+    pub changes: Vec<ReconciledLoan>,
+}
+
+/// Loan count and outstanding exposure for one risk band.
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskBandSummary {
+    pub band: RiskBand,
+    pub count: usize,
+    pub total_outstanding: Money,
+}
+
+/// Dashboard totals over one lender's loans.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortfolioSummary {
+    pub lender_id: String,
+    pub loan_count: usize,
+    /// Principal not yet covered by payments, summed over every loan
+    pub principal_outstanding: Money,
+    /// Loans per status, keyed like the `status` filter; every status is present
+    pub loans_by_status: BTreeMap<String, usize>,
+    /// Mean `RiskModel` default probability; 0 when the lender has no loans
+    pub average_risk_score: f64,
+    /// Default probability weighted by each loan's outstanding principal; 0 when nothing is outstanding
+    pub weighted_risk_score: f64,
+    /// Installment amounts past due and unpaid
+    pub overdue_amount: Money,
+}
+
+/// Mean of the risk scores weighted by the paired outstanding amounts; 0 when they sum to zero.
+fn exposure_weighted_risk(exposures: impl IntoIterator<Item = (f64, Money)>) -> f64 {
+    let (weighted, total) = exposures
+        .into_iter()
+        .fold((0.0, 0.0), |(weighted, total), (risk, outstanding)| {
+            let outstanding = outstanding.to_f64();
+            (weighted + risk * outstanding, total + outstanding)
+        });
+    if total > 0.0 { weighted / total } else { 0.0 }
+}
+
+/// A loan's default probability from [`LoanTracker::assess_risk`], with the borrower's record on
+/// their other loans that went into it.
+#[derive(Debug, Clone)]
+pub struct RiskAssessment {
+    pub risk_score: f64,
+    pub borrower_history: BorrowerHistory,
+}
+
+/// A loan with the default probability `loans_above_risk` ranked it by.
+#[derive(Debug, Clone)]
+pub struct RiskRankedLoan {
+    pub loan: Loan,
+    pub risk_score: f64,
+}
+
+/// Terms for a loan to be created by `create_loans_bulk`.
+#[derive(Debug, Clone)]
+pub struct NewLoan {
+    pub borrower_id: String,
+    pub lender_id: String,
+    pub principal: f64,
+    pub interest_rate: f64,
+    pub duration_months: i64,
+    pub interest_kind: InterestKind,
+    /// ISO 4217 code; checked against `KNOWN_CURRENCIES` when the loan is built.
+    pub currency: String,
+    /// Leading interest-free installments; at most `duration_months`.
+    pub promo_months: i64,
+    pub payment_frequency: PaymentFrequency,
+    /// Existing user, other than the borrower, who guarantees the loan.
+    pub guarantor_id: Option<String>,
+    /// Penalty for late installments; `None` leaves it to the configured default.
+    pub late_fee_policy: Option<LateFeePolicy>,
+}
+
+impl NewLoan {
+    /// The repayment schedule these terms produce for a loan disbursed at `start`; `None` if an
+    /// amount leaves the range of `Money`.
+    pub fn schedule_from(&self, start: DateTime<Utc>, rounding: InterestRounding) -> Option<Vec<ScheduledPayment>> {
+        generate_schedule_from(
+            start,
+            self.principal,
+            self.interest_rate,
+            self.duration_months,
+            self.promo_months,
+            self.interest_kind,
+            self.payment_frequency,
+            rounding,
+        )
+    }
+}
 
 pub struct LoanTracker<'a> {
     db: &'a Db,
+    interest_rounding: InterestRounding,
+    risk_model: RiskModel,
+    actor: &'a str,
+    webhook: Option<&'a WebhookDispatcher>,
 }
 
 impl<'a> LoanTracker<'a> {
     pub fn new(db: &'a Db) -> Self {
-        LoanTracker { db }
+        LoanTracker { db, interest_rounding: InterestRounding::default(), risk_model: RiskModel::default(), actor: SYSTEM_ACTOR, webhook: None }
+    }
+
+    /// Rounding policy used when building schedules for new loans.
+    pub fn with_interest_rounding(mut self, rounding: InterestRounding) -> Self {
+        self.interest_rounding = rounding;
+        self
+    }
+
+    /// Model every risk score from this tracker is computed with.
+    pub fn with_risk_model(mut self, model: RiskModel) -> Self {
+        self.risk_model = model;
+        self
+    }
+
+    /// User id recorded in the audit log for status changes made through this tracker.
+    pub fn with_actor(mut self, actor: &'a str) -> Self {
+        self.actor = actor;
+        self
+    }
+
+    /// Where status changes made through this tracker are announced; `None` announces nothing.
+    pub fn with_webhook(mut self, webhook: Option<&'a WebhookDispatcher>) -> Self {
+        self.webhook = webhook;
+        self
+    }
+
+    /// Saves the loan and, if its status differs from `previous`, logs the transition and
+    /// hands it to the webhook (new loans, with no previous status, are not announced).
+    fn save_with_audit(&self, loan: &Loan, previous: Option<&LoanStatus>) -> Result<()> {
+        self.db.save_loan(loan)?;
+        if previous != Some(&loan.status) {
+            self.db.record_status_change(loan.id, previous, &loan.status, self.actor)?;
+            if let Some(previous) = previous {
+                self.notify_status_change(loan, previous);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the status-change webhook, if one is configured, once a change from `previous`
+    /// has been stored.
+    fn notify_status_change(&self, loan: &Loan, previous: &LoanStatus) {
+        if let Some(webhook) = self.webhook.filter(|_| *previous != loan.status) {
+            webhook.dispatch(StatusChangeEvent {
+                loan_id: loan.id,
+                old_status: previous.clone(),
+                new_status: loan.status.clone(),
+                changed_at: Utc::now(),
+            });
+        }
+    }
+
+    pub fn get_audit_log(&self, loan_id: Uuid) -> Result<Vec<AuditEntry>> {
+        self.db.load_audit_log(loan_id)
     }
 
     pub fn create_loan(
         &self,
-        borrower_id_str: String,
-        lender_id_str: String,
+        borrower_id: String,
+        lender_id: String,
         principal: f64,
         interest_rate: f64,
         duration_months: i64,
-    ) -> Result<Uuid> {
-        let borrower_id = Uuid::parse_str(&borrower_id_str).map_err(|_| rusqlite::Error::InvalidColumnType(0, "UUID".to_string(), rusqlite::types::Type::Text))?;
-        let lender_id = Uuid::parse_str(&lender_id_str).map_err(|_| rusqlite::Error::InvalidColumnType(1, "UUID".to_string(), rusqlite::types::Type::Text))?;
-        
-        let id = Uuid::new_v4();
-        let now = Utc::now();
-        let mut schedule = Vec::new();
-        for m in 1..=duration_months {
-            schedule.push(now + Duration::days(30 * m)); // Approximate monthly
-        }
-        let loan = Loan {
-            id,
+    ) -> AppResult<Uuid> {
+        self.create_loan_with_kind(borrower_id, lender_id, principal, interest_rate, duration_months, InterestKind::default())
+    }
+
+    pub fn create_loan_with_kind(
+        &self,
+        borrower_id: String,
+        lender_id: String,
+        principal: f64,
+        interest_rate: f64,
+        duration_months: i64,
+        interest_kind: InterestKind,
+    ) -> AppResult<Uuid> {
+        self.create_new_loan(NewLoan {
             borrower_id,
             lender_id,
             principal,
             interest_rate,
+            duration_months,
+            interest_kind,
+            currency: DEFAULT_CURRENCY.to_string(),
+            promo_months: 0,
+            payment_frequency: PaymentFrequency::default(),
+            guarantor_id: None,
+            late_fee_policy: None,
+        })
+    }
+
+    /// Validates the terms and stores the loan. Invalid terms come back as
+    /// `AppError::InvalidInput`, storage failures as `AppError::Database`.
+    pub fn create_new_loan(&self, new: NewLoan) -> AppResult<Uuid> {
+        let loan = self.build_loan(new, Utc::now())?;
+        self.save_with_audit(&loan, None)?;
+        Ok(loan.id)
+    }
+
+    /// Like `create_new_loan`, reserving the Idempotency-Key `key` for `user_id` in the same
+    /// transaction. If the key is already taken nothing is stored and the id of the loan
+    /// created under it comes back as `Err`.
+    pub fn create_new_loan_once(&self, new: NewLoan, user_id: &str, key: &str) -> AppResult<std::result::Result<Uuid, Uuid>> {
+        let loan = self.build_loan(new, Utc::now())?;
+        Ok(match self.db.insert_loan_with_idempotency_key(&loan, self.actor, user_id, key)? {
+            Some(existing) => Err(existing),
+            None => Ok(loan.id),
+        })
+    }
+
+    /// Creates all the loans in one transaction, returning their ids in input order. If any
+    /// entry is invalid nothing is stored and the `InvalidInput` message names the failing
+    /// index as `loans[i]`.
+    pub fn create_loans_bulk(&self, loans: Vec<NewLoan>) -> AppResult<Vec<Uuid>> {
+        let now = Utc::now();
+        let loans = loans
+            .into_iter()
+            .enumerate()
+            .map(|(i, new)| {
+                self.build_loan(new, now).map_err(|e| match e {
+                    AppError::InvalidInput(msg) => AppError::InvalidInput(format!("loans[{}]: {}", i, msg)),
+                    other => other,
+                })
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+        self.db.insert_loans(&loans, self.actor)?;
+        Ok(loans.iter().map(|loan| loan.id).collect())
+    }
+
+    /// Every rule a new loan's terms must meet, checked in one place for the API, CLI and seeding.
+    fn build_loan(&self, new: NewLoan, now: DateTime<Utc>) -> AppResult<Loan> {
+        // Checked before the schedule loop so a huge term cannot allocate unbounded memory
+        validate_duration(new.duration_months)
+            .and_then(|()| validate_principal(new.principal))
+            .and_then(|()| validate_interest_rate(new.interest_rate))
+            .and_then(|()| validate_promo_months(new.promo_months, new.duration_months))
+            .and_then(|()| new.late_fee_policy.as_ref().map_or(Ok(()), LateFeePolicy::validate))
+            .map_err(AppError::InvalidInput)?;
+        let currency = validate_currency(&new.currency).map_err(AppError::InvalidInput)?;
+        if let Some(guarantor_id) = &new.guarantor_id {
+            if *guarantor_id == new.borrower_id {
+                return Err(AppError::InvalidInput("guarantor must be someone other than the borrower".to_string()));
+            }
+            if self.db.load_user(guarantor_id)?.is_none() {
+                return Err(AppError::InvalidInput(format!("guarantor {} does not exist", guarantor_id)));
+            }
+        }
+
+        let schedule = validate_schedule_total(new.schedule_from(now, self.interest_rounding)).map_err(AppError::InvalidInput)?;
+        Ok(Loan {
+            id: Uuid::new_v4(),
+            borrower_id: new.borrower_id,
+            lender_id: new.lender_id,
+            guarantor_id: new.guarantor_id,
+            principal: Money::from_f64(new.principal),
+            interest_rate: new.interest_rate,
             disbursement_date: now,
             repayment_schedule: schedule,
             start_date: now,
             last_repayment_date: None,
             status: LoanStatus::Active,
-        };
-        self.db.save_loan(&loan)?;
-        Ok(id)
+            amount_paid: Money::ZERO,
+            interest_kind: new.interest_kind,
+            currency,
+            promo_months: new.promo_months,
+            payment_frequency: new.payment_frequency,
+            archived: false,
+            late_fee_policy: new.late_fee_policy,
+            created_at: now,
+            updated_at: now,
+        })
     }
 
-    pub fn update_repayment(&self, loan_id: Uuid) -> Result<()> {
-        let mut loan = self.db.load_loan(loan_id)?
-            .ok_or_else(|| rusqlite::Error::QueryReturnedNoRows)?;
+    /// Payments recorded against the loan, oldest first.
+    pub fn get_payments(&self, loan_id: Uuid) -> Result<Vec<Payment>> {
+        self.db.load_payments(loan_id)
+    }
 
-        loan.last_repayment_date = Some(Utc::now());
-        loan.status = if Utc::now() > *loan.repayment_schedule.last().unwrap() {
-            LoanStatus::Repaid
-        } else {
-            LoanStatus::Active
-        };
+    /// Whether any payment has been recorded against the loan.
+    pub fn has_payments(&self, loan_id: Uuid) -> Result<bool> {
+        Ok(!self.db.load_payments(loan_id)?.is_empty())
+    }
+
+    /// Corrects the rate and/or term of a loan that has not been paid into yet, rebuilding the
+    /// schedule from its original start date with the same interest kind. `None` keeps a term as is.
+    pub fn update_terms(&self, loan_id: Uuid, interest_rate: Option<f64>, months: Option<i64>) -> AppResult<Loan> {
+        let mut loan = self.require_loan(loan_id)?;
+        if loan.amount_paid.is_positive() || self.has_payments(loan_id)? {
+            return Err(AppError::InvalidInput("terms cannot change once a payment is recorded".to_string()));
+        }
+        let months = months.unwrap_or(loan.term_months());
+        validate_duration(months)
+            .and_then(|_| validate_promo_months(loan.promo_months, months))
+            .and_then(|_| interest_rate.map_or(Ok(()), validate_interest_rate))
+            .map_err(AppError::InvalidInput)?;
+        if let Some(rate) = interest_rate {
+            loan.interest_rate = rate;
+        }
 
+        let schedule = generate_schedule_from(
+            loan.start_date,
+            loan.principal.to_f64(),
+            loan.interest_rate,
+            months,
+            loan.promo_months,
+            loan.interest_kind,
+            loan.payment_frequency,
+            self.interest_rounding,
+        );
+        let schedule = validate_schedule_total(schedule).map_err(AppError::InvalidInput)?;
+        loan.repayment_schedule = schedule;
         self.db.save_loan(&loan)?;
+        Ok(loan)
+    }
+
+    /// Records a repayment of the next unpaid scheduled installment.
+    pub fn update_repayment(&self, loan_id: Uuid) -> AppResult<()> {
+        let loan = self.require_loan(loan_id)?;
+        let installment = match loan.repayment_schedule.get(loan.installments_covered()) {
+            Some(p) => p.amount(),
+            None => loan.remaining_balance(),
+        };
+        let installment = installment.ok_or(MoneyOverflow)?;
+        self.apply_payment(loan, installment)?;
         Ok(())
     }
 
+    /// Applies `amount` to the loan's outstanding balance. The loan becomes `Repaid` once
+    /// cumulative payments cover principal plus interest; anything beyond that is not
+    /// credited and comes back as `excess`.
+    pub fn record_payment(&self, loan_id: Uuid, amount: f64) -> AppResult<PaymentReceipt> {
+        validate_payment_amount(amount).map_err(AppError::InvalidInput)?;
+        let loan = self.require_loan(loan_id)?;
+        Ok(self.apply_payment(loan, Money::from_f64(amount))?)
+    }
+
+    /// Pays an open loan off at `as_of` for exactly `settlement_quote`'s `net_settlement` under
+    /// the same policy: outstanding principal plus the schedule interest accrued up to then,
+    /// instead of the full-term interest. Installments not yet due collapse into one due at
+    /// `as_of`, so the stored loan reads as `Repaid` with only what was charged. Returns the
+    /// amount settled.
+    pub fn settle_early(&self, loan_id: Uuid, policy: &SettlementPolicy, as_of: DateTime<Utc>) -> AppResult<Money> {
+        let loan = self.require_loan(loan_id)?;
+        if !matches!(loan.status, LoanStatus::Active | LoanStatus::PartiallyRepaid | LoanStatus::Overdue) {
+            return Err(AppError::InvalidInput(format!("only open loans can be settled (loan is {})", loan.status)));
+        }
+
+        let (mut settled, quote) = settlement_terms(loan, policy, as_of)?;
+        if quote.net_settlement.is_positive() {
+            self.apply_payment(settled, quote.net_settlement)?;
+        } else {
+            let previous = std::mem::replace(&mut settled.status, LoanStatus::Repaid);
+            self.save_with_audit(&settled, Some(&previous))?;
+        }
+        Ok(quote.net_settlement)
+    }
+
+    fn apply_payment(&self, loan: Loan, amount: Money) -> Result<PaymentReceipt> {
+        let (loan, previous, payment, receipt) = Self::settle_payment(loan, amount, Utc::now())?;
+        self.db.save_applied_payment(&loan, &previous, payment.as_ref(), None, self.actor)?;
+        self.notify_status_change(&loan, &previous);
+        Ok(receipt)
+    }
+
+    /// `loan` with `amount` credited as of `now`, along with the status it had before, the
+    /// payment row to store (none when nothing was owed) and the receipt. Nothing is saved.
+    fn settle_payment(
+        mut loan: Loan,
+        amount: Money,
+        now: DateTime<Utc>,
+    ) -> std::result::Result<(Loan, LoanStatus, Option<Payment>, PaymentReceipt), MoneyOverflow> {
+        let owed = loan.remaining_balance().ok_or(MoneyOverflow)?;
+        let applied = amount.min(owed);
+        let excess = amount.checked_sub(applied).ok_or(MoneyOverflow)?;
+
+        let payment = applied.is_positive().then(|| Payment {
+            id: Uuid::new_v4(),
+            loan_id: loan.id,
+            amount: applied,
+            paid_at: now,
+        });
+        if payment.is_some() {
+            loan.amount_paid = loan.amount_paid.checked_add(applied).ok_or(MoneyOverflow)?;
+            loan.last_repayment_date = Some(now);
+        }
+
+        // Repaid once fully covered, PartiallyRepaid while 0 < paid < total due and on schedule;
+        // a loan still behind on earlier installments stays Overdue.
+        let remaining_balance = loan.remaining_balance().ok_or(MoneyOverflow)?;
+        let previous = loan.status.clone();
+        loan.status = loan.computed_status(now);
+
+        Ok((loan, previous, payment, PaymentReceipt { applied, remaining_balance, excess }))
+    }
+
     pub fn get_loan(&self, loan_id: Uuid) -> Result<Option<Loan>> {
         self.db.load_loan(loan_id)
     }
 
+    /// Like `get_loan`, but a missing loan is `AppError::NotFound` rather than `None`.
+    fn require_loan(&self, loan_id: Uuid) -> AppResult<Loan> {
+        self.db.load_loan(loan_id)?
+            .ok_or_else(|| AppError::NotFound("Loan not found".to_string()))
+    }
+
+    /// The loan's repayment plan with each installment's state and late fee as of now, charging
+    /// `default_late_fee` if the loan has no policy of its own; `None` if the loan does not exist.
+    pub fn get_schedule(&self, loan_id: Uuid, default_late_fee: Option<LateFeePolicy>) -> Result<Option<Vec<ScheduleEntry>>> {
+        let Some(loan) = self.db.load_loan(loan_id)? else {
+            return Ok(None);
+        };
+        let schedule = loan.with_default_late_fee_policy(default_late_fee).schedule_as_of(Utc::now());
+        Ok(Some(schedule.ok_or(MoneyOverflow)?))
+    }
+
+    pub fn get_loans_by_borrower(&self, borrower_id: &str) -> Result<Vec<Loan>> {
+        self.db.load_loans_by_borrower(borrower_id)
+    }
+
+    pub fn get_loans_by_lender(&self, lender_id: &str) -> Result<Vec<Loan>> {
+        self.db.load_loans_by_lender(lender_id)
+    }
+
+    /// Loans a lender has issued, or for any other role the loans they have borrowed.
+    pub fn loans_for_user(&self, user_id: &str, role: &UserRole) -> Result<Vec<Loan>> {
+        match role {
+            UserRole::Lender => self.get_loans_by_lender(user_id),
+            UserRole::Borrower | UserRole::Admin => self.get_loans_by_borrower(user_id),
+        }
+    }
+
     pub fn get_all_loans(&self) -> Result<Vec<Loan>> {
         self.db.load_all_loans()
     }
 
-    pub fn flag_overdues(&self) -> Result<usize> {
+    pub fn get_all_loans_sorted(&self, order: SortOrder) -> Result<Vec<Loan>> {
+        self.db.load_all_loans_sorted(order)
+    }
+
+    pub fn query_loans(&self, filter: &LoanFilter, order: SortOrder) -> Result<Vec<Loan>> {
+        self.db.query_loans(filter, order)
+    }
+
+    /// One page of loans plus the total number of loans.
+    pub fn get_loans_paged(&self, order: SortOrder, limit: usize, offset: usize) -> Result<(Vec<Loan>, usize)> {
+        Ok((self.db.load_loans_paged(order, limit, offset)?, self.db.count_loans()?))
+    }
+
+    /// Hides the loan from default listings and status sweeps without deleting anything.
+    pub fn archive_loan(&self, loan_id: Uuid) -> AppResult<Loan> {
+        self.set_archived(loan_id, true)
+    }
+
+    /// Undoes `archive_loan`.
+    pub fn restore_loan(&self, loan_id: Uuid) -> AppResult<Loan> {
+        self.set_archived(loan_id, false)
+    }
+
+    fn set_archived(&self, loan_id: Uuid, archived: bool) -> AppResult<Loan> {
+        let mut loan = self.require_loan(loan_id)?;
+        if loan.archived != archived {
+            loan.archived = archived;
+            self.db.save_loan(&loan)?;
+        }
+        Ok(loan)
+    }
+
+    pub fn get_metadata(&self, loan_id: Uuid) -> Result<BTreeMap<String, String>> {
+        self.db.load_loan_metadata(loan_id)
+    }
+
+    pub fn set_metadata(&self, loan_id: Uuid, key: &str, value: &str) -> Result<()> {
+        self.db.save_loan_metadata(loan_id, key, value)
+    }
+
+    /// Returns whether the key was present.
+    pub fn delete_metadata(&self, loan_id: Uuid, key: &str) -> Result<bool> {
+        self.db.delete_loan_metadata(loan_id, key)
+    }
+
+    /// Records a pending request to move one installment to `requested_due`.
+    pub fn request_extension(&self, loan: &Loan, installment_index: usize, requested_due: chrono::DateTime<Utc>) -> AppResult<ExtensionRequest> {
+        let installment = loan.repayment_schedule.get(installment_index)
+            .ok_or_else(|| AppError::InvalidInput(format!("loan has no installment {}", installment_index)))?;
+        let request = ExtensionRequest {
+            id: Uuid::new_v4(),
+            loan_id: loan.id,
+            installment_index,
+            current_due: installment.due_date,
+            requested_due,
+            status: ExtensionStatus::Pending,
+            requested_at: Utc::now(),
+            decided_at: None,
+        };
+        self.db.save_extension_request(&request)?;
+        Ok(request)
+    }
+
+    /// Closes a pending extension request; approval reschedules the installment it targets.
+    /// Approval is refused once the loan is closed or that installment has been moved since
+    /// the request was made.
+    pub fn decide_extension(&self, mut request: ExtensionRequest, approve: bool) -> AppResult<ExtensionRequest> {
+        if approve {
+            let mut loan = self.require_loan(request.loan_id)?;
+            if !matches!(loan.status, LoanStatus::Active | LoanStatus::PartiallyRepaid | LoanStatus::Overdue) {
+                return Err(AppError::InvalidInput(format!("only open loans can be rescheduled (loan is {})", loan.status)));
+            }
+            let installment = loan.repayment_schedule.get_mut(request.installment_index)
+                .filter(|installment| installment.due_date == request.current_due)
+                .ok_or_else(|| AppError::InvalidInput("installment has been rescheduled since the request was made".to_string()))?;
+            installment.due_date = request.requested_due;
+            self.db.save_loan(&loan)?;
+        }
+        request.status = if approve { ExtensionStatus::Approved } else { ExtensionStatus::Rejected };
+        request.decided_at = Some(Utc::now());
+        self.db.save_extension_request(&request)?;
+        Ok(request)
+    }
+
+    /// Quotes the payoff for a loan as of `as_of`; `settle_early` charges exactly this
+    /// `net_settlement`. See `settlement_terms` for how it is made up.
+    pub fn settlement_quote(&self, loan_id: Uuid, policy: &SettlementPolicy, as_of: DateTime<Utc>) -> Result<Option<SettlementQuote>> {
+        let quote = self.db.load_loan(loan_id)?.map(|loan| settlement_terms(loan, policy, as_of)).transpose()?;
+        Ok(quote.map(|(_, quote)| quote))
+    }
+
+    /// Installments whose due date has passed without being covered by payments.
+    pub fn count_missed_payments(&self, loan_id: Uuid) -> AppResult<usize> {
+        let loan = self.require_loan(loan_id)?;
+        Ok(loan.missed_installments(Utc::now()))
+    }
+
+    /// Pays every installment due by `as_of` from the borrower's wallet, oldest first. When the
+    /// wallet cannot cover the next due installment the loan is flagged overdue and left alone.
+    pub fn run_auto_debits(&self, as_of: DateTime<Utc>) -> Result<AutoDebitSummary> {
+        let mut summary = AutoDebitSummary::default();
+        for loan in self.db.load_all_loans()? {
+            if !matches!(loan.status, LoanStatus::Active | LoanStatus::PartiallyRepaid | LoanStatus::Overdue) {
+                continue;
+            }
+            let mut loan = loan;
+            loop {
+                let owed = match loan.next_unpaid_installment() {
+                    Some((next, owed)) if next.due_date <= as_of => owed,
+                    _ => break,
+                };
+
+                // The debit, the payment row and the loan update commit together or not at all
+                let (paid, previous, payment, _) = Self::settle_payment(loan.clone(), owed, Utc::now())?;
+                let debited = self.db.save_applied_payment(
+                    &paid,
+                    &previous,
+                    payment.as_ref(),
+                    Some(&loan.borrower_id),
+                    self.actor,
+                )?;
+                if !debited {
+                    if loan.status != LoanStatus::Overdue {
+                        let previous = std::mem::replace(&mut loan.status, LoanStatus::Overdue);
+                        self.save_with_audit(&loan, Some(&previous))?;
+                        summary.flagged_overdue += 1;
+                    }
+                    break;
+                }
+                self.notify_status_change(&paid, &previous);
+                summary.payments_made += 1;
+                summary.amount_debited = summary.amount_debited.checked_add(owed).ok_or(MoneyOverflow)?;
+                loan = paid;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Open loans whose next unpaid installment falls due within `lead_days` of `as_of` (or is
+    /// already late), skipping any reminded in the last `REMINDER_COOLDOWN_HOURS`.
+    /// `lender_id` narrows the result to one lender's book.
+    pub fn loans_due_for_reminder(&self, lender_id: Option<&str>, as_of: DateTime<Utc>, lead_days: i64) -> Result<Vec<Loan>> {
+        let horizon = as_of + Duration::days(lead_days);
+        let cooldown_start = as_of - Duration::hours(REMINDER_COOLDOWN_HOURS);
+        let filter = LoanFilter { lender_id: lender_id.map(str::to_string), ..LoanFilter::default() };
+
+        let mut due = Vec::new();
+        for loan in self.db.query_loans(&filter, SortOrder::DEFAULT)? {
+            if !matches!(loan.status, LoanStatus::Active | LoanStatus::PartiallyRepaid | LoanStatus::Overdue) {
+                continue;
+            }
+            if loan.next_unpaid_installment().is_none_or(|(next, _)| next.due_date > horizon) {
+                continue;
+            }
+            let last = self.db.last_recovery_action_at(loan.id, RecoveryAction::SendReminder)?;
+            if last.is_some_and(|at| at > cooldown_start) {
+                continue;
+            }
+            due.push(loan);
+        }
+        Ok(due)
+    }
+
+    /// Collection worklist: active/overdue loans with no payment in the last `days` days, longest-silent first.
+    pub fn loans_without_payment_since(&self, days: i64) -> Result<Vec<Loan>> {
+        self.db.load_loans_without_payment_since(Utc::now() - Duration::days(days))
+    }
+
+    /// Moves active loans to Overdue once their earliest unpaid installment is more than
+    /// `grace_days` past due, returning the ids it changed. Loans without a schedule are skipped.
+    /// With `dry_run` nothing is written; the ids returned are the loans that would change.
+    pub fn flag_overdues(&self, grace_days: i64, dry_run: bool) -> Result<Vec<Uuid>> {
         let loans = self.db.load_all_loans()?;
         let now = Utc::now();
-        let mut flagged_count = 0;
+        let grace = Duration::days(grace_days);
+        let mut flagged = Vec::new();
 
         for mut loan in loans {
-            if loan.status == LoanStatus::Active {
-                // Check if any repayment date has passed
-                let has_overdue_payment = loan.repayment_schedule.iter().any(|&due_date| now > due_date);
-                if has_overdue_payment {
-                    loan.status = LoanStatus::Overdue;
-                    self.db.save_loan(&loan)?;
-                    flagged_count += 1;
+            if matches!(loan.status, LoanStatus::Active | LoanStatus::PartiallyRepaid) {
+                let Some(earliest_unpaid) = loan.repayment_schedule.get(loan.installments_covered()) else {
+                    continue;
+                };
+                if now > earliest_unpaid.due_date + grace {
+                    if !dry_run {
+                        let previous = std::mem::replace(&mut loan.status, LoanStatus::Overdue);
+                        self.save_with_audit(&loan, Some(&previous))?;
+                    }
+                    flagged.push(loan.id);
                 }
             }
         }
-        Ok(flagged_count)
+        Ok(flagged)
+    }
+
+    /// Marks an overdue or defaulted loan as uncollectable. The status change is audited under
+    /// the tracker's actor, and the loan drops out of overdue, default and auto-debit sweeps.
+    pub fn write_off(&self, loan_id: Uuid) -> AppResult<Loan> {
+        let mut loan = self.require_loan(loan_id)?;
+        if !matches!(loan.status, LoanStatus::Overdue | LoanStatus::Defaulted) {
+            return Err(AppError::InvalidInput(
+                format!("only overdue or defaulted loans can be written off (loan is {})", loan.status),
+            ));
+        }
+        let previous = std::mem::replace(&mut loan.status, LoanStatus::WrittenOff);
+        self.save_with_audit(&loan, Some(&previous))?;
+        Ok(loan)
+    }
+
+    /// Recomputes every loan's status from its schedule and payments in a single transaction,
+    /// as if the overdue and default sweeps had run from scratch with these thresholds. Use
+    /// after changing grace periods or importing loans.
+    pub fn reconcile(&self, grace_days: i64, default_after_days: i64) -> Result<ReconcileSummary> {
+        let now = Utc::now();
+        let (checked, changed) = self.db.reconcile_loan_statuses(self.actor, |loan| {
+            loan.reconciled_status(now, grace_days, default_after_days)
+        })?;
+
+        let changes: Vec<ReconciledLoan> = changed
+            .into_iter()
+            .map(|(loan, previous)| {
+                if let Some(webhook) = self.webhook {
+                    webhook.dispatch(StatusChangeEvent {
+                        loan_id: loan.id,
+                        old_status: previous.clone(),
+                        new_status: loan.status.clone(),
+                        changed_at: now,
+                    });
+                }
+                ReconciledLoan { loan_id: loan.id, from: previous, to: loan.status }
+            })
+            .collect();
+        Ok(ReconcileSummary { checked, changed: changes.len(), changes })
+    }
+
+    /// Moves overdue loans to Defaulted once their earliest missed installment is more than
+    /// `grace_days` old, returning the ids it changed. With `dry_run` nothing is written.
+    pub fn flag_defaults(&self, grace_days: i64, dry_run: bool) -> Result<Vec<Uuid>> {
+        let cutoff = Utc::now() - Duration::days(grace_days);
+        let mut defaulted = Vec::new();
+
+        for mut loan in self.db.load_all_loans()? {
+            if loan.status != LoanStatus::Overdue {
+                continue;
+            }
+            let earliest_missed = loan.repayment_schedule.get(loan.installments_covered());
+            if earliest_missed.is_some_and(|p| p.due_date < cutoff) {
+                if !dry_run {
+                    loan.status = LoanStatus::Defaulted;
+                    self.save_with_audit(&loan, Some(&LoanStatus::Overdue))?;
+                }
+                defaulted.push(loan.id);
+            }
+        }
+        Ok(defaulted)
+    }
+
+    /// Buckets every loan of a lender into risk bands A–E; bands without loans are reported as zeros.
+    pub fn risk_distribution(&self, lender_id: &str) -> Result<Vec<RiskBandSummary>> {
+        let as_of = Utc::now();
+        let mut summaries: Vec<RiskBandSummary> = RiskBand::ALL
+            .iter()
+            .map(|&band| RiskBandSummary { band, count: 0, total_outstanding: Money::ZERO })
+            .collect();
+
+        for loan in self.db.load_loans_by_lender(lender_id)? {
+            let band = RiskBand::from_score(self.risk_score(&loan, as_of)?);
+            if let Some(summary) = summaries.iter_mut().find(|s| s.band == band) {
+                summary.count += 1;
+                let outstanding = loan.principal_outstanding().ok_or(MoneyOverflow)?;
+                summary.total_outstanding = summary.total_outstanding.checked_add(outstanding).ok_or(MoneyOverflow)?;
+            }
+        }
+        Ok(summaries)
+    }
+
+    /// Aggregates a lender's loans in one pass; a lender without loans gets all zeros.
+    pub fn portfolio_summary(&self, lender_id: &str) -> Result<PortfolioSummary> {
+        let now = Utc::now();
+        let mut summary = PortfolioSummary {
+            lender_id: lender_id.to_string(),
+            loan_count: 0,
+            principal_outstanding: Money::ZERO,
+            loans_by_status: LoanStatus::ALL.iter().map(|s| (format!("{:?}", s).to_lowercase(), 0)).collect(),
+            average_risk_score: 0.0,
+            weighted_risk_score: 0.0,
+            overdue_amount: Money::ZERO,
+        };
+        let mut total_risk = 0.0;
+        let mut exposures = Vec::new();
+
+        for loan in self.db.load_loans_by_lender(lender_id)? {
+            let risk = self.risk_score(&loan, now)?;
+            summary.loan_count += 1;
+            let outstanding = loan.principal_outstanding().ok_or(MoneyOverflow)?;
+            let overdue = loan.overdue_amount(now).ok_or(MoneyOverflow)?;
+            summary.principal_outstanding = summary.principal_outstanding.checked_add(outstanding).ok_or(MoneyOverflow)?;
+            summary.overdue_amount = summary.overdue_amount.checked_add(overdue).ok_or(MoneyOverflow)?;
+            *summary.loans_by_status.entry(format!("{:?}", loan.status).to_lowercase()).or_default() += 1;
+            total_risk += risk;
+            exposures.push((risk, outstanding));
+        }
+        if summary.loan_count > 0 {
+            summary.average_risk_score = total_risk / summary.loan_count as f64;
+        }
+        summary.weighted_risk_score = exposure_weighted_risk(exposures);
+        Ok(summary)
+    }
+
+    /// sum(risk × outstanding principal) / sum(outstanding principal) over a lender's loans, so
+    /// large exposures count for more than small ones; 0 when nothing is outstanding.
+    pub fn weighted_portfolio_risk(&self, lender_id: &str) -> Result<f64> {
+        let now = Utc::now();
+        let mut exposures = Vec::new();
+        for loan in self.db.load_loans_by_lender(lender_id)? {
+            exposures.push((self.risk_score(&loan, now)?, loan.principal_outstanding().ok_or(MoneyOverflow)?));
+        }
+        Ok(exposure_weighted_risk(exposures))
+    }
+
+    /// Default probability of `loan` as of `as_of` under the tracker's [`RiskModel`], scored with
+    /// its recorded payments (so late payments count as misses) and the borrower's record on
+    /// their other loans. Every risk score the API and CLI report comes from here.
+    pub fn assess_risk(&self, loan: &Loan, as_of: DateTime<Utc>) -> Result<RiskAssessment> {
+        let payments = self.db.load_payments(loan.id)?;
+        let borrower_history = self.borrower_history_excluding(&loan.borrower_id, loan.id)?;
+        let risk_score = RecoveryEngine::default()
+            .predict_default(loan, &self.risk_model.with_history(&payments).with_borrower_history(&borrower_history), as_of);
+        Ok(RiskAssessment { risk_score, borrower_history })
+    }
+
+    /// Just the score from [`LoanTracker::assess_risk`].
+    pub fn risk_score(&self, loan: &Loan, as_of: DateTime<Utc>) -> Result<f64> {
+        Ok(self.assess_risk(loan, as_of)?.risk_score)
+    }
+
+    /// Track record of a borrower over all of their loans, archived ones included; see
+    /// [`BorrowerHistory`].
+    pub fn borrower_history(&self, borrower_id: &str) -> Result<BorrowerHistory> {
+        self.summarize_borrower(borrower_id, None)
     }
-}
 
+    /// The borrower's record on their loans other than `loan_id`, so a loan being scored isn't
+    /// also counted against itself.
+    pub fn borrower_history_excluding(&self, borrower_id: &str, loan_id: Uuid) -> Result<BorrowerHistory> {
+        self.summarize_borrower(borrower_id, Some(loan_id))
+    }
+
+    fn summarize_borrower(&self, borrower_id: &str, exclude: Option<Uuid>) -> Result<BorrowerHistory> {
+        let now = Utc::now();
+        let mut history = BorrowerHistory {
+            borrower_id: borrower_id.to_string(),
+            total_loans: 0,
+            repaid_count: 0,
+            defaulted_count: 0,
+            average_days_late: 0.0,
+        };
+        let mut days_late = Vec::new();
+        // Archiving a loan hides it from listings, not from the borrower's record
+        let filter = LoanFilter { borrower_id: Some(borrower_id.to_string()), include_archived: true, ..LoanFilter::default() };
+        for loan in self.db.query_loans(&filter, SortOrder::DEFAULT)? {
+            if Some(loan.id) == exclude {
+                continue;
+            }
+            history.total_loans += 1;
+            match loan.status {
+                LoanStatus::Repaid => history.repaid_count += 1,
+                LoanStatus::Defaulted | LoanStatus::WrittenOff => history.defaulted_count += 1,
+                _ => {}
+            }
+            days_late.extend(loan.installment_days_late(&self.db.load_payments(loan.id)?, now));
+        }
+        if !days_late.is_empty() {
+            history.average_days_late = days_late.iter().sum::<i64>() as f64 / days_late.len() as f64;
+        }
+        Ok(history)
+    }
+
+    /// Open loans (neither repaid, written off nor archived) whose default probability is above
+    /// `threshold`, riskiest first; equal scores put the loan further overdue first.
+    pub fn loans_above_risk(&self, threshold: f64) -> Result<Vec<RiskRankedLoan>> {
+        let now = Utc::now();
+        let mut ranked = Vec::new();
+        for loan in self.db.query_loans(&LoanFilter::default(), SortOrder::DEFAULT)? {
+            if matches!(loan.status, LoanStatus::Repaid | LoanStatus::WrittenOff) {
+                continue;
+            }
+            let risk_score = self.risk_score(&loan, now)?;
+            if risk_score > threshold {
+                ranked.push(RiskRankedLoan { loan, risk_score });
+            }
+        }
+        ranked.sort_by(|a, b| {
+            b.risk_score.total_cmp(&a.risk_score)
+                .then_with(|| b.loan.days_overdue(now).cmp(&a.loan.days_overdue(now)))
+        });
+        Ok(ranked)
+    }
+}
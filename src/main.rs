@@ -1,22 +1,16 @@
-mod models;
-mod user;
-mod loan;
-mod db;
-mod recovery;
-mod api;
-mod config;
-mod error;
-mod auth;
-
-use crate::config::Config;
-use crate::models::{UserRole, RiskScorable};
-use crate::user::UserManager;
-use crate::loan::LoanTracker;
-use crate::recovery::RecoveryEngine;
-use crate::db::Db;
-use clap::{Parser, Subcommand};
+use lendwise_recovery::config::Config;
+use lendwise_recovery::models::{InterestKind, LateFeePolicy, Loan, LoanStatus, PaymentFrequency, RiskScorable, UserRole};
+use lendwise_recovery::user::{validate_password, UserManager};
+use lendwise_recovery::loan::{LoanTracker, NewLoan};
+use lendwise_recovery::recovery::RecoveryEngine;
+use lendwise_recovery::db::{Db, LoanFilter, SortOrder};
+use lendwise_recovery::demo;
+use lendwise_recovery::timezone::format_local_date;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
 use uuid::Uuid;
-use actix_web;
 
 #[derive(Parser)]
 #[command(name = "smart-loan-recovery")]
@@ -35,45 +29,183 @@ enum Commands {
         name: String,
         /// User role (borrower or lender)
         #[arg(short, long)]
-        role: String
+        role: String,
+        /// Contact email
+        #[arg(long)]
+        email: Option<String>,
+        /// Contact phone number
+        #[arg(long)]
+        phone: Option<String>,
+        /// Password for session login (at least 6 characters)
+        #[arg(long)]
+        password: Option<String>,
     },
     /// Create a new loan
     CreateLoan {
-        /// Borrower UUID
+        /// Borrower ID
         #[arg(short, long)]
         borrower_id: String,
-        /// Lender UUID
+        /// Lender ID
         #[arg(short, long)]
         lender_id: String,
         /// Loan principal amount
         #[arg(short, long)]
         principal: f64,
-        /// Annual interest rate (percentage)
+        /// Annual interest rate (percentage); defaults to DEFAULT_INTEREST_RATE
         #[arg(short = 'r', long)]
-        interest_rate: f64,
-        /// Loan duration in months
+        interest_rate: Option<f64>,
+        /// Loan duration in months; defaults to DEFAULT_DURATION_MONTHS
         #[arg(short, long)]
-        months: i64
+        months: Option<i64>,
+        /// How interest applies: amortized, simple or compound_monthly
+        #[arg(long, default_value = "amortized", value_parser = parse_interest_kind)]
+        interest_kind: InterestKind,
+        /// ISO 4217 currency code of the principal
+        #[arg(long, default_value = "USD")]
+        currency: String,
+        /// Leading interest-free months, at most the loan duration
+        #[arg(long, default_value_t = 0)]
+        promo_months: i64,
+        /// Installment spacing: weekly, biweekly or monthly
+        #[arg(long, default_value = "monthly", value_parser = parse_payment_frequency)]
+        frequency: PaymentFrequency,
+        /// User ID of a guarantor, liable if the loan defaults
+        #[arg(long)]
+        guarantor_id: Option<String>,
+        /// Late fee per late installment per period: flat:<amount> or percentage:<pct>
+        #[arg(long, value_parser = parse_late_fee_policy)]
+        late_fee: Option<LateFeePolicy>,
     },
     /// Flag overdue loans
-    FlagOverdues,
+    FlagOverdues {
+        /// List the loans that would be flagged without changing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Mark overdue loans as defaulted once their earliest missed payment is old enough
+    FlagDefaults {
+        /// Days past the earliest missed payment (defaults to DEFAULT_AFTER_DAYS)
+        #[arg(short, long)]
+        grace_days: Option<i64>,
+        /// List the loans that would be defaulted without changing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Recompute every loan's status from its schedule and payments in one transaction
+    Reconcile,
     /// Get recovery recommendation for a loan
     Recommend {
         /// Loan UUID
         #[arg(short, long)]
         loan_id: String
     },
+    /// Print how a loan's risk score evolves if no further payments arrive
+    SimulateRisk {
+        /// Loan UUID
+        #[arg(short, long)]
+        loan_id: String,
+        /// Days between samples
+        #[arg(long, default_value_t = 30)]
+        step_days: i64,
+        /// Number of samples after today
+        #[arg(long, default_value_t = 12)]
+        steps: u32,
+    },
+    /// List loans as a table, optionally filtered
+    ListLoans {
+        /// Only loans in this status (e.g. active, overdue, partially_repaid)
+        #[arg(short, long, value_parser = parse_loan_status)]
+        status: Option<LoanStatus>,
+        /// Only loans taken by this borrower
+        #[arg(short, long)]
+        borrower_id: Option<String>,
+    },
+    /// List users as a table, optionally by role
+    ListUsers {
+        /// Only users with this role (borrower, lender or admin)
+        #[arg(short, long, value_parser = parse_user_role)]
+        role: Option<UserRole>,
+    },
+    /// Export users and loans to files in a directory
+    Export {
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "json")]
+        format: ExportFormat,
+        /// Directory to write users.<ext> and loans.<ext> into
+        #[arg(short, long, default_value = ".")]
+        out_dir: PathBuf,
+    },
+    /// Write a timestamped JSON backup and delete all but the newest BACKUP_RETENTION
+    Backup {
+        /// Directory to keep backups in (default: BACKUP_DIR)
+        #[arg(short, long)]
+        dir: Option<PathBuf>,
+    },
+    /// Restore users and loans from a JSON export
+    Restore {
+        /// Directory holding users.json and loans.json
+        #[arg(short, long, default_value = ".")]
+        in_dir: PathBuf,
+    },
     /// Run the demo
     Demo,
+    /// Fill the configured database with randomized users and loans, some of them overdue
+    Seed {
+        /// Users to register; about one in five is a lender
+        #[arg(short, long, default_value_t = 20)]
+        users: usize,
+        /// Loans to create between them
+        #[arg(short, long, default_value_t = 50)]
+        loans: usize,
+        /// RNG seed; the same seed generates the same names, amounts and repayment histories
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+fn parse_interest_kind(value: &str) -> Result<InterestKind, String> {
+    InterestKind::parse(value).ok_or_else(|| "expected amortized, simple or compound_monthly".to_string())
+}
+
+fn parse_payment_frequency(value: &str) -> Result<PaymentFrequency, String> {
+    PaymentFrequency::parse(value).ok_or_else(|| "expected weekly, biweekly or monthly".to_string())
+}
+
+fn parse_late_fee_policy(value: &str) -> Result<LateFeePolicy, String> {
+    LateFeePolicy::parse(value).ok_or_else(|| "expected flat:<amount> or percentage:<0-100>".to_string())
+}
+
+fn parse_loan_status(value: &str) -> Result<LoanStatus, String> {
+    LoanStatus::parse(value).ok_or_else(|| {
+        let known: Vec<String> = LoanStatus::ALL.iter().map(|s| format!("{:?}", s).to_lowercase()).collect();
+        format!("unknown status; expected one of {}", known.join(", "))
+    })
 }
 
-fn run_cli(cli: Cli, db: Db) -> Result<(), Box<dyn std::error::Error>> {
+fn parse_user_role(value: &str) -> Result<UserRole, String> {
+    match value.trim().to_lowercase().as_str() {
+        "borrower" => Ok(UserRole::Borrower),
+        "lender" => Ok(UserRole::Lender),
+        "admin" => Ok(UserRole::Admin),
+        _ => Err("expected borrower, lender or admin".to_string()),
+    }
+}
+
+fn run_cli(cli: Cli, db: Db, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     let user_manager = UserManager::new(&db);
-    let loan_tracker = LoanTracker::new(&db);
-    let recovery_engine = RecoveryEngine;
+    let loan_tracker = LoanTracker::new(&db)
+        .with_interest_rounding(config.interest_rounding)
+        .with_risk_model(config.risk_model());
+    let recovery_engine = RecoveryEngine::new(config.recovery_thresholds);
 
     match cli.command.unwrap() {
-        Commands::RegisterUser { name, role } => {
+        Commands::RegisterUser { name, role, email, phone, password } => {
             let user_role = match role.to_lowercase().as_str() {
                 "borrower" => UserRole::Borrower,
                 "lender" => UserRole::Lender,
@@ -83,40 +215,111 @@ fn run_cli(cli: Cli, db: Db) -> Result<(), Box<dyn std::error::Error>> {
                 }
             };
 
-            match user_manager.register_user(name.clone(), None, user_role, None, None) {
+            if let Some(Err(e)) = password.as_deref().map(validate_password) {
+                eprintln!("❌ {}", e);
+                return Ok(());
+            }
+            let registered = user_manager
+                .register_user(name.clone(), email, phone, user_role, None, None)
+                .and_then(|user_id| match password {
+                    Some(ref p) => user_manager.set_password(&user_id, p).map(|_| user_id),
+                    None => Ok(user_id),
+                });
+            match registered {
                 Ok(user_id) => println!("✅ Registered {} as {} with ID: {}", name, role, user_id),
                 Err(e) => eprintln!("❌ Failed to register user: {}", e),
             }
         }
 
-        Commands::CreateLoan { borrower_id, lender_id, principal, interest_rate, months } => {
-            let borrower_uuid = Uuid::parse_str(&borrower_id)
-                .map_err(|_| "Invalid borrower UUID format")?;
-            let lender_uuid = Uuid::parse_str(&lender_id)
-                .map_err(|_| "Invalid lender UUID format")?;
-
-            match loan_tracker.create_loan(borrower_uuid.to_string(), lender_uuid.to_string(), principal, interest_rate, months) {
-                Ok(loan_id) => println!("✅ Created loan with ID: {}", loan_id),
+        Commands::CreateLoan { borrower_id, lender_id, principal, interest_rate, months, interest_kind, currency, promo_months, frequency, guarantor_id, late_fee } => {
+            let Some(interest_rate) = interest_rate.or(config.default_interest_rate) else {
+                eprintln!("❌ Pass --interest-rate or set DEFAULT_INTEREST_RATE");
+                return Ok(());
+            };
+            let Some(months) = months.or(config.default_duration_months) else {
+                eprintln!("❌ Pass --months or set DEFAULT_DURATION_MONTHS");
+                return Ok(());
+            };
+            let new_loan = NewLoan {
+                borrower_id,
+                lender_id,
+                principal,
+                interest_rate,
+                duration_months: months,
+                interest_kind,
+                currency,
+                promo_months,
+                payment_frequency: frequency,
+                guarantor_id,
+                late_fee_policy: late_fee,
+            };
+            match loan_tracker.create_new_loan(new_loan.clone()) {
+                Ok(loan_id) => println!("✅ Created loan with ID: {} ({:.2} {})", loan_id, new_loan.principal, new_loan.currency.to_ascii_uppercase()),
                 Err(e) => eprintln!("❌ Failed to create loan: {}", e),
             }
         }
 
-        Commands::FlagOverdues => {
-            match loan_tracker.flag_overdues() {
-                Ok(count) => println!("✅ Overdue loans flagged successfully: {} loans flagged", count),
+        Commands::FlagOverdues { dry_run } => {
+            match loan_tracker.flag_overdues(config.grace_period_days, dry_run) {
+                Ok(flagged) if dry_run => {
+                    println!("🔍 Dry run: {} loans would be flagged overdue", flagged.len());
+                    for loan_id in flagged {
+                        println!("   • {}", loan_id);
+                    }
+                }
+                Ok(flagged) => {
+                    println!("✅ Overdue loans flagged successfully: {} loans flagged", flagged.len());
+                    for loan_id in flagged {
+                        println!("   • {}", loan_id);
+                    }
+                }
                 Err(e) => eprintln!("❌ Failed to flag overdues: {}", e),
             }
         }
 
+        Commands::FlagDefaults { grace_days, dry_run } => {
+            match loan_tracker.flag_defaults(grace_days.unwrap_or(config.default_after_days), dry_run) {
+                Ok(defaulted) if dry_run => {
+                    println!("🔍 Dry run: {} loans would be marked defaulted", defaulted.len());
+                    for loan_id in defaulted {
+                        println!("   • {}", loan_id);
+                    }
+                }
+                Ok(defaulted) => {
+                    println!("✅ Defaulted loans flagged successfully: {} loans flagged", defaulted.len());
+                    for loan_id in defaulted {
+                        println!("   • {}", loan_id);
+                    }
+                }
+                Err(e) => eprintln!("❌ Failed to flag defaults: {}", e),
+            }
+        }
+
+        Commands::Reconcile => {
+            match loan_tracker.reconcile(config.grace_period_days, config.default_after_days) {
+                Ok(summary) => {
+                    println!("✅ Reconciled {} loans: {} changed status", summary.checked, summary.changed);
+                    for change in summary.changes {
+                        println!("   • {}: {:?} -> {:?}", change.loan_id, change.from, change.to);
+                    }
+                }
+                Err(e) => eprintln!("❌ Failed to reconcile loan statuses: {}", e),
+            }
+        }
+
         Commands::Recommend { loan_id } => {
             let loan_uuid = Uuid::parse_str(&loan_id)
                 .map_err(|_| "Invalid loan UUID format")?;
 
             match loan_tracker.get_loan(loan_uuid) {
                 Ok(Some(loan)) => {
-                    let risk_score = recovery_engine.predict_default(&loan);
-                    let action = recovery_engine.recommend_action(risk_score, 0); // Simplified: assume 0 missed payments for demo
+                    let missed_payments = loan_tracker.count_missed_payments(loan.id)?;
+                    let assessment = loan_tracker.assess_risk(&loan, chrono::Utc::now())?;
+                    let (risk_score, history) = (assessment.risk_score, assessment.borrower_history);
+                    let action = recovery_engine.recommend_action(risk_score, missed_payments, &loan.status);
                     println!("📊 Loan {} - Risk Score: {:.2}", loan_id, risk_score);
+                    println!("📅 Missed Payments: {}", missed_payments);
+                    println!("🧾 Borrower Reliability: {:.2} ({} other loans, {} defaulted)", history.reliability(), history.total_loans, history.defaulted_count);
                     println!("💡 Recommended Action: {:?}", action);
                 }
                 Ok(None) => eprintln!("❌ Loan not found"),
@@ -124,8 +327,112 @@ fn run_cli(cli: Cli, db: Db) -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
+        Commands::SimulateRisk { loan_id, step_days, steps } => {
+            let loan_uuid = Uuid::parse_str(&loan_id)
+                .map_err(|_| "Invalid loan UUID format")?;
+            if step_days < 1 {
+                return Err("step-days must be at least 1".into());
+            }
+            let Some(loan) = loan_tracker.get_loan(loan_uuid)? else {
+                eprintln!("❌ Loan not found");
+                return Ok(());
+            };
+
+            let now = chrono::Utc::now();
+            println!("{:<10}  {:<16}  {:>5}", "DATE", "STATUS", "RISK");
+            for step in 0..=i64::from(steps) {
+                let as_of = now + chrono::Duration::days(step * step_days);
+                // The stored status goes stale as installments fall due, so project it too
+                let projected = Loan { status: loan.computed_status(as_of), ..loan.clone() };
+                println!(
+                    "{:<10}  {:<16}  {:>5.2}",
+                    format_local_date(as_of, &config.display_timezone), projected.status.to_string(), projected.calculate_risk_score(as_of)
+                );
+            }
+        }
+
+        Commands::ListLoans { status, borrower_id } => {
+            let filter = LoanFilter { status, borrower_id, ..LoanFilter::default() };
+            let loans = loan_tracker.query_loans(&filter, SortOrder::DEFAULT)?;
+            let now = chrono::Utc::now();
+            println!(
+                "{:<36}  {:<10}  {:<10}  {:>12}  {:<3}  {:<16}  {:>5}  {:>7}  {:<10}",
+                "ID", "BORROWER", "LENDER", "PRINCIPAL", "CCY", "STATUS", "RISK", "OVERDUE", "DISBURSED"
+            );
+            for loan in &loans {
+                let risk = loan_tracker.risk_score(loan, now)?;
+                println!(
+                    "{:<36}  {:<10}  {:<10}  {:>12}  {:<3}  {:<16}  {:>5.2}  {:>6}d  {:<10}",
+                    loan.id, loan.borrower_id, loan.lender_id, loan.principal, loan.currency,
+                    loan.status.to_string(), risk, loan.days_overdue(now),
+                    format_local_date(loan.disbursement_date, &config.display_timezone)
+                );
+            }
+            println!("{} loan(s)", loans.len());
+        }
+
+        Commands::ListUsers { role } => {
+            let users: Vec<_> = user_manager.get_all_users_sorted(SortOrder::DEFAULT)?
+                .into_iter()
+                .filter(|u| role.as_ref().is_none_or(|r| *r == u.role))
+                .collect();
+            println!("{:<36}  {:<8}  NAME", "ID", "ROLE");
+            for user in &users {
+                println!("{:<36}  {:<8}  {}", user.id, format!("{:?}", user.role).to_lowercase(), user.name);
+            }
+            println!("{} user(s)", users.len());
+        }
+
+        Commands::Export { format, out_dir } => {
+            fs::create_dir_all(&out_dir)?;
+            let (users_path, loans_path) = match format {
+                ExportFormat::Json => {
+                    let paths = (out_dir.join("users.json"), out_dir.join("loans.json"));
+                    db.save_to_json(&paths.0, &paths.1)?;
+                    paths
+                }
+                ExportFormat::Csv => {
+                    let paths = (out_dir.join("users.csv"), out_dir.join("loans.csv"));
+                    db.export_users_csv(BufWriter::new(File::create(&paths.0)?))?;
+                    db.export_loans_csv(BufWriter::new(File::create(&paths.1)?))?;
+                    paths
+                }
+            };
+            println!("✅ Exported users to {} and loans to {}", users_path.display(), loans_path.display());
+        }
+
+        Commands::Backup { dir } => {
+            let dir = dir.unwrap_or_else(|| PathBuf::from(&config.backup_dir));
+            let files = db.write_backup(&dir, config.backup_retention, chrono::Utc::now())?;
+            println!("✅ Backed up users to {} and loans to {}", files.users.display(), files.loans.display());
+            for path in &files.removed {
+                println!("   🗑️  Removed {}", path.display());
+            }
+        }
+
+        Commands::Restore { in_dir } => {
+            let (users, loans) = db.import_from_json(in_dir.join("users.json"), in_dir.join("loans.json"))?;
+            println!("✅ Restored {} users and {} loans from {}", users, loans, in_dir.display());
+        }
+
         Commands::Demo => {
-            run_demo(db);
+            demo::run_demo(config.display_timezone);
+        }
+
+        Commands::Seed { users, loans, seed } => {
+            match lendwise_recovery::seed::seed(&db, users, loans, seed) {
+                Ok(summary) => {
+                    println!(
+                        "✅ Seeded {} lenders, {} borrowers and {} loans (seed {})",
+                        summary.lenders.len(), summary.borrowers.len(), summary.loans.len(), seed
+                    );
+                    println!("   Lenders: {}", summary.lenders.join(", "));
+                    for (status, count) in &summary.loans_by_status {
+                        println!("   • {}: {}", status, count);
+                    }
+                }
+                Err(e) => eprintln!("❌ Failed to seed data: {}", e),
+            }
         }
     }
 
@@ -150,126 +457,21 @@ async fn main() -> std::io::Result<()> {
     let cli = Cli::parse();
 
     // Check if running in CLI mode or server mode
-    if let Some(_) = cli.command {
+    if cli.command.is_some() {
         // CLI mode
-        let db = match Db::new_with_path(&config.database_url) {
-            Ok(db) => db,
+        let db = match Db::new_with_settings(&config.database_url, config.db_pool_size, config.db_busy_timeout_ms) {
+            Ok(db) => db.with_write_retries(config.db_write_retries),
             Err(e) => {
                 eprintln!("❌ Failed to initialize database: {}", e);
                 return Ok(());
             }
         };
-        if let Err(e) = run_cli(cli, db) {
+        if let Err(e) = run_cli(cli, db, &config) {
             eprintln!("❌ CLI Error: {}", e);
         }
         Ok(())
     } else {
         // Server mode (no subcommand provided)
-        crate::api::run_server(config).await
+        lendwise_recovery::api::run_server(config).await
     }
 }
-
-fn run_demo(db: Db) {
-    println!("🚀 Smart Loan Recovery System Starting...");
-
-    // Initialize system components with database
-    let user_manager = UserManager::new(&db);
-    let loan_tracker = LoanTracker::new(&db);
-
-    // Demo: Register users
-    println!(" 📝 Registering users...");
-
-    let borrower_id = match user_manager.register_user(
-        "Alice Johnson".to_string(),
-        None,
-        UserRole::Borrower,
-        None,
-        None
-    ) {
-        Ok(id) => id,
-        Err(e) => {
-            eprintln!("❌ Failed to register borrower: {}", e);
-            return;
-        }
-    };
-
-    let lender_id = match user_manager.register_user(
-        "Bob Smith".to_string(),
-        None,
-        UserRole::Lender,
-        None,
-        Some("Demo Bank".to_string())
-    ) {
-        Ok(id) => id,
-        Err(e) => {
-            eprintln!("❌ Failed to register lender: {}", e);
-            return;
-        }
-    };
-
-    println!("✅ Registered borrower: {}", borrower_id);
-    println!("✅ Registered lender: {}", lender_id);
-
-    // Demo: Create a loan
-    println!(" 💰 Creating a loan...");
-
-    let loan_id = match loan_tracker.create_loan(
-        borrower_id.clone(),
-        lender_id,
-        10000.0,  // $10,000 principal
-        5.5,      // 5.5% interest rate
-        12        // 12 months duration
-    ) {
-        Ok(id) => id,
-        Err(e) => {
-            eprintln!("❌ Failed to create loan: {}", e);
-            return;
-        }
-    };
-
-    println!("✅ Created loan: {} for borrower {}", loan_id, borrower_id);
-
-    // Demo: Get loan details
-    match loan_tracker.get_loan(loan_id) {
-        Ok(Some(loan)) => {
-            println!(" 📊 Loan Details:");
-            println!("   ID: {}", loan.id);
-            println!("   Principal: ${:.2}", loan.principal);
-            println!("   Interest Rate: {:.1}%", loan.interest_rate);
-            println!("   Status: {:?}", loan.status);
-            println!("   Risk Score: {:.2}", loan.calculate_risk_score());
-        }
-        Ok(None) => println!("❌ Loan not found"),
-        Err(e) => eprintln!("❌ Failed to load loan: {}", e),
-    }
-
-    // Demo: Update repayment
-    println!(" 💳 Processing repayment...");
-    if let Err(e) = loan_tracker.update_repayment(loan_id) {
-        eprintln!("❌ Failed to update repayment: {}", e);
-    } else {
-        println!("✅ Repayment updated successfully");
-    }
-
-    // Demo: Check updated loan status
-    match loan_tracker.get_loan(loan_id) {
-        Ok(Some(loan)) => {
-            println!(" 📈 Updated Loan Status: {:?}", loan.status);
-            println!("   Risk Score: {:.2}", loan.calculate_risk_score());
-        }
-        Ok(None) => println!("❌ Loan not found"),
-        Err(e) => eprintln!("❌ Failed to load loan: {}", e),
-    }
-
-    // Demo: Save to JSON backup
-    println!(" 💾 Creating JSON backup...");
-    if let Err(e) = db.save_to_json("users_backup.json", "loans_backup.json") {
-        eprintln!("❌ Failed to create JSON backup: {}", e);
-    } else {
-        println!("✅ JSON backup created successfully");
-    }
-
-    println!(" 🎉 Smart Loan Recovery System Demo Complete!");
-    println!("💡 Data is now persisted in SQLite database 'loans.db'");
-}
-
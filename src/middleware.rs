@@ -0,0 +1,192 @@
+//! Request Middleware
+//!
+//! [`ConcurrencyLimit`] caps the number of requests being handled at once so a
+//! traffic spike cannot pile up behind the database. Requests over the limit are
+//! turned away immediately with `503 Service Unavailable` and a `Retry-After` hint.
+//!
+//! [`AssignRequestId`] tags every request with a fresh UUID, returned in the
+//! `X-Request-Id` header and in error bodies so a support ticket can be matched
+//! to the server's log lines.
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{self, HeaderName, HeaderValue},
+    Error, HttpMessage, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Seconds clients are told to wait before retrying a rejected request.
+const RETRY_AFTER_SECS: u64 = 1;
+
+/// Concurrency limit middleware. Clone it into every worker so the
+/// in-flight counter is shared across the whole server.
+#[derive(Clone)]
+pub struct ConcurrencyLimit {
+    max_in_flight: usize,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(max_in_flight: usize) -> Self {
+        Self {
+            max_in_flight,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Number of requests currently being handled
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+/// Releases an in-flight slot when the request finishes, even if the handler errors.
+struct Permit(Arc<AtomicUsize>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ConcurrencyLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = ConcurrencyLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ConcurrencyLimitMiddleware {
+            service,
+            max_in_flight: self.max_in_flight,
+            in_flight: self.in_flight.clone(),
+        }))
+    }
+}
+
+pub struct ConcurrencyLimitMiddleware<S> {
+    service: S,
+    max_in_flight: usize,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl<S, B> Service<ServiceRequest> for ConcurrencyLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let previous = self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let permit = Permit(self.in_flight.clone());
+
+        if previous >= self.max_in_flight {
+            drop(permit);
+            log::warn!("Rejecting {} {}: {} requests already in flight", req.method(), req.path(), previous);
+            let response = HttpResponse::ServiceUnavailable()
+                .insert_header((header::RETRY_AFTER, RETRY_AFTER_SECS.to_string()))
+                .json(serde_json::json!({
+                    "error": "503 Service Unavailable",
+                    "message": "Server is busy, please retry shortly",
+                    "details": null,
+                    "request_id": current_request_id()
+                }));
+            return Box::pin(async move { Ok(req.into_response(response).map_into_right_body()) });
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+            drop(permit);
+            res.map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+/// Response header carrying the id assigned by [`AssignRequestId`].
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Id of the request being handled, stored in the request extensions by [`AssignRequestId`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestId(pub Uuid);
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: Uuid;
+}
+
+/// Id of the request the current task is handling, if it passed through [`AssignRequestId`].
+/// Lets code without the `HttpRequest` at hand, such as `AppError::error_response`, report it.
+pub fn current_request_id() -> Option<Uuid> {
+    CURRENT_REQUEST_ID.try_with(|id| *id).ok()
+}
+
+/// Assigns each request a UUID. Wrap it inside `Logger` so the access log can print the
+/// header with `%{X-Request-Id}o`.
+#[derive(Clone, Copy, Default)]
+pub struct AssignRequestId;
+
+impl<S, B> Transform<S, ServiceRequest> for AssignRequestId
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AssignRequestIdMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AssignRequestIdMiddleware { service }))
+    }
+}
+
+pub struct AssignRequestIdMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for AssignRequestIdMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let id = Uuid::new_v4();
+        req.extensions_mut().insert(RequestId(id));
+
+        // Inner services may build their response synchronously inside `call`, so that runs
+        // in the scope too, not just the returned future.
+        let fut = CURRENT_REQUEST_ID.sync_scope(id, || self.service.call(req));
+        Box::pin(CURRENT_REQUEST_ID.scope(id, async move {
+            let mut res = fut.await?;
+            let value = HeaderValue::from_str(&id.to_string()).expect("a UUID is a valid header value");
+            res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            Ok(res)
+        }))
+    }
+}
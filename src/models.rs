@@ -1,4 +1,5 @@
-use chrono::{DateTime, Utc};
+use crate::money::Money;
+use chrono::{DateTime, Duration, Months, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -15,54 +16,1410 @@ pub struct User {
     pub name: String,
     pub role: UserRole,
     pub email: Option<String>,
+    #[serde(default)]
+    pub phone: Option<String>,
     /// For borrowers: the lender they chose
     pub lender_id: Option<String>,
     /// For lenders: the organization they belong to
     pub organization: Option<String>,
+    /// Prepaid funds that due installments are auto-debited from (borrowers)
+    #[serde(default)]
+    pub wallet_balance: Money,
+    /// When the user registered
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    /// Last time the profile row was written
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum LoanStatus {
     Active,
+    /// Some money received, still owing, and not behind schedule
+    PartiallyRepaid,
     Overdue,
     Defaulted,
     Repaid,
+    /// Judged uncollectable; terminal, and ignored by collection sweeps
+    WrittenOff,
+}
+
+impl LoanStatus {
+    pub const ALL: [LoanStatus; 6] = [
+        LoanStatus::Active,
+        LoanStatus::PartiallyRepaid,
+        LoanStatus::Overdue,
+        LoanStatus::Defaulted,
+        LoanStatus::Repaid,
+        LoanStatus::WrittenOff,
+    ];
+
+    /// Parses the stored variant name, ignoring case and underscores (`overdue`, `partially_repaid`).
+    pub fn parse(s: &str) -> Option<LoanStatus> {
+        let wanted: String = s.trim().chars().filter(|c| *c != '_').collect();
+        LoanStatus::ALL
+            .into_iter()
+            .find(|status| format!("{:?}", status).eq_ignore_ascii_case(&wanted))
+    }
+}
+
+impl std::fmt::Display for LoanStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            LoanStatus::Active => "Active",
+            LoanStatus::PartiallyRepaid => "Partially repaid",
+            LoanStatus::Overdue => "Overdue",
+            LoanStatus::Defaulted => "Defaulted",
+            LoanStatus::Repaid => "Repaid",
+            LoanStatus::WrittenOff => "Written off",
+        };
+        f.write_str(label)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Loan {
     pub id: uuid::Uuid,
-    pub borrower_id: uuid::Uuid,
-    pub lender_id: uuid::Uuid,
-    pub principal: f64,
+    pub borrower_id: String, // User id of the borrower
+    pub lender_id: String,   // User id of the lender
+    /// User id of the guarantor, liable for the balance if the loan defaults
+    #[serde(default)]
+    pub guarantor_id: Option<String>,
+    pub principal: Money,
     pub interest_rate: f64, // Annual interest rate in percentage
     pub disbursement_date: DateTime<Utc>,
-    pub repayment_schedule: Vec<DateTime<Utc>>,
+    pub repayment_schedule: Vec<ScheduledPayment>,
     pub start_date: DateTime<Utc>,
     pub last_repayment_date: Option<DateTime<Utc>>,
     pub status: LoanStatus,
+    /// Cumulative amount received against this loan
+    #[serde(default)]
+    pub amount_paid: Money,
+    /// How `interest_rate` is applied over the term
+    #[serde(default)]
+    pub interest_kind: InterestKind,
+    /// ISO 4217 code every amount on this loan is denominated in
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// Leading installments that carry no interest; the normal rate applies after them
+    #[serde(default)]
+    pub promo_months: i64,
+    /// How often installments fall due
+    #[serde(default)]
+    pub payment_frequency: PaymentFrequency,
+    /// Kept for retention but hidden from default listings and skipped by status sweeps
+    #[serde(default)]
+    pub archived: bool,
+    /// Penalty for late installments; `None` defers to the configured default
+    #[serde(default)]
+    pub late_fee_policy: Option<LateFeePolicy>,
+    /// When the loan was first stored
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    /// Last time the loan row was written, by any change
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
 }
 
-pub trait RiskScorable {
-    fn calculate_risk_score(&self) -> f64;
+/// Currency assumed for loans recorded before currencies were tracked.
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
 }
 
-impl RiskScorable for Loan {
-    /// Score in [0, 1]: higher means higher predicted default / recovery difficulty.
-    fn calculate_risk_score(&self) -> f64 {
-        let status_base: f64 = match self.status {
-            LoanStatus::Defaulted => 0.92,
-            LoanStatus::Overdue => 0.78,
-            LoanStatus::Repaid => 0.06,
-            LoanStatus::Active => 0.22,
+/// One installment of an amortized loan: when it is due and how it splits into principal and interest.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledPayment {
+    pub due_date: DateTime<Utc>,
+    pub principal: Money,
+    pub interest: Money,
+}
+
+impl ScheduledPayment {
+    /// Principal plus interest, or `None` if that leaves the range of `Money`.
+    pub fn amount(&self) -> Option<Money> {
+        self.principal.checked_add(self.interest)
+    }
+
+    /// Total of the installments' amounts, in cents; `None` if it leaves the range of `Money`.
+    pub fn total<'a, I: IntoIterator<Item = &'a ScheduledPayment>>(installments: I) -> Option<Money> {
+        installments.into_iter().try_fold(Money::ZERO, |total, p| total.checked_add(p.amount()?))
+    }
+
+    /// Splits a standard EMI, `P*r*(1+r)^n / ((1+r)^n - 1)` with `r` the monthly rate, across
+    /// the given (monthly) due dates, rounding interest to cents on every installment. Like every
+    /// schedule builder here it returns `None` if an amount leaves the range of `Money`.
+    pub fn amortize(principal: f64, annual_rate_pct: f64, due_dates: &[DateTime<Utc>]) -> Option<Vec<ScheduledPayment>> {
+        Self::amortize_with(principal, annual_rate_pct, due_dates, InterestRounding::PerInstallment)
+    }
+
+    /// Like [`ScheduledPayment::amortize`] with an explicit interest rounding policy. Principal
+    /// portions are always rounded to cents with the last installment absorbing the remainder,
+    /// so they add up to exactly `principal`; a zero rate divides evenly.
+    pub fn amortize_with(
+        principal: f64,
+        annual_rate_pct: f64,
+        due_dates: &[DateTime<Utc>],
+        rounding: InterestRounding,
+    ) -> Option<Vec<ScheduledPayment>> {
+        Self::amortize_at(principal, PaymentFrequency::Monthly.period_rate(annual_rate_pct), due_dates, rounding)
+    }
+
+    /// EMI schedule with `r` the interest rate for one period between due dates.
+    fn amortize_at(principal: f64, r: f64, due_dates: &[DateTime<Utc>], rounding: InterestRounding) -> Option<Vec<ScheduledPayment>> {
+        let n = due_dates.len();
+        if n == 0 {
+            return Some(Vec::new());
+        }
+        let emi = if r.abs() < f64::EPSILON {
+            principal / n as f64
+        } else {
+            let growth = (1.0 + r).powi(n as i32);
+            principal * r * growth / (growth - 1.0)
+        };
+
+        let mut exact_balance = principal;
+        let mut balance = Money::from_f64(principal);
+        let mut exact_interest_total = 0.0;
+        let mut booked_interest = Money::ZERO;
+        let mut schedule = Vec::with_capacity(n);
+        for (i, &due_date) in due_dates.iter().enumerate() {
+            let last = i + 1 == n;
+            let exact_interest = exact_balance * r;
+            exact_interest_total += exact_interest;
+            let interest = match rounding {
+                InterestRounding::PerInstallment => Money::from_f64(exact_interest),
+                // Book the rounded running total, carrying fractions of a cent forward
+                InterestRounding::AtPayoff => Money::from_f64(exact_interest_total).checked_sub(booked_interest)?,
+            };
+            booked_interest = booked_interest.checked_add(interest)?;
+            // Whole cents, with the last installment taking exactly what is left
+            let principal_part = if last { balance } else { Money::from_f64(emi - interest.to_f64()) };
+            balance = balance.checked_sub(principal_part)?;
+            exact_balance = balance.to_f64();
+            schedule.push(ScheduledPayment { due_date, principal: principal_part, interest });
+        }
+        Some(schedule)
+    }
+
+    /// Equal installments for loans whose interest is fixed up front (`Simple`, `CompoundMonthly`):
+    /// principal and `total_interest` are each split evenly, rounded to cents, with the last
+    /// installment absorbing the remainders so both add up exactly.
+    pub fn level(
+        principal: f64,
+        total_interest: f64,
+        due_dates: &[DateTime<Utc>],
+        rounding: InterestRounding,
+    ) -> Option<Vec<ScheduledPayment>> {
+        let n = due_dates.len();
+        let principal_total = Money::from_f64(principal);
+        let interest_total = Money::from_f64(total_interest);
+        let mut booked_principal = Money::ZERO;
+        let mut booked_interest = Money::ZERO;
+        let mut schedule = Vec::with_capacity(n);
+        for (i, &due_date) in due_dates.iter().enumerate() {
+            let last = i + 1 == n;
+            let principal_part = if last { principal_total.checked_sub(booked_principal)? } else { Money::from_f64(principal / n as f64) };
+            let interest = match rounding {
+                _ if last => interest_total.checked_sub(booked_interest)?,
+                InterestRounding::PerInstallment => Money::from_f64(total_interest / n as f64),
+                InterestRounding::AtPayoff => Money::from_f64(total_interest * (i + 1) as f64 / n as f64).checked_sub(booked_interest)?,
+            };
+            booked_principal = booked_principal.checked_add(principal_part)?;
+            booked_interest = booked_interest.checked_add(interest)?;
+            schedule.push(ScheduledPayment { due_date, principal: principal_part, interest });
+        }
+        Some(schedule)
+    }
+
+    /// Schedule over `due_dates`, spaced at `frequency`, for the given interest kind.
+    pub fn build(
+        kind: InterestKind,
+        frequency: PaymentFrequency,
+        principal: f64,
+        annual_rate_pct: f64,
+        due_dates: &[DateTime<Utc>],
+        rounding: InterestRounding,
+    ) -> Option<Vec<ScheduledPayment>> {
+        match kind {
+            InterestKind::Amortized => Self::amortize_at(principal, frequency.period_rate(annual_rate_pct), due_dates, rounding),
+            InterestKind::Simple | InterestKind::CompoundMonthly => {
+                let total = kind.total_repayable_over(principal, annual_rate_pct, due_dates.len(), frequency);
+                Self::level(principal, total - principal, due_dates, rounding)
+            }
+        }
+    }
+
+    /// Like [`ScheduledPayment::build`], but the first `promo_installments` are interest-free,
+    /// each repaying an even share of principal. The rest of the principal is then scheduled
+    /// under `kind` over the remaining installments, with interest from the end of the promotion.
+    pub fn build_with_promo(
+        kind: InterestKind,
+        frequency: PaymentFrequency,
+        principal: f64,
+        annual_rate_pct: f64,
+        due_dates: &[DateTime<Utc>],
+        rounding: InterestRounding,
+        promo_installments: usize,
+    ) -> Option<Vec<ScheduledPayment>> {
+        let promo = promo_installments.min(due_dates.len());
+        if promo == 0 {
+            return Self::build(kind, frequency, principal, annual_rate_pct, due_dates, rounding);
+        }
+        if promo == due_dates.len() {
+            return Self::level(principal, 0.0, due_dates, rounding);
+        }
+        let promo_principal = promo_principal(principal, promo, due_dates.len());
+        let mut schedule = Self::level(promo_principal.to_f64(), 0.0, &due_dates[..promo], rounding)?;
+        let financed = Money::from_f64(principal).checked_sub(promo_principal)?;
+        schedule.extend(Self::build(kind, frequency, financed.to_f64(), annual_rate_pct, &due_dates[promo..], rounding)?);
+        Some(schedule)
+    }
+}
+
+/// Principal repaid by the interest-free installments of a promotional schedule.
+fn promo_principal(principal: f64, promo: usize, installments: usize) -> Money {
+    Money::from_f64(principal * promo as f64 / installments as f64)
+}
+
+/// How often a loan's installments fall due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentFrequency {
+    /// Every 7 days.
+    Weekly,
+    /// Every 14 days.
+    Biweekly,
+    /// Same day each calendar month, clamped to the month's last day.
+    #[default]
+    Monthly,
+}
+
+impl PaymentFrequency {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "weekly" => Some(PaymentFrequency::Weekly),
+            "biweekly" | "fortnightly" => Some(PaymentFrequency::Biweekly),
+            "monthly" => Some(PaymentFrequency::Monthly),
+            _ => None,
+        }
+    }
+
+    pub fn periods_per_year(self) -> u32 {
+        match self {
+            PaymentFrequency::Weekly => 52,
+            PaymentFrequency::Biweekly => 26,
+            PaymentFrequency::Monthly => 12,
+        }
+    }
+
+    /// Interest rate for one period, as a fraction: the annual percentage spread evenly over the year.
+    pub fn period_rate(self, annual_rate_pct: f64) -> f64 {
+        annual_rate_pct / 100.0 / self.periods_per_year() as f64
+    }
+
+    /// Installments in a term of `months`, to the nearest whole period (52 for a year paid weekly).
+    pub fn installments(self, months: i64) -> usize {
+        let months = months.max(0);
+        match self {
+            PaymentFrequency::Monthly => months as usize,
+            _ => (months as f64 * self.periods_per_year() as f64 / 12.0).round() as usize,
+        }
+    }
+
+    /// Term in months covered by `installments`, to the nearest month; the inverse of `installments`.
+    pub fn months(self, installments: usize) -> i64 {
+        match self {
+            PaymentFrequency::Monthly => installments as i64,
+            _ => (installments as f64 * 12.0 / self.periods_per_year() as f64).round() as i64,
+        }
+    }
+
+    /// Due dates for a term of `months` starting at `start`, the first one period after it.
+    pub fn due_dates(self, start: DateTime<Utc>, months: i64) -> Vec<DateTime<Utc>> {
+        let step_days = match self {
+            PaymentFrequency::Weekly => 7,
+            PaymentFrequency::Biweekly => 14,
+            PaymentFrequency::Monthly => {
+                return (1..=months.max(0) as u32)
+                    .filter_map(|m| start.checked_add_months(Months::new(m)))
+                    .collect();
+            }
+        };
+        (1..=self.installments(months) as i64)
+            .map(|k| start + Duration::days(step_days * k))
+            .collect()
+    }
+
+    /// Periods an installment due at `due` has been late by `as_of`: one as soon as the due
+    /// date passes, and another each time a further period begins. 0 while it is not yet late.
+    pub fn periods_late(self, due: DateTime<Utc>, as_of: DateTime<Utc>) -> u32 {
+        let mut periods = 0;
+        let mut boundary = due;
+        while boundary < as_of {
+            periods += 1;
+            boundary = match self {
+                PaymentFrequency::Weekly => boundary + Duration::days(7),
+                PaymentFrequency::Biweekly => boundary + Duration::days(14),
+                // Counted from `due` each time so short months don't pull later boundaries earlier
+                PaymentFrequency::Monthly => match due.checked_add_months(Months::new(periods)) {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+        }
+        periods
+    }
+}
+
+/// Penalty on installments left unpaid past their due date, charged once for every period (at
+/// the loan's payment frequency) each one is late.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LateFeePolicy {
+    /// The same amount per late installment per period.
+    Flat { amount: Money },
+    /// A percentage of the late installment's amount per period.
+    Percentage { pct: f64 },
+}
+
+impl LateFeePolicy {
+    /// Parses `flat:25` or `percentage:5`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (kind, number) = value.trim().split_once(':')?;
+        let policy = match kind.trim().to_ascii_lowercase().as_str() {
+            "flat" => LateFeePolicy::Flat { amount: Money::parse(number)? },
+            "percentage" | "pct" => LateFeePolicy::Percentage { pct: number.trim().parse().ok()? },
+            _ => return None,
+        };
+        policy.validate().ok()?;
+        Some(policy)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            LateFeePolicy::Flat { amount } if !amount.is_positive() => Err("flat late fee must be a positive amount".to_string()),
+            LateFeePolicy::Percentage { pct } if !pct.is_finite() || pct <= 0.0 || pct > 100.0 => {
+                Err("late fee percentage must be greater than 0 and at most 100".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fee owed on one installment of `installment` that is `periods` late; `None` if it
+    /// leaves the range of `Money`.
+    pub fn fee(&self, installment: Money, periods: u32) -> Option<Money> {
+        let per_period = match *self {
+            LateFeePolicy::Flat { amount } => amount,
+            LateFeePolicy::Percentage { pct } => installment.scale(pct / 100.0),
+        };
+        per_period.cents().checked_mul(periods as i64).map(Money::from_cents)
+    }
+}
+
+/// Where one installment stands relative to payments received and the current date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallmentState {
+    Paid,
+    Upcoming,
+    Overdue,
+}
+
+/// A repayment schedule entry as shown to clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    /// 1-based position in the schedule
+    pub installment: usize,
+    pub due_date: DateTime<Utc>,
+    pub principal: Money,
+    pub interest: Money,
+    pub amount: Money,
+    pub state: InstallmentState,
+    /// Penalty accrued on this installment so far; zero unless it is overdue
+    pub late_fee: Money,
+}
+
+/// One row of an amortization table: how a period's installment splits and what it leaves owed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentLine {
+    /// 1-based period number
+    pub period: usize,
+    pub due_date: DateTime<Utc>,
+    pub opening_balance: Money,
+    pub interest: Money,
+    pub principal: Money,
+    pub closing_balance: Money,
+}
+
+/// When interest is rounded to cents in an amortization schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterestRounding {
+    /// Each installment's interest is rounded on its own; the total is their sum.
+    #[default]
+    PerInstallment,
+    /// Each installment books the running total of exact interest, rounded, less what earlier
+    /// ones booked, so fractions of a cent carry forward and the total is rounded only once.
+    AtPayoff,
+}
+
+impl InterestRounding {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "per_installment" => Some(InterestRounding::PerInstallment),
+            "at_payoff" => Some(InterestRounding::AtPayoff),
+            _ => None,
+        }
+    }
+}
+
+/// How a loan's annual `interest_rate` turns into interest owed over its term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterestKind {
+    /// Interest each period on the declining balance, repaid in equal (EMI) installments.
+    #[default]
+    Amortized,
+    /// Flat interest on the original principal: `P * rate * months / 12`.
+    Simple,
+    /// Interest compounded monthly on the full principal for the whole term: `P * (1 + rate/12)^months - P`.
+    CompoundMonthly,
+}
+
+impl InterestKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "amortized" => Some(InterestKind::Amortized),
+            "simple" => Some(InterestKind::Simple),
+            "compound_monthly" | "compoundmonthly" => Some(InterestKind::CompoundMonthly),
+            _ => None,
+        }
+    }
+
+    /// Principal plus interest owed over `months` monthly installments under this kind, unrounded.
+    pub fn total_repayable(self, principal: f64, annual_rate_pct: f64, months: usize) -> f64 {
+        self.total_repayable_over(principal, annual_rate_pct, months, PaymentFrequency::Monthly)
+    }
+
+    /// Principal plus interest owed over `periods` installments at `frequency`, unrounded.
+    /// `CompoundMonthly` still compounds monthly, over the term those installments span.
+    pub fn total_repayable_over(self, principal: f64, annual_rate_pct: f64, periods: usize, frequency: PaymentFrequency) -> f64 {
+        let r = frequency.period_rate(annual_rate_pct);
+        if periods == 0 || r.abs() < f64::EPSILON {
+            return principal;
+        }
+        let n = periods as f64;
+        match self {
+            InterestKind::Amortized => {
+                let growth = (1.0 + r).powi(periods as i32);
+                principal * r * growth / (growth - 1.0) * n
+            }
+            InterestKind::Simple => principal * (1.0 + r * n),
+            InterestKind::CompoundMonthly => {
+                let monthly = PaymentFrequency::Monthly.period_rate(annual_rate_pct);
+                match frequency {
+                    PaymentFrequency::Monthly => principal * (1.0 + monthly).powi(periods as i32),
+                    _ => principal * (1.0 + monthly).powf(n * 12.0 / frequency.periods_per_year() as f64),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Payment {
+    pub id: uuid::Uuid,
+    pub loan_id: uuid::Uuid,
+    pub amount: Money,
+    pub paid_at: DateTime<Utc>,
+}
+
+/// One recorded status transition of a loan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub loan_id: uuid::Uuid,
+    /// `None` for the entry written when the loan was created
+    pub from_status: Option<LoanStatus>,
+    pub to_status: LoanStatus,
+    pub changed_at: DateTime<Utc>,
+    /// User id that caused the change, or `system` for background jobs
+    pub actor: String,
+}
+
+/// One recorded role change of a user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoleChange {
+    pub user_id: String,
+    pub from_role: UserRole,
+    pub to_role: UserRole,
+    pub changed_at: DateTime<Utc>,
+    /// User id that made the change
+    pub actor: String,
+}
+
+/// Bounds on a new loan's principal; an unset bound doesn't constrain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PrincipalLimits {
+    pub min_principal: Option<f64>,
+    pub max_principal: Option<f64>,
+}
+
+impl PrincipalLimits {
+    /// Each bound from `self`, or from `fallback` where `self` leaves it unset.
+    pub fn or(self, fallback: PrincipalLimits) -> PrincipalLimits {
+        PrincipalLimits {
+            min_principal: self.min_principal.or(fallback.min_principal),
+            max_principal: self.max_principal.or(fallback.max_principal),
+        }
+    }
+
+    pub fn check(&self, principal: f64) -> Result<(), String> {
+        if let Some(min) = self.min_principal.filter(|min| principal < *min) {
+            return Err(format!("principal {:.2} is below the minimum of {:.2}", principal, min));
+        }
+        if let Some(max) = self.max_principal.filter(|max| principal > *max) {
+            return Err(format!("principal {:.2} is above the maximum of {:.2}", principal, max));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExtensionStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// A borrower's request to push one installment's due date back, decided by the lender.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtensionRequest {
+    pub id: uuid::Uuid,
+    pub loan_id: uuid::Uuid,
+    /// Index into the loan's `repayment_schedule`
+    pub installment_index: usize,
+    pub current_due: DateTime<Utc>,
+    pub requested_due: DateTime<Utc>,
+    pub status: ExtensionStatus,
+    pub requested_at: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+impl Loan {
+    /// Index of the first scheduled installment due at or after `as_of`.
+    pub fn next_due_index(&self, as_of: DateTime<Utc>) -> Option<usize> {
+        self.repayment_schedule.iter().position(|p| p.due_date >= as_of)
+    }
+
+    /// The schedule as an amortization table, balances rounded to cents. The final line always
+    /// closes at exactly zero, absorbing any cent left over from rounding. `None` if a balance
+    /// leaves the range of `Money`.
+    pub fn payment_breakdown(&self) -> Option<Vec<PaymentLine>> {
+        let last = self.repayment_schedule.len().saturating_sub(1);
+        let mut balance = self.principal;
+        self.repayment_schedule
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let opening_balance = balance;
+                let closing_balance = if i == last { Money::ZERO } else { balance.checked_sub(p.principal)? };
+                balance = closing_balance;
+                Some(PaymentLine {
+                    period: i + 1,
+                    due_date: p.due_date,
+                    opening_balance,
+                    interest: p.interest,
+                    principal: opening_balance.checked_sub(closing_balance)?,
+                    closing_balance,
+                })
+            })
+            .collect()
+    }
+
+    /// Rough share of the principal recovered so far, in percent (status-based estimate).
+    pub fn recovery_progress(&self) -> f64 {
+        match self.status {
+            LoanStatus::Repaid => 100.0,
+            LoanStatus::Active => 42.0,
+            LoanStatus::PartiallyRepaid => match self.total_due() {
+                Some(total) => (self.amount_paid.to_f64() / total.to_f64() * 100.0).clamp(0.0, 100.0),
+                None => 0.0,
+            },
+            LoanStatus::Overdue => 28.0,
+            LoanStatus::Defaulted => 12.0,
+            LoanStatus::WrittenOff => 0.0,
+        }
+    }
+
+    pub fn outstanding_amount(&self) -> Money {
+        self.principal.scale(1.0 - self.recovery_progress() / 100.0)
+    }
+
+    /// Principal plus all scheduled interest; `None` if the schedule adds up to more than
+    /// `Money` can hold.
+    pub fn total_due(&self) -> Option<Money> {
+        if self.repayment_schedule.is_empty() {
+            return Some(self.principal);
+        }
+        ScheduledPayment::total(&self.repayment_schedule)
+    }
+
+    /// Number of leading installments fully covered by the amount paid so far.
+    pub fn installments_covered(&self) -> usize {
+        let mut cumulative = Some(Money::ZERO);
+        self.repayment_schedule
+            .iter()
+            .take_while(|p| {
+                // A running total past the range of `Money` is more than was ever paid
+                cumulative = cumulative.and_then(|c| c.checked_add(p.amount()?));
+                cumulative.is_some_and(|c| c <= self.amount_paid)
+            })
+            .count()
+    }
+
+    /// First installment payments have not fully covered, with the amount still owed on it.
+    pub fn next_unpaid_installment(&self) -> Option<(ScheduledPayment, Money)> {
+        let covered = self.installments_covered();
+        let next = *self.repayment_schedule.get(covered)?;
+        let due_through_next = ScheduledPayment::total(&self.repayment_schedule[..=covered])?;
+        Some((next, due_through_next.checked_sub(self.amount_paid)?))
+    }
+
+    /// Principal plus interest owed over the full term under `interest_kind`, unrounded.
+    /// Level (`Simple` / `CompoundMonthly`) schedules add up to exactly this, rounded to cents;
+    /// amortized ones can differ by a few cents of per-installment rounding.
+    /// Installments inside the promotional window add principal only.
+    pub fn total_repayable(&self) -> f64 {
+        let periods = self.repayment_schedule.len();
+        let promo = self.promo_installments().min(periods);
+        let principal = self.principal.to_f64();
+        let frequency = self.payment_frequency;
+        if promo == 0 || promo == periods {
+            let rate = if promo == 0 { self.interest_rate } else { 0.0 };
+            return self.interest_kind.total_repayable_over(principal, rate, periods, frequency);
+        }
+        let promo_principal = promo_principal(principal, promo, periods).to_f64();
+        promo_principal + self.interest_kind.total_repayable_over(principal - promo_principal, self.interest_rate, periods - promo, frequency)
+    }
+
+    /// Length of the term in months, whatever the payment frequency.
+    pub fn term_months(&self) -> i64 {
+        self.payment_frequency.months(self.repayment_schedule.len())
+    }
+
+    /// Installments falling inside the `promo_months` window.
+    pub fn promo_installments(&self) -> usize {
+        self.payment_frequency.installments(self.promo_months)
+    }
+
+    /// What is still owed after the payments received so far; never negative. `None` only if
+    /// the schedule adds up to more than `Money` can hold.
+    pub fn remaining_balance(&self) -> Option<Money> {
+        Some(self.total_due()?.checked_sub(self.amount_paid)?.non_negative())
+    }
+
+    /// The loan with `policy` as its late-fee policy if it has none of its own.
+    pub fn with_default_late_fee_policy(mut self, policy: Option<LateFeePolicy>) -> Loan {
+        self.late_fee_policy = self.late_fee_policy.or(policy);
+        self
+    }
+
+    /// Late fee accrued on installment `index` by `as_of`; zero if it is among the `covered`
+    /// leading installments payments have paid off.
+    fn installment_late_fee(&self, index: usize, covered: usize, as_of: DateTime<Utc>) -> Option<Money> {
+        let (Some(policy), Some(p)) = (self.late_fee_policy, self.repayment_schedule.get(index)) else {
+            return Some(Money::ZERO);
         };
-        // Slightly lift risk for high coupon active loans (demo heuristic)
-        let rate_bump: f64 = if matches!(self.status, LoanStatus::Active) && self.interest_rate > 15.0 {
-            0.12
+        if index < covered {
+            return Some(Money::ZERO);
+        }
+        policy.fee(p.amount()?, self.payment_frequency.periods_late(p.due_date, as_of))
+    }
+
+    /// Penalty accrued by `as_of` on every installment still unpaid past its due date, under
+    /// the loan's `late_fee_policy` (none means no fee). `None` if it leaves the range of `Money`.
+    pub fn late_fee(&self, as_of: DateTime<Utc>) -> Option<Money> {
+        let covered = self.installments_covered();
+        (covered..self.repayment_schedule.len())
+            .try_fold(Money::ZERO, |total, i| total.checked_add(self.installment_late_fee(i, covered, as_of)?))
+    }
+
+    /// `remaining_balance` plus the late fee accrued by `as_of`.
+    pub fn outstanding_balance(&self, as_of: DateTime<Utc>) -> Option<Money> {
+        self.remaining_balance()?.checked_add(self.late_fee(as_of)?)
+    }
+
+    /// Schedule interest earned between `disbursement_date` and `as_of`: every installment due
+    /// by then contributes its full interest, and the installment in progress a pro-rata share.
+    /// Promotional installments carry no interest, so nothing accrues until that window ends.
+    pub fn accrued_interest(&self, as_of: DateTime<Utc>) -> f64 {
+        let mut period_start = self.disbursement_date;
+        let mut accrued = 0.0;
+        for p in &self.repayment_schedule {
+            if p.due_date <= as_of {
+                accrued += p.interest.to_f64();
+                period_start = p.due_date;
+                continue;
+            }
+            let period = (p.due_date - period_start).num_seconds() as f64;
+            let elapsed = (as_of - period_start).num_seconds().max(0) as f64;
+            if period > 0.0 {
+                accrued += p.interest.to_f64() * (elapsed / period).min(1.0);
+            }
+            break;
+        }
+        accrued
+    }
+
+    /// Money at stake as of `as_of`: principal plus interest accrued so far, less payments received.
+    /// `None` if it leaves the range of `Money`.
+    pub fn exposure(&self, as_of: DateTime<Utc>) -> Option<Money> {
+        let owed = self.principal.checked_add(Money::from_f64(self.accrued_interest(as_of)))?;
+        Some(owed.checked_sub(self.amount_paid)?.non_negative())
+    }
+
+    /// Principal portion of the installments payments have not yet fully covered; `None` if it
+    /// leaves the range of `Money`.
+    pub fn principal_outstanding(&self) -> Option<Money> {
+        if self.repayment_schedule.is_empty() {
+            return Some(self.principal.checked_sub(self.amount_paid)?.non_negative());
+        }
+        Money::checked_sum(self.repayment_schedule[self.installments_covered()..].iter().map(|p| p.principal))
+    }
+
+    /// Amount due on installments falling before `as_of` that payments have not covered; `None`
+    /// if it leaves the range of `Money`.
+    pub fn overdue_amount(&self, as_of: DateTime<Utc>) -> Option<Money> {
+        let due = ScheduledPayment::total(self.repayment_schedule.iter().filter(|p| p.due_date < as_of))?;
+        Some(due.checked_sub(self.amount_paid)?.non_negative())
+    }
+
+    /// Whole days the earliest unpaid installment is past due at `as_of`; 0 when it is not yet due
+    /// (including due exactly now) or nothing is owed.
+    pub fn days_overdue(&self, as_of: DateTime<Utc>) -> i64 {
+        self.repayment_schedule
+            .get(self.installments_covered())
+            .map(|p| (as_of - p.due_date).num_days().max(0))
+            .unwrap_or(0)
+    }
+
+    /// Scheduled installments already due before `as_of` that payments have not covered.
+    pub fn missed_installments(&self, as_of: DateTime<Utc>) -> usize {
+        let due = self.repayment_schedule.iter().filter(|p| p.due_date < as_of).count();
+        due.saturating_sub(self.installments_covered())
+    }
+
+    /// Days late of each installment due before `as_of`: from its due date until `payments` first
+    /// added up to everything due through it, or until `as_of` if they never have. Installments
+    /// covered by `amount_paid` without recorded payments count as paid on time.
+    pub fn installment_days_late(&self, payments: &[Payment], as_of: DateTime<Utc>) -> Vec<i64> {
+        let mut paid: Vec<&Payment> = payments.iter().filter(|p| p.paid_at <= as_of).collect();
+        paid.sort_by_key(|p| p.paid_at);
+
+        let mut due_so_far = Some(Money::ZERO);
+        self.repayment_schedule
+            .iter()
+            .filter(|p| p.due_date < as_of)
+            .map(|installment| {
+                // A total due past the range of `Money` is never covered
+                due_so_far = due_so_far.and_then(|d| d.checked_add(installment.amount()?));
+                let late_until_now = (as_of - installment.due_date).num_days().max(0);
+                let Some(due) = due_so_far else {
+                    return late_until_now;
+                };
+                let mut cumulative = Some(Money::ZERO);
+                let covered_at = paid.iter().find(|p| {
+                    cumulative = cumulative.and_then(|c| c.checked_add(p.amount));
+                    cumulative.is_none_or(|c| c >= due)
+                });
+                match covered_at {
+                    Some(p) => (p.paid_at - installment.due_date).num_days().max(0),
+                    None if payments.is_empty() && self.amount_paid >= due => 0,
+                    None => late_until_now,
+                }
+            })
+            .collect()
+    }
+
+    /// The schedule with each installment marked paid (covered by payments so far),
+    /// overdue (due before `as_of` and not covered) or upcoming. `None` if an installment's
+    /// amount or late fee leaves the range of `Money`.
+    pub fn schedule_as_of(&self, as_of: DateTime<Utc>) -> Option<Vec<ScheduleEntry>> {
+        let covered = self.installments_covered();
+        self.repayment_schedule
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                Some(ScheduleEntry {
+                    installment: i + 1,
+                    due_date: p.due_date,
+                    principal: p.principal,
+                    interest: p.interest,
+                    amount: p.amount()?,
+                    state: if i < covered {
+                        InstallmentState::Paid
+                    } else if p.due_date < as_of {
+                        InstallmentState::Overdue
+                    } else {
+                        InstallmentState::Upcoming
+                    },
+                    late_fee: self.installment_late_fee(i, covered, as_of)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Status implied by the schedule and payments as of `as_of`, regardless of what is stored.
+    /// Defaults are a lender decision and are never derived, only kept.
+    pub fn computed_status(&self, as_of: DateTime<Utc>) -> LoanStatus {
+        if matches!(self.status, LoanStatus::Defaulted | LoanStatus::WrittenOff) {
+            return self.status.clone();
+        }
+        if self.remaining_balance().is_some_and(Money::is_zero) {
+            return LoanStatus::Repaid;
+        }
+        if self.missed_installments(as_of) > 0 {
+            LoanStatus::Overdue
+        } else if self.amount_paid.is_positive() {
+            LoanStatus::PartiallyRepaid
         } else {
-            0.0
+            LoanStatus::Active
+        }
+    }
+
+    /// Status the overdue and default sweeps would settle on at `as_of`, worked out from the
+    /// schedule and payments alone: the earliest unpaid installment makes the loan Overdue once
+    /// it is more than `grace_days` late and Defaulted once it was due over `default_after_days`
+    /// ago. Only a write-off, which no schedule implies, is kept as stored.
+    pub fn reconciled_status(&self, as_of: DateTime<Utc>, grace_days: i64, default_after_days: i64) -> LoanStatus {
+        if self.status == LoanStatus::WrittenOff {
+            return LoanStatus::WrittenOff;
+        }
+        if self.remaining_balance().is_some_and(Money::is_zero) {
+            return LoanStatus::Repaid;
+        }
+        match self.repayment_schedule.get(self.installments_covered()) {
+            Some(p) if p.due_date < as_of - Duration::days(default_after_days) && as_of > p.due_date + Duration::days(grace_days) => {
+                LoanStatus::Defaulted
+            }
+            Some(p) if as_of > p.due_date + Duration::days(grace_days) => LoanStatus::Overdue,
+            _ if self.amount_paid.is_positive() => LoanStatus::PartiallyRepaid,
+            _ => LoanStatus::Active,
+        }
+    }
+}
+
+/// Risk bands used for portfolio reporting, from A (safest) to E (riskiest).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RiskBand {
+    A,
+    B,
+    C,
+    D,
+    E,
+}
+
+impl RiskBand {
+    pub const ALL: [RiskBand; 5] = [RiskBand::A, RiskBand::B, RiskBand::C, RiskBand::D, RiskBand::E];
+
+    /// Buckets a score in [0, 1] into equal-width bands of 0.2.
+    pub fn from_score(score: f64) -> Self {
+        match score {
+            s if s < 0.2 => RiskBand::A,
+            s if s < 0.4 => RiskBand::B,
+            s if s < 0.6 => RiskBand::C,
+            s if s < 0.8 => RiskBand::D,
+            _ => RiskBand::E,
+        }
+    }
+}
+
+pub trait RiskScorable {
+    /// Default risk in [0, 1] as the loan stands at `as_of`.
+    fn calculate_risk_score(&self, as_of: DateTime<Utc>) -> f64;
+}
+
+/// A pluggable default-risk model.
+///
+/// Contract: `score` returns a value in [0, 1], higher meaning more likely to default.
+/// Recovery thresholds (see `RecoveryEngine::recommend_action`) assume that range, so the
+/// engine reads every model through [`Clamped`], which enforces it for models that stray.
+pub trait RiskScorer {
+    fn score(&self, loan: &Loan, as_of: DateTime<Utc>) -> f64;
+}
+
+/// Wraps a [`RiskScorer`] so its output always honours the [0, 1] contract. Out-of-range
+/// scores are clamped and logged; NaN is treated as maximum risk.
+pub struct Clamped<'a>(pub &'a dyn RiskScorer);
+
+impl RiskScorer for Clamped<'_> {
+    fn score(&self, loan: &Loan, as_of: DateTime<Utc>) -> f64 {
+        let raw = self.0.score(loan, as_of);
+        if (0.0..=1.0).contains(&raw) {
+            return raw;
+        }
+        let clamped = if raw.is_nan() { 1.0 } else { raw.clamp(0.0, 1.0) };
+        log::warn!("Risk model scored loan {} at {}; clamped to {}", loan.id, raw, clamped);
+        clamped
+    }
+}
+
+/// Tunable weights for the default-risk score. The score starts from a prior for the
+/// loan's stored status and adds weighted signals, each normalised to [0, 1]:
+/// how long the oldest unpaid installment has been late, the share of the schedule that is
+/// due but unpaid, principal size against `large_principal`, exposure (outstanding principal
+/// plus accrued interest) against `large_exposure`, and the interest rate against
+/// `rate_ceiling_pct`, plus installments paid late or not at all, each discounted by half for
+/// every `miss_half_life_days` since it fell due. Scored together with the borrower's record on
+/// other loans (see [`WithPaymentHistory::with_borrower_history`]), a borrower's unreliability
+/// is added too. The result is clamped to [0, 1]. Fields left out when deserializing keep their
+/// default values.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RiskModel {
+    pub active_prior: f64,
+    pub partially_repaid_prior: f64,
+    pub overdue_prior: f64,
+    pub defaulted_prior: f64,
+    /// Repaid loans score this flat value; no other signal applies.
+    pub repaid_prior: f64,
+    pub days_overdue_weight: f64,
+    /// Days late at which the days-overdue signal saturates
+    pub days_overdue_cap: f64,
+    pub missed_ratio_weight: f64,
+    pub principal_weight: f64,
+    /// Principal at which the size signal saturates
+    pub large_principal: f64,
+    pub exposure_weight: f64,
+    /// Exposure at which the exposure signal saturates
+    pub large_exposure: f64,
+    pub interest_rate_weight: f64,
+    /// Annual rate (percent) at which the rate signal saturates
+    pub rate_ceiling_pct: f64,
+    /// Weight of the recency-discounted share of installments that were missed
+    pub recent_miss_weight: f64,
+    /// Age in days at which a miss counts half as much as one made today
+    pub miss_half_life_days: f64,
+    /// Weight of `1 - BorrowerHistory::reliability`, when the borrower's history is known
+    pub unreliability_weight: f64,
+}
+
+impl Default for RiskModel {
+    fn default() -> Self {
+        RiskModel {
+            active_prior: 0.20,
+            partially_repaid_prior: 0.15,
+            overdue_prior: 0.60,
+            defaulted_prior: 0.85,
+            repaid_prior: 0.02,
+            days_overdue_weight: 0.35,
+            days_overdue_cap: 90.0,
+            missed_ratio_weight: 0.20,
+            // Size is carried by exposure, which also tracks accrual and repayments
+            principal_weight: 0.0,
+            large_principal: 100_000.0,
+            exposure_weight: 0.10,
+            large_exposure: 100_000.0,
+            interest_rate_weight: 0.25,
+            rate_ceiling_pct: 50.0,
+            recent_miss_weight: 0.15,
+            miss_half_life_days: 90.0,
+            unreliability_weight: 0.25,
+        }
+    }
+}
+
+impl RiskModel {
+    /// Score in [0, 1] as of `as_of`: higher means higher predicted default / recovery difficulty.
+    /// Without payment history, installments covered by `amount_paid` count as paid on time.
+    pub fn score(&self, loan: &Loan, as_of: DateTime<Utc>) -> f64 {
+        self.score_with_history(loan, None, None, as_of)
+    }
+
+    /// Scores with the loan's payments, so installments paid after their due date count as misses.
+    pub fn with_history<'a>(&'a self, payments: &'a [Payment]) -> WithPaymentHistory<'a> {
+        WithPaymentHistory { model: self, payments, reliability: None }
+    }
+
+    /// Sum over installments due before `as_of` that were not covered by their due date, each
+    /// weighted `0.5^(age / miss_half_life_days)`.
+    fn decayed_misses(&self, loan: &Loan, payments: Option<&[Payment]>, as_of: DateTime<Utc>) -> f64 {
+        let mut paid: Vec<&Payment> = payments.unwrap_or_default().iter().filter(|p| p.paid_at <= as_of).collect();
+        paid.sort_by_key(|p| p.paid_at);
+
+        let mut due_so_far = Some(Money::ZERO);
+        let mut total = 0.0;
+        for installment in loan.repayment_schedule.iter().filter(|p| p.due_date < as_of) {
+            // A total due past the range of `Money` is never covered on time
+            due_so_far = due_so_far.and_then(|d| d.checked_add(installment.amount()?));
+            let on_time = due_so_far.is_some_and(|due| match payments {
+                Some(_) => {
+                    let paid_by_due = Money::checked_sum(paid.iter()
+                        .take_while(|p| p.paid_at <= installment.due_date)
+                        .map(|p| p.amount));
+                    paid_by_due.is_none_or(|paid| paid >= due)
+                }
+                None => loan.amount_paid >= due,
+            });
+            if on_time {
+                continue;
+            }
+            let age_days = (as_of - installment.due_date).num_days().max(0) as f64;
+            total += if self.miss_half_life_days > 0.0 { 0.5f64.powf(age_days / self.miss_half_life_days) } else { 1.0 };
+        }
+        total
+    }
+
+    fn score_with_history(&self, loan: &Loan, payments: Option<&[Payment]>, reliability: Option<f64>, as_of: DateTime<Utc>) -> f64 {
+        let prior = match loan.status {
+            LoanStatus::Repaid => return self.repaid_prior.clamp(0.0, 1.0),
+            LoanStatus::WrittenOff => return 1.0,
+            LoanStatus::Active => self.active_prior,
+            LoanStatus::PartiallyRepaid => self.partially_repaid_prior,
+            LoanStatus::Overdue => self.overdue_prior,
+            LoanStatus::Defaulted => self.defaulted_prior,
         };
-        f64::min(status_base + rate_bump, 0.99)
+
+        let days_overdue = loan.days_overdue(as_of) as f64;
+        let installments = loan.repayment_schedule.len().max(1) as f64;
+        let missed_ratio = loan.missed_installments(as_of) as f64 / installments;
+        let recent_misses = self.decayed_misses(loan, payments, as_of) / installments;
+
+        let saturate = |value: f64, cap: f64| if cap > 0.0 { (value / cap).clamp(0.0, 1.0) } else { 0.0 };
+        let score = prior
+            + self.days_overdue_weight * saturate(days_overdue, self.days_overdue_cap)
+            + self.missed_ratio_weight * missed_ratio
+            + self.principal_weight * saturate(loan.principal.to_f64(), self.large_principal)
+            + self.exposure_weight * loan.exposure(as_of).map_or(1.0, |e| saturate(e.to_f64(), self.large_exposure))
+            + self.interest_rate_weight * saturate(loan.interest_rate, self.rate_ceiling_pct)
+            + self.recent_miss_weight * recent_misses.min(1.0)
+            + self.unreliability_weight * (1.0 - reliability.unwrap_or(1.0).clamp(0.0, 1.0));
+        score.clamp(0.0, 1.0)
+    }
+}
+
+/// A [`RiskModel`] paired with a loan's recorded payments; see [`RiskModel::with_history`].
+pub struct WithPaymentHistory<'a> {
+    model: &'a RiskModel,
+    payments: &'a [Payment],
+    reliability: Option<f64>,
+}
+
+impl WithPaymentHistory<'_> {
+    /// Also weighs in how the borrower handled their other loans.
+    pub fn with_borrower_history(mut self, history: &BorrowerHistory) -> Self {
+        self.reliability = Some(history.reliability());
+        self
+    }
+}
+
+impl RiskScorer for WithPaymentHistory<'_> {
+    fn score(&self, loan: &Loan, as_of: DateTime<Utc>) -> f64 {
+        self.model.score_with_history(loan, Some(self.payments), self.reliability, as_of)
+    }
+}
+
+/// Days late at which the lateness part of `BorrowerHistory::reliability` saturates.
+const RELIABILITY_LATE_DAYS_CAP: f64 = 90.0;
+
+/// How a borrower has handled a set of their loans.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BorrowerHistory {
+    pub borrower_id: String,
+    pub total_loans: usize,
+    pub repaid_count: usize,
+    /// Loans defaulted or written off
+    pub defaulted_count: usize,
+    /// Mean days from an installment's due date until payments covered it (until `as_of` if
+    /// they haven't), over every installment already due; 0 when none is
+    pub average_days_late: f64,
+}
+
+impl BorrowerHistory {
+    /// In [0, 1]: 1 for a borrower with nothing against them, including one with no loans,
+    /// falling as more of their loans default and their installments are paid later.
+    pub fn reliability(&self) -> f64 {
+        if self.total_loans == 0 {
+            return 1.0;
+        }
+        let default_share = self.defaulted_count as f64 / self.total_loans as f64;
+        let lateness = (self.average_days_late / RELIABILITY_LATE_DAYS_CAP).clamp(0.0, 1.0);
+        (1.0 - 0.7 * default_share - 0.3 * lateness).clamp(0.0, 1.0)
+    }
+}
+
+impl RiskScorer for RiskModel {
+    fn score(&self, loan: &Loan, as_of: DateTime<Utc>) -> f64 {
+        RiskModel::score(self, loan, as_of)
+    }
+}
+
+impl RiskScorable for Loan {
+    /// Score in [0, 1] under the default [`RiskModel`].
+    fn calculate_risk_score(&self, as_of: DateTime<Utc>) -> f64 {
+        RiskModel::default().score(self, as_of)
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn loan_due(days_ago: i64) -> Loan {
+        let now = Utc::now();
+        let due_dates: Vec<DateTime<Utc>> = (0..6).map(|i| now - Duration::days(days_ago) + Duration::days(30 * i)).collect();
+        Loan {
+            id: uuid::Uuid::new_v4(),
+            borrower_id: "BORR".to_string(),
+            lender_id: "LEND".to_string(),
+            guarantor_id: None,
+            principal: Money::from_cents(500_000),
+            interest_rate: 12.0,
+            disbursement_date: now - Duration::days(days_ago + 30),
+            repayment_schedule: ScheduledPayment::amortize(5_000.0, 12.0, &due_dates).unwrap(),
+            start_date: now - Duration::days(days_ago + 30),
+            last_repayment_date: None,
+            // Not yet flagged, so only the schedule signals tell the two apart
+            status: LoanStatus::Active,
+            amount_paid: Money::ZERO,
+            interest_kind: InterestKind::Amortized,
+            currency: DEFAULT_CURRENCY.to_string(),
+            promo_months: 0,
+            payment_frequency: PaymentFrequency::Monthly,
+            archived: false,
+            late_fee_policy: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_payment_breakdown_chains_balances_and_closes_at_zero() {
+        let loan = loan_due(0);
+        let lines = loan.payment_breakdown().unwrap();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0].period, 1);
+        assert_eq!(lines[0].opening_balance, Money::from_cents(500_000));
+        assert_eq!(lines[0].interest, Money::from_cents(5_000));
+        for pair in lines.windows(2) {
+            assert_eq!(pair[0].closing_balance, pair[1].opening_balance);
+        }
+        for line in &lines {
+            assert_eq!(line.opening_balance.checked_sub(line.principal), Some(line.closing_balance));
+        }
+        assert_eq!(lines.last().unwrap().closing_balance, Money::ZERO);
+        assert_eq!(Money::checked_sum(lines.iter().map(|l| l.principal)), Some(loan.principal));
+    }
+
+    #[test]
+    fn test_interest_accrues_only_after_promo_window() {
+        let mut loan = loan_due(0);
+        let due_dates: Vec<DateTime<Utc>> = (1..=12).map(|i| loan.disbursement_date + Duration::days(30 * i)).collect();
+        let full_price = ScheduledPayment::build(InterestKind::Simple, PaymentFrequency::Monthly, 1_200.0, 12.0, &due_dates, InterestRounding::default()).unwrap();
+        loan.principal = Money::from_cents(120_000);
+        loan.interest_kind = InterestKind::Simple;
+        loan.promo_months = 3;
+        loan.repayment_schedule = ScheduledPayment::build_with_promo(InterestKind::Simple, PaymentFrequency::Monthly, 1_200.0, 12.0, &due_dates, InterestRounding::default(), 3).unwrap();
+
+        assert!(loan.repayment_schedule[..3].iter().all(|p| p.interest == Money::ZERO && p.principal == Money::from_cents(10_000)));
+        assert!(loan.repayment_schedule[3..].iter().all(|p| p.interest.is_positive()));
+        assert_eq!(loan.accrued_interest(due_dates[1] + Duration::days(15)), 0.0);
+        assert_eq!(loan.accrued_interest(due_dates[2]), 0.0);
+        assert!(loan.accrued_interest(due_dates[2] + Duration::days(15)) > 0.0);
+
+        // 900 financed at 1% a month over the last 9 installments
+        assert!((loan.total_repayable() - (1_200.0 + 900.0 * 0.01 * 9.0)).abs() < 1e-9);
+        assert_eq!(loan.total_due(), Some(Money::from_f64(loan.total_repayable())));
+        let full_total = ScheduledPayment::total(&full_price).unwrap();
+        assert!(Money::from_f64(loan.total_repayable()) < full_total);
+    }
+
+    #[test]
+    fn test_payment_frequency_sets_schedule_length_and_period_rate() {
+        let start: DateTime<Utc> = "2024-01-31T00:00:00Z".parse().unwrap();
+        let mut totals = Vec::new();
+        for (frequency, installments) in [(PaymentFrequency::Weekly, 52), (PaymentFrequency::Biweekly, 26), (PaymentFrequency::Monthly, 12)] {
+            let due_dates = frequency.due_dates(start, 12);
+            assert_eq!(due_dates.len(), installments);
+            assert_eq!(frequency.installments(12), installments);
+            assert_eq!(frequency.months(installments), 12);
+
+            let schedule = ScheduledPayment::build(InterestKind::Amortized, frequency, 1_200.0, 12.0, &due_dates, InterestRounding::default()).unwrap();
+            assert_eq!(Money::checked_sum(schedule.iter().map(|p| p.principal)), Some(Money::from_cents(120_000)));
+            // First period's interest is on the full balance at the per-period rate
+            assert_eq!(schedule[0].interest, Money::from_f64(1_200.0 * 0.12 / frequency.periods_per_year() as f64));
+            totals.push(ScheduledPayment::total(&schedule).unwrap());
+        }
+        assert_eq!(PaymentFrequency::Weekly.due_dates(start, 12)[1] - start, Duration::days(14));
+        assert_eq!(PaymentFrequency::Biweekly.due_dates(start, 12)[0] - start, Duration::days(14));
+        assert_eq!(PaymentFrequency::Monthly.due_dates(start, 1)[0].to_rfc3339(), "2024-02-29T00:00:00+00:00");
+        // Paying more often repays principal sooner, so less interest builds up
+        assert!(totals[0] < totals[1] && totals[1] < totals[2]);
+    }
+
+    #[test]
+    fn test_days_overdue_counts_from_earliest_unpaid_installment() {
+        let mut loan = loan_due(10);
+        let first_due = loan.repayment_schedule[0].due_date;
+        assert_eq!(loan.days_overdue(first_due), 0);
+        assert_eq!(loan.days_overdue(first_due - Duration::days(3)), 0);
+        assert_eq!(loan.days_overdue(first_due + Duration::days(10)), 10);
+
+        loan.amount_paid = loan.repayment_schedule[0].amount().unwrap();
+        let second_due = loan.repayment_schedule[1].due_date;
+        assert_eq!(loan.days_overdue(second_due + Duration::days(2)), 2);
+    }
+
+    #[test]
+    fn test_recent_misses_outweigh_old_ones() {
+        let now = Utc::now();
+        let due_dates: Vec<DateTime<Utc>> = (0..12).map(|i| now - Duration::days(360) + Duration::days(30 * i)).collect();
+        let mut loan = loan_due(0);
+        loan.repayment_schedule = ScheduledPayment::amortize(1_200.0, 12.0, &due_dates).unwrap();
+        loan.principal = Money::from_cents(120_000);
+        let pay = |i: usize, late_days: i64| Payment {
+            id: uuid::Uuid::new_v4(),
+            loan_id: loan.id,
+            amount: loan.repayment_schedule[i].amount().unwrap(),
+            paid_at: due_dates[i] + Duration::days(late_days),
+        };
+        // Both borrowers paid every installment due so far, two of them 20 days late
+        let old_misser: Vec<Payment> = (0..12).filter(|i| due_dates[*i] < now).map(|i| pay(i, if i < 2 { 20 } else { 0 })).collect();
+        let recent_misser: Vec<Payment> = (0..12).filter(|i| due_dates[*i] < now).map(|i| pay(i, if (9..11).contains(&i) { 20 } else { 0 })).collect();
+        loan.amount_paid = Money::checked_sum(old_misser.iter().map(|p| p.amount)).unwrap();
+        loan.status = LoanStatus::PartiallyRepaid;
+
+        let model = RiskModel::default();
+        let old = model.with_history(&old_misser).score(&loan, now);
+        let recent = model.with_history(&recent_misser).score(&loan, now);
+        assert!(recent > old, "recent {} vs old {}", recent, old);
+        // Without payment dates everything covered counts as on time, so even old misses add a little
+        assert!(old > model.score(&loan, now));
+    }
+
+    #[test]
+    fn test_long_overdue_scores_materially_higher() {
+        let model = RiskModel::default();
+        let now = Utc::now();
+        let slightly_late = model.score(&loan_due(5), now);
+        let very_late = model.score(&loan_due(90), now);
+        assert!(very_late - slightly_late > 0.3, "{} vs {}", very_late, slightly_late);
+        assert!((0.0..=1.0).contains(&slightly_late));
+        assert!((0.0..=1.0).contains(&very_late));
+    }
+
+    #[test]
+    fn test_weights_are_tunable_and_score_is_clamped() {
+        let loan = loan_due(90);
+        let heavy = RiskModel { days_overdue_weight: 5.0, ..RiskModel::default() };
+        assert_eq!(heavy.score(&loan, Utc::now()), 1.0);
+
+        let rate_blind = RiskModel { interest_rate_weight: 0.0, ..RiskModel::default() };
+        let mut cheap = loan_due(5);
+        let mut pricey = loan_due(5);
+        cheap.interest_rate = 5.0;
+        pricey.interest_rate = 45.0;
+        assert_eq!(rate_blind.score(&cheap, Utc::now()), rate_blind.score(&pricey, Utc::now()));
+        assert!(RiskModel::default().score(&pricey, Utc::now()) > RiskModel::default().score(&cheap, Utc::now()));
+    }
+
+    #[test]
+    fn test_simple_vs_compound_totals() {
+        let mut loan = loan_due(0);
+        loan.principal = Money::from_cents(1_000_000);
+        loan.interest_rate = 12.0;
+        loan.repayment_schedule.resize(12, loan.repayment_schedule[0]);
+
+        loan.interest_kind = InterestKind::Simple;
+        let simple = loan.total_repayable();
+        loan.interest_kind = InterestKind::CompoundMonthly;
+        let compound = loan.total_repayable();
+
+        assert!((simple - 11_200.0).abs() < 1e-6);
+        // 10000 * 1.01^12
+        assert!((compound - 11_268.25).abs() < 0.01);
+        assert!(compound > simple);
+    }
+
+    fn twelve_month_loan(principal: f64, disbursed_days_ago: i64) -> Loan {
+        let disbursed = Utc::now() - Duration::days(disbursed_days_ago);
+        let due_dates: Vec<DateTime<Utc>> = (1..=12).map(|i| disbursed + Duration::days(30 * i)).collect();
+        Loan {
+            principal: Money::from_f64(principal),
+            disbursement_date: disbursed,
+            start_date: disbursed,
+            repayment_schedule: ScheduledPayment::amortize(principal, 12.0, &due_dates).unwrap(),
+            ..loan_due(0)
+        }
+    }
+
+    #[test]
+    fn test_accrued_interest_at_0_6_and_12_months() {
+        let loan = twelve_month_loan(12_000.0, 400);
+        let interest = |n: usize| loan.repayment_schedule[..n].iter().map(|p| p.interest.to_f64()).sum::<f64>();
+        let at = |months: i64| loan.disbursement_date + Duration::days(30 * months);
+
+        assert_eq!(loan.accrued_interest(at(0)), 0.0);
+        assert_eq!(loan.accrued_interest(loan.disbursement_date - Duration::days(5)), 0.0);
+        assert!((loan.accrued_interest(at(6)) - interest(6)).abs() < 1e-9);
+        assert!((loan.accrued_interest(at(12)) - interest(12)).abs() < 1e-9);
+        // Nothing further accrues past maturity
+        assert!((loan.accrued_interest(at(24)) - interest(12)).abs() < 1e-9);
+        // Halfway through the seventh installment earns half its interest
+        let mid = at(6) + Duration::days(15);
+        let expected = interest(6) + loan.repayment_schedule[6].interest.to_f64() / 2.0;
+        assert!((loan.accrued_interest(mid) - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_larger_exposure_outranks_equally_late_loan() {
+        let model = RiskModel::default();
+        let as_of = Utc::now();
+        // One installment a couple of weeks late on each
+        let small = Loan { status: LoanStatus::Overdue, ..twelve_month_loan(2_000.0, 45) };
+        let large = Loan { status: LoanStatus::Overdue, ..twelve_month_loan(80_000.0, 45) };
+        assert!(large.exposure(as_of) > small.exposure(as_of));
+        assert!(model.score(&large, as_of) > model.score(&small, as_of));
+
+        // Repayments shrink exposure
+        let mostly_paid = Loan { amount_paid: Money::from_cents(7_000_000), ..large.clone() };
+        assert!(mostly_paid.exposure(as_of) < large.exposure(as_of));
+    }
+
+    #[test]
+    fn test_late_fee_flat_and_percentage_per_period_late() {
+        let as_of = Utc::now();
+        // First installment due 45 days ago (two monthly periods begun), second 15 days ago (one)
+        let loan = loan_due(45);
+        let first = loan.repayment_schedule[0].amount().unwrap();
+        let second = loan.repayment_schedule[1].amount().unwrap();
+        assert_eq!(loan.late_fee(as_of), Some(Money::ZERO));
+
+        let flat = Loan { late_fee_policy: Some(LateFeePolicy::Flat { amount: Money::from_cents(2_500) }), ..loan.clone() };
+        assert_eq!(flat.late_fee(as_of), Some(Money::from_cents(2_500 * 3)));
+        assert_eq!(flat.outstanding_balance(as_of), flat.remaining_balance().and_then(|b| b.checked_add(Money::from_cents(7_500))));
+        let fees: Vec<Money> = flat.schedule_as_of(as_of).unwrap().iter().map(|e| e.late_fee).collect();
+        assert_eq!(&fees[..3], &[Money::from_cents(5_000), Money::from_cents(2_500), Money::ZERO]);
+
+        let pct = loan.clone().with_default_late_fee_policy(Some(LateFeePolicy::Percentage { pct: 10.0 }));
+        let expected = Money::from_cents(first.scale(0.1).cents() * 2).checked_add(second.scale(0.1));
+        assert_eq!(pct.late_fee(as_of), expected);
+        // A loan's own policy wins over the default
+        assert_eq!(flat.clone().with_default_late_fee_policy(pct.late_fee_policy).late_fee(as_of), Some(Money::from_cents(7_500)));
+
+        // Paying off the first installment stops its fee
+        let paid_first = Loan { amount_paid: first, ..flat };
+        assert_eq!(paid_first.late_fee(as_of), Some(Money::from_cents(2_500)));
+
+        assert_eq!(PaymentFrequency::Weekly.periods_late(as_of - Duration::days(15), as_of), 3);
+        assert_eq!(PaymentFrequency::Monthly.periods_late(as_of, as_of), 0);
+        assert_eq!(LateFeePolicy::parse("flat:25"), Some(LateFeePolicy::Flat { amount: Money::from_cents(2_500) }));
+        assert_eq!(LateFeePolicy::parse("percentage:5"), Some(LateFeePolicy::Percentage { pct: 5.0 }));
+        assert_eq!(LateFeePolicy::parse("percentage:150"), None);
+    }
+}
@@ -0,0 +1,196 @@
+//! Exact money amounts. A [`Money`] counts minor units (hundredths of the loan's currency), so
+//! sums of installments and payments never drift the way repeated `f64` arithmetic does.
+//! Convert from `f64` only where amounts enter the system (request bodies, CLI flags) and back
+//! only where a float is genuinely wanted (risk signals, accrual rates).
+//!
+//! There are no `+` / `-` operators: sums go through [`Money::checked_add`],
+//! [`Money::checked_sub`] and [`Money::checked_sum`], so an amount that leaves the range comes
+//! back as `None` for the caller to report rather than a panic.
+
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// An amount in minor units. Every currency is tracked to two decimal places, including ones
+/// like JPY whose notes have none; those simply carry `.00`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn from_cents(cents: i64) -> Self {
+        Money(cents)
+    }
+
+    pub const fn cents(self) -> i64 {
+        self.0
+    }
+
+    /// Rounds to the nearest cent, halves away from zero. Non-finite input is treated as zero.
+    pub fn from_f64(amount: f64) -> Self {
+        if amount.is_finite() {
+            Money((amount * 100.0).round() as i64)
+        } else {
+            Money::ZERO
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / 100.0
+    }
+
+    /// Parses a decimal amount such as `"1250"`, `"1250.5"` or `"-3.07"`; more than two
+    /// fractional digits is rejected rather than rounded.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole.is_empty() || !whole.bytes().all(|b| b.is_ascii_digit()) || fraction.len() > 2 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let fraction_cents: i64 = match fraction.len() {
+            0 => 0,
+            1 => fraction.parse::<i64>().ok()? * 10,
+            _ => fraction.parse().ok()?,
+        };
+        let cents = whole.parse::<i64>().ok()?.checked_mul(100)?.checked_add(fraction_cents)?;
+        Some(Money(if negative { -cents } else { cents }))
+    }
+
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        self.0.checked_add(other.0).map(Money)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Option<Money> {
+        self.0.checked_sub(other.0).map(Money)
+    }
+
+    /// Total of `amounts`, or `None` if a running total leaves the range.
+    pub fn checked_sum<I: IntoIterator<Item = Money>>(amounts: I) -> Option<Money> {
+        amounts.into_iter().try_fold(Money::ZERO, Money::checked_add)
+    }
+
+    /// `self * factor`, rounded to the nearest cent.
+    pub fn scale(self, factor: f64) -> Money {
+        Money::from_f64(self.to_f64() * factor)
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// `self`, or zero if it is negative.
+    pub fn non_negative(self) -> Money {
+        self.max(Money::ZERO)
+    }
+}
+
+/// An amount, or a total of amounts, beyond what [`Money`] can hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("amount out of range")]
+pub struct MoneyOverflow;
+
+/// Always two decimal places; width and alignment are honoured, precision is ignored.
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let abs = self.0.unsigned_abs();
+        f.pad_integral(self.0 >= 0, "", &format!("{}.{:02}", abs / 100, abs % 100))
+    }
+}
+
+/// Serialized as a decimal string (`"1250.00"`) so no JSON client reads it back as a float.
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Accepts the decimal string form, and plain JSON numbers from older exports.
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MoneyVisitor;
+
+        impl de::Visitor<'_> for MoneyVisitor {
+            type Value = Money;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a decimal amount such as \"12.50\"")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Money, E> {
+                Money::parse(v).ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Money, E> {
+                Ok(Money::from_f64(v))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Money, E> {
+                v.checked_mul(100).map(Money).ok_or_else(|| E::custom("amount out of range"))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Money, E> {
+                i64::try_from(v).ok().and_then(|v| v.checked_mul(100)).map(Money).ok_or_else(|| E::custom("amount out of range"))
+            }
+        }
+
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+/// Stored as an INTEGER count of cents.
+impl ToSql for Money {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.0))
+    }
+}
+
+/// For storage code that meets an amount out of range while working out what to write.
+impl From<MoneyOverflow> for rusqlite::Error {
+    fn from(e: MoneyOverflow) -> Self {
+        rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+    }
+}
+
+impl FromSql for Money {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        i64::column_result(value).map(Money)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_display_and_json_round_trip() {
+        assert_eq!(Money::parse("1250"), Some(Money::from_cents(125_000)));
+        assert_eq!(Money::parse("0.5"), Some(Money::from_cents(50)));
+        assert_eq!(Money::parse("-3.07"), Some(Money::from_cents(-307)));
+        assert_eq!(Money::parse("1.005"), None);
+        assert_eq!(Money::parse("abc"), None);
+        assert_eq!(Money::from_cents(-307).to_string(), "-3.07");
+        assert_eq!(format!("{:>8}", Money::from_cents(1_050)), "   10.50");
+
+        let json = serde_json::to_string(&Money::from_cents(125_050)).unwrap();
+        assert_eq!(json, "\"1250.50\"");
+        assert_eq!(serde_json::from_str::<Money>(&json).unwrap(), Money::from_cents(125_050));
+        assert_eq!(serde_json::from_str::<Money>("99.99").unwrap(), Money::from_cents(9_999));
+    }
+
+    #[test]
+    fn test_cent_sums_do_not_drift() {
+        let tenth = Money::from_f64(0.1);
+        assert_eq!(Money::checked_sum(std::iter::repeat_n(tenth, 1_000)), Some(Money::from_cents(10_000)));
+        assert_eq!(Money::from_cents(i64::MAX).checked_add(Money::from_cents(1)), None);
+        assert_eq!(Money::checked_sum([Money::from_cents(i64::MAX), Money::from_cents(1), Money::from_cents(-1)]), None);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::error::AppResult;
+use crate::models::{Loan, User};
+use crate::money::{Money, MoneyOverflow};
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// How many days ahead of an installment's due date the borrower gets a reminder.
+pub const REMINDER_LEAD_DAYS: i64 = 3;
+/// A loan is not reminded again until this long after its last logged reminder.
+pub const REMINDER_COOLDOWN_HOURS: i64 = 24;
+
+/// Where a borrower's reminders go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderChannel {
+    Email,
+    InApp,
+}
+
+impl ReminderChannel {
+    /// Email when the borrower has an address on file, otherwise the in-app inbox.
+    pub fn preferred_for(user: &User) -> ReminderChannel {
+        match user.email.as_deref().map(str::trim) {
+            Some(email) if !email.is_empty() => ReminderChannel::Email,
+            _ => ReminderChannel::InApp,
+        }
+    }
+}
+
+/// A reminder ready for delivery. The structured fields carry everything `message` says, so a
+/// delivery backend (email, SMS, ...) can lay out its own template instead of reusing the text.
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedReminder {
+    pub loan_id: Uuid,
+    pub borrower_id: String,
+    pub channel: ReminderChannel,
+    /// Email address for `Email`; `None` for in-app delivery
+    pub recipient: Option<String>,
+    pub amount_due: Money,
+    pub due_date: DateTime<Utc>,
+    /// 0 when the installment is not late yet
+    pub days_overdue: i64,
+    /// Earliest unpaid installment due on or after the reminder date, if any remain
+    pub next_due_date: Option<DateTime<Utc>>,
+    pub message: String,
+}
+
+/// Renders the reminder for the loan's next unpaid installment; `None` if nothing is left to pay.
+pub fn render_reminder(loan: &Loan, borrower: &User, as_of: DateTime<Utc>) -> Option<RenderedReminder> {
+    let (installment, amount_due) = loan.next_unpaid_installment()?;
+    let days_overdue = (as_of - installment.due_date).num_days().max(0);
+    let short_id: String = loan.id.to_string().chars().take(8).collect();
+    let due = installment.due_date.format("%Y-%m-%d");
+    let next_due_date = loan.repayment_schedule[loan.installments_covered()..]
+        .iter()
+        .map(|p| p.due_date)
+        .find(|due_date| *due_date >= as_of);
+
+    let message = if installment.due_date < as_of {
+        let next = match next_due_date {
+            Some(date) => format!(" Your next installment is due on {}.", date.format("%Y-%m-%d")),
+            None => String::new(),
+        };
+        format!(
+            "Hi {}, your installment of {} for loan {} was due on {} and is now {} day{} overdue. \
+             Please pay as soon as possible to avoid further recovery action.{}",
+            borrower.name,
+            amount_due,
+            short_id,
+            due,
+            days_overdue,
+            if days_overdue == 1 { "" } else { "s" },
+            next
+        )
+    } else {
+        format!(
+            "Hi {}, this is a reminder that your installment of {} for loan {} is due on {}.",
+            borrower.name, amount_due, short_id, due
+        )
+    };
+
+    let channel = ReminderChannel::preferred_for(borrower);
+    Some(RenderedReminder {
+        loan_id: loan.id,
+        borrower_id: borrower.id.clone(),
+        channel,
+        recipient: match channel {
+            ReminderChannel::Email => borrower.email.clone(),
+            ReminderChannel::InApp => None,
+        },
+        amount_due,
+        due_date: installment.due_date,
+        days_overdue,
+        next_due_date,
+        message,
+    })
+}
+
+/// Message telling `recipient` that the loan has defaulted. The borrower is asked to contact
+/// their lender; anyone else is addressed as the loan's guarantor and told they may be liable.
+pub fn render_default_notice(loan: &Loan, recipient: &User) -> Result<String, MoneyOverflow> {
+    let short_id: String = loan.id.to_string().chars().take(8).collect();
+    let balance = loan.remaining_balance().ok_or(MoneyOverflow)?;
+    Ok(if recipient.id == loan.borrower_id {
+        format!(
+            "Hi {}, loan {} is now in default with {} {} outstanding. Please contact your lender to arrange repayment.",
+            recipient.name, short_id, balance, loan.currency
+        )
+    } else {
+        format!(
+            "Hi {}, loan {} to borrower {}, which you guarantee, is now in default with {} {} outstanding. \
+             As guarantor you may be liable for this amount.",
+            recipient.name, short_id, loan.borrower_id, balance, loan.currency
+        )
+    })
+}
+
+/// Sends the default notice to the borrower and, when the loan has one, the guarantor.
+/// Returns the ids of the users notified, borrower first.
+pub async fn notify_default(
+    loan: &Loan,
+    borrower: &User,
+    guarantor: Option<&User>,
+    channel: &dyn NotificationChannel,
+) -> AppResult<Vec<String>> {
+    let mut notified = Vec::with_capacity(2);
+    for recipient in std::iter::once(borrower).chain(guarantor) {
+        channel.send(recipient, &render_default_notice(loan, recipient)?).await?;
+        notified.push(recipient.id.clone());
+    }
+    Ok(notified)
+}
+
+/// Delivery backend for rendered messages (email, SMS, ...). `send` returns a boxed future
+/// instead of being an `async fn` so the trait stays object-safe and callers can hold
+/// `&dyn NotificationChannel`.
+pub trait NotificationChannel: Send + Sync {
+    fn send<'a>(&'a self, to: &'a User, body: &'a str) -> BoxFuture<'a, AppResult<()>>;
+}
+
+/// Writes each message to the application log instead of delivering it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogChannel;
+
+impl NotificationChannel for LogChannel {
+    fn send<'a>(&'a self, to: &'a User, body: &'a str) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            log::info!("Notification to {} ({}): {}", to.name, to.id, body);
+            Ok(())
+        })
+    }
+}
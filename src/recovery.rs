@@ -1,25 +1,169 @@
-use crate::models::{Loan, RiskScorable};
+use crate::error::AppResult;
+use crate::models::{Clamped, Loan, LoanStatus, RiskScorer, User};
+use crate::notify::{render_reminder, NotificationChannel, RenderedReminder};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RecoveryAction {
     SendReminder,
     RenegotiateTerms,
     EscalateToCollection,
+    /// Stop collecting and book the loss; see `LoanTracker::write_off`
+    WriteOff,
 }
 
-pub struct RecoveryEngine;
+impl RecoveryAction {
+    pub const ALL: [RecoveryAction; 4] = [
+        RecoveryAction::SendReminder,
+        RecoveryAction::RenegotiateTerms,
+        RecoveryAction::EscalateToCollection,
+        RecoveryAction::WriteOff,
+    ];
+}
+
+/// Risk above which a defaulted loan is a write-off candidate.
+pub const WRITE_OFF_RISK: f64 = 0.9;
+/// Missed installments a defaulted loan must have before a write-off is recommended.
+pub const WRITE_OFF_MISSED_PAYMENTS: usize = 6;
+
+/// What happened after a recovery action was taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecoveryOutcome {
+    PaidAfterReminder,
+    RenegotiatedSuccessfully,
+    RecoveredByCollection,
+    PromiseToPay,
+    NoResponse,
+    Refused,
+}
+
+impl RecoveryOutcome {
+    /// Outcomes where money was recovered or the loan was put back on track.
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self,
+            RecoveryOutcome::PaidAfterReminder
+                | RecoveryOutcome::RenegotiatedSuccessfully
+                | RecoveryOutcome::RecoveredByCollection
+        )
+    }
+}
+
+/// A recovery action taken on a loan; `outcome` stays `None` until it is known.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryActionRecord {
+    pub id: uuid::Uuid,
+    pub loan_id: uuid::Uuid,
+    pub action: RecoveryAction,
+    pub outcome: Option<RecoveryOutcome>,
+    pub taken_at: DateTime<Utc>,
+}
+
+/// Success statistics for one action type. Actions still awaiting an outcome are not part of the rate.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionEffectiveness {
+    pub action: RecoveryAction,
+    pub total: usize,
+    pub with_outcome: usize,
+    pub successes: usize,
+    pub success_rate: f64,
+}
+
+pub fn action_effectiveness(records: &[RecoveryActionRecord]) -> Vec<ActionEffectiveness> {
+    RecoveryAction::ALL
+        .iter()
+        .map(|&action| {
+            let taken: Vec<&RecoveryActionRecord> = records.iter().filter(|r| r.action == action).collect();
+            let with_outcome = taken.iter().filter(|r| r.outcome.is_some()).count();
+            let successes = taken
+                .iter()
+                .filter(|r| r.outcome.map(|o| o.is_success()).unwrap_or(false))
+                .count();
+            let success_rate = if with_outcome == 0 {
+                0.0
+            } else {
+                successes as f64 / with_outcome as f64
+            };
+            ActionEffectiveness {
+                action,
+                total: taken.len(),
+                with_outcome,
+                successes,
+                success_rate,
+            }
+        })
+        .collect()
+}
+
+/// Cutoffs `recommend_action` uses to pick an action. A loan escalates when its risk exceeds
+/// `escalate_risk` or it has missed more than `escalate_missed` installments; otherwise it is
+/// renegotiated past `renegotiate_risk` / `renegotiate_missed`, and reminded below both.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryThresholds {
+    pub escalate_risk: f64,
+    pub renegotiate_risk: f64,
+    pub escalate_missed: usize,
+    pub renegotiate_missed: usize,
+}
+
+impl Default for RecoveryThresholds {
+    fn default() -> Self {
+        RecoveryThresholds {
+            escalate_risk: 0.7,
+            renegotiate_risk: 0.4,
+            escalate_missed: 2,
+            renegotiate_missed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecoveryEngine {
+    pub thresholds: RecoveryThresholds,
+}
 
 impl RecoveryEngine {
-    pub fn predict_default(&self, loan: &Loan) -> f64 {
-        loan.calculate_risk_score() // From trait
+    pub fn new(thresholds: RecoveryThresholds) -> Self {
+        RecoveryEngine { thresholds }
     }
 
-    pub fn recommend_action(&self, risk_score: f64, repayment_history: usize) -> RecoveryAction { // History: e.g., missed payments
+    /// Default risk in [0, 1] under `model` as of `as_of`, clamped if the model breaks its
+    /// range contract.
+    pub fn predict_default(&self, loan: &Loan, model: &dyn RiskScorer, as_of: DateTime<Utc>) -> f64 {
+        Clamped(model).score(loan, as_of)
+    }
+
+    pub fn recommend_action(&self, risk_score: f64, repayment_history: usize, status: &LoanStatus) -> RecoveryAction { // History: e.g., missed payments
+        if *status == LoanStatus::Defaulted && risk_score > WRITE_OFF_RISK && repayment_history >= WRITE_OFF_MISSED_PAYMENTS {
+            return RecoveryAction::WriteOff;
+        }
+        let t = &self.thresholds;
         match (risk_score, repayment_history) {
-            (score, hist) if score > 0.7 || hist > 2 => RecoveryAction::EscalateToCollection,
-            (score, hist) if score > 0.4 || hist > 0 => RecoveryAction::RenegotiateTerms,
+            (score, hist) if score > t.escalate_risk || hist > t.escalate_missed => RecoveryAction::EscalateToCollection,
+            (score, hist) if score > t.renegotiate_risk || hist > t.renegotiate_missed => RecoveryAction::RenegotiateTerms,
             _ => RecoveryAction::SendReminder,
         }
     }
+
+    /// Carries out `action` where that needs no person: a `SendReminder` renders the borrower's
+    /// reminder and sends it through `channel`. Returns the message sent, or `None` when the
+    /// action is left to staff or the loan has nothing left to pay.
+    pub async fn dispatch(
+        &self,
+        action: RecoveryAction,
+        loan: &Loan,
+        borrower: &User,
+        channel: &dyn NotificationChannel,
+        as_of: DateTime<Utc>,
+    ) -> AppResult<Option<RenderedReminder>> {
+        if action != RecoveryAction::SendReminder {
+            return Ok(None);
+        }
+        let Some(reminder) = render_reminder(loan, borrower, as_of) else {
+            return Ok(None);
+        };
+        channel.send(borrower, &reminder.message).await?;
+        Ok(Some(reminder))
+    }
 }
\ No newline at end of file
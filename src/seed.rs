@@ -0,0 +1,118 @@
+//! Randomized sample data for exercising listings, pagination and portfolio analytics.
+//!
+//! Names, principals, rates, terms and repayment progress all come from one seeded RNG, so the
+//! same seed always produces the same book. User ids and loan UUIDs are still freshly
+//! generated, as they are for any other user or loan.
+
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::loan::{LoanTracker, SYSTEM_ACTOR};
+use crate::models::{Loan, LoanStatus, ScheduledPayment, UserRole};
+use crate::money::MoneyOverflow;
+use crate::user::UserManager;
+use chrono::{DateTime, Months, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+const FIRST_NAMES: &[&str] = &[
+    "Amina", "Brian", "Caro", "David", "Esther", "Felix", "Grace", "Hassan", "Irene", "James",
+    "Kamau", "Lydia", "Moses", "Njeri", "Otieno", "Purity", "Quincy", "Rose", "Samuel", "Wanjiru",
+];
+const LAST_NAMES: &[&str] = &[
+    "Achieng", "Barasa", "Chege", "Kariuki", "Mutua", "Njoroge", "Odhiambo", "Wafula", "Wekesa", "Zawadi",
+];
+const ORGANIZATIONS: &[&str] = &["Harambee Credit", "Jamii Finance", "Pesa Bora", "Umoja Capital", "Savanna Lending"];
+const TERMS_MONTHS: &[i64] = &[3, 6, 9, 12, 18, 24, 36];
+/// Share of loans left one to three installments behind.
+const BEHIND_PROBABILITY: f64 = 0.25;
+
+/// What `seed` generated.
+#[derive(Debug, Clone, Default)]
+pub struct SeedSummary {
+    pub lenders: Vec<String>,
+    pub borrowers: Vec<String>,
+    pub loans: Vec<Uuid>,
+    /// Generated loans per status, keyed like the `status` filter
+    pub loans_by_status: BTreeMap<String, usize>,
+}
+
+/// Registers `users` users (about one lender per five, at least one of each role when there
+/// are loans to make) and `loans` loans between them, disbursed up to a full term ago and paid
+/// on schedule except for roughly a quarter that have fallen behind.
+pub fn seed(db: &Db, users: usize, loans: usize, rng_seed: u64) -> AppResult<SeedSummary> {
+    if loans > 0 && users < 2 {
+        return Err(AppError::InvalidInput("seeding loans needs at least 2 users (a lender and a borrower)".to_string()));
+    }
+    let mut rng = StdRng::seed_from_u64(rng_seed);
+    let user_manager = UserManager::new(db);
+    let tracker = LoanTracker::new(db);
+    let mut summary = SeedSummary::default();
+
+    let lender_count = if users < 2 { users } else { (users / 5).max(1) };
+    // Borrowers with the lender they registered under, who makes all of their loans
+    let mut clients = Vec::new();
+    for i in 0..users {
+        let name = format!("{} {}", pick(&mut rng, FIRST_NAMES), pick(&mut rng, LAST_NAMES));
+        if i < lender_count {
+            let organization = pick(&mut rng, ORGANIZATIONS).to_string();
+            summary.lenders.push(user_manager.register_user(name, None, None, UserRole::Lender, None, Some(organization))?);
+        } else {
+            let lender_id = summary.lenders[rng.gen_range(0..summary.lenders.len())].clone();
+            let borrower_id = user_manager.register_user(name, None, None, UserRole::Borrower, Some(lender_id.clone()), None)?;
+            summary.borrowers.push(borrower_id.clone());
+            clients.push((borrower_id, lender_id));
+        }
+    }
+
+    let now = Utc::now();
+    for _ in 0..loans {
+        let (borrower_id, lender_id) = pick(&mut rng, &clients).clone();
+        let principal = f64::from(rng.gen_range(10..=1000u32)) * 50.0;
+        let interest_rate = f64::from(rng.gen_range(30..=250u32)) / 10.0;
+        let months = *pick(&mut rng, TERMS_MONTHS);
+        let loan_id = tracker.create_loan(borrower_id, lender_id, principal, interest_rate, months)?;
+
+        // Backdate the whole loan so part of its schedule has already fallen due
+        let elapsed = rng.gen_range(0..=months) as u32;
+        let mut loan = load_loan(db, loan_id)?;
+        let back = |date: DateTime<Utc>| date.checked_sub_months(Months::new(elapsed)).unwrap_or(date);
+        loan.disbursement_date = back(loan.disbursement_date);
+        loan.start_date = back(loan.start_date);
+        for p in loan.repayment_schedule.iter_mut() {
+            p.due_date = back(p.due_date);
+        }
+        db.save_loan(&loan)?;
+
+        let due = loan.repayment_schedule.iter().filter(|p| p.due_date <= now).count();
+        let missed = if due > 0 && rng.gen_bool(BEHIND_PROBABILITY) { rng.gen_range(1..=due.min(3)) } else { 0 };
+        let paid = ScheduledPayment::total(&loan.repayment_schedule[..due - missed]).ok_or(MoneyOverflow)?;
+        if paid.is_positive() {
+            tracker.record_payment(loan_id, paid.to_f64())?;
+        }
+
+        let mut loan = load_loan(db, loan_id)?;
+        let status = loan.computed_status(now);
+        if status != loan.status {
+            let previous = std::mem::replace(&mut loan.status, status);
+            db.save_loan(&loan)?;
+            db.record_status_change(loan.id, Some(&previous), &loan.status, SYSTEM_ACTOR)?;
+        }
+        *summary.loans_by_status.entry(status_key(&loan.status)).or_default() += 1;
+        summary.loans.push(loan_id);
+    }
+    Ok(summary)
+}
+
+fn load_loan(db: &Db, loan_id: Uuid) -> AppResult<Loan> {
+    db.load_loan(loan_id)?.ok_or_else(|| AppError::NotFound(format!("seeded loan {} disappeared", loan_id)))
+}
+
+fn pick<'a, T>(rng: &mut StdRng, items: &'a [T]) -> &'a T {
+    &items[rng.gen_range(0..items.len())]
+}
+
+fn status_key(status: &LoanStatus) -> String {
+    format!("{:?}", status).to_lowercase()
+}
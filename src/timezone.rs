@@ -0,0 +1,161 @@
+//! Display timezones. Everything is stored and computed in UTC; dates are converted to a
+//! person's zone only when they are shown (CLI tables, the demo, or a schedule requested with
+//! `?tz=`).
+//!
+//! A zone is either a fixed UTC offset such as `+03:00` or an IANA name like `Africa/Nairobi`
+//! or `America/New_York`. Named zones come from the tz database bundled by `chrono-tz`, so they
+//! follow daylight saving time and historical offset changes without needing `tzdata` on the host.
+
+use chrono::{DateTime, FixedOffset, Offset, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+
+/// UTC itself, the default display zone.
+pub fn utc() -> FixedOffset {
+    FixedOffset::east_opt(0).expect("zero offset is valid")
+}
+
+/// Parses `UTC`, `Z`, or an offset like `+03:00`, `-0530`, `+3` or `UTC+03:00`. The sign may
+/// be left out for eastern offsets, which also covers a `+` decoded to a space in a query string.
+pub fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim();
+    let rest = value
+        .strip_prefix("UTC")
+        .or_else(|| value.strip_prefix("utc"))
+        .or_else(|| value.strip_prefix("GMT"))
+        .unwrap_or(value)
+        .trim();
+    if rest.is_empty() || rest.eq_ignore_ascii_case("z") {
+        return Some(utc());
+    }
+    // Everything below slices by byte position
+    if !rest.is_ascii() {
+        return None;
+    }
+
+    let (sign, digits) = match rest.as_bytes()[0] {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => (1, rest),
+    };
+    let (hours, minutes) = match digits.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+    if hours.is_empty() || hours.len() > 2 || minutes.len() > 2 {
+        return None;
+    }
+    let (hours, minutes): (i32, i32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// A display zone: a fixed offset, or a named zone whose offset depends on the date.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl Zone {
+    /// A fixed offset (see `parse_offset`) or an IANA zone name such as `Europe/London`.
+    pub fn parse(value: &str) -> Option<Zone> {
+        if let Some(offset) = parse_offset(value) {
+            return Some(Zone::Fixed(offset));
+        }
+        value.trim().parse::<Tz>().ok().map(Zone::Named)
+    }
+
+    /// The UTC offset in force in this zone at `at`.
+    pub fn offset_at(&self, at: DateTime<Utc>) -> FixedOffset {
+        match self {
+            Zone::Fixed(offset) => *offset,
+            Zone::Named(zone) => at.with_timezone(zone).offset().fix(),
+        }
+    }
+
+    /// `at` as it reads on a clock in this zone.
+    pub fn localize(&self, at: DateTime<Utc>) -> DateTime<FixedOffset> {
+        at.with_timezone(&self.offset_at(at))
+    }
+}
+
+impl From<FixedOffset> for Zone {
+    fn from(offset: FixedOffset) -> Self {
+        Zone::Fixed(offset)
+    }
+}
+
+/// Fixed zones print as their offset (`+03:00`), named ones as their name.
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Zone::Fixed(offset) => write!(f, "{}", offset),
+            Zone::Named(zone) => f.write_str(zone.name()),
+        }
+    }
+}
+
+/// `at` as a date and time in `zone`, with the offset spelled out: `2024-03-01 15:30 +03:00`.
+pub fn format_local(at: DateTime<Utc>, zone: &Zone) -> String {
+    zone.localize(at).format("%Y-%m-%d %H:%M %:z").to_string()
+}
+
+/// Just the calendar date of `at` in `zone`, for table columns.
+pub fn format_local_date(at: DateTime<Utc>, zone: &Zone) -> String {
+    zone.localize(at).format("%Y-%m-%d").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_offset_forms() {
+        assert_eq!(parse_offset("UTC"), Some(utc()));
+        assert_eq!(parse_offset("Z"), Some(utc()));
+        assert_eq!(parse_offset("+03:00"), FixedOffset::east_opt(3 * 3600));
+        assert_eq!(parse_offset("UTC+3"), FixedOffset::east_opt(3 * 3600));
+        assert_eq!(parse_offset(" 03:00"), FixedOffset::east_opt(3 * 3600));
+        assert_eq!(parse_offset("-0530"), FixedOffset::west_opt(5 * 3600 + 1800));
+        assert_eq!(parse_offset("+15:00"), None);
+        assert_eq!(parse_offset("+03:75"), None);
+        assert_eq!(parse_offset("Africa/Nairobi"), None);
+        assert_eq!(parse_offset("1é1"), None);
+        assert_eq!(parse_offset("+é12"), None);
+    }
+
+    #[test]
+    fn test_utc_instant_rendered_in_fixed_offset() {
+        let at = Utc.with_ymd_and_hms(2024, 2, 29, 22, 15, 0).unwrap();
+        let nairobi = Zone::parse("+03:00").unwrap();
+        // Crosses midnight into the next day locally
+        assert_eq!(format_local(at, &nairobi), "2024-03-01 01:15 +03:00");
+        assert_eq!(format_local_date(at, &nairobi), "2024-03-01");
+        assert_eq!(format_local(at, &Zone::parse("-05:00").unwrap()), "2024-02-29 17:15 -05:00");
+        assert_eq!(format_local(at, &utc().into()), "2024-02-29 22:15 +00:00");
+        // Converting for display never changes the instant
+        assert_eq!(nairobi.localize(at).with_timezone(&Utc), at);
+    }
+
+    #[test]
+    fn test_named_zone_from_tz_database() {
+        let zone = Zone::parse("America/New_York").unwrap();
+        assert_eq!(zone.to_string(), "America/New_York");
+        let winter = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2024, 7, 15, 12, 0, 0).unwrap();
+        assert_eq!(format_local(winter, &zone), "2024-01-15 07:00 -05:00");
+        assert_eq!(format_local(summer, &zone), "2024-07-15 08:00 -04:00");
+        // Later years follow the zone's current daylight saving rule
+        assert_eq!(zone.offset_at(Utc.with_ymd_and_hms(2030, 7, 1, 0, 0, 0).unwrap()).local_minus_utc(), -4 * 3600);
+        assert_eq!(Zone::parse("Africa/Nairobi").unwrap().offset_at(summer).local_minus_utc(), 3 * 3600);
+
+        assert!(Zone::parse("Mars/Olympus").is_none());
+        assert!(Zone::parse("../../etc/passwd").is_none());
+        assert!(Zone::parse("/etc/localtime").is_none());
+    }
+}
@@ -1,7 +1,13 @@
 use crate::models::{User, UserRole};
-use crate::db::Db;
+use crate::db::{Db, SortOrder};
+use crate::error::{AppError, AppResult};
+use crate::loan::SYSTEM_ACTOR;
+use crate::money::Money;
 use rusqlite::Result;
 use rand::prelude::*;
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Utc;
 
 const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
 const ID_LEN: usize = 4;
@@ -23,35 +29,223 @@ fn generate_id(db: &Db) -> Result<String> {
     Err(rusqlite::Error::InvalidQuery)
 }
 
+/// Minimal shape check: something before a single `@`, and a dot inside the domain.
+pub fn validate_email(email: &str) -> std::result::Result<(), String> {
+    let invalid = || Err(format!("Invalid email address '{}'", email));
+    let Some((local, domain)) = email.split_once('@') else {
+        return invalid();
+    };
+    let dotted_domain = domain.split('.').count() >= 2 && domain.split('.').all(|part| !part.is_empty());
+    if local.is_empty() || domain.contains('@') || email.chars().any(char::is_whitespace) || !dotted_domain {
+        return invalid();
+    }
+    Ok(())
+}
+
+/// Digits with an optional leading `+`; spaces, dashes and parentheses are allowed as separators.
+pub fn validate_phone(phone: &str) -> std::result::Result<(), String> {
+    let body = phone.strip_prefix('+').unwrap_or(phone);
+    let digits = body.chars().filter(char::is_ascii_digit).count();
+    let separators_only = body.chars().all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '(' | ')'));
+    if !separators_only || !(7..=15).contains(&digits) {
+        return Err(format!("Invalid phone number '{}'", phone));
+    }
+    Ok(())
+}
+
+/// Shortest password accepted for session login.
+pub const MIN_PASSWORD_LEN: usize = 6;
+
+pub fn validate_password(password: &str) -> std::result::Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(format!("Password must be at least {} characters long", MIN_PASSWORD_LEN));
+    }
+    Ok(())
+}
+
+/// Salted argon2 hash in PHC string format.
+fn hash_password(password: &str) -> AppResult<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::InvalidInput(format!("Could not hash password: {}", e)))
+}
+
+/// False for a wrong password as well as for a stored hash that cannot be parsed.
+fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+        .unwrap_or(false)
+}
+
+/// Trims an optional contact field; blank counts as absent.
+fn normalize_contact(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
 pub struct UserManager<'a> {
     db: &'a Db,
+    actor: &'a str,
 }
 
 impl<'a> UserManager<'a> {
     pub fn new(db: &'a Db) -> Self {
-        UserManager { db }
+        UserManager { db, actor: SYSTEM_ACTOR }
+    }
+
+    /// Records role changes made through this manager under `actor` (a user id) instead of `system`.
+    pub fn with_actor(mut self, actor: &'a str) -> Self {
+        self.actor = actor;
+        self
     }
 
-    pub fn register_user(&self, name: String, email: Option<String>, role: UserRole, lender_id: Option<String>, organization: Option<String>) -> Result<String> {
-        let id = generate_id(self.db)?;
-        let user = User {
-            id: id.clone(),
+    /// Validates the optional contact details and stores a new user.
+    pub fn register_user(
+        &self,
+        name: String,
+        email: Option<String>,
+        phone: Option<String>,
+        role: UserRole,
+        lender_id: Option<String>,
+        organization: Option<String>,
+    ) -> AppResult<String> {
+        let user = self.new_user(name, email, phone, role, lender_id, organization)?;
+        self.db.save_user(&user)?;
+        Ok(user.id)
+    }
+
+    /// Like `register_user`, but the user can log in straight away: the password is validated
+    /// and hashed first, then stored in the same write as the user.
+    #[allow(clippy::too_many_arguments)]
+    pub fn register_user_with_password(
+        &self,
+        name: String,
+        email: Option<String>,
+        phone: Option<String>,
+        role: UserRole,
+        lender_id: Option<String>,
+        organization: Option<String>,
+        password: &str,
+    ) -> AppResult<String> {
+        validate_password(password).map_err(AppError::InvalidInput)?;
+        let hash = hash_password(password)?;
+        let user = self.new_user(name, email, phone, role, lender_id, organization)?;
+        self.db.save_user_with_password_hash(&user, &hash)?;
+        Ok(user.id)
+    }
+
+    /// A not yet stored user with a fresh id and normalized, validated contact details.
+    fn new_user(
+        &self,
+        name: String,
+        email: Option<String>,
+        phone: Option<String>,
+        role: UserRole,
+        lender_id: Option<String>,
+        organization: Option<String>,
+    ) -> AppResult<User> {
+        let email = normalize_contact(email);
+        let phone = normalize_contact(phone);
+        if let Some(ref e) = email {
+            validate_email(e).map_err(AppError::InvalidInput)?;
+        }
+        if let Some(ref p) = phone {
+            validate_phone(p).map_err(AppError::InvalidInput)?;
+        }
+
+        let now = Utc::now();
+        Ok(User {
+            id: generate_id(self.db)?,
             name,
             role,
             email,
+            phone,
             lender_id,
             organization,
-        };
-        self.db.save_user(&user)?;
-        Ok(id)
+            wallet_balance: Money::ZERO,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Sets (or replaces) the password a user logs in with.
+    pub fn set_password(&self, id: &str, password: &str) -> AppResult<()> {
+        validate_password(password).map_err(AppError::InvalidInput)?;
+        let hash = hash_password(password)?;
+        if !self.db.set_password_hash(id, &hash)? {
+            return Err(AppError::NotFound("User not found".to_string()));
+        }
+        Ok(())
+    }
+
+    /// The user with this name whose password matches. Names are not unique, so the password is
+    /// checked against every user sharing the name, earliest registered first. Unknown names,
+    /// wrong passwords and users who never set a password all fail the same way, with `AuthRequired`.
+    pub fn authenticate(&self, name: &str, password: &str) -> AppResult<User> {
+        for user in self.db.load_users_by_name(name)? {
+            if let Some(hash) = self.db.load_password_hash(&user.id)? {
+                if verify_password(&hash, password) {
+                    return Ok(user);
+                }
+            }
+        }
+        Err(AppError::AuthRequired)
+    }
+
+    /// Deletes a user who is no longer party to any open loan.
+    pub fn delete_user(&self, id: &str) -> AppResult<()> {
+        if self.db.load_user(id)?.is_none() {
+            return Err(AppError::NotFound("User not found".to_string()));
+        }
+        if self.db.count_open_loans_for_user(id)? > 0 {
+            return Err(AppError::InvalidInput("user has active loans".to_string()));
+        }
+        self.db.delete_user(id)?;
+        Ok(())
+    }
+
+    /// Moves a user to `role`, recording the change in the role audit log. A lender still
+    /// managing active loans cannot be made anything else; those loans would be left without one.
+    pub fn update_role(&self, id: &str, role: UserRole) -> AppResult<User> {
+        let mut user = self.db.load_user(id)?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+        if user.role == role {
+            return Ok(user);
+        }
+        if user.role == UserRole::Lender {
+            let active = self.db.count_active_loans_by_lender(id)?;
+            if active > 0 {
+                return Err(AppError::InvalidInput(format!(
+                    "lender still manages {} active loan(s); settle or reassign them before changing role", active
+                )));
+            }
+        }
+        self.db.update_user_role(id, &user.role, &role, self.actor)?;
+        user.role = role;
+        Ok(user)
     }
 
     pub fn get_user(&self, id: &str) -> Result<Option<User>> {
         self.db.load_user(id)
     }
 
+    /// Returns the new balance, or `None` if the user does not exist.
+    pub fn top_up_wallet(&self, user_id: &str, amount: Money) -> Result<Option<Money>> {
+        self.db.credit_wallet(user_id, amount)
+    }
+
     pub fn get_all_users(&self) -> Result<Vec<User>> {
         self.db.load_all_users()
     }
+
+    pub fn get_all_users_sorted(&self, order: SortOrder) -> Result<Vec<User>> {
+        self.db.load_all_users_sorted(order)
+    }
+
+    /// One page of users plus the total number of users.
+    pub fn get_users_paged(&self, order: SortOrder, limit: usize, offset: usize) -> Result<(Vec<User>, usize)> {
+        Ok((self.db.load_users_paged(order, limit, offset)?, self.db.count_users()?))
+    }
 }
 
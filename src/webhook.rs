@@ -0,0 +1,99 @@
+//! Outgoing webhook for loan status changes. Events are queued without blocking the request
+//! that caused them and POSTed from a background task; a failed delivery is retried with
+//! exponential backoff and, once attempts run out, logged and dropped.
+
+use crate::models::LoanStatus;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Deliveries tried per event before it is given up on.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+/// Wait before the first retry; doubled after each further failure.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// A receiver slower than this counts as a failed attempt.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// JSON body POSTed to the webhook.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusChangeEvent {
+    pub loan_id: Uuid,
+    pub old_status: LoanStatus,
+    pub new_status: LoanStatus,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Handle for queueing events; cheap to clone. The default handle has no webhook and
+/// ignores everything it is given.
+#[derive(Debug, Clone, Default)]
+pub struct WebhookDispatcher {
+    sender: Option<mpsc::UnboundedSender<StatusChangeEvent>>,
+}
+
+impl WebhookDispatcher {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Starts delivering to `url`, or returns a disabled dispatcher when there is none.
+    /// Must be called from within a Tokio runtime.
+    pub fn spawn(url: Option<String>) -> Self {
+        Self::spawn_with_backoff(url, DEFAULT_MAX_ATTEMPTS, DEFAULT_INITIAL_BACKOFF)
+    }
+
+    pub fn spawn_with_backoff(url: Option<String>, max_attempts: u32, initial_backoff: Duration) -> Self {
+        let Some(url) = url.map(|u| u.trim().to_string()).filter(|u| !u.is_empty()) else {
+            return Self::disabled();
+        };
+        let url: Arc<str> = url.into();
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                // Each event retries on its own so one stuck delivery doesn't hold up the rest
+                tokio::spawn(deliver(client.clone(), url.clone(), event, max_attempts, initial_backoff));
+            }
+        });
+        WebhookDispatcher { sender: Some(sender) }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Queues `event` for delivery; a no-op when no webhook is configured.
+    pub fn dispatch(&self, event: StatusChangeEvent) {
+        if let Some(sender) = &self.sender {
+            if sender.send(event).is_err() {
+                log::warn!("Webhook dispatcher has stopped; dropping status change event");
+            }
+        }
+    }
+}
+
+async fn deliver(client: reqwest::Client, url: Arc<str>, event: StatusChangeEvent, max_attempts: u32, initial_backoff: Duration) {
+    let mut backoff = initial_backoff;
+    for attempt in 1..=max_attempts.max(1) {
+        let error = match client.post(&*url).json(&event).send().await {
+            Ok(resp) if resp.status().is_success() => return,
+            Ok(resp) => format!("HTTP {}", resp.status()),
+            Err(e) => e.to_string(),
+        };
+        if attempt == max_attempts.max(1) {
+            log::error!(
+                "Webhook delivery for loan {} ({:?} -> {:?}) failed after {} attempts: {}",
+                event.loan_id, event.old_status, event.new_status, attempt, error
+            );
+            return;
+        }
+        log::warn!("Webhook delivery for loan {} failed (attempt {}): {}; retrying in {:?}", event.loan_id, attempt, error, backoff);
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+}
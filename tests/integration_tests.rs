@@ -1,15 +1,82 @@
 use actix_web::{test, App, http::StatusCode};
-use actix_web::web;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use actix_web::cookie::{Cookie, Key};
+use actix_web::dev::ServiceResponse;
+use actix_identity::{Identity, IdentityMiddleware};
+use actix_session::{SessionMiddleware, storage::CookieSessionStore};
 use lendwise_recovery::config::Config;
 use serde_json::json;
 use lendwise_recovery::api::*;
 use lendwise_recovery::db::Db;
+use lendwise_recovery::loan::{generate_amortization_schedule_from, LoanTracker, SettlementPolicy, MAX_AMOUNT};
+use lendwise_recovery::models::{ExtensionStatus, InterestRounding, LateFeePolicy, LoanStatus, Payment, PaymentFrequency, ScheduledPayment, UserRole};
+use chrono::{Duration, Utc};
+use lendwise_recovery::user::UserManager;
+use lendwise_recovery::webhook::WebhookDispatcher;
+use lendwise_recovery::middleware::{AssignRequestId, ConcurrencyLimit, RequestId};
+use lendwise_recovery::error::AppError;
+use lendwise_recovery::money::Money;
+use lendwise_recovery::models::User;
+use lendwise_recovery::notify::NotificationChannel;
+use lendwise_recovery::recovery::{RecoveryAction, RecoveryEngine};
+use std::sync::Arc;
+
+/// Fresh in-memory database per test so assertions on counts are not affected by other tests.
+fn fresh_db() -> Db {
+    Db::new_in_memory().expect("Failed to create test database")
+}
+
+/// Reads a money field, which the API serializes as a decimal string.
+fn amount(value: &serde_json::Value) -> f64 {
+    value.as_str().and_then(|s| s.parse().ok()).unwrap_or_else(|| panic!("expected a decimal string, got {}", value))
+}
+
+fn session_middleware() -> SessionMiddleware<CookieSessionStore> {
+    SessionMiddleware::new(CookieSessionStore::default(), Key::from(&[7; 64]))
+}
+
+/// Test-only route that logs the caller in as the user id in the path.
+async fn test_login(req: HttpRequest, path: web::Path<String>) -> HttpResponse {
+    Identity::login(&req.extensions(), path.into_inner()).expect("Failed to log in");
+    HttpResponse::Ok().finish()
+}
+
+fn session_cookie<B>(resp: &ServiceResponse<B>) -> Cookie<'static> {
+    resp.response()
+        .cookies()
+        .next()
+        .expect("Login did not set a session cookie")
+        .into_owned()
+}
+
+/// Test app with session identity, the test login route and the given routes.
+/// Takes a `web::Data<Db>` so the test can keep inspecting the database.
+macro_rules! session_app {
+    ($db:expr $(, $path:expr => $route:expr)* $(,)?) => {
+        test::init_service(
+            App::new()
+                .app_data($db.clone())
+                .wrap(IdentityMiddleware::default())
+                .wrap(session_middleware())
+                .route("/test/login/{id}", web::post().to(test_login))
+                $(.route($path, $route))*
+        ).await
+    };
+}
+
+/// Logs in as the given user id and returns the session cookie.
+macro_rules! login {
+    ($app:expr, $user_id:expr) => {{
+        let req = test::TestRequest::post().uri(&format!("/test/login/{}", $user_id)).to_request();
+        session_cookie(&test::call_service(&$app, req).await)
+    }};
+}
 
 #[actix_web::test]
 async fn test_user_registration() {
     let unique_mail = format!("test.user.{}@example.com", uuid::Uuid::new_v4());
     // Initialize database for testing
-    let db = Db::new_with_path("test_loans.db").expect("Failed to create test database");
+    let db = fresh_db();
 
     // Create test app
     let app = test::init_service(
@@ -21,11 +88,12 @@ async fn test_user_registration() {
     // Test user registration
     let req = test::TestRequest::post()
         .uri("/users")
-        .set_json(&json!({
+        .set_json(json!({
             "name": "Test User",
             "role": "borrower",
             "email": unique_mail,
-            "lender_name": "Demo Lender"
+            "lender_name": "Demo Lender",
+            "password": "hunter22"
         }))
         .to_request();
 
@@ -40,7 +108,7 @@ async fn test_user_registration() {
 #[actix_web::test]
 async fn test_get_users() {
     // Initialize database for testing
-    let db = Db::new_with_path("test_loans.db").expect("Failed to create test database");
+    let db = fresh_db();
 
     // Create test app
     let app = test::init_service(
@@ -57,13 +125,14 @@ async fn test_get_users() {
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::OK);
 
-    let _body: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["items"].is_array());
 }
 
 #[actix_web::test]
 async fn test_invalid_user_registration() {
     // Initialize database for testing
-    let db = Db::new_with_path("test_loans.db").expect("Failed to create test database");
+    let db = fresh_db();
 
     // Create test app
     let app = test::init_service(
@@ -75,7 +144,7 @@ async fn test_invalid_user_registration() {
     // Test invalid role
     let req = test::TestRequest::post()
         .uri("/users")
-        .set_json(&json!({
+        .set_json(json!({
             "name": "Test User",
             "role": "invalid_role"
         }))
@@ -91,5 +160,3660 @@ async fn test_server_startup() {
     let config = Config::from_env().expect("Failed to load config");
     assert_eq!(config.server_host, "127.0.0.1");
     assert_eq!(config.server_port, 3000);
+    assert!(config.workers >= 1);
+    assert_eq!(config.keep_alive_secs, 5);
+    assert_eq!((config.default_interest_rate, config.default_duration_months), (None, None));
+    assert_eq!((config.backup_retention, config.backup_interval_secs), (lendwise_recovery::db::DEFAULT_BACKUP_RETENTION, 0));
     assert!(!config.session_secret.is_empty());
-}
\ No newline at end of file
+}
+#[actix_web::test]
+async fn test_lender_risk_distribution() {
+    let db = fresh_db();
+    let users = UserManager::new(&db);
+    let lender_id = users
+        .register_user("Band Lender".to_string(), None, None, UserRole::Lender, None, Some("Band Bank".to_string()))
+        .unwrap();
+    let empty_lender_id = users
+        .register_user("Empty Lender".to_string(), None, None, UserRole::Lender, None, Some("Empty Bank".to_string()))
+        .unwrap();
+    let borrower_id = users
+        .register_user("Band Borrower".to_string(), None, None, UserRole::Borrower, Some(lender_id.clone()), None)
+        .unwrap();
+
+    // Repaid -> A, Active -> B, Overdue -> D, Defaulted -> E
+    let tracker = LoanTracker::new(&db);
+    let mut loan_ids = Vec::new();
+    for status in [LoanStatus::Repaid, LoanStatus::Active, LoanStatus::Overdue, LoanStatus::Defaulted] {
+        let loan_id = tracker
+            .create_loan(borrower_id.clone(), lender_id.clone(), 1000.0, 10.0, 12)
+            .unwrap();
+        let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+        loan.status = status;
+        db.save_loan(&loan).unwrap();
+        loan_ids.push(loan_id);
+    }
+    // The repaid loan is paid off and the active one is two installments in
+    let repaid = tracker.get_loan(loan_ids[0]).unwrap().unwrap();
+    tracker.record_payment(repaid.id, repaid.total_due().unwrap().to_f64()).unwrap();
+    let active = tracker.get_loan(loan_ids[1]).unwrap().unwrap();
+    let two_installments = ScheduledPayment::total(&active.repayment_schedule[..2]).unwrap();
+    tracker.record_payment(active.id, two_installments.to_f64()).unwrap();
+    let active_outstanding = Money::checked_sum(active.repayment_schedule[2..].iter().map(|p| p.principal)).unwrap().to_f64();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/lenders/me/risk-distribution", web::get().to(lender_risk_distribution))
+    ).await;
+
+    let login = test::call_service(&app, test::TestRequest::post().uri(&format!("/test/login/{}", lender_id)).to_request()).await;
+    let req = test::TestRequest::get()
+        .uri("/lenders/me/risk-distribution")
+        .cookie(session_cookie(&login))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let bands = body["bands"].as_array().unwrap();
+    let counts: Vec<u64> = bands.iter().map(|b| b["count"].as_u64().unwrap()).collect();
+    assert_eq!(counts, vec![1, 1, 0, 1, 1]);
+    // Exposure is the principal still owed, not a status-based guess
+    let outstanding: Vec<f64> = bands.iter().map(|b| amount(&b["total_outstanding"])).collect();
+    assert!(outstanding[0].abs() < 0.01, "{:?}", outstanding);
+    assert!((outstanding[1] - active_outstanding).abs() < 0.01, "{:?}", outstanding);
+    assert!((outstanding[3] - 1000.0).abs() < 0.01 && (outstanding[4] - 1000.0).abs() < 0.01, "{:?}", outstanding);
+
+    // A lender with no loans still gets every band, all zero
+    let login = test::call_service(&app, test::TestRequest::post().uri(&format!("/test/login/{}", empty_lender_id)).to_request()).await;
+    let req = test::TestRequest::get()
+        .uri("/lenders/me/risk-distribution")
+        .cookie(session_cookie(&login))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let bands = body["bands"].as_array().unwrap();
+    assert_eq!(bands.len(), 5);
+    assert!(bands.iter().all(|b| b["count"] == 0 && b["total_outstanding"] == "0.00"));
+}
+
+#[actix_web::test]
+async fn test_global_stats_counts_by_status() {
+    let db = Db::new_in_memory().unwrap();
+    let tracker = LoanTracker::new(&db);
+    let repaid = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 300.0, 0.0, 3).unwrap();
+    tracker.record_payment(repaid, 300.0).unwrap();
+    tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 600.0, 0.0, 6).unwrap();
+
+    let counts = db.loan_status_counts().unwrap();
+    assert_eq!(counts.get("Repaid"), Some(&1));
+    assert_eq!(counts.values().sum::<i64>() as usize, db.count_loans().unwrap());
+
+    let expected_loans = db.count_loans().unwrap();
+    let expected_users = db.count_users().unwrap();
+    let app = session_app!(web::Data::new(db), "/stats" => web::get().to(get_stats));
+
+    let req = test::TestRequest::get().uri("/stats").cookie(login!(app, "DEMO")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get().uri("/stats").cookie(login!(app, "BANK")).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["total_loans"], expected_loans);
+    assert_eq!(body["total_users"], expected_users);
+    assert_eq!(body["loans_by_status"]["repaid"], 1);
+    assert_eq!(body["loans_by_status"]["writtenoff"], 0);
+    assert!(amount(&body["principal_outstanding"]) >= 600.0);
+}
+
+#[actix_web::test]
+async fn test_lender_portfolio_summary() {
+    let db = fresh_db();
+    let users = UserManager::new(&db);
+    let lender_id = users
+        .register_user("Summary Lender".to_string(), None, None, UserRole::Lender, None, Some("Sum Bank".to_string()))
+        .unwrap();
+    let empty_lender_id = users
+        .register_user("Idle Lender".to_string(), None, None, UserRole::Lender, None, Some("Idle Bank".to_string()))
+        .unwrap();
+
+    let tracker = LoanTracker::new(&db);
+    tracker.create_loan("DEMO".to_string(), lender_id.clone(), 1200.0, 0.0, 12).unwrap();
+    let behind = tracker.create_loan("DEMO".to_string(), lender_id.clone(), 600.0, 0.0, 6).unwrap();
+    // Two 100.00 installments of `behind` fall due in the past, one of them paid
+    let mut loan = tracker.get_loan(behind).unwrap().unwrap();
+    for (i, p) in loan.repayment_schedule.iter_mut().enumerate().take(2) {
+        p.due_date = Utc::now() - Duration::days(60 - 30 * i as i64);
+    }
+    loan.amount_paid = Money::from_cents(10_000);
+    loan.status = LoanStatus::Overdue;
+    db.save_loan(&loan).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/lenders/{id}/summary", web::get().to(lender_portfolio_summary))
+    ).await;
+
+    let cookie = login!(app, lender_id);
+    let req = test::TestRequest::get().uri(&format!("/lenders/{}/summary", lender_id)).cookie(cookie.clone()).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["loan_count"], 2);
+    assert_eq!(body["principal_outstanding"], "1700.00");
+    assert_eq!(body["overdue_amount"], "100.00");
+    assert_eq!(body["loans_by_status"]["active"], 1);
+    assert_eq!(body["loans_by_status"]["overdue"], 1);
+    assert_eq!(body["loans_by_status"]["defaulted"], 0);
+    let risk = body["average_risk_score"].as_f64().unwrap();
+    assert!(risk > 0.0 && risk <= 1.0);
+    let weighted = body["weighted_risk_score"].as_f64().unwrap();
+    assert!(weighted > 0.0 && weighted <= 1.0);
+
+    // Another lender's portfolio is off limits
+    let req = test::TestRequest::get().uri(&format!("/lenders/{}/summary", empty_lender_id)).cookie(cookie).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let cookie = login!(app, empty_lender_id);
+    let req = test::TestRequest::get().uri(&format!("/lenders/{}/summary", empty_lender_id)).cookie(cookie).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["loan_count"], 0);
+    assert_eq!(body["principal_outstanding"], "0.00");
+    assert_eq!(body["average_risk_score"], 0.0);
+    assert_eq!(body["weighted_risk_score"], 0.0);
+    assert_eq!(body["overdue_amount"], "0.00");
+    assert_eq!(body["loans_by_status"]["active"], 0);
+}
+
+#[actix_web::test]
+async fn test_weighted_portfolio_risk_weights_by_outstanding_principal() {
+    let db = fresh_db();
+    let lender_id = UserManager::new(&db)
+        .register_user("Weighted Lender".to_string(), None, None, UserRole::Lender, None, None)
+        .unwrap();
+    let tracker = LoanTracker::new(&db);
+    assert_eq!(tracker.weighted_portfolio_risk(&lender_id).unwrap(), 0.0);
+
+    // A large loan in good standing and a small one with two missed installments
+    let large = tracker.create_loan("DEMO".to_string(), lender_id.clone(), 9000.0, 0.0, 9).unwrap();
+    let small = tracker.create_loan("DEMO".to_string(), lender_id.clone(), 1000.0, 0.0, 10).unwrap();
+    let mut loan = tracker.get_loan(small).unwrap().unwrap();
+    for (i, p) in loan.repayment_schedule.iter_mut().enumerate().take(2) {
+        p.due_date = Utc::now() - Duration::days(90 - 30 * i as i64);
+    }
+    loan.status = LoanStatus::Overdue;
+    db.save_loan(&loan).unwrap();
+
+    let now = Utc::now();
+    // Scored like every other risk figure, so the small loan's misses also weigh on the large one
+    let risk_of = |id| tracker.risk_score(&tracker.get_loan(id).unwrap().unwrap(), now).unwrap();
+    let (large_risk, small_risk) = (risk_of(large), risk_of(small));
+    assert!(small_risk > large_risk, "{} vs {}", small_risk, large_risk);
+
+    let weighted = tracker.weighted_portfolio_risk(&lender_id).unwrap();
+    let expected = (large_risk * 9000.0 + small_risk * 1000.0) / 10_000.0;
+    assert!((weighted - expected).abs() < 1e-3, "{} vs {}", weighted, expected);
+    // The big, healthy loan pulls the figure well below the plain average
+    assert!(weighted < (large_risk + small_risk) / 2.0);
+    let summary = tracker.portfolio_summary(&lender_id).unwrap();
+    assert!((summary.weighted_risk_score - weighted).abs() < 1e-3);
+
+    // Fully repaid loans carry no exposure
+    tracker.record_payment(large, 9000.0).unwrap();
+    tracker.record_payment(small, 1000.0).unwrap();
+    assert_eq!(tracker.weighted_portfolio_risk(&lender_id).unwrap(), 0.0);
+}
+
+#[actix_web::test]
+async fn test_borrower_history_with_prior_default_raises_risk_on_new_loan() {
+    use lendwise_recovery::models::RiskModel;
+
+    let db = fresh_db();
+    let users = UserManager::new(&db);
+    let lender_id = users.register_user("History Lender".to_string(), None, None, UserRole::Lender, None, None).unwrap();
+    let register = |name: &str| {
+        users.register_user(name.to_string(), None, None, UserRole::Borrower, Some(lender_id.clone()), None).unwrap()
+    };
+    let (reliable, defaulter) = (register("Reliable Borrower"), register("Defaulting Borrower"));
+    let tracker = LoanTracker::new(&db);
+
+    let clean = tracker.borrower_history(&reliable).unwrap();
+    assert_eq!(clean.total_loans, 0);
+    assert_eq!(clean.reliability(), 1.0);
+
+    // Each borrower has one earlier loan: one repaid in full, one defaulted after missing installments
+    let repaid = tracker.create_loan(reliable.clone(), lender_id.clone(), 1200.0, 0.0, 3).unwrap();
+    tracker.record_payment(repaid, 1200.0).unwrap();
+    let defaulted = tracker.create_loan(defaulter.clone(), lender_id.clone(), 1200.0, 0.0, 3).unwrap();
+    let mut loan = tracker.get_loan(defaulted).unwrap().unwrap();
+    for (i, p) in loan.repayment_schedule.iter_mut().enumerate() {
+        p.due_date = Utc::now() - Duration::days(120 - 30 * i as i64);
+    }
+    loan.status = LoanStatus::Defaulted;
+    db.save_loan(&loan).unwrap();
+
+    let good = tracker.borrower_history(&reliable).unwrap();
+    assert_eq!((good.total_loans, good.repaid_count, good.defaulted_count), (1, 1, 0));
+    assert_eq!(good.average_days_late, 0.0);
+    let bad = tracker.borrower_history(&defaulter).unwrap();
+    assert_eq!((bad.total_loans, bad.repaid_count, bad.defaulted_count), (1, 0, 1));
+    assert!(bad.average_days_late > 50.0, "{}", bad.average_days_late);
+    assert!(bad.reliability() < good.reliability());
+
+    // Identical new loans: only the borrower's record tells them apart
+    let new_good = tracker.create_loan(reliable.clone(), lender_id.clone(), 5000.0, 10.0, 12).unwrap();
+    let new_bad = tracker.create_loan(defaulter.clone(), lender_id.clone(), 5000.0, 10.0, 12).unwrap();
+    let now = Utc::now();
+    let recovery = RecoveryEngine::default();
+    let score = |id, borrower: &str| {
+        let loan = tracker.get_loan(id).unwrap().unwrap();
+        let history = tracker.borrower_history_excluding(borrower, id).unwrap();
+        assert_eq!(history.total_loans, 1);
+        recovery.predict_default(&loan, &RiskModel::default().with_history(&[]).with_borrower_history(&history), now)
+    };
+    let (good_risk, bad_risk) = (score(new_good, &reliable), score(new_bad, &defaulter));
+    assert!(bad_risk > good_risk, "{} vs {}", bad_risk, good_risk);
+    // A spotless record adds nothing over the plain model
+    let plain = recovery.predict_default(&tracker.get_loan(new_good).unwrap().unwrap(), &RiskModel::default(), now);
+    assert!((good_risk - plain).abs() < 1e-9);
+
+    // Archiving the default hides it from listings, not from the borrower's record
+    tracker.archive_loan(defaulted).unwrap();
+    let still_bad = tracker.borrower_history_excluding(&defaulter, new_bad).unwrap();
+    assert_eq!((still_bad.total_loans, still_bad.defaulted_count), (1, 1));
+    // Reports score the new loan the same way, record included
+    let assessed = tracker.assess_risk(&tracker.get_loan(new_bad).unwrap().unwrap(), now).unwrap();
+    assert!((assessed.risk_score - bad_risk).abs() < 1e-9);
+    assert_eq!(assessed.borrower_history.defaulted_count, 1);
+    let ranked = tracker.loans_above_risk(0.0).unwrap();
+    let listed = ranked.iter().find(|r| r.loan.id == new_bad).unwrap();
+    assert!((listed.risk_score - bad_risk).abs() < 1e-6);
+    let summary = tracker.portfolio_summary(&lender_id).unwrap();
+    let mean = [new_good, new_bad, repaid].iter().map(|&id| tracker.risk_score(&tracker.get_loan(id).unwrap().unwrap(), now).unwrap()).sum::<f64>() / 3.0;
+    assert_eq!(summary.loan_count, 3);
+    assert!((summary.average_risk_score - mean).abs() < 1e-6, "{} vs {}", summary.average_risk_score, mean);
+}
+
+#[actix_web::test]
+async fn test_at_risk_report_ranks_open_loans_by_risk() {
+    use lendwise_recovery::models::RiskModel;
+
+    let db = web::Data::new(fresh_db());
+    let lender = UserManager::new(&db)
+        .register_user("Worklist Lender".to_string(), None, None, UserRole::Lender, None, None)
+        .unwrap();
+    let tracker = LoanTracker::new(&db);
+    // Backdates `missed` unpaid installments of a fresh 6-month loan
+    let behind = |lender: &str, missed: usize| {
+        let id = tracker.create_loan("DEMO".to_string(), lender.to_string(), 600.0, 10.0, 6).unwrap();
+        let mut loan = tracker.get_loan(id).unwrap().unwrap();
+        for (i, p) in loan.repayment_schedule.iter_mut().enumerate().take(missed) {
+            p.due_date = Utc::now() - Duration::days(30 * (missed - i) as i64);
+        }
+        if missed > 0 {
+            loan.status = LoanStatus::Overdue;
+        }
+        db.save_loan(&loan).unwrap();
+        id
+    };
+    let current = behind(&lender, 0);
+    let one_late = behind(&lender, 1);
+    let three_late = behind(&lender, 3);
+    let elsewhere = behind("BANK", 3);
+    let repaid = behind(&lender, 2);
+    let owed = tracker.get_loan(repaid).unwrap().unwrap().remaining_balance().unwrap();
+    tracker.record_payment(repaid, owed.to_f64()).unwrap();
+
+    let now = Utc::now();
+    let risk = |id| RecoveryEngine::default().predict_default(&tracker.get_loan(id).unwrap().unwrap(), &RiskModel::default(), now);
+    assert!(risk(three_late) > risk(one_late) && risk(one_late) > risk(current));
+    // Between the loan in good standing and the late ones
+    let threshold = (risk(current) + risk(one_late)) / 2.0;
+
+    let app = session_app!(db, "/reports/at-risk" => web::get().to(at_risk_report));
+    let own = login!(app, lender.as_str());
+    let report = |query: String, cookie: Cookie<'static>| test::TestRequest::get().uri(&format!("/reports/at-risk?{}", query)).cookie(cookie).to_request();
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, report(format!("threshold={}", threshold), own.clone())).await;
+    let ids: Vec<&str> = body["items"].as_array().unwrap().iter().map(|i| i["loan_id"].as_str().unwrap()).collect();
+    assert_eq!(ids, [three_late.to_string(), one_late.to_string()]);
+    assert_eq!(body["total"], 2);
+    let top = &body["items"][0];
+    assert_eq!(top["borrower_name"], "Demo Borrower");
+    assert_eq!(top["days_overdue"], 90);
+    assert_eq!(top["missed_installments"], 3);
+    assert!(top["recommended_action"].is_string());
+    assert!(top["risk_score"].as_f64().unwrap() > body["items"][1]["risk_score"].as_f64().unwrap());
+    assert_eq!(top["outstanding"], tracker.get_loan(three_late).unwrap().unwrap().remaining_balance().unwrap().to_string());
+
+    // Paginated: the riskiest loan alone, with more to come
+    let body: serde_json::Value = test::call_and_read_body_json(&app, report(format!("threshold={}&limit=1", threshold), own.clone())).await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 1);
+    assert_eq!(body["items"][0]["loan_id"], three_late.to_string());
+    assert_eq!(body["has_next"], true);
+
+    // A zero threshold lists every open loan, never the repaid one or another lender's
+    let body: serde_json::Value = test::call_and_read_body_json(&app, report("threshold=0".to_string(), own.clone())).await;
+    let ids: Vec<&str> = body["items"].as_array().unwrap().iter().map(|i| i["loan_id"].as_str().unwrap()).collect();
+    assert_eq!(ids, [three_late.to_string(), one_late.to_string(), current.to_string()]);
+    assert!(!ids.contains(&elsewhere.to_string().as_str()));
+
+    let resp = test::call_service(&app, report("threshold=1.5".to_string(), own)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let borrower = login!(app, "DEMO");
+    let resp = test::call_service(&app, report(String::new(), borrower)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_demo_runs_in_sandbox() {
+    let prod_path = std::env::temp_dir().join(format!("lendwise-prod-{}.db", uuid::Uuid::new_v4()));
+    let prod_path = prod_path.to_str().unwrap().to_string();
+    let before = {
+        let db = Db::new_with_path(&prod_path).unwrap();
+        (db.load_all_users().unwrap().len(), db.load_all_loans().unwrap().len())
+    };
+
+    for _ in 0..2 {
+        let sandbox = lendwise_recovery::demo::run_demo(lendwise_recovery::timezone::utc().into());
+        assert!(!sandbox.exists(), "demo sandbox {:?} was not cleaned up", sandbox);
+    }
+
+    let db = Db::new_with_path(&prod_path).unwrap();
+    let after = (db.load_all_users().unwrap().len(), db.load_all_loans().unwrap().len());
+    assert_eq!(before, after);
+}
+
+#[actix_web::test]
+async fn test_seed_generates_reproducible_varied_book() {
+    use lendwise_recovery::seed::seed;
+
+    let book = |db: &Db, loans: &[uuid::Uuid]| {
+        let mut terms: Vec<_> = loans.iter().map(|id| {
+            let loan = db.load_loan(*id).unwrap().unwrap();
+            (loan.principal, loan.amount_paid, loan.repayment_schedule.len(), loan.status)
+        }).collect();
+        terms.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+        terms
+    };
+
+    let (first, second) = (fresh_db(), fresh_db());
+    let summary = seed(&first, 10, 40, 7).unwrap();
+    assert_eq!((summary.lenders.len(), summary.borrowers.len(), summary.loans.len()), (2, 8, 40));
+    assert_eq!(summary.loans_by_status.values().sum::<usize>(), 40);
+    assert!(summary.loans_by_status.get("overdue").copied().unwrap_or(0) > 0, "{:?}", summary.loans_by_status);
+
+    let again = seed(&second, 10, 40, 7).unwrap();
+    assert_eq!(book(&first, &summary.loans), book(&second, &again.loans));
+    assert_eq!(summary.loans_by_status, again.loans_by_status);
+
+    // Every loan is between a borrower and the lender they registered under
+    for id in &summary.loans {
+        let loan = first.load_loan(*id).unwrap().unwrap();
+        let borrower = first.load_user(&loan.borrower_id).unwrap().unwrap();
+        assert_eq!(borrower.lender_id.as_deref(), Some(loan.lender_id.as_str()));
+    }
+    let principals: std::collections::HashSet<_> = book(&first, &summary.loans).iter().map(|t| t.0).collect();
+    assert!(principals.len() > 10);
+
+    assert!(matches!(seed(&fresh_db(), 1, 5, 7), Err(AppError::InvalidInput(_))));
+}
+
+#[actix_web::test]
+async fn test_loans_without_recent_payment() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let now = Utc::now();
+
+    let create = |disbursed_days_ago: i64| {
+        let id = tracker.create_loan("SLNT".to_string(), "BANK".to_string(), 500.0, 10.0, 12).unwrap();
+        let mut loan = tracker.get_loan(id).unwrap().unwrap();
+        loan.disbursement_date = now - Duration::days(disbursed_days_ago);
+        db.save_loan(&loan).unwrap();
+        id
+    };
+    let recently_paid = create(60);
+    let never_paid = create(90);
+    let long_silent = create(45);
+
+    tracker.update_repayment(recently_paid).unwrap();
+    db.save_payment(&Payment {
+        id: uuid::Uuid::new_v4(),
+        loan_id: long_silent,
+        amount: Money::from_cents(5_000),
+        paid_at: now - Duration::days(40),
+    }).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .route("/loans", web::get().to(get_loans))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/loans?no_payment_since=30").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let ids: Vec<String> = body["items"].as_array().unwrap().iter().map(|l| l["id"].as_str().unwrap().to_string()).collect();
+    assert_eq!(ids, vec![never_paid.to_string(), long_silent.to_string()]);
+}
+
+#[actix_web::test]
+async fn test_max_active_loans_per_lender() {
+    let db = fresh_db();
+    let users = UserManager::new(&db);
+    let capped = users
+        .register_user("Capped Lender".to_string(), None, None, UserRole::Lender, None, Some("Cap Bank".to_string()))
+        .unwrap();
+    let other = users
+        .register_user("Other Lender".to_string(), None, None, UserRole::Lender, None, Some("Other Bank".to_string()))
+        .unwrap();
+
+    let mut config = Config::from_env().unwrap();
+    config.max_active_loans_per_lender = Some(2);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .app_data(web::Data::new(config))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans", web::post().to(create_loan))
+    ).await;
+
+    for (lender, expected) in [
+        (&capped, StatusCode::OK),
+        (&capped, StatusCode::OK),
+        (&capped, StatusCode::BAD_REQUEST),
+        (&other, StatusCode::OK),
+    ] {
+        let login = test::call_service(&app, test::TestRequest::post().uri(&format!("/test/login/{}", lender)).to_request()).await;
+        let req = test::TestRequest::post()
+            .uri("/loans")
+            .cookie(session_cookie(&login))
+            .set_json(json!({
+                "borrower_id": "DEMO",
+                "lender_id": lender,
+                "principal": 1000.0,
+                "interest_rate": 10.0,
+                "months": 6
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), expected, "lender {}", lender);
+    }
+}
+
+#[actix_web::test]
+async fn test_principal_limits_global_and_per_lender() {
+    let db = fresh_db();
+    let other = UserManager::new(&db)
+        .register_user("Other Lender".to_string(), None, None, UserRole::Lender, None, Some("Other Bank".to_string()))
+        .unwrap();
+
+    let mut config = Config::from_env().unwrap();
+    config.min_principal = Some(500.0);
+    config.max_principal = Some(5_000.0);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .app_data(web::Data::new(config))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans", web::post().to(create_loan))
+            .route("/lenders/{id}/policy", web::get().to(get_lender_policy))
+            .route("/lenders/{id}/policy", web::put().to(set_lender_policy))
+    ).await;
+
+    let bank = login!(app, "BANK");
+    let other_cookie = login!(app, other);
+    let create = |cookie: &Cookie<'static>, lender: &str, principal: f64| {
+        test::TestRequest::post()
+            .uri("/loans")
+            .cookie(cookie.clone())
+            .set_json(json!({
+                "borrower_id": "DEMO",
+                "lender_id": lender,
+                "principal": principal,
+                "interest_rate": 10.0,
+                "months": 6
+            }))
+            .to_request()
+    };
+
+    // Global limits apply to everyone without a policy of their own.
+    for (principal, expected) in [(100.0, StatusCode::BAD_REQUEST), (1_000.0, StatusCode::OK), (6_000.0, StatusCode::BAD_REQUEST)] {
+        let resp = test::call_service(&app, create(&bank, "BANK", principal)).await;
+        assert_eq!(resp.status(), expected, "principal {}", principal);
+    }
+
+    // Only the lender themself may set their policy, and min must not exceed max.
+    let req = test::TestRequest::put()
+        .uri(&format!("/lenders/{}/policy", other))
+        .cookie(bank.clone())
+        .set_json(json!({"max_principal": 20000.0}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::put()
+        .uri("/lenders/BANK/policy")
+        .cookie(bank.clone())
+        .set_json(json!({"min_principal": 900.0, "max_principal": 800.0}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    // BANK raises its own ceiling; its floor still comes from the global config.
+    let req = test::TestRequest::put()
+        .uri("/lenders/BANK/policy")
+        .cookie(bank.clone())
+        .set_json(json!({"max_principal": 20000.0}))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["effective"]["min_principal"], 500.0);
+    assert_eq!(body["effective"]["max_principal"], 20000.0);
+
+    let req = test::TestRequest::get().uri("/lenders/BANK/policy").cookie(bank.clone()).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["policy"]["min_principal"], serde_json::Value::Null);
+    assert_eq!(body["policy"]["max_principal"], 20000.0);
+
+    for (cookie, lender, principal, expected) in [
+        (&bank, "BANK", 6_000.0, StatusCode::OK),
+        (&bank, "BANK", 100.0, StatusCode::BAD_REQUEST),
+        (&other_cookie, other.as_str(), 6_000.0, StatusCode::BAD_REQUEST),
+    ] {
+        let resp = test::call_service(&app, create(cookie, lender, principal)).await;
+        assert_eq!(resp.status(), expected, "lender {} principal {}", lender, principal);
+    }
+}
+
+#[actix_web::test]
+async fn test_recovery_effectiveness() {
+    let db = fresh_db();
+    let loan_id = LoanTracker::new(&db)
+        .create_loan("DEMO".to_string(), "BANK".to_string(), 2000.0, 12.0, 6)
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/recovery/actions", web::post().to(log_recovery_action))
+            .route("/recovery/actions/{id}/outcome", web::put().to(set_recovery_outcome))
+            .route("/recovery/effectiveness", web::get().to(recovery_effectiveness))
+    ).await;
+    let login = test::call_service(&app, test::TestRequest::post().uri("/test/login/BANK").to_request()).await;
+    let cookie = session_cookie(&login);
+
+    // Three reminders paid, one ignored, one still pending
+    let outcomes = [
+        json!("PaidAfterReminder"),
+        json!("PaidAfterReminder"),
+        json!("PaidAfterReminder"),
+        json!("NoResponse"),
+        serde_json::Value::Null,
+    ];
+    let mut last_id = String::new();
+    for outcome in outcomes {
+        let req = test::TestRequest::post()
+            .uri("/recovery/actions")
+            .cookie(cookie.clone())
+            .set_json(json!({ "loan_id": loan_id, "action": "SendReminder", "outcome": outcome }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        last_id = body["id"].as_str().unwrap().to_string();
+    }
+    let req = test::TestRequest::post()
+        .uri("/recovery/actions")
+        .cookie(cookie.clone())
+        .set_json(json!({ "loan_id": loan_id, "action": "RenegotiateTerms", "outcome": "Refused" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().uri("/recovery/effectiveness").cookie(cookie.clone()).to_request();
+    let stats: Vec<serde_json::Value> = test::read_body_json(test::call_service(&app, req).await).await;
+    let reminders = stats.iter().find(|s| s["action"] == "SendReminder").unwrap();
+    assert_eq!(reminders["total"], 5);
+    assert_eq!(reminders["with_outcome"], 4);
+    assert!((reminders["success_rate"].as_f64().unwrap() - 0.75).abs() < 1e-9);
+    let renegotiations = stats.iter().find(|s| s["action"] == "RenegotiateTerms").unwrap();
+    assert_eq!(renegotiations["success_rate"], 0.0);
+
+    // Resolving the pending reminder as paid moves the rate to 4/5
+    let req = test::TestRequest::put()
+        .uri(&format!("/recovery/actions/{}/outcome", last_id))
+        .cookie(cookie.clone())
+        .set_json(json!({ "outcome": "PaidAfterReminder" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().uri("/recovery/effectiveness").cookie(cookie).to_request();
+    let stats: Vec<serde_json::Value> = test::read_body_json(test::call_service(&app, req).await).await;
+    let reminders = stats.iter().find(|s| s["action"] == "SendReminder").unwrap();
+    assert!((reminders["success_rate"].as_f64().unwrap() - 0.8).abs() < 1e-9);
+}
+
+#[actix_web::test]
+async fn test_logged_write_off_action_still_loads_in_effectiveness_report() {
+    let db = web::Data::new(fresh_db());
+    let loan_id = LoanTracker::new(&db)
+        .create_loan("DEMO".to_string(), "BANK".to_string(), 2000.0, 12.0, 6)
+        .unwrap();
+    let app = session_app!(db,
+        "/recovery/actions" => web::post().to(log_recovery_action),
+        "/recovery/effectiveness" => web::get().to(recovery_effectiveness),
+    );
+    let cookie = login!(app, "BANK");
+
+    let req = test::TestRequest::post()
+        .uri("/recovery/actions")
+        .cookie(cookie.clone())
+        .set_json(json!({ "loan_id": loan_id, "action": "WriteOff", "outcome": null }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    assert_eq!(db.load_recovery_actions().unwrap()[0].action, RecoveryAction::WriteOff);
+
+    let req = test::TestRequest::get().uri("/recovery/effectiveness").cookie(cookie).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let stats: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    let write_offs = stats.iter().find(|s| s["action"] == "WriteOff").unwrap();
+    assert_eq!(write_offs["total"], 1);
+}
+
+#[actix_web::test]
+async fn test_extension_request_and_approval() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1200.0, 10.0, 3).unwrap();
+    let original = tracker.get_loan(loan_id).unwrap().unwrap().repayment_schedule;
+
+    let app = session_app!(db,
+        "/me/loans/{id}/extension" => web::post().to(request_extension),
+        "/loans/{id}/extensions/{req}/approve" => web::post().to(approve_extension),
+    );
+
+    // Only the loan's borrower may ask
+    let stranger = login!(app, "MSHW");
+    let req = test::TestRequest::post()
+        .uri(&format!("/me/loans/{}/extension", loan_id))
+        .cookie(stranger)
+        .set_json(json!({ "days": 10 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let borrower = login!(app, "DEMO");
+    let req = test::TestRequest::post()
+        .uri(&format!("/me/loans/{}/extension", loan_id))
+        .cookie(borrower)
+        .set_json(json!({ "days": 10 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let request: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(request["status"], "Pending");
+    assert_eq!(request["installment_index"], 0);
+
+    let lender = login!(app, "BANK");
+    let req = test::TestRequest::post()
+        .uri(&format!("/loans/{}/extensions/{}/approve", loan_id, request["id"].as_str().unwrap()))
+        .cookie(lender)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let decided: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(decided["status"], "Approved");
+
+    let schedule = tracker.get_loan(loan_id).unwrap().unwrap().repayment_schedule;
+    assert_eq!(schedule[0].due_date, original[0].due_date + Duration::days(10));
+    assert_eq!(schedule[0].amount(), original[0].amount());
+    assert_eq!(schedule[1..], original[1..]);
+}
+
+#[actix_web::test]
+async fn test_extension_rejection_keeps_schedule() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1200.0, 10.0, 3).unwrap();
+    let original = tracker.get_loan(loan_id).unwrap().unwrap().repayment_schedule;
+
+    let app = session_app!(db,
+        "/me/loans/{id}/extension" => web::post().to(request_extension),
+        "/loans/{id}/extensions/{req}/approve" => web::post().to(approve_extension),
+        "/loans/{id}/extensions/{req}/reject" => web::post().to(reject_extension),
+    );
+
+    let borrower = login!(app, "DEMO");
+    let req = test::TestRequest::post()
+        .uri(&format!("/me/loans/{}/extension", loan_id))
+        .cookie(borrower)
+        .set_json(json!({ "days": 7 }))
+        .to_request();
+    let request: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let request_id = request["id"].as_str().unwrap().to_string();
+
+    let lender = login!(app, "BANK");
+    let req = test::TestRequest::post()
+        .uri(&format!("/loans/{}/extensions/{}/reject", loan_id, request_id))
+        .cookie(lender.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let decided: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(decided["status"], "Rejected");
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().repayment_schedule, original);
+
+    // A decided request cannot be approved afterwards
+    let req = test::TestRequest::post()
+        .uri(&format!("/loans/{}/extensions/{}/approve", loan_id, request_id))
+        .cookie(lender)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().repayment_schedule, original);
+}
+
+#[actix_web::test]
+async fn test_extension_approval_refuses_stale_or_closed_loans() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1200.0, 10.0, 3).unwrap();
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    let later = |i: usize| loan.repayment_schedule[i].due_date + Duration::days(5);
+    assert!(matches!(tracker.request_extension(&loan, 3, later(2)), Err(AppError::InvalidInput(_))));
+
+    // The installment moved after the borrower asked, so the request no longer applies
+    let stale = tracker.request_extension(&loan, 0, later(0)).unwrap();
+    let mut moved = loan.clone();
+    moved.repayment_schedule[0].due_date = later(0) + Duration::days(1);
+    db.save_loan(&moved).unwrap();
+    assert!(matches!(tracker.decide_extension(stale.clone(), true), Err(AppError::InvalidInput(_))));
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().repayment_schedule, moved.repayment_schedule);
+    // Turning it down is still possible
+    assert_eq!(tracker.decide_extension(stale, false).unwrap().status, ExtensionStatus::Rejected);
+
+    let pending = tracker.request_extension(&moved, 1, later(1)).unwrap();
+    tracker.record_payment(loan_id, moved.total_due().unwrap().to_f64()).unwrap();
+    let repaid = tracker.get_loan(loan_id).unwrap().unwrap();
+    assert_eq!(repaid.status, LoanStatus::Repaid);
+    assert!(matches!(tracker.decide_extension(pending, true), Err(AppError::InvalidInput(_))));
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().repayment_schedule, repaid.repayment_schedule);
+}
+
+#[actix_web::test]
+async fn test_create_loan_rejects_absurd_months() {
+    let db = fresh_db();
+    assert!(LoanTracker::new(&db)
+        .create_loan("DEMO".to_string(), "BANK".to_string(), 1000.0, 10.0, 100_000)
+        .is_err());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .app_data(web::Data::new(Config::from_env().unwrap()))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans", web::post().to(create_loan))
+    ).await;
+    let lender = login!(app, "BANK");
+    let req = test::TestRequest::post()
+        .uri("/loans")
+        .cookie(lender)
+        .set_json(json!({
+            "borrower_id": "DEMO",
+            "lender_id": "BANK",
+            "principal": 1000.0,
+            "interest_rate": 10.0,
+            "months": 100_000
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["message"], "months must be between 1 and 600");
+}
+
+#[actix_web::test]
+async fn test_create_loan_rejects_bad_principal_and_rate() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    for (principal, rate) in [(-500.0, 10.0), (0.0, 10.0), (f64::NAN, 10.0), (f64::INFINITY, 10.0), (1000.0, f64::NAN), (1000.0, -1.0), (1000.0, 5000.0)] {
+        // Bad terms are the caller's fault, not a storage failure
+        let result = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), principal, rate, 12);
+        assert!(matches!(result, Err(AppError::InvalidInput(_))), "{} at {}%", principal, rate);
+    }
+    let id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1000.0, 100.0, 12).unwrap();
+    // Amounts past MAX_AMOUNT would overflow Money once interest is added, so they never get in
+    assert!(matches!(tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1e17, 10.0, 12), Err(AppError::InvalidInput(_))));
+    assert!(tracker.create_loan("DEMO".to_string(), "BANK".to_string(), MAX_AMOUNT, 100.0, 600).is_ok());
+    for amount in [1e17, f64::MAX, f64::NAN, 0.0] {
+        assert!(matches!(tracker.record_payment(id, amount), Err(AppError::InvalidInput(_))), "{}", amount);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(Config::from_env().unwrap()))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans", web::post().to(create_loan))
+    ).await;
+    let lender = login!(app, "BANK");
+    let cases = [
+        (json!(-500.0), json!(10.0), 12, "principal must be a positive number"),
+        (json!(1e308), json!(10.0), 12, "principal must not exceed 1000000000"),
+        (json!(1000.0), json!(5000.0), 12, "interest_rate must be between 0 and 100"),
+        (json!(1000.0), json!(-0.5), 12, "interest_rate must be between 0 and 100"),
+        (json!(1000.0), json!(10.0), 601, "months must be between 1 and 600"),
+    ];
+    for (principal, rate, months, message) in cases {
+        let req = test::TestRequest::post()
+            .uri("/loans")
+            .cookie(lender.clone())
+            .set_json(json!({
+                "borrower_id": "DEMO", "lender_id": "BANK", "principal": principal, "interest_rate": rate, "months": months
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["message"], message);
+    }
+    // Compounding 100% a year for 50 years is refused on any principal rather than overflowing
+    let req = test::TestRequest::post()
+        .uri("/loans")
+        .cookie(lender.clone())
+        .set_json(json!({
+            "borrower_id": "DEMO", "lender_id": "BANK", "principal": 1000.0, "interest_rate": 100.0, "months": 600, "interest_kind": "compound_monthly"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["message"], "principal plus interest must not exceed 1000000000000");
+
+    // A promotional window longer than the term is refused; one inside it is stored with the loan
+    let create = |promo_months: i64| {
+        test::TestRequest::post()
+            .uri("/loans")
+            .cookie(lender.clone())
+            .set_json(json!({
+                "borrower_id": "DEMO", "lender_id": "BANK", "principal": 1200.0, "interest_rate": 12.0, "months": 6, "promo_months": promo_months
+            }))
+            .to_request()
+    };
+    let resp = test::call_service(&app, create(7)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["message"], "promo_months must be between 0 and the loan term (6 months)");
+    let resp = test::call_service(&app, create(2)).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let loan_id = uuid::Uuid::parse_str(body["id"].as_str().unwrap()).unwrap();
+    let loan = LoanTracker::new(&db).get_loan(loan_id).unwrap().unwrap();
+    assert_eq!(loan.promo_months, 2);
+    assert_eq!(loan.repayment_schedule[1].interest, Money::ZERO);
+    assert!(loan.repayment_schedule[2].interest.is_positive());
+}
+
+#[actix_web::test]
+async fn test_zero_month_loan_is_rejected_and_empty_schedule_repays() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let before = tracker.get_all_loans().unwrap().len();
+    assert!(tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1000.0, 10.0, 0).is_err());
+    assert_eq!(tracker.get_all_loans().unwrap().len(), before);
+
+    // A loan stored without any schedule is settled in full rather than panicking
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1000.0, 10.0, 1).unwrap();
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.repayment_schedule.clear();
+    db.save_loan(&loan).unwrap();
+    tracker.update_repayment(loan_id).unwrap();
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    assert_eq!(loan.status, LoanStatus::Repaid);
+    assert_eq!(loan.amount_paid, Money::from_cents(100_000));
+}
+
+#[actix_web::test]
+async fn test_loan_currency_is_validated_and_carried_through() {
+    let db = fresh_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(Config::from_env().unwrap()))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans", web::post().to(create_loan))
+            .route("/loans", web::get().to(get_loans))
+    ).await;
+    let lender = login!(app, "BANK");
+    let create = |currency: serde_json::Value| {
+        let mut body = json!({
+            "borrower_id": "DEMO", "lender_id": "BANK", "principal": 1000.0, "interest_rate": 10.0, "months": 3
+        });
+        if !currency.is_null() {
+            body["currency"] = currency;
+        }
+        test::TestRequest::post().uri("/loans").cookie(lender.clone()).set_json(body).to_request()
+    };
+
+    let resp = test::call_service(&app, create(json!("XYZ"))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = test::call_service(&app, create(json!("kes"))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let kes_id = uuid::Uuid::parse_str(body["id"].as_str().unwrap()).unwrap();
+    let resp = test::call_service(&app, create(serde_json::Value::Null)).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let usd_id = uuid::Uuid::parse_str(body["id"].as_str().unwrap()).unwrap();
+
+    let tracker = LoanTracker::new(&db);
+    assert_eq!(tracker.get_loan(kes_id).unwrap().unwrap().currency, "KES");
+    assert_eq!(tracker.get_loan(usd_id).unwrap().unwrap().currency, "USD");
+
+    let req = test::TestRequest::get().uri("/loans").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let loans = body["items"].as_array().unwrap();
+    let currency_of = |id: uuid::Uuid| loans.iter().find(|l| l["id"] == id.to_string()).unwrap()["currency"].clone();
+    assert_eq!(currency_of(kes_id), "KES");
+    assert_eq!(currency_of(usd_id), "USD");
+}
+
+#[actix_web::test]
+async fn test_timestamps_track_creation_and_last_write() {
+    let db = fresh_db();
+    let user_id = UserManager::new(&db)
+        .register_user("Stamp User".to_string(), None, None, UserRole::Borrower, Some("BANK".to_string()), None)
+        .unwrap();
+    let user = UserManager::new(&db).get_user(&user_id).unwrap().unwrap();
+    assert!(user.updated_at >= user.created_at);
+
+    let tracker = LoanTracker::new(&db);
+    let older = tracker.create_loan(user_id.clone(), "BANK".to_string(), 100.0, 5.0, 3).unwrap();
+    let newer = tracker.create_loan(user_id.clone(), "BANK".to_string(), 200.0, 5.0, 3).unwrap();
+    let before = tracker.get_loan(older).unwrap().unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    tracker.record_payment(older, 10.0).unwrap();
+    let after = tracker.get_loan(older).unwrap().unwrap();
+    assert_eq!(after.created_at, before.created_at);
+    assert!(after.updated_at > before.updated_at);
+
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    let mut renamed = user.clone();
+    renamed.name = "Stamp User II".to_string();
+    db.save_user(&renamed).unwrap();
+    let reloaded = UserManager::new(&db).get_user(&user_id).unwrap().unwrap();
+    assert_eq!(reloaded.created_at, user.created_at);
+    assert!(reloaded.updated_at > user.updated_at);
+
+    let db = web::Data::new(db);
+    let app = session_app!(db, "/loans" => web::get().to(get_loans), "/users/{id}" => web::get().to(get_user));
+    let req = test::TestRequest::get().uri(&format!("/loans?borrower_id={}&sort=-updated_at", user_id)).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let ids: Vec<&str> = body["items"].as_array().unwrap().iter().map(|l| l["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec![older.to_string(), newer.to_string()]);
+    assert!(body["items"][0]["created_at"].is_string() && body["items"][0]["updated_at"].is_string());
+
+    let req = test::TestRequest::get().uri(&format!("/users/{}", user_id)).cookie(login!(app, user_id.as_str())).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["created_at"].is_string() && body["updated_at"].is_string());
+}
+
+#[actix_web::test]
+async fn test_list_ordering_is_stable() {
+    let db = fresh_db();
+    let users = UserManager::new(&db);
+    let mut registered = Vec::new();
+    for name in ["Zed", "Amy", "Kim", "Bob"] {
+        registered.push(
+            users.register_user(name.to_string(), None, None, UserRole::Lender, None, Some("Org".to_string())).unwrap(),
+        );
+    }
+    let tracker = LoanTracker::new(&db);
+    for principal in [300.0, 100.0, 200.0] {
+        tracker.create_loan("DEMO".to_string(), "BANK".to_string(), principal, 5.0, 3).unwrap();
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .route("/users", web::get().to(get_users))
+            .route("/loans", web::get().to(get_loans))
+    ).await;
+
+    let fetch_ids = |uri: &'static str| {
+        let app = &app;
+        async move {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", uri);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            body["items"].as_array().unwrap().iter().map(|v| v["id"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+        }
+    };
+
+    let first = fetch_ids("/users").await;
+    assert_eq!(first, fetch_ids("/users").await);
+    // Default order is creation order, so the users registered here come last, in sequence
+    assert_eq!(first[first.len() - registered.len()..], registered[..]);
+
+    let loans = fetch_ids("/loans").await;
+    assert_eq!(loans, fetch_ids("/loans").await);
+    assert_eq!(fetch_ids("/loans?sort=-principal").await, fetch_ids("/loans?sort=-principal").await);
+
+    let req = test::TestRequest::get().uri("/users?sort=-name").to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let names: Vec<&str> = body["items"].as_array().unwrap().iter().map(|u| u["name"].as_str().unwrap()).collect();
+    let mut expected = names.clone();
+    expected.sort_by(|a, b| b.cmp(a));
+    assert_eq!(names, expected);
+
+    let req = test::TestRequest::get().uri("/loans?sort=password").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_settlement_quote_for_overdue_loan() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 3000.0, 12.0, 3).unwrap();
+
+    // Disbursed 100 days ago: two installments are past due, the first one was paid
+    let now = Utc::now();
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.disbursement_date = now - Duration::days(100);
+    loan.repayment_schedule = [now - Duration::days(70), now - Duration::days(40), now + Duration::days(20)]
+        .into_iter()
+        .map(|due_date| ScheduledPayment { due_date, principal: Money::from_cents(100_000), interest: Money::from_cents(3_000) })
+        .collect();
+    loan.status = LoanStatus::Overdue;
+    db.save_loan(&loan).unwrap();
+    tracker.record_payment(loan_id, 1030.0).unwrap();
+
+    let mut config = Config::from_env().unwrap();
+    config.late_fee_policy = Some(LateFeePolicy::Flat { amount: Money::from_cents(2500) });
+    // The same late fee the loan's schedule shows
+    let schedule_late_fee = tracker.get_loan(loan_id).unwrap().unwrap()
+        .with_default_late_fee_policy(config.late_fee_policy)
+        .late_fee(now)
+        .unwrap();
+    assert!(schedule_late_fee.is_positive());
+    config.early_settlement_discount_pct = 50.0;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .app_data(web::Data::new(config))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans/{id}/settlement", web::get().to(settlement_quote))
+            .route("/loans/{id}/settle", web::post().to(settle_loan))
+    ).await;
+    let borrower = login!(app, "DEMO");
+    let req = test::TestRequest::get()
+        .uri(&format!("/loans/{}/settlement", loan_id))
+        .cookie(borrower.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let quote: serde_json::Value = test::read_body_json(resp).await;
+
+    let f = |k: &str| amount(&quote[k]);
+    assert_eq!(f("outstanding_principal"), 2000.0);
+    assert_eq!(quote["missed_installments"], 1);
+    assert_eq!(f("late_fees"), schedule_late_fee.to_f64());
+    // Second installment's 30 plus two thirds of the third's, none of it paid yet
+    assert!((f("accrued_interest") - 50.0).abs() < 0.011, "{}", quote);
+    assert!((f("early_settlement_discount") - 25.0).abs() < 0.011, "{}", quote);
+    let sum = f("outstanding_principal") + f("accrued_interest") + f("late_fees") - f("early_settlement_discount");
+    assert!((sum - f("net_settlement")).abs() < 1e-9);
+
+    // Settling charges exactly the quoted figure, late fees included
+    let req = test::TestRequest::post().uri(&format!("/loans/{}/settle", loan_id)).cookie(borrower).to_request();
+    let settled: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!((amount(&settled["settled_amount"]) - f("net_settlement")).abs() < 0.011, "{} vs {}", settled, quote);
+    assert!((amount(&settled["amount_paid"]) - (1030.0 + amount(&settled["settled_amount"]))).abs() < 1e-9);
+    assert_eq!(settled["status"], "repaid");
+}
+
+async fn slow_handler() -> HttpResponse {
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    HttpResponse::Ok().finish()
+}
+
+#[actix_web::test]
+async fn test_concurrency_limit_sheds_excess_requests() {
+    let limit = ConcurrencyLimit::new(2);
+    let app = test::init_service(
+        App::new()
+            .wrap(limit.clone())
+            .route("/slow", web::get().to(slow_handler))
+    ).await;
+
+    let calls = (0..3).map(|_| test::call_service(&app, test::TestRequest::get().uri("/slow").to_request()));
+    let responses = futures_util::future::join_all(calls).await;
+
+    let ok = responses.iter().filter(|r| r.status() == StatusCode::OK).count();
+    let rejected: Vec<_> = responses.iter().filter(|r| r.status() == StatusCode::SERVICE_UNAVAILABLE).collect();
+    assert_eq!(ok, 2);
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(rejected[0].headers().get("Retry-After").unwrap(), "1");
+
+    // Slots are released once the in-flight requests finish
+    assert_eq!(limit.in_flight(), 0);
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/slow").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+async fn echo_request_id(req: HttpRequest) -> HttpResponse {
+    let id = req.extensions().get::<RequestId>().map(|id| id.0.to_string());
+    HttpResponse::Ok().body(id.unwrap_or_default())
+}
+
+async fn missing_handler() -> Result<HttpResponse, AppError> {
+    Err(AppError::NotFound("Loan not found".to_string()))
+}
+
+#[actix_web::test]
+async fn test_request_id_is_returned_in_header_and_error_body() {
+    let app = test::init_service(
+        App::new()
+            .wrap(AssignRequestId)
+            .route("/echo", web::get().to(echo_request_id))
+            .route("/missing", web::get().to(missing_handler))
+    ).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/echo").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let header = resp.headers().get("X-Request-Id").unwrap().to_str().unwrap().to_string();
+    let body = test::read_body(resp).await;
+    assert_eq!(std::str::from_utf8(&body).unwrap(), header);
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/missing").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let second = resp.headers().get("X-Request-Id").unwrap().to_str().unwrap().to_string();
+    assert_ne!(second, header);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["request_id"], second);
+    assert_eq!(body["message"], "Loan not found");
+}
+
+#[actix_web::test]
+async fn test_record_payment_tracks_balance_and_overpayment() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1200.0, 10.0, 12).unwrap();
+    let total_due = tracker.get_loan(loan_id).unwrap().unwrap().total_due().unwrap();
+    assert!(total_due > Money::from_cents(120_000));
+    let after_first = total_due.checked_sub(Money::from_cents(100_000)).unwrap();
+
+    let receipt = tracker.record_payment(loan_id, 1000.0).unwrap();
+    assert_eq!(receipt.remaining_balance, after_first);
+    assert_eq!(receipt.excess, Money::ZERO);
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    assert_eq!(loan.status, LoanStatus::PartiallyRepaid);
+    assert_eq!(loan.amount_paid, Money::from_cents(100_000));
+
+    let receipt = tracker.record_payment(loan_id, 500.0).unwrap();
+    assert_eq!(receipt.remaining_balance, Money::ZERO);
+    assert_eq!(receipt.applied, after_first);
+    assert_eq!(receipt.excess, Money::from_cents(150_000).checked_sub(total_due).unwrap());
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    assert_eq!(loan.status, LoanStatus::Repaid);
+    assert_eq!(loan.amount_paid, total_due);
+
+    assert!(tracker.record_payment(loan_id, -5.0).is_err());
+}
+
+#[actix_web::test]
+async fn test_tracker_reports_missing_loans_as_not_found() {
+    use actix_web::ResponseError;
+    use lendwise_recovery::error::AppError;
+
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let missing = uuid::Uuid::new_v4();
+
+    let err = tracker.update_repayment(missing).unwrap_err();
+    assert!(matches!(err, AppError::NotFound(_)), "{:?}", err);
+    assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    assert!(matches!(tracker.record_payment(missing, 10.0), Err(AppError::NotFound(_))));
+    assert!(matches!(tracker.update_terms(missing, Some(5.0), None), Err(AppError::NotFound(_))));
+    assert!(matches!(tracker.count_missed_payments(missing), Err(AppError::NotFound(_))));
+    assert!(matches!(tracker.write_off(missing), Err(AppError::NotFound(_))));
+
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 100.0, 5.0, 2).unwrap();
+    assert!(matches!(tracker.record_payment(loan_id, -5.0), Err(AppError::InvalidInput(_))));
+}
+
+#[actix_web::test]
+async fn test_settle_early_charges_only_accrued_interest() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1200.0, 12.0, 12).unwrap();
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    let full_term_cost = loan.total_due().unwrap();
+    let month_three = loan.repayment_schedule[2].due_date;
+    let interest_to_month_three = Money::checked_sum(loan.repayment_schedule[..3].iter().map(|p| p.interest)).unwrap();
+
+    let payoff = tracker.settle_early(loan_id, &SettlementPolicy::default(), month_three).unwrap();
+    assert_eq!(payoff, Money::from_cents(120_000).checked_add(interest_to_month_three).unwrap());
+    let saved = full_term_cost.checked_sub(payoff).unwrap();
+    assert!(saved > Money::from_cents(4_000), "early {} vs full term {}", payoff, full_term_cost);
+
+    let settled = tracker.get_loan(loan_id).unwrap().unwrap();
+    assert_eq!(settled.status, LoanStatus::Repaid);
+    assert_eq!(settled.remaining_balance(), Some(Money::ZERO));
+    assert_eq!(settled.repayment_schedule.len(), 4);
+    assert!(tracker.settle_early(loan_id, &SettlementPolicy::default(), month_three).is_err(), "a repaid loan cannot be settled again");
+
+    let other = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 600.0, 10.0, 6).unwrap();
+    let app = session_app!(web::Data::new(db), "/loans/{id}/settle" => web::post().to(settle_loan));
+    let req = test::TestRequest::post().uri(&format!("/loans/{}/settle", other)).cookie(login!(app, "TALA")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::post().uri(&format!("/loans/{}/settle", other)).cookie(login!(app, "DEMO")).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["status"], "repaid");
+    // Settled on the day it was disbursed, so essentially no interest has accrued
+    assert!((amount(&body["settled_amount"]) - 600.0).abs() < 0.05);
+}
+
+#[actix_web::test]
+async fn test_live_status_detects_drift() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 900.0, 0.0, 3).unwrap();
+
+    // First installment has already passed and nothing was paid, but the flagger never ran
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.repayment_schedule[0].due_date = Utc::now() - Duration::days(3);
+    db.save_loan(&loan).unwrap();
+
+    let app = session_app!(db, "/loans/{id}/status" => web::get().to(loan_status));
+    let lender = login!(app, "BANK");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/loans/{}/status?live=true", loan_id))
+        .cookie(lender.clone())
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["stored_status"], "Active");
+    assert_eq!(body["computed_status"], "Overdue");
+    assert_eq!(body["drift"], true);
+
+    // Without live=true only the stored value is reported, and nothing was persisted
+    let req = test::TestRequest::get()
+        .uri(&format!("/loans/{}/status", loan_id))
+        .cookie(lender)
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["stored_status"], "Active");
+    assert!(body["computed_status"].is_null());
+    assert_eq!(db.load_loan(loan_id).unwrap().unwrap().status, LoanStatus::Active);
+}
+
+#[actix_web::test]
+async fn test_amortization_schedule() {
+    use chrono::{Datelike, TimeZone};
+    let start = Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap();
+
+    // 12,000 at 12% over 12 months: EMI of 1066.19 with interest front-loaded
+    let schedule = generate_amortization_schedule_from(start, 12_000.0, 12.0, 12, InterestRounding::PerInstallment).unwrap();
+    assert_eq!(schedule.len(), 12);
+    assert_eq!((schedule[0].due_date.month(), schedule[0].due_date.day()), (2, 29));
+    assert_eq!((schedule[1].due_date.month(), schedule[1].due_date.day()), (3, 31));
+    assert_eq!((schedule[2].due_date.month(), schedule[2].due_date.day()), (4, 30));
+    assert_eq!(schedule[0].interest, Money::from_cents(12_000));
+    assert_eq!(schedule[0].amount(), Some(Money::from_cents(106_619)));
+    assert!(schedule[11].interest < schedule[0].interest);
+    let principal = Money::checked_sum(schedule.iter().map(|p| p.principal));
+    assert_eq!(principal, Some(Money::from_cents(1_200_000)));
+
+    // Zero interest splits principal evenly without NaN
+    let flat = generate_amortization_schedule_from(start, 1000.0, 0.0, 4, InterestRounding::PerInstallment).unwrap();
+    assert!(flat.iter().all(|p| p.principal == Money::from_cents(25_000) && p.interest == Money::ZERO));
+}
+
+#[actix_web::test]
+async fn test_partially_repaid_transitions() {
+    let path = std::env::temp_dir().join(format!("lendwise-test-{}.db", uuid::Uuid::new_v4()));
+    let db = Db::new_with_path(path.to_str().unwrap()).unwrap();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 900.0, 0.0, 3).unwrap();
+
+    tracker.update_repayment(loan_id).unwrap();
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    assert_eq!(loan.status, LoanStatus::PartiallyRepaid);
+    assert_eq!(loan.amount_paid, Money::from_cents(30_000));
+    assert_eq!(loan.status.to_string(), "Partially repaid");
+
+    tracker.update_repayment(loan_id).unwrap();
+    tracker.update_repayment(loan_id).unwrap();
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().status, LoanStatus::Repaid);
+
+    // Rows with a status this build does not know still load
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.status = LoanStatus::Overdue;
+    db.save_loan(&loan).unwrap();
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute("UPDATE loans SET status = 'Frozen' WHERE id = ?1", [loan_id.to_string()]).unwrap();
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().status, LoanStatus::Active);
+}
+
+#[actix_web::test]
+async fn test_loan_metadata_round_trip() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 500.0, 5.0, 2).unwrap();
+
+    let app = session_app!(db,
+        "/loans" => web::get().to(get_loans),
+        "/loans/{id}/metadata" => web::get().to(get_loan_metadata),
+        "/loans/{id}/metadata/{key}" => web::put().to(set_loan_metadata),
+        "/loans/{id}/metadata/{key}" => web::delete().to(delete_loan_metadata),
+    );
+    let lender = login!(app, "BANK");
+
+    for (key, value) in [("crm_id", "CRM-1042"), ("branch.code", "NBO-07")] {
+        let req = test::TestRequest::put()
+            .uri(&format!("/loans/{}/metadata/{}", loan_id, key))
+            .cookie(lender.clone())
+            .set_json(json!({ "value": value }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    // Bad key names and oversized values are refused
+    let req = test::TestRequest::put()
+        .uri(&format!("/loans/{}/metadata/Bad%20Key", loan_id))
+        .cookie(lender.clone())
+        .set_json(json!({ "value": "x" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    let req = test::TestRequest::put()
+        .uri(&format!("/loans/{}/metadata/notes", loan_id))
+        .cookie(lender.clone())
+        .set_json(json!({ "value": "x".repeat(2000) }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    // The borrower can read but not write
+    let borrower = login!(app, "DEMO");
+    let req = test::TestRequest::put()
+        .uri(&format!("/loans/{}/metadata/crm_id", loan_id))
+        .cookie(borrower.clone())
+        .set_json(json!({ "value": "mine" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::get()
+        .uri(&format!("/loans/{}/metadata", loan_id))
+        .cookie(borrower)
+        .to_request();
+    let metadata: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(metadata, json!({ "crm_id": "CRM-1042", "branch.code": "NBO-07" }));
+
+    // Metadata comes back with the loan itself
+    let body: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/loans").to_request()).await;
+    let loan = body["items"].as_array().unwrap().iter().find(|l| l["id"] == loan_id.to_string()).unwrap();
+    assert_eq!(loan["metadata"]["crm_id"], "CRM-1042");
+    assert_eq!(loan["metadata"]["branch.code"], "NBO-07");
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/loans/{}/metadata/crm_id", loan_id))
+        .cookie(lender.clone())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+    let req = test::TestRequest::delete()
+        .uri(&format!("/loans/{}/metadata/crm_id", loan_id))
+        .cookie(lender)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_interest_rounding_policies_reconcile() {
+    use chrono::TimeZone;
+    let start = Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap();
+
+    // Exact (unrounded) interest for the same loan, computed independently
+    let (principal, rate, months) = (10_000.0, 17.3, 7);
+    let r: f64 = rate / 100.0 / 12.0;
+    let emi = principal * r * (1.0 + r).powi(months) / ((1.0 + r).powi(months) - 1.0);
+
+    let per_installment = generate_amortization_schedule_from(start, principal, rate, months as i64, InterestRounding::PerInstallment).unwrap();
+    let at_payoff = generate_amortization_schedule_from(start, principal, rate, months as i64, InterestRounding::AtPayoff).unwrap();
+
+    // Per-installment: the total is the sum of each installment's rounded interest
+    let per_installment_interest = Money::checked_sum(per_installment.iter().map(|p| p.interest)).unwrap();
+
+    // At payoff: fractions of a cent carry forward, so the total is the exact interest rounded once
+    let mut balance = principal;
+    let mut exact_interest = 0.0;
+    for p in &at_payoff {
+        exact_interest += balance * r;
+        balance -= p.principal.to_f64();
+    }
+    let at_payoff_interest = Money::checked_sum(at_payoff.iter().map(|p| p.interest)).unwrap();
+    assert_eq!(at_payoff_interest, Money::from_f64(exact_interest));
+
+    for (schedule, interest) in [(&per_installment, per_installment_interest), (&at_payoff, at_payoff_interest)] {
+        let principal_total = Money::checked_sum(schedule.iter().map(|p| p.principal)).unwrap();
+        let grand_total = ScheduledPayment::total(schedule.iter()).unwrap();
+        assert_eq!(principal_total, Money::from_f64(principal));
+        assert_eq!(grand_total, principal_total.checked_add(interest).unwrap());
+        assert!((grand_total.to_f64() - emi * months as f64).abs() < 0.05);
+    }
+}
+
+#[actix_web::test]
+async fn test_all_loans_tags_role_per_loan() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    // BANK lends to DEMO and also borrows from MSHW; TALA's loan does not involve BANK
+    let lent = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1000.0, 10.0, 6).unwrap();
+    let borrowed = tracker.create_loan("BANK".to_string(), "MSHW".to_string(), 5000.0, 8.0, 12).unwrap();
+    let unrelated = tracker.create_loan("DEMO".to_string(), "TALA".to_string(), 300.0, 12.0, 3).unwrap();
+
+    let app = session_app!(db, "/me/all-loans" => web::get().to(my_all_loans));
+    let bank = login!(app, "BANK");
+    let req = test::TestRequest::get().uri("/me/all-loans").cookie(bank).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+    let roles: std::collections::HashMap<String, String> = body["loans"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| (l["id"].as_str().unwrap().to_string(), l["role"].as_str().unwrap().to_string()))
+        .collect();
+    assert_eq!(roles.get(&lent.to_string()).map(String::as_str), Some("lender"));
+    assert_eq!(roles.get(&borrowed.to_string()).map(String::as_str), Some("borrower"));
+    assert!(!roles.contains_key(&unrelated.to_string()));
+}
+
+#[actix_web::test]
+async fn test_recommend_uses_missed_payments() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1200.0, 0.0, 6).unwrap();
+
+    // Four installments have fallen due; only the first was paid
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    let now = Utc::now();
+    for (i, installment) in loan.repayment_schedule.iter_mut().enumerate() {
+        installment.due_date = now - Duration::days(100) + Duration::days(30 * i as i64);
+    }
+    db.save_loan(&loan).unwrap();
+    tracker.record_payment(loan_id, 200.0).unwrap();
+    assert_eq!(tracker.count_missed_payments(loan_id).unwrap(), 3);
+
+    let app = session_app!(db, "/recommend/{loan_id}" => web::post().to(recommend_action));
+    let lender = login!(app, "BANK");
+    let req = test::TestRequest::post()
+        .uri(&format!("/recommend/{}", loan_id))
+        .cookie(lender)
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["missed_payments"], 3);
+    assert_eq!(body["recommended_action"], "EscalateToCollection");
+
+    // Catching up clears the history
+    tracker.record_payment(loan_id, 600.0).unwrap();
+    assert_eq!(tracker.count_missed_payments(loan_id).unwrap(), 0);
+}
+
+#[actix_web::test]
+async fn test_wallet_auto_debit() {
+    let db = web::Data::new(fresh_db());
+    let users = UserManager::new(&db);
+    let tracker = LoanTracker::new(&db);
+    let funded = users.register_user("Funded".to_string(), None, None, UserRole::Borrower, Some("BANK".to_string()), None).unwrap();
+    let empty = users.register_user("Empty".to_string(), None, None, UserRole::Borrower, Some("BANK".to_string()), None).unwrap();
+
+    let mut loans = Vec::new();
+    for borrower in [&funded, &empty] {
+        let loan_id = tracker.create_loan(borrower.clone(), "BANK".to_string(), 600.0, 0.0, 3).unwrap();
+        let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+        loan.repayment_schedule[0].due_date = Utc::now() - Duration::days(1);
+        db.save_loan(&loan).unwrap();
+        loans.push(loan_id);
+    }
+
+    let app = session_app!(db, "/me/wallet/top-up" => web::post().to(top_up_wallet));
+    let cookie = login!(app, funded.as_str());
+    let req = test::TestRequest::post()
+        .uri("/me/wallet/top-up")
+        .cookie(cookie)
+        .set_json(json!({ "amount": 250.0 }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["wallet_balance"], "250.00");
+
+    let summary = tracker.run_auto_debits(Utc::now()).unwrap();
+    assert_eq!(summary.payments_made, 1);
+    assert_eq!(summary.flagged_overdue, 1);
+
+    let paid = tracker.get_loan(loans[0]).unwrap().unwrap();
+    assert_eq!(paid.amount_paid, Money::from_cents(20_000));
+    assert_eq!(paid.status, LoanStatus::PartiallyRepaid);
+    assert_eq!(users.get_user(&funded).unwrap().unwrap().wallet_balance, Money::from_cents(5000));
+
+    let unpaid = tracker.get_loan(loans[1]).unwrap().unwrap();
+    assert_eq!(unpaid.amount_paid, Money::ZERO);
+    assert_eq!(unpaid.status, LoanStatus::Overdue);
+
+    // Nothing else is due yet, so a second run is a no-op
+    assert_eq!(tracker.run_auto_debits(Utc::now()).unwrap(), Default::default());
+}
+
+#[actix_web::test]
+async fn test_in_memory_dbs_are_isolated() {
+    let first = Db::new_in_memory().unwrap();
+    let second = Db::new_in_memory().unwrap();
+    let baseline = LoanTracker::new(&second).get_all_loans().unwrap().len();
+
+    let loan_id = LoanTracker::new(&first).create_loan("DEMO".to_string(), "BANK".to_string(), 100.0, 5.0, 1).unwrap();
+    // Clones share the pool, and so the database; a second in-memory Db does not
+    assert!(LoanTracker::new(&first.clone()).get_loan(loan_id).unwrap().is_some());
+    assert!(LoanTracker::new(&second).get_loan(loan_id).unwrap().is_none());
+    assert_eq!(LoanTracker::new(&second).get_all_loans().unwrap().len(), baseline);
+}
+
+#[actix_web::test]
+async fn test_import_from_json_restores_and_is_idempotent() {
+    let source = fresh_db();
+    let dir = std::env::temp_dir().join(format!("lendwise-restore-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (users_path, loans_path) = (dir.join("users.json"), dir.join("loans.json"));
+    source.save_to_json(&users_path, &loans_path).unwrap();
+    let expected = (source.count_users().unwrap(), source.count_loans().unwrap());
+
+    let target = Db::new_in_memory().unwrap();
+    assert_eq!(target.import_from_json(&users_path, &loans_path).unwrap(), expected);
+    assert_eq!(target.import_from_json(&users_path, &loans_path).unwrap(), expected);
+    assert_eq!((target.count_users().unwrap(), target.count_loans().unwrap()), expected);
+
+    let original = source.load_all_loans().unwrap().remove(0);
+    let restored = target.load_loan(original.id).unwrap().unwrap();
+    assert_eq!(restored.principal, original.principal);
+    assert_eq!(restored.repayment_schedule.len(), original.repayment_schedule.len());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[actix_web::test]
+async fn test_write_backup_rotates_out_the_oldest_beyond_retention() {
+    let db = fresh_db();
+    let dir = std::env::temp_dir().join(format!("lendwise-backups-{}", uuid::Uuid::new_v4()));
+    let unrelated = dir.join("notes.txt");
+    let start = Utc::now() - Duration::days(10);
+
+    // Three backups fit within a retention of 3
+    let mut written = Vec::new();
+    for day in 0..3 {
+        let files = db.write_backup(&dir, 3, start + Duration::days(day)).unwrap();
+        assert!(files.removed.is_empty());
+        written.push(files);
+    }
+    std::fs::write(&unrelated, "not a backup").unwrap();
+    let file_name = |path: &std::path::Path| path.file_name().unwrap().to_str().unwrap().to_string();
+    assert!(file_name(&written[0].users).starts_with("users_backup_"));
+    assert!(file_name(&written[0].loans).ends_with(".json"));
+
+    // A fourth pushes out the oldest pair, and only that pair
+    let newest = db.write_backup(&dir, 3, start + Duration::days(3)).unwrap();
+    assert_eq!(newest.removed, vec![written[0].users.clone(), written[0].loans.clone()]);
+    assert!(!written[0].users.exists() && !written[0].loans.exists());
+    for files in written.iter().skip(1).chain([&newest]) {
+        assert!(files.users.exists() && files.loans.exists());
+    }
+    assert!(unrelated.exists());
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 7);
+
+    // Shrinking retention removes the rest oldest first, and a backup still restores
+    let removed = Db::rotate_backups(&dir, 1).unwrap();
+    assert_eq!(removed.len(), 4);
+    assert!(newest.users.exists());
+    let target = Db::new_in_memory().unwrap();
+    let (users, loans) = target.import_from_json(&newest.users, &newest.loans).unwrap();
+    assert_eq!((users, loans), (db.count_users().unwrap(), db.count_loans().unwrap()));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[actix_web::test]
+async fn test_db_transaction_rolls_back_on_error() {
+    let db = Db::new_in_memory().unwrap();
+    let before = db.count_users().unwrap();
+    let insert = "INSERT INTO users (id, name, role) VALUES (?1, ?2, 'Borrower')";
+
+    let result = db.transaction(|tx| {
+        tx.execute(insert, rusqlite::params!["TXN1", "First"])?;
+        // Same primary key again violates the constraint
+        tx.execute(insert, rusqlite::params!["TXN1", "Second"])?;
+        Ok(())
+    });
+    assert!(result.is_err());
+    assert_eq!(db.count_users().unwrap(), before);
+    assert!(db.load_user("TXN1").unwrap().is_none());
+
+    let stored = db.transaction(|tx| tx.execute(insert, rusqlite::params!["TXN2", "Kept"])).unwrap();
+    assert_eq!(stored, 1);
+    assert_eq!(db.count_users().unwrap(), before + 1);
+}
+
+#[actix_web::test]
+async fn test_db_opens_in_wal_mode() {
+    let path = std::env::temp_dir().join(format!("lendwise-test-{}.db", uuid::Uuid::new_v4()));
+    let db = Db::new_with_settings(path.to_str().unwrap(), 2, 250).unwrap();
+    LoanTracker::new(&db).create_loan("DEMO".to_string(), "BANK".to_string(), 100.0, 5.0, 1).unwrap();
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+    assert_eq!(mode, "wal");
+}
+
+#[actix_web::test]
+async fn test_checkpoint_flushes_wal_into_database_file() {
+    let path = std::env::temp_dir().join(format!("lendwise-test-{}.db", uuid::Uuid::new_v4()));
+    let db = Db::new_with_settings(path.to_str().unwrap(), 2, 250).unwrap();
+    LoanTracker::new(&db).create_loan("DEMO".to_string(), "BANK".to_string(), 100.0, 5.0, 1).unwrap();
+
+    db.checkpoint().unwrap();
+    let wal = std::path::PathBuf::from(format!("{}-wal", path.display()));
+    assert_eq!(std::fs::metadata(&wal).map(|m| m.len()).unwrap_or(0), 0);
+}
+
+#[actix_web::test]
+async fn test_pooled_db_handles_concurrent_get_loans() {
+    let path = std::env::temp_dir().join(format!("lendwise-test-{}.db", uuid::Uuid::new_v4()));
+    // Deliberately fewer connections than callers so checkouts have to wait their turn
+    let db = Db::new_with_pool_size(path.to_str().unwrap(), 4).unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db.clone()))
+            .route("/loans", web::get().to(get_loans)),
+    )
+    .await;
+
+    let responses = futures_util::future::join_all((0..50).map(|_| {
+        test::call_service(&app, test::TestRequest::get().uri("/loans").to_request())
+    }))
+    .await;
+    assert_eq!(responses.len(), 50);
+    assert!(responses.iter().all(|resp| resp.status() == StatusCode::OK));
+
+    // Same pool from real threads, so connections are checked out simultaneously
+    std::thread::scope(|s| {
+        let handles: Vec<_> = (0..50)
+            .map(|_| s.spawn(|| LoanTracker::new(&db).get_all_loans()))
+            .collect();
+        for handle in handles {
+            assert!(!handle.join().unwrap().unwrap().is_empty());
+        }
+    });
+}
+
+#[actix_web::test]
+async fn test_compare_models_reports_action_diff() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1000.0, 10.0, 6).unwrap();
+
+    let app = session_app!(db, "/recovery/compare-models" => web::post().to(compare_risk_models));
+    // The candidate treats every active loan as near-certain to default
+    let body = json!({
+        "baseline": { "name": "current" },
+        "candidate": { "name": "pessimistic", "active_prior": 0.9 }
+    });
+
+    let bank = login!(app, "BANK");
+    let req = test::TestRequest::post().uri("/recovery/compare-models").cookie(bank).set_json(&body).to_request();
+    let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp["baseline"], "current");
+    assert_eq!(resp["candidate"], "pessimistic");
+    assert!(resp["action_changes"].as_u64().unwrap() >= 1);
+
+    let row = resp["loans"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|l| l["loan_id"] == loan_id.to_string())
+        .expect("lender's loan is compared");
+    assert_eq!(row["baseline"]["recommended_action"], "SendReminder");
+    assert_eq!(row["candidate"]["recommended_action"], "EscalateToCollection");
+    assert_eq!(row["action_changed"], true);
+    assert!(row["score_delta"].as_f64().unwrap() > 0.0);
+
+    let demo = login!(app, "DEMO");
+    let req = test::TestRequest::post().uri("/recovery/compare-models").cookie(demo).set_json(&body).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_list_endpoints_paginate() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    for principal in 1..=7 {
+        tracker.create_loan("DEMO".to_string(), "BANK".to_string(), principal as f64 * 100.0, 5.0, 3).unwrap();
+    }
+    let total_loans = db.count_loans().unwrap();
+    let total_users = db.count_users().unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .route("/users", web::get().to(get_users))
+            .route("/loans", web::get().to(get_loans)),
+    )
+    .await;
+
+    let page = |uri: String| {
+        let app = &app;
+        async move {
+            let req = test::TestRequest::get().uri(&uri).to_request();
+            let body: serde_json::Value = test::call_and_read_body_json(app, req).await;
+            body
+        }
+    };
+
+    let first = page("/loans?limit=3&sort=principal".to_string()).await;
+    // `total` is the whole table, not the length of this page
+    assert_eq!(first["items"].as_array().unwrap().len(), 3);
+    assert!(total_loans > 3);
+    assert_eq!(first["total"], total_loans);
+    assert_eq!(first["limit"], 3);
+    assert_eq!(first["has_next"], true);
+
+    // Consecutive pages don't overlap and together cover the whole table
+    let mut seen = Vec::new();
+    for offset in (0..total_loans).step_by(3) {
+        let body = page(format!("/loans?limit=3&offset={}&sort=principal", offset)).await;
+        assert_eq!(body["offset"], offset);
+        assert_eq!(body["has_next"], offset + 3 < total_loans);
+        seen.extend(body["items"].as_array().unwrap().iter().map(|l| l["id"].as_str().unwrap().to_string()));
+    }
+    let unique: std::collections::HashSet<_> = seen.iter().collect();
+    assert_eq!(seen.len(), total_loans);
+    assert_eq!(unique.len(), total_loans);
+
+    // Oversized limits are capped, and the default applies when none is given
+    let body = page("/users?limit=100000".to_string()).await;
+    assert_eq!(body["limit"], MAX_PAGE_SIZE);
+    assert_eq!(body["total"], total_users);
+    assert_eq!(page("/users".to_string()).await["limit"], DEFAULT_PAGE_SIZE);
+
+    // Filtered listings report the filtered total, not the table size
+    let body = page("/users?role=lender&limit=1".to_string()).await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 1);
+    assert!(body["total"].as_u64().unwrap() < total_users as u64);
+}
+
+#[actix_web::test]
+async fn test_filter_loans_by_status_and_party() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let demo_bank = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 100.0, 5.0, 3).unwrap();
+    let demo_tala = tracker.create_loan("DEMO".to_string(), "TALA".to_string(), 200.0, 5.0, 3).unwrap();
+    let bank_mshw = tracker.create_loan("BANK".to_string(), "MSHW".to_string(), 300.0, 5.0, 3).unwrap();
+    let mut overdue = tracker.get_loan(demo_tala).unwrap().unwrap();
+    overdue.status = LoanStatus::Overdue;
+    db.save_loan(&overdue).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .route("/loans", web::get().to(get_loans)),
+    )
+    .await;
+
+    let ids = |uri: &'static str| {
+        let app = &app;
+        async move {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let body: serde_json::Value = test::call_and_read_body_json(app, req).await;
+            let mut ids: Vec<String> = body["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|l| l["id"].as_str().unwrap().to_string())
+                .collect();
+            ids.sort();
+            ids
+        }
+    };
+    let sorted = |mut v: Vec<uuid::Uuid>| {
+        v.sort();
+        v.into_iter().map(|id| id.to_string()).collect::<Vec<_>>()
+    };
+
+    let demo = ids("/loans?borrower_id=DEMO").await;
+    assert!(demo.contains(&demo_bank.to_string()) && demo.contains(&demo_tala.to_string()));
+    assert!(!demo.contains(&bank_mshw.to_string()));
+    assert_eq!(ids("/loans?lender_id=MSHW").await, sorted(vec![bank_mshw]));
+    assert_eq!(ids("/loans?borrower_id=DEMO&lender_id=TALA&status=overdue").await, sorted(vec![demo_tala]));
+    assert!(ids("/loans?lender_id=TALA&status=Repaid").await.is_empty());
+    assert!(!ids("/loans?status=Active").await.contains(&demo_tala.to_string()));
+
+    let req = test::TestRequest::get().uri("/loans?status=frozen").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_filter_loans_by_disbursement_window() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    // One loan disbursed on the 15th of each month, January to June 2024
+    let ids: Vec<uuid::Uuid> = (1..=6)
+        .map(|month| {
+            let id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 100.0, 5.0, 3).unwrap();
+            let mut loan = tracker.get_loan(id).unwrap().unwrap();
+            loan.disbursement_date = format!("2024-{:02}-15T09:30:00Z", month).parse().unwrap();
+            db.save_loan(&loan).unwrap();
+            id
+        })
+        .collect();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .route("/loans", web::get().to(get_loans)),
+    )
+    .await;
+    let get = |uri: String| {
+        let app = &app;
+        async move { test::call_service(app, test::TestRequest::get().uri(&uri).to_request()).await }
+    };
+    let ids_in = |body: serde_json::Value| {
+        let mut found: Vec<String> = body["items"].as_array().unwrap().iter().map(|l| l["id"].as_str().unwrap().to_string()).collect();
+        found.sort();
+        found
+    };
+    let expected = |months: std::ops::RangeInclusive<usize>| {
+        let mut want: Vec<String> = ids[months.start() - 1..*months.end()].iter().map(|id| id.to_string()).collect();
+        want.sort();
+        want
+    };
+
+    // Both bounds are inclusive
+    let resp = get("/loans?disbursed_from=2024-02-15T09:30:00Z&disbursed_to=2024-04-15T09:30:00Z".to_string()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(ids_in(test::read_body_json(resp).await), expected(2..=4));
+
+    let resp = get("/loans?disbursed_from=2024-05-01T00:00:00Z&lender_id=BANK".to_string()).await;
+    let found = ids_in(test::read_body_json(resp).await);
+    assert!(expected(5..=6).iter().all(|id| found.contains(id)));
+    assert!(!found.contains(&ids[3].to_string()));
+
+    let resp = get("/loans?disbursed_to=2024-01-31T23:59:59Z".to_string()).await;
+    assert_eq!(ids_in(test::read_body_json(resp).await), expected(1..=1));
+
+    let resp = get("/loans?disbursed_from=2024-06-01T00:00:00Z&disbursed_to=2024-03-01T00:00:00Z".to_string()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = get("/loans?disbursed_from=last-tuesday".to_string()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_generate_reminders_batch_and_dry_run() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let now = Utc::now();
+    let shift_schedule = |loan_id: uuid::Uuid, first_due: chrono::DateTime<Utc>| {
+        let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+        for (i, installment) in loan.repayment_schedule.iter_mut().enumerate() {
+            installment.due_date = first_due + Duration::days(30 * i as i64);
+        }
+        db.save_loan(&loan).unwrap();
+    };
+
+    let upcoming = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 600.0, 0.0, 3).unwrap();
+    shift_schedule(upcoming, now + Duration::days(1));
+    let late = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 900.0, 0.0, 3).unwrap();
+    shift_schedule(late, now - Duration::days(5));
+    let not_yet = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 300.0, 0.0, 3).unwrap();
+    shift_schedule(not_yet, now + Duration::days(20));
+    let other_lender = tracker.create_loan("DEMO".to_string(), "TALA".to_string(), 300.0, 0.0, 3).unwrap();
+    shift_schedule(other_lender, now - Duration::days(1));
+
+    let expected: Vec<String> = tracker
+        .loans_due_for_reminder(Some("BANK"), now, lendwise_recovery::notify::REMINDER_LEAD_DAYS)
+        .unwrap()
+        .iter()
+        .map(|l| l.id.to_string())
+        .collect();
+    assert!(expected.contains(&upcoming.to_string()) && expected.contains(&late.to_string()));
+    assert!(!expected.contains(&not_yet.to_string()) && !expected.contains(&other_lender.to_string()));
+
+    let app = session_app!(db, "/recovery/reminders/generate" => web::post().to(generate_reminders));
+    let bank = login!(app, "BANK");
+    let actions_before = db.load_recovery_actions().unwrap().len();
+
+    let req = test::TestRequest::post().uri("/recovery/reminders/generate?dry_run=true").cookie(bank.clone()).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["dry_run"], true);
+    let reminders = body["reminders"].as_array().unwrap();
+    let rendered: Vec<String> = reminders.iter().map(|r| r["loan_id"].as_str().unwrap().to_string()).collect();
+    assert_eq!(rendered, expected);
+    assert_eq!(db.load_recovery_actions().unwrap().len(), actions_before, "dry run must not log");
+
+    let late_msg = reminders.iter().find(|r| r["loan_id"] == late.to_string()).unwrap();
+    assert_eq!(late_msg["channel"], "email");
+    assert_eq!(late_msg["recipient"], "demo.borrower@lendwise.test");
+    assert_eq!(late_msg["days_overdue"], 5);
+    let message = late_msg["message"].as_str().unwrap();
+    assert!(message.contains("Demo Borrower") && message.contains("300.00") && message.contains("5 days overdue"));
+
+    // A real run logs one reminder per message, and the cooldown keeps the next run empty
+    let req = test::TestRequest::post().uri("/recovery/reminders/generate").cookie(bank.clone()).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["generated"], expected.len());
+    let logged = db.load_recovery_actions().unwrap();
+    assert_eq!(logged.len(), actions_before + expected.len());
+    assert!(logged.iter().any(|r| r.loan_id == late && r.action == lendwise_recovery::recovery::RecoveryAction::SendReminder));
+
+    let req = test::TestRequest::post().uri("/recovery/reminders/generate").cookie(bank).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["generated"], 0);
+}
+
+#[actix_web::test]
+async fn test_remind_renders_message_for_lender() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let now = Utc::now();
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 900.0, 0.0, 3).unwrap();
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    for (i, installment) in loan.repayment_schedule.iter_mut().enumerate() {
+        installment.due_date = now - Duration::days(10) + Duration::days(30 * i as i64);
+    }
+    db.save_loan(&loan).unwrap();
+    let next_due = loan.repayment_schedule[1].due_date;
+
+    let app = session_app!(db, "/loans/{id}/remind" => web::post().to(remind_loan));
+    let req = test::TestRequest::post().uri(&format!("/loans/{}/remind", loan_id)).cookie(login!(app, "DEMO")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post().uri(&format!("/loans/{}/remind", loan_id)).cookie(login!(app, "BANK")).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["amount_due"], "300.00");
+    assert_eq!(body["days_overdue"], 10);
+    let message = body["message"].as_str().unwrap();
+    assert!(message.contains("300.00") && message.contains("10 days overdue"));
+    assert!(message.contains(&next_due.format("%Y-%m-%d").to_string()));
+    assert_eq!(db.load_recovery_actions().unwrap().iter().filter(|r| r.loan_id == loan_id).count(), 0);
+}
+
+#[actix_web::test]
+async fn test_get_single_user() {
+    let db = fresh_db();
+    let user_id = UserManager::new(&db)
+        .register_user("Solo Borrower".to_string(), Some("solo@example.com".to_string()), None, UserRole::Borrower, Some("BANK".to_string()), None)
+        .unwrap();
+    UserManager::new(&db).set_password(&user_id, "hunter22").unwrap();
+    let other_lender = UserManager::new(&db)
+        .register_user("Other Lender".to_string(), None, None, UserRole::Lender, None, Some("Other Bank".to_string()))
+        .unwrap();
+    let db = web::Data::new(db);
+    let app = session_app!(db, "/users/{id}" => web::get().to(get_user));
+
+    let req = test::TestRequest::get().uri(&format!("/users/{}", user_id)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+    // The user themselves and their lender see contact details and the wallet
+    for viewer in [user_id.as_str(), "BANK"] {
+        let req = test::TestRequest::get().uri(&format!("/users/{}", user_id)).cookie(login!(app, viewer)).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["id"], user_id);
+        assert_eq!(body["name"], "Solo Borrower");
+        assert_eq!(body["lender_id"], "BANK");
+        assert_eq!(body["email"], "solo@example.com", "{}", viewer);
+        assert_eq!(body["wallet_balance"], "0.00");
+        assert!(!body.to_string().contains("argon2"), "password hash must not be exposed");
+    }
+
+    // Another borrower or lender only gets the profile
+    for viewer in ["DEMO", other_lender.as_str()] {
+        let req = test::TestRequest::get().uri(&format!("/users/{}", user_id)).cookie(login!(app, viewer)).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["name"], "Solo Borrower");
+        for field in ["email", "phone", "wallet_balance"] {
+            assert!(body.get(field).is_none(), "{} sees {}", viewer, field);
+        }
+    }
+
+    let req = test::TestRequest::get().uri("/users/ZZZZ").cookie(login!(app, "DEMO")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_my_loans_lists_only_the_callers_side() {
+    let db = web::Data::new(fresh_db());
+    let lender_id = UserManager::new(&db)
+        .register_user("Mine Lender".to_string(), None, None, UserRole::Lender, None, Some("Mine Bank".to_string()))
+        .unwrap();
+    let borrower_id = UserManager::new(&db)
+        .register_user("Mine Borrower".to_string(), None, None, UserRole::Borrower, Some(lender_id.clone()), None)
+        .unwrap();
+    let app = session_app!(db, "/loans/mine" => web::get().to(my_loans));
+
+    let mine = |cookie: Cookie<'static>| test::TestRequest::get().uri("/loans/mine").cookie(cookie).to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, mine(login!(app, borrower_id.as_str()))).await).await;
+    assert_eq!(body["loans"], json!([]));
+
+    let loan_id = LoanTracker::new(&db).create_loan(borrower_id.clone(), lender_id.clone(), 500.0, 10.0, 3).unwrap();
+    for user in [&borrower_id, &lender_id] {
+        let body: serde_json::Value = test::read_body_json(test::call_service(&app, mine(login!(app, user.as_str()))).await).await;
+        let loans = body["loans"].as_array().unwrap();
+        assert_eq!(loans.len(), 1, "{}", user);
+        assert_eq!(loans[0]["id"], loan_id.to_string());
+    }
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/loans/mine").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_get_single_loan() {
+    let db = web::Data::new(fresh_db());
+    let loan_id = LoanTracker::new(&db).create_loan("DEMO".to_string(), "BANK".to_string(), 750.0, 12.0, 6).unwrap();
+    let app = session_app!(db, "/loans/{id}" => web::get().to(get_loan));
+    let borrower = login!(app, "DEMO");
+
+    let req = test::TestRequest::get().uri(&format!("/loans/{}", loan_id)).cookie(borrower.clone()).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["id"], loan_id.to_string());
+    assert_eq!(body["principal"], "750.00");
+    assert_eq!(body["repayment_schedule"].as_array().unwrap().len(), 6);
+    assert_eq!(body["days_overdue"], 0);
+    let risk = body["risk_score"].as_f64().unwrap();
+    assert!((0.0..=1.0).contains(&risk));
+
+    let req = test::TestRequest::get().uri(&format!("/loans/{}", uuid::Uuid::new_v4())).cookie(borrower).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["message"], "Loan not found");
+}
+
+#[actix_web::test]
+async fn test_get_loan_etag_answers_304_until_the_loan_changes() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 900.0, 10.0, 6).unwrap();
+    let app = session_app!(db, "/loans/{id}" => web::get().to(get_loan));
+    let borrower = login!(app, "DEMO");
+    let get = |if_none_match: Option<&str>| {
+        let mut req = test::TestRequest::get().uri(&format!("/loans/{}", loan_id)).cookie(borrower.clone());
+        if let Some(tag) = if_none_match {
+            req = req.insert_header(("If-None-Match", tag.to_string()));
+        }
+        req.to_request()
+    };
+
+    let resp = test::call_service(&app, get(None)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let etag = resp.headers().get("ETag").expect("missing ETag").to_str().unwrap().to_string();
+
+    let resp = test::call_service(&app, get(Some(&etag))).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers().get("ETag").unwrap().to_str().unwrap(), etag);
+    assert!(test::read_body(resp).await.is_empty());
+
+    // Any tag in a list matches, weak or strong
+    let list = format!("\"stale\", W/{}", etag);
+    assert_eq!(test::call_service(&app, get(Some(&list))).await.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(test::call_service(&app, get(Some("\"stale\""))).await.status(), StatusCode::OK);
+
+    // A payment rewrites the loan, so the old tag no longer matches
+    tracker.record_payment(loan_id, 100.0).unwrap();
+    let resp = test::call_service(&app, get(Some(&etag))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_ne!(resp.headers().get("ETag").unwrap().to_str().unwrap(), etag);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["amount_paid"], "100.00");
+
+    // Parties only: a stranger gets no 304, whatever tag they send
+    let stranger = login!(app, "MSHW");
+    let req = test::TestRequest::get().uri(&format!("/loans/{}", loan_id)).cookie(stranger).insert_header(("If-None-Match", "*")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_record_payment_endpoint() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 600.0, 0.0, 3).unwrap();
+
+    let app = session_app!(db, "/loans/{id}/payments" => web::post().to(record_loan_payment));
+    let pay = |cookie: Cookie<'static>, amount: serde_json::Value| {
+        test::TestRequest::post().uri(&format!("/loans/{}/payments", loan_id)).cookie(cookie).set_json(json!({ "amount": amount })).to_request()
+    };
+
+    let outsider = login!(app, "TALA");
+    assert_eq!(test::call_service(&app, pay(outsider, json!(100.0))).await.status(), StatusCode::FORBIDDEN);
+
+    let borrower = login!(app, "DEMO");
+    for amount in [json!(0.0), json!(-50.0)] {
+        assert_eq!(test::call_service(&app, pay(borrower.clone(), amount)).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, pay(borrower, json!(200.0))).await;
+    assert_eq!(body["applied"], "200.00");
+    assert_eq!(body["remaining_balance"], "400.00");
+    assert_eq!(body["status"], "partiallyrepaid");
+    let risk = body["risk_score"].as_f64().unwrap();
+    assert!((0.0..=1.0).contains(&risk));
+
+    // The lender can post on the borrower's behalf; overpayment comes back as excess
+    let lender = login!(app, "BANK");
+    let body: serde_json::Value = test::call_and_read_body_json(&app, pay(lender, json!(450.0))).await;
+    assert_eq!(body["applied"], "400.00");
+    assert_eq!(body["excess"], "50.00");
+    assert_eq!(body["remaining_balance"], "0.00");
+    assert_eq!(body["status"], "repaid");
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().amount_paid, Money::from_cents(60_000));
+    assert_eq!(db.load_payments(loan_id).unwrap().len(), 2);
+}
+
+/// Channel that keeps every message instead of delivering it.
+#[derive(Default)]
+struct RecordingChannel(std::sync::Mutex<Vec<(String, String)>>);
+
+impl NotificationChannel for RecordingChannel {
+    fn send<'a>(&'a self, to: &'a User, body: &'a str) -> futures_util::future::BoxFuture<'a, lendwise_recovery::error::AppResult<()>> {
+        self.0.lock().unwrap().push((to.id.clone(), body.to_string()));
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[actix_web::test]
+async fn test_reminders_dispatch_through_notification_channel() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 600.0, 0.0, 3).unwrap();
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.repayment_schedule[0].due_date = Utc::now() - Duration::days(2);
+    db.save_loan(&loan).unwrap();
+
+    // Only reminders go out automatically; other actions are left to staff
+    let borrower = UserManager::new(&db).get_user("DEMO").unwrap().unwrap();
+    let channel = RecordingChannel::default();
+    for action in [RecoveryAction::RenegotiateTerms, RecoveryAction::EscalateToCollection] {
+        let sent = RecoveryEngine::default().dispatch(action, &loan, &borrower, &channel, Utc::now()).await.unwrap();
+        assert!(sent.is_none());
+    }
+    assert!(channel.0.lock().unwrap().is_empty());
+
+    let channel = Arc::new(RecordingChannel::default());
+    let app = test::init_service(
+        App::new()
+            .app_data(db.clone())
+            .app_data(web::Data::from(channel.clone() as Arc<dyn NotificationChannel>))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/recovery/reminders/generate", web::post().to(generate_reminders))
+    ).await;
+    let bank = login!(app, "BANK");
+
+    let req = test::TestRequest::post().uri("/recovery/reminders/generate?dry_run=true").cookie(bank.clone()).to_request();
+    test::call_service(&app, req).await;
+    assert!(channel.0.lock().unwrap().is_empty(), "dry run must not send");
+
+    let req = test::TestRequest::post().uri("/recovery/reminders/generate").cookie(bank).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let sent = channel.0.lock().unwrap();
+    assert_eq!(sent.len(), body["generated"].as_u64().unwrap() as usize);
+    let reminder = body["reminders"].as_array().unwrap().iter().find(|r| r["loan_id"] == loan_id.to_string()).unwrap();
+    assert!(sent.contains(&("DEMO".to_string(), reminder["message"].as_str().unwrap().to_string())));
+}
+
+#[actix_web::test]
+async fn test_guarantor_is_validated_and_told_about_default() {
+    let db = web::Data::new(fresh_db());
+    let guarantor = UserManager::new(&db)
+        .register_user("Uncle Guarantor".to_string(), None, None, UserRole::Borrower, Some("BANK".to_string()), None)
+        .unwrap();
+    let mut config = Config::from_env().unwrap();
+    config.default_after_days = 90;
+    let channel = Arc::new(RecordingChannel::default());
+    let app = test::init_service(
+        App::new()
+            .app_data(db.clone())
+            .app_data(web::Data::new(config))
+            .app_data(web::Data::from(channel.clone() as Arc<dyn NotificationChannel>))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans", web::post().to(create_loan))
+            .route("/loans/{id}", web::get().to(get_loan))
+            .route("/defaults", web::post().to(flag_defaults))
+    ).await;
+    let bank = login!(app, "BANK");
+    let create = |guarantor_id: &str| test::TestRequest::post()
+        .uri("/loans")
+        .cookie(bank.clone())
+        .set_json(json!({
+            "borrower_id": "DEMO", "lender_id": "BANK", "principal": 600.0, "interest_rate": 5.0, "months": 6,
+            "guarantor_id": guarantor_id
+        }))
+        .to_request();
+
+    for (guarantor_id, message) in [("DEMO", "guarantor must be someone other than the borrower"), ("ZZZZ", "guarantor ZZZZ does not exist")] {
+        let resp = test::call_service(&app, create(guarantor_id)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["message"], message);
+    }
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, create(&guarantor)).await;
+    let loan_id: uuid::Uuid = body["id"].as_str().unwrap().parse().unwrap();
+    let req = test::TestRequest::get().uri(&format!("/loans/{}", loan_id)).cookie(bank.clone()).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["guarantor_id"], guarantor.as_str());
+
+    let mut loan = db.load_loan(loan_id).unwrap().unwrap();
+    loan.repayment_schedule = generate_amortization_schedule_from(Utc::now() - Duration::days(150), 600.0, 5.0, 6, InterestRounding::default()).unwrap();
+    loan.status = LoanStatus::Overdue;
+    db.save_loan(&loan).unwrap();
+
+    let req = test::TestRequest::post().uri("/defaults").cookie(bank).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["loan_ids"].as_array().unwrap().contains(&json!(loan_id)));
+    let sent = channel.0.lock().unwrap();
+    let borrower_notice = sent.iter().find(|(to, _)| to == "DEMO").unwrap();
+    assert!(borrower_notice.1.contains("is now in default"));
+    let guarantor_notice = sent.iter().find(|(to, _)| *to == guarantor).unwrap();
+    assert!(guarantor_notice.1.contains("As guarantor you may be liable"));
+}
+
+/// Misbehaving model that ignores the [0, 1] contract.
+struct OutOfRangeModel(f64);
+
+impl lendwise_recovery::models::RiskScorer for OutOfRangeModel {
+    fn score(&self, _loan: &lendwise_recovery::models::Loan, _as_of: chrono::DateTime<Utc>) -> f64 {
+        self.0
+    }
+}
+
+#[actix_web::test]
+async fn test_engine_clamps_out_of_range_risk_scores() {
+    use lendwise_recovery::recovery::{RecoveryAction, RecoveryEngine};
+
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1000.0, 10.0, 6).unwrap();
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+
+    let engine = RecoveryEngine::default();
+    let risk = engine.predict_default(&loan, &OutOfRangeModel(1.5), Utc::now());
+    assert_eq!(risk, 1.0);
+    assert_eq!(engine.recommend_action(risk, 0, &loan.status), RecoveryAction::EscalateToCollection);
+    assert_eq!(engine.predict_default(&loan, &OutOfRangeModel(-0.3), Utc::now()), 0.0);
+    assert_eq!(engine.predict_default(&loan, &OutOfRangeModel(f64::NAN), Utc::now()), 1.0);
+    assert_eq!(engine.predict_default(&loan, &OutOfRangeModel(0.42), Utc::now()), 0.42);
+}
+
+#[actix_web::test]
+async fn test_risk_is_scored_as_of_the_given_date() {
+    use lendwise_recovery::models::{RiskModel, RiskScorable};
+
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1200.0, 10.0, 6).unwrap();
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    let engine = RecoveryEngine::default();
+    let model = RiskModel::default();
+
+    // Nothing is due at disbursement; four months on, three installments have been missed
+    let at_start = loan.disbursement_date;
+    let later = at_start + Duration::days(120);
+    assert_eq!(engine.predict_default(&loan, &model, at_start), engine.predict_default(&loan, &model, at_start));
+    assert_eq!(engine.predict_default(&loan, &model, at_start), loan.calculate_risk_score(at_start));
+    assert!(
+        engine.predict_default(&loan, &model, later) > engine.predict_default(&loan, &model, at_start) + 0.1,
+        "{} vs {}",
+        engine.predict_default(&loan, &model, later),
+        engine.predict_default(&loan, &model, at_start)
+    );
+}
+
+#[actix_web::test]
+async fn test_recovery_thresholds_default_and_custom() {
+    use lendwise_recovery::recovery::RecoveryThresholds;
+
+    let active = LoanStatus::Active;
+    let default = RecoveryEngine::default();
+    assert_eq!(default.thresholds, RecoveryThresholds { escalate_risk: 0.7, renegotiate_risk: 0.4, escalate_missed: 2, renegotiate_missed: 0 });
+    assert_eq!(default.recommend_action(0.71, 0, &active), RecoveryAction::EscalateToCollection);
+    assert_eq!(default.recommend_action(0.7, 0, &active), RecoveryAction::RenegotiateTerms);
+    assert_eq!(default.recommend_action(0.2, 3, &active), RecoveryAction::EscalateToCollection);
+    assert_eq!(default.recommend_action(0.2, 1, &active), RecoveryAction::RenegotiateTerms);
+    assert_eq!(default.recommend_action(0.4, 0, &active), RecoveryAction::SendReminder);
+
+    let lenient = RecoveryEngine::new(RecoveryThresholds { escalate_risk: 0.9, renegotiate_risk: 0.6, escalate_missed: 4, renegotiate_missed: 1 });
+    assert_eq!(lenient.recommend_action(0.71, 0, &active), RecoveryAction::RenegotiateTerms);
+    assert_eq!(lenient.recommend_action(0.5, 1, &active), RecoveryAction::SendReminder);
+    assert_eq!(lenient.recommend_action(0.5, 3, &active), RecoveryAction::RenegotiateTerms);
+    assert_eq!(lenient.recommend_action(0.5, 5, &active), RecoveryAction::EscalateToCollection);
+    assert_eq!(lenient.recommend_action(0.95, 0, &active), RecoveryAction::EscalateToCollection);
+}
+
+#[actix_web::test]
+async fn test_write_off_is_recommended_audited_and_final() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db).with_actor("BANK");
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1200.0, 10.0, 12).unwrap();
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.repayment_schedule = generate_amortization_schedule_from(Utc::now() - Duration::days(400), 1200.0, 10.0, 12, InterestRounding::default()).unwrap();
+    db.save_loan(&loan).unwrap();
+    assert!(tracker.write_off(loan_id).is_err(), "an active loan cannot be written off");
+
+    loan.status = LoanStatus::Defaulted;
+    db.save_loan(&loan).unwrap();
+    let engine = RecoveryEngine::default();
+    let missed = tracker.count_missed_payments(loan_id).unwrap();
+    assert_eq!(engine.recommend_action(0.95, missed, &loan.status), RecoveryAction::WriteOff);
+    assert_eq!(engine.recommend_action(0.95, missed, &LoanStatus::Overdue), RecoveryAction::EscalateToCollection);
+    assert_eq!(engine.recommend_action(0.85, missed, &loan.status), RecoveryAction::EscalateToCollection);
+    assert_eq!(engine.recommend_action(0.95, 2, &loan.status), RecoveryAction::EscalateToCollection);
+
+    let written_off = tracker.write_off(loan_id).unwrap();
+    assert_eq!(written_off.status, LoanStatus::WrittenOff);
+    let last = tracker.get_audit_log(loan_id).unwrap().pop().unwrap();
+    assert_eq!(last.from_status, Some(LoanStatus::Defaulted));
+    assert_eq!(last.to_status, LoanStatus::WrittenOff);
+    assert_eq!(last.actor, "BANK");
+
+    // Sweeps and payments leave it alone
+    assert!(!tracker.flag_overdues(0, false).unwrap().contains(&loan_id));
+    assert!(!tracker.flag_defaults(0, false).unwrap().contains(&loan_id));
+    tracker.record_payment(loan_id, 50.0).unwrap();
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().status, LoanStatus::WrittenOff);
+}
+
+#[actix_web::test]
+async fn test_interest_kind_persists_and_drives_schedule() {
+    use lendwise_recovery::models::InterestKind;
+
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let mut totals = Vec::new();
+    for kind in [InterestKind::Amortized, InterestKind::Simple, InterestKind::CompoundMonthly] {
+        let id = tracker
+            .create_loan_with_kind("DEMO".to_string(), "BANK".to_string(), 10_000.0, 12.0, 12, kind)
+            .unwrap();
+        let loan = tracker.get_loan(id).unwrap().unwrap();
+        assert_eq!(loan.interest_kind, kind);
+        assert_eq!(loan.repayment_schedule.len(), 12);
+        // Level schedules add up to the kind's total to the cent; EMI rounding may drift a little
+        let expected = (loan.total_repayable() * 100.0).round() / 100.0;
+        let tolerance = if kind == InterestKind::Amortized { 0.05 } else { 1e-6 };
+        let total_due = loan.total_due().unwrap();
+        assert!((total_due.to_f64() - expected).abs() < tolerance, "{:?}: {} vs {}", kind, total_due, expected);
+        totals.push(total_due);
+    }
+    assert_eq!(totals[1], Money::from_cents(1_120_000));
+    assert_eq!(totals[2], Money::from_cents(1_126_825));
+    assert!(totals[0] < totals[1], "declining-balance interest is cheaper than flat");
+
+    // Loans created without a kind keep the amortized schedule
+    let id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 500.0, 10.0, 3).unwrap();
+    assert_eq!(tracker.get_loan(id).unwrap().unwrap().interest_kind, InterestKind::Amortized);
+}
+
+#[actix_web::test]
+async fn test_register_user_validates_email_and_phone() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(fresh_db()))
+            .route("/users", web::post().to(register_user)),
+    )
+    .await;
+
+    let register = |email: &str, phone: &str| {
+        test::TestRequest::post()
+            .uri("/users")
+            .set_json(json!({
+                "name": "Contact Test",
+                "role": "borrower",
+                "email": email,
+                "phone": phone,
+                "lender_name": "Demo Lender",
+                "password": "hunter22"
+            }))
+            .to_request()
+    };
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, register("jane@example.co.ke", "+254 712-345678")).await;
+    assert_eq!(body["email"], "jane@example.co.ke");
+    assert_eq!(body["phone"], "+254 712-345678");
+
+    for (email, phone) in [("no-at-sign.example.com", ""), ("jane@localhost", ""), ("jane@example.com", "call me"), ("jane@example.com", "12")] {
+        let resp = test::call_service(&app, register(email, phone)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{} / {}", email, phone);
+    }
+}
+
+#[actix_web::test]
+async fn test_users_without_phone_column_still_load() {
+    let path = std::env::temp_dir().join(format!("lendwise-test-{}.db", uuid::Uuid::new_v4()));
+    {
+        // Users table as it was before phone numbers existed
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute(
+            "CREATE TABLE users (id TEXT PRIMARY KEY, name TEXT NOT NULL, role TEXT NOT NULL, email TEXT, lender_id TEXT, organization TEXT)",
+            [],
+        )
+        .unwrap();
+        conn.execute("INSERT INTO users (id, name, role, email) VALUES ('OLDU', 'Old User', 'Borrower', 'old@example.com')", [])
+            .unwrap();
+    }
+
+    let db = Db::new_with_path(path.to_str().unwrap()).unwrap();
+    let user = UserManager::new(&db).get_user("OLDU").unwrap().unwrap();
+    assert_eq!(user.email.as_deref(), Some("old@example.com"));
+    assert_eq!(user.phone, None);
+}
+
+#[actix_web::test]
+async fn test_migrations_bring_old_schema_current() {
+    use lendwise_recovery::db::SCHEMA_VERSION;
+    use lendwise_recovery::models::InterestKind;
+
+    let path = std::env::temp_dir().join(format!("lendwise-test-{}.db", uuid::Uuid::new_v4()));
+    let loan_id = uuid::Uuid::new_v4();
+    {
+        // Schema as first released: no versioning, no payments or balances
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id TEXT PRIMARY KEY, name TEXT NOT NULL, role TEXT NOT NULL,
+                                 email TEXT, lender_id TEXT, organization TEXT);
+             CREATE TABLE loans (id TEXT PRIMARY KEY, borrower_id TEXT NOT NULL, lender_id TEXT NOT NULL,
+                                 principal REAL NOT NULL, interest_rate REAL NOT NULL,
+                                 disbursement_date TEXT NOT NULL, start_date TEXT NOT NULL,
+                                 last_repayment_date TEXT, status TEXT NOT NULL, repayment_schedule TEXT NOT NULL);
+             INSERT INTO users (id, name, role, email) VALUES ('OLDB', 'Old Borrower', 'Borrower', 'old@example.com');",
+        )
+        .unwrap();
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO loans VALUES (?1, 'OLDB', 'BANK', 1000.0, 10.0, ?2, ?2, NULL, 'Active', '[]')",
+            rusqlite::params![loan_id.to_string(), now],
+        )
+        .unwrap();
+        let version: u32 = conn.query_row("PRAGMA user_version", [], |r| r.get(0)).unwrap();
+        assert_eq!(version, 0);
+    }
+
+    let db = Db::new_with_path(path.to_str().unwrap()).unwrap();
+    assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+
+    let user = UserManager::new(&db).get_user("OLDB").unwrap().unwrap();
+    assert_eq!(user.email.as_deref(), Some("old@example.com"));
+    assert_eq!(user.wallet_balance, Money::ZERO);
+    let loan = db.load_loan(loan_id).unwrap().unwrap();
+    assert_eq!(loan.amount_paid, Money::ZERO);
+    assert_eq!(loan.interest_kind, InterestKind::Amortized);
+    // Tables added by migrations are usable straight away
+    LoanTracker::new(&db).record_payment(loan_id, 100.0).unwrap();
+    db.save_loan_metadata(loan_id, "crm_id", "X1").unwrap();
+    drop(db);
+
+    // Reopening a current database changes nothing
+    let db = Db::new_with_path(path.to_str().unwrap()).unwrap();
+    assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+    assert_eq!(db.load_loan(loan_id).unwrap().unwrap().amount_paid, Money::from_cents(10_000));
+}
+
+#[actix_web::test]
+async fn test_with_retry_retries_only_busy_errors() {
+    use std::cell::Cell;
+
+    let busy = |code| rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(code), Some("database is locked".to_string()));
+    let db = fresh_db().with_write_retries(3);
+
+    // Busy, then locked, then through
+    let attempts = Cell::new(0);
+    let value = db.with_retry(|| {
+        attempts.set(attempts.get() + 1);
+        match attempts.get() {
+            1 => Err(busy(rusqlite::ffi::SQLITE_BUSY)),
+            2 => Err(busy(rusqlite::ffi::SQLITE_LOCKED)),
+            _ => Ok(42),
+        }
+    }).unwrap();
+    assert_eq!((value, attempts.get()), (42, 3));
+
+    // Other errors are not retried
+    attempts.set(0);
+    let err = db.with_retry(|| -> rusqlite::Result<()> {
+        attempts.set(attempts.get() + 1);
+        Err(rusqlite::Error::QueryReturnedNoRows)
+    }).unwrap_err();
+    assert!(matches!(err, rusqlite::Error::QueryReturnedNoRows));
+    assert_eq!(attempts.get(), 1);
+
+    // Still busy once the retries run out
+    attempts.set(0);
+    let err = db.with_retry(|| -> rusqlite::Result<()> {
+        attempts.set(attempts.get() + 1);
+        Err(busy(rusqlite::ffi::SQLITE_BUSY))
+    }).unwrap_err();
+    assert_eq!(err.sqlite_error_code(), Some(rusqlite::ErrorCode::DatabaseBusy));
+    assert_eq!(attempts.get(), 4);
+}
+
+#[actix_web::test]
+async fn test_delete_user_and_loan() {
+    let db = web::Data::new(fresh_db());
+    let users = UserManager::new(&db);
+    let borrower = users
+        .register_user("Leaving Borrower".to_string(), None, None, UserRole::Borrower, Some("BANK".to_string()), None)
+        .unwrap();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan(borrower.clone(), "BANK".to_string(), 400.0, 5.0, 2).unwrap();
+    tracker.record_payment(loan_id, 50.0).unwrap();
+    tracker.set_metadata(loan_id, "crm_id", "C-9").unwrap();
+
+    let app = session_app!(db,
+        "/users/{id}" => web::delete().to(delete_user),
+        "/loans/{id}" => web::delete().to(delete_loan),
+    );
+    let bank = login!(app, "BANK");
+    let delete = |uri: String, cookie: Cookie<'static>| test::TestRequest::delete().uri(&uri).cookie(cookie).to_request();
+
+    // Still owes money, so the user stays
+    let resp = test::call_service(&app, delete(format!("/users/{}", borrower), bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["message"], "user has active loans");
+
+    // Only lenders delete; borrowers cannot remove their own loan
+    let borrower_cookie = login!(app, borrower.as_str());
+    let resp = test::call_service(&app, delete(format!("/loans/{}", loan_id), borrower_cookie)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Deleting a loan only archives it; its history is retained
+    let resp = test::call_service(&app, delete(format!("/loans/{}", loan_id), bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert!(tracker.get_loan(loan_id).unwrap().unwrap().archived);
+    assert_eq!(tracker.get_metadata(loan_id).unwrap().get("crm_id").map(String::as_str), Some("C-9"));
+    assert_eq!(db.load_payments(loan_id).unwrap().len(), 1);
+    let resp = test::call_service(&app, delete(format!("/loans/{}", loan_id), bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    // An archived loan no longer holds the borrower back
+
+    let resp = test::call_service(&app, delete(format!("/users/{}", borrower), bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert!(users.get_user(&borrower).unwrap().is_none());
+
+    // Unknown ids are 404s
+    let resp = test::call_service(&app, delete(format!("/loans/{}", uuid::Uuid::new_v4()), bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = test::call_service(&app, delete(format!("/users/{}", borrower), bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // Another lender's borrower is off limits
+    let resp = test::call_service(&app, delete("/users/DEMO".to_string(), bank)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_update_user_role_guards_active_lenders_and_audits() {
+    let db = web::Data::new(fresh_db());
+    let users = UserManager::new(&db);
+    let misfiled = users
+        .register_user("Misfiled Lender".to_string(), None, None, UserRole::Borrower, Some("BANK".to_string()), None)
+        .unwrap();
+    let admin = users
+        .register_user("Ops Admin".to_string(), None, None, UserRole::Admin, None, None)
+        .unwrap();
+
+    let app = session_app!(db, "/users/{id}/role" => web::patch().to(update_user_role));
+    let bank = login!(app, "BANK");
+    let patch = |id: &str, role: &str, cookie: Cookie<'static>| {
+        test::TestRequest::patch().uri(&format!("/users/{}/role", id)).cookie(cookie).set_json(json!({ "role": role })).to_request()
+    };
+
+    // Borrowers cannot change roles, not even their own
+    let own = login!(app, misfiled.as_str());
+    let resp = test::call_service(&app, patch(&misfiled, "lender", own)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = test::call_service(&app, patch(&misfiled, "lender", bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["role"], "lender");
+
+    // Lenders neither grant admin nor touch other lenders' borrowers
+    let resp = test::call_service(&app, patch(&misfiled, "admin", bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(&app, patch("DEMO", "lender", bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(&app, patch(&misfiled, "owner", bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // A lender with an active loan keeps the role until the loan is gone
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), misfiled.clone(), 300.0, 5.0, 3).unwrap();
+    let resp = test::call_service(&app, patch(&misfiled, "borrower", bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["message"].as_str().unwrap().contains("1 active loan"), "{}", body);
+    assert_eq!(users.get_user(&misfiled).unwrap().unwrap().role, UserRole::Lender);
+
+    tracker.archive_loan(loan_id).unwrap();
+    let admin_cookie = login!(app, admin.as_str());
+    let resp = test::call_service(&app, patch(&misfiled, "borrower", admin_cookie.clone())).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(users.get_user(&misfiled).unwrap().unwrap().role, UserRole::Borrower);
+
+    // Admins reach any user
+    let resp = test::call_service(&app, patch("DEMO", "lender", admin_cookie)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let changes = db.load_role_changes(&misfiled).unwrap();
+    let summary: Vec<_> = changes.iter().map(|c| (c.from_role.clone(), c.to_role.clone(), c.actor.as_str())).collect();
+    assert_eq!(summary, vec![
+        (UserRole::Borrower, UserRole::Lender, "BANK"),
+        (UserRole::Lender, UserRole::Borrower, admin.as_str()),
+    ]);
+}
+
+#[actix_web::test]
+async fn test_weekly_loan_keeps_its_frequency_through_storage_and_term_changes() {
+    use lendwise_recovery::loan::NewLoan;
+    use lendwise_recovery::models::InterestKind;
+
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_new_loan(NewLoan {
+        borrower_id: "DEMO".to_string(),
+        lender_id: "BANK".to_string(),
+        principal: 1200.0,
+        interest_rate: 12.0,
+        duration_months: 12,
+        interest_kind: InterestKind::Amortized,
+        currency: "USD".to_string(),
+        promo_months: 0,
+        payment_frequency: PaymentFrequency::Weekly,
+        guarantor_id: None,
+        late_fee_policy: None,
+    }).unwrap();
+
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    assert_eq!(loan.payment_frequency, PaymentFrequency::Weekly);
+    assert_eq!(loan.repayment_schedule.len(), 52);
+    assert_eq!(loan.term_months(), 12);
+    assert_eq!(loan.repayment_schedule[1].due_date - loan.repayment_schedule[0].due_date, Duration::days(7));
+
+    // A rate change rebuilds over the same 12 months, still weekly
+    let updated = tracker.update_terms(loan_id, Some(6.0), None).unwrap();
+    assert_eq!(updated.repayment_schedule.len(), 52);
+    let updated = tracker.update_terms(loan_id, None, Some(6)).unwrap();
+    assert_eq!(updated.repayment_schedule.len(), 26);
+}
+
+#[actix_web::test]
+async fn test_update_loan_terms_rebuilds_schedule_until_first_payment() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1200.0, 12.0, 6).unwrap();
+    let original_start = tracker.get_loan(loan_id).unwrap().unwrap().start_date;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(Config::from_env().unwrap()))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans/{id}", web::patch().to(update_loan_terms))
+    ).await;
+    let bank = login!(app, "BANK");
+    let patch = |body: serde_json::Value, cookie: Cookie<'static>| test::TestRequest::patch()
+        .uri(&format!("/loans/{}", loan_id))
+        .cookie(cookie)
+        .set_json(body)
+        .to_request();
+
+    // Longer term only: same rate, schedule regenerated from the original start
+    let resp = test::call_service(&app, patch(json!({"months": 12}), bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    assert_eq!(loan.repayment_schedule.len(), 12);
+    assert_eq!(loan.interest_rate, 12.0);
+    let expected = generate_amortization_schedule_from(original_start, 1200.0, 12.0, 12, InterestRounding::default()).unwrap();
+    assert_eq!(loan.repayment_schedule, expected);
+
+    // Rate only keeps the new term
+    let resp = test::call_service(&app, patch(json!({"interest_rate": 0.0}), bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["repayment_schedule"].as_array().unwrap().len(), 12);
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    assert_eq!(ScheduledPayment::total(&loan.repayment_schedule), Some(Money::from_cents(120_000)));
+
+    let resp = test::call_service(&app, patch(json!({"months": 0}), bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(&app, patch(json!({}), bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let borrower = login!(app, "DEMO");
+    let resp = test::call_service(&app, patch(json!({"months": 3}), borrower)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Once money has moved the terms are frozen
+    tracker.record_payment(loan_id, 100.0).unwrap();
+    let resp = test::call_service(&app, patch(json!({"months": 3}), bank)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(tracker.update_terms(loan_id, Some(5.0), None).is_err());
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().repayment_schedule.len(), 12);
+}
+
+#[actix_web::test]
+async fn test_csv_export_quotes_fields_and_flattens_schedule() {
+    let db = fresh_db();
+    let users = UserManager::new(&db);
+    let borrower = users
+        .register_user("Doe, Jane \"JJ\"".to_string(), None, None, UserRole::Borrower, Some("BANK".to_string()), None)
+        .unwrap();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan(borrower.clone(), "BANK".to_string(), 300.0, 0.0, 3).unwrap();
+
+    let mut out = Vec::new();
+    db.export_users_csv(&mut out).unwrap();
+    let users_csv = String::from_utf8(out).unwrap();
+    let mut lines = users_csv.split("\r\n");
+    assert_eq!(lines.next(), Some("id,name,role,email,phone,lender_id,organization,wallet_balance"));
+    assert!(users_csv.contains(&format!("{},\"Doe, Jane \"\"JJ\"\"\",Borrower,,,BANK,,0.00\r\n", borrower)));
+
+    let mut out = Vec::new();
+    db.export_loans_csv(&mut out).unwrap();
+    let loans_csv = String::from_utf8(out).unwrap();
+    assert!(loans_csv.starts_with("id,borrower_id,lender_id,principal,currency,interest_rate,interest_kind,status,"));
+    let row = loans_csv.lines().find(|l| l.starts_with(&loan_id.to_string())).expect("loan row");
+    let schedule = row.rsplit(',').next().unwrap();
+    let installments: Vec<&str> = schedule.split(';').collect();
+    assert_eq!(installments.len(), 3);
+    assert!(installments.iter().all(|i| i.ends_with(" 100.00")));
+}
+
+#[actix_web::test]
+async fn test_loan_schedule_marks_paid_overdue_and_upcoming() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 400.0, 0.0, 4).unwrap();
+    // Backdate so two installments have fallen due
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.start_date = Utc::now() - Duration::days(75);
+    loan.repayment_schedule = generate_amortization_schedule_from(loan.start_date, 400.0, 0.0, 4, InterestRounding::default()).unwrap();
+    db.save_loan(&loan).unwrap();
+    tracker.record_payment(loan_id, 100.0).unwrap();
+
+    let app = session_app!(web::Data::new(db),
+        "/loans/{id}/schedule" => web::get().to(get_loan_schedule),
+    );
+    let borrower = login!(app, "DEMO");
+    let req = test::TestRequest::get().uri(&format!("/loans/{}/schedule", loan_id)).cookie(borrower.clone()).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let states: Vec<&str> = body["schedule"].as_array().unwrap().iter().map(|e| e["state"].as_str().unwrap()).collect();
+    assert_eq!(states, ["paid", "overdue", "upcoming", "upcoming"]);
+    assert_eq!(body["schedule"][0]["installment"], 1);
+    assert_eq!(body["schedule"][0]["amount"], "100.00");
+    let breakdown = body["breakdown"].as_array().unwrap();
+    assert_eq!(breakdown[1]["opening_balance"], "300.00");
+    assert_eq!(breakdown[1]["closing_balance"], "200.00");
+    assert_eq!(breakdown[3]["closing_balance"], "0.00");
+    assert_eq!(body["timezone"], "+00:00");
+
+    // ?tz= shifts the rendered due dates, not the instants they stand for
+    let utc_due = chrono::DateTime::parse_from_rfc3339(body["schedule"][1]["due_date"].as_str().unwrap()).unwrap();
+    let req = test::TestRequest::get().uri(&format!("/loans/{}/schedule?tz=%2B03:00", loan_id)).cookie(borrower.clone()).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["timezone"], "+03:00");
+    let local_due = body["schedule"][1]["due_date"].as_str().unwrap();
+    assert!(local_due.ends_with("+03:00"), "{}", local_due);
+    assert_eq!(chrono::DateTime::parse_from_rfc3339(local_due).unwrap(), utc_due);
+    let req = test::TestRequest::get().uri(&format!("/loans/{}/schedule?tz=Africa/Nairobi", loan_id)).cookie(borrower.clone()).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["timezone"], "Africa/Nairobi");
+    let local_due = body["schedule"][1]["due_date"].as_str().unwrap();
+    assert!(local_due.ends_with("+03:00"), "{}", local_due);
+    assert_eq!(chrono::DateTime::parse_from_rfc3339(local_due).unwrap(), utc_due);
+    let req = test::TestRequest::get().uri(&format!("/loans/{}/schedule?tz=Mars/Olympus", loan_id)).cookie(borrower.clone()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    // Multi-byte input is rejected, not sliced mid-character
+    let req = test::TestRequest::get().uri(&format!("/loans/{}/schedule?tz=1%C3%A91", loan_id)).cookie(borrower.clone()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::get().uri(&format!("/loans/{}/schedule", uuid::Uuid::new_v4())).cookie(borrower).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    let stranger = login!(app, "ZZZZ");
+    let req = test::TestRequest::get().uri(&format!("/loans/{}/schedule", loan_id)).cookie(stranger).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_archived_loans_are_hidden_and_skipped_by_sweeps() {
+    let db = web::Data::new(fresh_db());
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 500.0, 5.0, 3).unwrap();
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.repayment_schedule = generate_amortization_schedule_from(Utc::now() - Duration::days(60), 500.0, 5.0, 3, InterestRounding::default()).unwrap();
+    db.save_loan(&loan).unwrap();
+
+    let app = session_app!(db,
+        "/loans" => web::get().to(get_loans),
+        "/loans/{id}/archive" => web::post().to(archive_loan),
+        "/loans/{id}/restore" => web::post().to(restore_loan),
+    );
+    let bank = login!(app, "BANK");
+    let listed = |uri: &'static str| {
+        let app = &app;
+        async move {
+            let resp = test::call_service(app, test::TestRequest::get().uri(uri).to_request()).await;
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            body["items"].as_array().unwrap().iter().any(|l| l["id"] == loan_id.to_string())
+        }
+    };
+    let total = || {
+        let app = &app;
+        async move {
+            let resp = test::call_service(app, test::TestRequest::get().uri("/loans").to_request()).await;
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            body["total"].as_u64().unwrap()
+        }
+    };
+    let total_before = total().await;
+
+    let resp = test::call_service(&app, test::TestRequest::post().uri(&format!("/loans/{}/archive", loan_id)).cookie(bank.clone()).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["archived"], true);
+
+    // Still on record, but out of default listings and the overdue sweep
+    assert!(tracker.get_loan(loan_id).unwrap().unwrap().archived);
+    assert!(!listed("/loans").await);
+    // The unfiltered page's total agrees with the items it pages through
+    assert_eq!(total().await, total_before - 1);
+    assert!(!listed("/loans?lender_id=BANK").await);
+    assert!(listed("/loans?include_archived=true").await);
+    assert!(tracker.get_loans_by_lender("BANK").unwrap().iter().all(|l| l.id != loan_id));
+    assert!(!tracker.flag_overdues(0, false).unwrap().contains(&loan_id));
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().status, LoanStatus::Active);
+
+    let resp = test::call_service(&app, test::TestRequest::post().uri(&format!("/loans/{}/restore", loan_id)).cookie(bank).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(listed("/loans").await);
+    assert!(tracker.flag_overdues(0, false).unwrap().contains(&loan_id));
+}
+
+#[actix_web::test]
+async fn test_flag_overdues_returns_flipped_loan_ids() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let mut late = Vec::new();
+    for _ in 0..2 {
+        let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 500.0, 5.0, 3).unwrap();
+        let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+        loan.repayment_schedule = generate_amortization_schedule_from(Utc::now() - Duration::days(60), 500.0, 5.0, 3, InterestRounding::default()).unwrap();
+        db.save_loan(&loan).unwrap();
+        late.push(loan_id);
+    }
+    let on_time = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 500.0, 5.0, 3).unwrap();
+
+    let flagged = tracker.flag_overdues(0, false).unwrap();
+    assert!(late.iter().all(|id| flagged.contains(id)));
+    assert!(!flagged.contains(&on_time));
+    for id in &late {
+        assert_eq!(tracker.get_loan(*id).unwrap().unwrap().status, LoanStatus::Overdue);
+    }
+
+    // Already overdue loans are not reported again
+    let again = tracker.flag_overdues(0, false).unwrap();
+    assert!(late.iter().all(|id| !again.contains(id)));
+}
+
+#[actix_web::test]
+async fn test_flag_overdues_uses_earliest_unpaid_installment_and_grace() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    // First of three 100.00 installments fell due 40 days ago, the second 10 days ago
+    let with_first_due = |paid: f64| {
+        let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 300.0, 0.0, 3).unwrap();
+        let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+        loan.repayment_schedule = generate_amortization_schedule_from(Utc::now() - Duration::days(70), 300.0, 0.0, 3, InterestRounding::default()).unwrap();
+        loan.amount_paid = Money::from_f64(paid);
+        if paid > 0.0 {
+            loan.status = LoanStatus::PartiallyRepaid;
+        }
+        db.save_loan(&loan).unwrap();
+        loan_id
+    };
+    let unpaid = with_first_due(0.0);
+    let first_paid = with_first_due(100.0);
+    let empty = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 300.0, 0.0, 3).unwrap();
+    let mut loan = tracker.get_loan(empty).unwrap().unwrap();
+    loan.repayment_schedule.clear();
+    db.save_loan(&loan).unwrap();
+
+    // The paid loan's earliest unpaid installment is only 10 days late
+    let flagged = tracker.flag_overdues(20, false).unwrap();
+    assert_eq!(flagged, vec![unpaid]);
+
+    let flagged = tracker.flag_overdues(5, false).unwrap();
+    assert_eq!(flagged, vec![first_paid]);
+    assert_eq!(tracker.get_loan(empty).unwrap().unwrap().status, LoanStatus::Active);
+}
+
+#[actix_web::test]
+async fn test_flag_defaults_after_grace_period() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let overdue_since = |days: i64| {
+        let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 600.0, 5.0, 6).unwrap();
+        let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+        // First installment falls due `days` ago
+        loan.repayment_schedule = generate_amortization_schedule_from(Utc::now() - Duration::days(days + 30), 600.0, 5.0, 6, InterestRounding::default()).unwrap();
+        loan.status = LoanStatus::Overdue;
+        db.save_loan(&loan).unwrap();
+        loan_id
+    };
+    let long_gone = overdue_since(120);
+    let recent = overdue_since(20);
+    let active = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 600.0, 5.0, 6).unwrap();
+
+    let defaulted = tracker.flag_defaults(90, false).unwrap();
+    assert!(defaulted.contains(&long_gone));
+    assert!(!defaulted.contains(&recent));
+    assert!(!defaulted.contains(&active));
+    assert_eq!(tracker.get_loan(long_gone).unwrap().unwrap().status, LoanStatus::Defaulted);
+    assert_eq!(tracker.get_loan(recent).unwrap().unwrap().status, LoanStatus::Overdue);
+
+    // A shorter grace period catches the recent one too
+    assert!(tracker.flag_defaults(10, false).unwrap().contains(&recent));
+}
+
+#[actix_web::test]
+async fn test_dry_run_sweeps_report_loans_without_changing_them() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 600.0, 5.0, 6).unwrap();
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.repayment_schedule = generate_amortization_schedule_from(Utc::now() - Duration::days(150), 600.0, 5.0, 6, InterestRounding::default()).unwrap();
+    db.save_loan(&loan).unwrap();
+    let history_before = tracker.get_audit_log(loan_id).unwrap().len();
+
+    let mut config = Config::from_env().unwrap();
+    config.grace_period_days = 0;
+    config.default_after_days = 90;
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(config))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/overdues", web::post().to(flag_overdues))
+            .route("/defaults", web::post().to(flag_defaults))
+    ).await;
+    let bank = login!(app, "BANK");
+    let post = |uri: &str, cookie: Cookie<'static>| test::TestRequest::post().uri(uri).cookie(cookie).to_request();
+
+    let resp = test::call_service(&app, post("/overdues?dry_run=true", bank.clone())).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["dry_run"], true);
+    assert!(body["loan_ids"].as_array().unwrap().contains(&json!(loan_id)));
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().status, LoanStatus::Active);
+    assert_eq!(tracker.get_audit_log(loan_id).unwrap().len(), history_before);
+
+    // Only overdue loans can default, so the preview is empty until the real sweep runs
+    assert!(tracker.flag_defaults(90, true).unwrap().is_empty());
+    let resp = test::call_service(&app, post("/overdues", bank.clone())).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["dry_run"], false);
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().status, LoanStatus::Overdue);
+
+    let resp = test::call_service(&app, post("/defaults?dry_run=true", bank)).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["loan_ids"].as_array().unwrap().contains(&json!(loan_id)));
+    assert_eq!(tracker.get_loan(loan_id).unwrap().unwrap().status, LoanStatus::Overdue);
+    assert_eq!(tracker.get_audit_log(loan_id).unwrap().len(), history_before + 1);
+}
+
+#[actix_web::test]
+async fn test_loan_history_records_each_status_change() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db).with_actor("BANK");
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 300.0, 0.0, 3).unwrap();
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.repayment_schedule = generate_amortization_schedule_from(Utc::now() - Duration::days(45), 300.0, 0.0, 3, InterestRounding::default()).unwrap();
+    db.save_loan(&loan).unwrap();
+
+    LoanTracker::new(&db).flag_overdues(0, false).unwrap();
+    LoanTracker::new(&db).with_actor("DEMO").record_payment(loan_id, 300.0).unwrap();
+    // Reruns that change nothing add no entries
+    tracker.flag_overdues(0, false).unwrap();
+
+    let app = session_app!(web::Data::new(db),
+        "/loans/{id}/history" => web::get().to(get_loan_history),
+    );
+    let lender = login!(app, "BANK");
+    let req = test::TestRequest::get().uri(&format!("/loans/{}/history", loan_id)).cookie(lender).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let history = body["history"].as_array().unwrap();
+    let steps: Vec<(serde_json::Value, &str, &str)> = history.iter()
+        .map(|e| (e["from_status"].clone(), e["to_status"].as_str().unwrap(), e["actor"].as_str().unwrap()))
+        .collect();
+    assert_eq!(steps, vec![
+        (serde_json::Value::Null, "Active", "BANK"),
+        (json!("Active"), "Overdue", "system"),
+        (json!("Overdue"), "Repaid", "DEMO"),
+    ]);
+
+    let stranger = login!(app, "ZZZZ");
+    let req = test::TestRequest::get().uri(&format!("/loans/{}/history", loan_id)).cookie(stranger).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_lender_only_endpoints_reject_consistently() {
+    let db = web::Data::new(fresh_db());
+    let app = session_app!(db,
+        "/recovery/effectiveness" => web::get().to(recovery_effectiveness),
+        "/lenders/me/risk-distribution" => web::get().to(lender_risk_distribution),
+    );
+    let borrower = login!(app, "DEMO");
+    let ghost = login!(app, "GONE");
+    let lender = login!(app, "BANK");
+    for uri in ["/recovery/effectiveness", "/lenders/me/risk-distribution"] {
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{uri} without a session");
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).cookie(ghost.clone()).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{uri} for a deleted user");
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).cookie(borrower.clone()).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN, "{uri} as a borrower");
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).cookie(lender.clone()).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{uri} as a lender");
+    }
+}
+
+#[actix_web::test]
+async fn test_login_requires_matching_password() {
+    let db = web::Data::new(fresh_db());
+    let app = test::init_service(
+        App::new()
+            .app_data(db.clone())
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/users", web::post().to(register_user))
+            .route("/login", web::post().to(login))
+            .route("/me/wallet", web::get().to(get_wallet))
+    ).await;
+    let name = format!("Pat {}", uuid::Uuid::new_v4());
+
+    let register = |password: Option<&str>, name: &str| test::TestRequest::post()
+        .uri("/users")
+        .set_json(json!({"name": name, "role": "borrower", "lender_name": "Demo Lender", "password": password}))
+        .to_request();
+    let resp = test::call_service(&app, register(Some("short"), &name)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(&app, register(Some("s3cret-pass"), &name)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body.get("password_hash").is_none());
+    let user_id = body["id"].as_str().unwrap().to_string();
+
+    // Rewriting the user row must not drop the stored hash
+    let user = UserManager::new(&db).get_user(&user_id).unwrap().unwrap();
+    db.save_user(&user).unwrap();
+
+    let login_req = |name: &str, password: &str| test::TestRequest::post()
+        .uri("/login")
+        .set_json(json!({"name": name, "password": password}))
+        .to_request();
+    let resp = test::call_service(&app, login_req(&name, "wrong-pass")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(&app, login_req("Nobody Here", "s3cret-pass")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = test::call_service(&app, login_req(&name, "s3cret-pass")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let cookie = session_cookie(&resp);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["user_id"], user_id.as_str());
+    assert_eq!(body["role"], "borrower");
+    let req = test::TestRequest::get().uri("/me/wallet").cookie(cookie).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // The API only registers users with a password; ones created without (CLI, seed data)
+    // cannot log in at all
+    let nameless = format!("Legacy {}", uuid::Uuid::new_v4());
+    let resp = test::call_service(&app, register(None, &nameless)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    UserManager::new(&db).register_user(nameless.clone(), None, None, UserRole::Borrower, Some("BANK".to_string()), None).unwrap();
+    let resp = test::call_service(&app, login_req(&nameless, "")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_batch_loan_import_is_all_or_nothing() {
+    let db = fresh_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(Config::from_env().unwrap()))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans/batch", web::post().to(create_loans_batch))
+    ).await;
+    let bank = login!(app, "BANK");
+    let row = |principal: f64, months: i64| json!({
+        "borrower_id": "DEMO", "lender_id": "BANK", "principal": principal, "interest_rate": 10.0, "months": months
+    });
+    let before = db.count_loans().unwrap();
+
+    let req = test::TestRequest::post().uri("/loans/batch").cookie(bank.clone())
+        .set_json(json!([row(100.0, 3), row(200.0, 0), row(300.0, 6)]))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["message"], "loans[1]: months must be between 1 and 600");
+    assert_eq!(db.count_loans().unwrap(), before);
+
+    let req = test::TestRequest::post().uri("/loans/batch").cookie(bank.clone())
+        .set_json(json!([row(100.0, 3), row(200.0, 4), row(300.0, 6)]))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["created"], 3);
+    assert_eq!(db.count_loans().unwrap(), before + 3);
+    let tracker = LoanTracker::new(&db);
+    for (id, months) in body["ids"].as_array().unwrap().iter().zip([3, 4, 6]) {
+        let id = uuid::Uuid::parse_str(id.as_str().unwrap()).unwrap();
+        assert_eq!(tracker.get_loan(id).unwrap().unwrap().repayment_schedule.len(), months);
+        assert_eq!(tracker.get_audit_log(id).unwrap()[0].actor, "BANK");
+    }
+
+    let req = test::TestRequest::post().uri("/loans/batch").cookie(bank)
+        .set_json(json!([]))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_indexed_filters_over_10k_loans() {
+    use lendwise_recovery::db::{LoanFilter, SortOrder};
+    use lendwise_recovery::loan::NewLoan;
+    use lendwise_recovery::models::InterestKind;
+
+    let path = std::env::temp_dir().join(format!("lendwise-test-{}.db", uuid::Uuid::new_v4()));
+    let db = Db::new_with_path(path.to_str().unwrap()).unwrap();
+    let tracker = LoanTracker::new(&db);
+    let batch: Vec<NewLoan> = (0..10_000)
+        .map(|i| NewLoan {
+            borrower_id: format!("B{:03}", i % 10),
+            lender_id: if i % 4 == 0 { "LEN1" } else { "LEN2" }.to_string(),
+            principal: 100.0 + i as f64,
+            interest_rate: 10.0,
+            duration_months: 3,
+            interest_kind: InterestKind::Amortized,
+            currency: "KES".to_string(),
+            promo_months: 0,
+            payment_frequency: PaymentFrequency::Monthly,
+            guarantor_id: None,
+            late_fee_policy: None,
+        })
+        .collect();
+    tracker.create_loans_bulk(batch).unwrap();
+
+    let filter = LoanFilter { borrower_id: Some("B003".to_string()), ..LoanFilter::default() };
+    let loans = tracker.query_loans(&filter, SortOrder::DEFAULT).unwrap();
+    assert_eq!(loans.len(), 1000);
+    assert!(loans.iter().all(|l| l.borrower_id == "B003"));
+
+    let filter = LoanFilter { borrower_id: Some("B003".to_string()), lender_id: Some("LEN1".to_string()), ..LoanFilter::default() };
+    let loans = tracker.query_loans(&filter, SortOrder::DEFAULT).unwrap();
+    // i % 10 == 3 and i % 4 == 0 never coincide (3 is odd)
+    assert!(loans.is_empty());
+    let filter = LoanFilter { borrower_id: Some("B004".to_string()), lender_id: Some("LEN1".to_string()), ..LoanFilter::default() };
+    assert_eq!(tracker.query_loans(&filter, SortOrder::DEFAULT).unwrap().len(), 500);
+
+    // SQLite picks the indexes for these lookups rather than scanning
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    for (sql, index) in [
+        ("SELECT id FROM loans WHERE borrower_id = 'B003'", "idx_loans_borrower_id"),
+        ("SELECT id FROM loans WHERE lender_id = 'LEN1'", "idx_loans_lender_id"),
+        ("SELECT id FROM loans WHERE status = 'Overdue'", "idx_loans_status"),
+        ("SELECT id FROM users WHERE name = 'Demo Lender'", "idx_users_name"),
+    ] {
+        let plan: Vec<String> = conn
+            .prepare(&format!("EXPLAIN QUERY PLAN {}", sql)).unwrap()
+            .query_map([], |r| r.get::<_, String>(3)).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+        assert!(plan.iter().any(|step| step.contains(index)), "{}: {:?}", sql, plan);
+    }
+}
+
+#[actix_web::test]
+async fn test_users_sharing_a_name_each_log_in_with_their_own_password() {
+    let db = web::Data::new(fresh_db());
+    let users = UserManager::new(&db);
+    let name = format!("Alice Johnson {}", uuid::Uuid::new_v4());
+    let register = || users.register_user(name.clone(), None, None, UserRole::Borrower, Some("BANK".to_string()), None).unwrap();
+    // The earliest Alice never set a password
+    let (passwordless, first, second) = (register(), register(), register());
+    users.set_password(&first, "first-pass").unwrap();
+    users.set_password(&second, "second-pass").unwrap();
+
+    assert_eq!(users.authenticate(&name, "first-pass").unwrap().id, first);
+    assert_eq!(users.authenticate(&name, "second-pass").unwrap().id, second);
+    assert!(matches!(users.authenticate(&name, "wrong-pass"), Err(AppError::AuthRequired)));
+    assert!(users.authenticate(&name, "").is_err());
+    assert!(db.load_password_hash(&passwordless).unwrap().is_none());
+
+    let app = session_app!(db, "/login" => web::post().to(login));
+    let req = test::TestRequest::post().uri("/login").set_json(json!({ "name": name, "password": "second-pass" })).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+/// Webhook receiver that answers 500 to its first `failures` requests and records every body.
+#[derive(Default)]
+struct MockWebhook {
+    failures: std::sync::atomic::AtomicUsize,
+    received: std::sync::Mutex<Vec<serde_json::Value>>,
+}
+
+async fn mock_webhook_handler(body: web::Json<serde_json::Value>, mock: web::Data<MockWebhook>) -> HttpResponse {
+    mock.received.lock().unwrap().push(body.into_inner());
+    let failing = mock.failures
+        .fetch_update(std::sync::atomic::Ordering::SeqCst, std::sync::atomic::Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok();
+    if failing {
+        HttpResponse::InternalServerError().finish()
+    } else {
+        HttpResponse::Ok().finish()
+    }
+}
+
+#[actix_web::test]
+async fn test_status_changes_are_posted_to_webhook_with_retry() {
+    assert!(!WebhookDispatcher::spawn(None).is_enabled());
+
+    let mock = web::Data::new(MockWebhook { failures: 1.into(), ..Default::default() });
+    let server_mock = mock.clone();
+    let server = actix_web::HttpServer::new(move || {
+        App::new().app_data(server_mock.clone()).route("/hook", web::post().to(mock_webhook_handler))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let addr = server.addrs()[0];
+    let server = server.run();
+    let server_handle = server.handle();
+    actix_web::rt::spawn(server);
+
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 600.0, 5.0, 6).unwrap();
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.repayment_schedule = generate_amortization_schedule_from(Utc::now() - Duration::days(60), 600.0, 5.0, 6, InterestRounding::default()).unwrap();
+    db.save_loan(&loan).unwrap();
+
+    let webhook = WebhookDispatcher::spawn_with_backoff(Some(format!("http://{}/hook", addr)), 3, std::time::Duration::from_millis(10));
+    let mut config = Config::from_env().unwrap();
+    config.grace_period_days = 0;
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(config))
+            .app_data(web::Data::new(webhook))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/overdues", web::post().to(flag_overdues))
+    ).await;
+    let bank = login!(app, "BANK");
+
+    // Creating the loan was not a status change, so nothing went out before the sweep
+    let req = test::TestRequest::post().uri("/overdues").cookie(bank).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while mock.received.lock().unwrap().len() < 2 && std::time::Instant::now() < deadline {
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let received = mock.received.lock().unwrap().clone();
+    assert_eq!(received.len(), 2, "first delivery fails and is retried once: {:?}", received);
+    assert_eq!(received[0], received[1]);
+    assert_eq!(received[1]["loan_id"], json!(loan_id));
+    assert_eq!(received[1]["old_status"], "Active");
+    assert_eq!(received[1]["new_status"], "Overdue");
+    assert!(received[1]["changed_at"].is_string());
+
+    server_handle.stop(true).await;
+}
+
+#[actix_web::test]
+async fn test_idempotency_key_replays_first_loan() {
+    use lendwise_recovery::loan::NewLoan;
+    use lendwise_recovery::models::InterestKind;
+
+    let db = fresh_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(Config::from_env().unwrap()))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans", web::post().to(create_loan))
+    ).await;
+    let bank = login!(app, "BANK");
+    let before = LoanTracker::new(&db).get_all_loans().unwrap().len();
+    let create = |key: Option<&str>| {
+        let mut req = test::TestRequest::post()
+            .uri("/loans")
+            .cookie(bank.clone())
+            .set_json(json!({
+                "borrower_id": "DEMO",
+                "lender_id": "BANK",
+                "principal": 800.0,
+                "interest_rate": 8.0,
+                "months": 4
+            }));
+        if let Some(key) = key {
+            req = req.insert_header(("Idempotency-Key", key));
+        }
+        req.to_request()
+    };
+
+    let first = test::call_service(&app, create(Some("retry-1"))).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert!(first.headers().get("Idempotent-Replayed").is_none());
+    let first: serde_json::Value = test::read_body_json(first).await;
+
+    let replay = test::call_service(&app, create(Some("retry-1"))).await;
+    assert_eq!(replay.status(), StatusCode::OK);
+    assert_eq!(replay.headers().get("Idempotent-Replayed").unwrap(), "true");
+    let replay: serde_json::Value = test::read_body_json(replay).await;
+    assert_eq!(replay["id"], first["id"]);
+    assert_eq!(LoanTracker::new(&db).get_all_loans().unwrap().len(), before + 1);
+
+    // A different key, or none, is a new loan
+    let other: serde_json::Value = test::call_and_read_body_json(&app, create(Some("retry-2"))).await;
+    assert_ne!(other["id"], first["id"]);
+    assert_eq!(test::call_service(&app, create(None)).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, create(Some("  "))).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(LoanTracker::new(&db).get_all_loans().unwrap().len(), before + 3);
+
+    // A request that loses the race for a key stores nothing and gets the first loan back
+    let new_loan = NewLoan {
+        borrower_id: "DEMO".to_string(),
+        lender_id: "BANK".to_string(),
+        principal: 800.0,
+        interest_rate: 8.0,
+        duration_months: 4,
+        interest_kind: InterestKind::Amortized,
+        currency: "USD".to_string(),
+        promo_months: 0,
+        payment_frequency: PaymentFrequency::Monthly,
+        guarantor_id: None,
+        late_fee_policy: None,
+    };
+    let first_id = uuid::Uuid::parse_str(first["id"].as_str().unwrap()).unwrap();
+    let again = LoanTracker::new(&db).create_new_loan_once(new_loan, "BANK", "retry-1").unwrap();
+    assert_eq!(again, Err(first_id));
+    assert_eq!(LoanTracker::new(&db).get_all_loans().unwrap().len(), before + 3);
+
+    // Keys are kept for the window, then forgotten
+    assert!(db.load_idempotent_loan("BANK", "retry-1", Utc::now() - Duration::hours(1)).unwrap().is_some());
+    assert!(db.load_idempotent_loan("BANK", "retry-1", Utc::now() + Duration::seconds(1)).unwrap().is_none());
+    assert_eq!(db.purge_idempotency_keys(Utc::now() + Duration::seconds(1)).unwrap(), 2);
+    assert!(db.load_idempotent_loan("BANK", "retry-1", Utc::now() - Duration::hours(1)).unwrap().is_none());
+}
+
+#[actix_web::test]
+async fn test_late_fees_accrue_in_schedule_under_loan_or_default_policy() {
+    let db = fresh_db();
+    let mut config = Config::from_env().unwrap();
+    config.late_fee_policy = Some(LateFeePolicy::Flat { amount: Money::from_cents(500) });
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(config))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans", web::post().to(create_loan))
+            .route("/loans/{id}", web::get().to(get_loan))
+            .route("/loans/{id}/schedule", web::get().to(get_loan_schedule))
+    ).await;
+    let bank = login!(app, "BANK");
+    let create = |policy: serde_json::Value| {
+        test::TestRequest::post()
+            .uri("/loans")
+            .cookie(bank.clone())
+            .set_json(json!({
+                "borrower_id": "DEMO",
+                "lender_id": "BANK",
+                "principal": 400.0,
+                "interest_rate": 0.0,
+                "months": 4,
+                "late_fee_policy": policy
+            }))
+            .to_request()
+    };
+
+    let bad = test::call_service(&app, create(json!({"kind": "percentage", "pct": 0.0}))).await;
+    assert_eq!(bad.status(), StatusCode::BAD_REQUEST);
+    let own: serde_json::Value = test::call_and_read_body_json(&app, create(json!({"kind": "percentage", "pct": 10.0}))).await;
+    let defaulted: serde_json::Value = test::call_and_read_body_json(&app, create(serde_json::Value::Null)).await;
+
+    // First installment two monthly periods late, second one period late
+    let tracker = LoanTracker::new(&db);
+    for id in [&own["id"], &defaulted["id"]] {
+        let mut loan = tracker.get_loan(id.as_str().unwrap().parse().unwrap()).unwrap().unwrap();
+        loan.start_date = Utc::now() - Duration::days(75);
+        loan.repayment_schedule = generate_amortization_schedule_from(loan.start_date, 400.0, 0.0, 4, InterestRounding::default()).unwrap();
+        db.save_loan(&loan).unwrap();
+    }
+
+    for (id, first_fee, second_fee, total, outstanding) in [
+        (&own["id"], "20.00", "10.00", "30.00", "430.00"),
+        (&defaulted["id"], "10.00", "5.00", "15.00", "415.00"),
+    ] {
+        let id = id.as_str().unwrap();
+        let req = test::TestRequest::get().uri(&format!("/loans/{}/schedule", id)).cookie(bank.clone()).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["schedule"][0]["late_fee"], first_fee, "loan {}", id);
+        assert_eq!(body["schedule"][1]["late_fee"], second_fee, "loan {}", id);
+        assert_eq!(body["schedule"][2]["late_fee"], "0.00", "loan {}", id);
+        assert_eq!(body["late_fee"], total, "loan {}", id);
+        assert_eq!(body["outstanding_balance"], outstanding, "loan {}", id);
+
+        let req = test::TestRequest::get().uri(&format!("/loans/{}", id)).cookie(bank.clone()).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["outstanding_balance"], outstanding, "loan {}", id);
+    }
+}
+
+#[actix_web::test]
+async fn test_reconcile_recomputes_statuses_in_one_pass() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let new_loan = || tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 300.0, 0.0, 3).unwrap();
+
+    // Long past due but never swept
+    let stale = new_loan();
+    let mut loan = tracker.get_loan(stale).unwrap().unwrap();
+    loan.repayment_schedule = generate_amortization_schedule_from(Utc::now() - Duration::days(150), 300.0, 0.0, 3, InterestRounding::default()).unwrap();
+    db.save_loan(&loan).unwrap();
+    // Imported as overdue although it is paid in full
+    let paid = new_loan();
+    let mut loan = tracker.get_loan(paid).unwrap().unwrap();
+    loan.status = LoanStatus::Overdue;
+    loan.amount_paid = loan.total_due().unwrap();
+    db.save_loan(&loan).unwrap();
+    // Flagged overdue under an old grace period, but nothing is due yet
+    let early = new_loan();
+    let mut loan = tracker.get_loan(early).unwrap().unwrap();
+    loan.status = LoanStatus::Overdue;
+    db.save_loan(&loan).unwrap();
+    let untouched = new_loan();
+    let written_off = new_loan();
+    let mut loan = tracker.get_loan(written_off).unwrap().unwrap();
+    loan.status = LoanStatus::WrittenOff;
+    db.save_loan(&loan).unwrap();
+
+    let mut config = Config::from_env().unwrap();
+    config.grace_period_days = 0;
+    config.default_after_days = 90;
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db.clone()))
+            .app_data(web::Data::new(config))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/reconcile", web::post().to(reconcile_loans))
+    ).await;
+
+    let borrower = login!(app, "DEMO");
+    let req = test::TestRequest::post().uri("/reconcile").cookie(borrower).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let bank = login!(app, "BANK");
+    let req = test::TestRequest::post().uri("/reconcile").cookie(bank.clone()).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let changes = body["changes"].as_array().unwrap();
+    let change_of = |id: uuid::Uuid| changes.iter().find(|c| c["loan_id"] == json!(id)).map(|c| (c["from"].clone(), c["to"].clone()));
+    assert_eq!(change_of(stale), Some((json!("Active"), json!("Defaulted"))));
+    assert_eq!(change_of(paid), Some((json!("Overdue"), json!("Repaid"))));
+    assert_eq!(change_of(early), Some((json!("Overdue"), json!("Active"))));
+    assert_eq!(change_of(untouched), None);
+    assert_eq!(change_of(written_off), None);
+    assert_eq!(body["changed"], changes.len());
+    assert!(body["checked"].as_u64().unwrap() >= 5);
+
+    assert_eq!(tracker.get_loan(stale).unwrap().unwrap().status, LoanStatus::Defaulted);
+    assert_eq!(tracker.get_loan(written_off).unwrap().unwrap().status, LoanStatus::WrittenOff);
+    let history = tracker.get_audit_log(stale).unwrap();
+    assert_eq!(history.last().unwrap().actor, "BANK");
+
+    // A second pass finds nothing left to fix
+    let req = test::TestRequest::post().uri("/reconcile").cookie(bank).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["changed"], 0);
+}
+
+#[actix_web::test]
+async fn test_malformed_bodies_get_readable_invalid_input_errors() {
+    let db = fresh_db();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(db))
+            .app_data(web::Data::new(Config::from_env().unwrap()))
+            .wrap(IdentityMiddleware::default())
+            .wrap(session_middleware())
+            .route("/test/login/{id}", web::post().to(test_login))
+            .route("/loans", web::post().to(create_loan))
+            .route("/loans/batch", web::post().to(create_loans_batch))
+    ).await;
+    let bank = login!(app, "BANK");
+    let post = |uri: &str, body: &str| {
+        test::TestRequest::post()
+            .uri(uri)
+            .cookie(bank.clone())
+            .insert_header(("Content-Type", "application/json"))
+            .set_payload(body.to_string())
+            .to_request()
+    };
+
+    let loan = r#""borrower_id": "DEMO", "lender_id": "BANK", "interest_rate": 5.0, "months": 6"#;
+    for (uri, body, expected) in [
+        ("/loans", format!("{{{}}}", loan), "field 'principal' is required"),
+        ("/loans", format!(r#"{{{}, "principal": "lots"}}"#, loan), "field 'principal' is invalid: invalid type: string \"lots\", expected f64"),
+        ("/loans", format!(r#"{{{}, "principal": 100.0, "princpal": 1}}"#, loan), "unknown field 'princpal'"),
+        ("/loans/batch", format!(r#"[{{{}, "principal": 100.0}}, {{{}}}]"#, loan, loan), "field '[1].principal' is required"),
+        ("/loans", r#"{"borrower_id": "DEMO","#.to_string(), "request body is not valid JSON (line 1, column 23)"),
+        ("/loans", String::new(), "request body is empty"),
+    ] {
+        let resp = test::call_service(&app, post(uri, &body)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", body);
+        let error: serde_json::Value = test::read_body_json(resp).await;
+        assert!(error["message"].as_str().unwrap().starts_with(expected), "{} -> {}", body, error["message"]);
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/loans")
+        .cookie(bank.clone())
+        .insert_header(("Content-Type", "text/plain"))
+        .set_payload(format!(r#"{{{}, "principal": 100.0}}"#, loan))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let error: serde_json::Value = test::read_body_json(resp).await;
+    assert!(error["message"].as_str().unwrap().contains("Content-Type: application/json"));
+
+    // A well-formed body still goes through
+    let resp = test::call_service(&app, post("/loans", &format!(r#"{{{}, "principal": 100.0}}"#, loan))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_configured_miss_half_life_reaches_risk_scores() {
+    let db = fresh_db();
+    let tracker = LoanTracker::new(&db);
+    let loan_id = tracker.create_loan("DEMO".to_string(), "BANK".to_string(), 1200.0, 5.0, 12).unwrap();
+    let mut loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    loan.repayment_schedule = generate_amortization_schedule_from(Utc::now() - Duration::days(100), 1200.0, 5.0, 12, InterestRounding::default()).unwrap();
+    db.save_loan(&loan).unwrap();
+    // The three installments due so far are caught up today, all of them late
+    let overdue = ScheduledPayment::total(&loan.repayment_schedule[..3]).unwrap();
+    tracker.record_payment(loan_id, overdue.to_f64()).unwrap();
+    let loan = tracker.get_loan(loan_id).unwrap().unwrap();
+    assert_eq!(loan.status, LoanStatus::PartiallyRepaid);
+
+    let mut config = Config::from_env().unwrap();
+    assert_eq!(config.risk_model(), lendwise_recovery::models::RiskModel::default());
+    let now = Utc::now();
+    config.miss_half_life_days = 7.0;
+    let forgetful = LoanTracker::new(&db).with_risk_model(config.risk_model()).risk_score(&loan, now).unwrap();
+    config.miss_half_life_days = 3650.0;
+    let long_memory = LoanTracker::new(&db).with_risk_model(config.risk_model()).risk_score(&loan, now).unwrap();
+    assert!(long_memory > forgetful, "{} vs {}", long_memory, forgetful);
+}